@@ -3,7 +3,8 @@ use crate::{
     gml::{
         self,
         compiler::Compiler,
-        mappings,
+        debugger::CodeSource,
+        mappings, profiler,
         runtime::{Instruction, Node},
         Context, Value,
     },
@@ -47,6 +48,9 @@ pub struct Action {
 
     /// Body of this action. Body type depends on the action_kind.
     pub body: Body,
+
+    /// The GML this action runs, if it's a code action, so that errors can quote the line they happened on
+    pub source: Option<gml::String>,
 }
 
 /// Abstraction for a tree of Actions
@@ -169,6 +173,7 @@ impl Tree {
                                         },
                                         is_condition: action.is_condition,
                                     },
+                                    source: None,
                                 });
                             } else {
                                 return Err(format!("Unknown function: {} in action {}", action.fn_name, i))
@@ -193,6 +198,7 @@ impl Tree {
                                     body: GmlBody::Code(compiler.compile(&action.fn_code.0).map_err(|e| e.message)?),
                                     is_condition: action.is_condition,
                                 },
+                                source: Some(action.fn_code.0.as_ref().into()),
                             });
                         },
                     }
@@ -205,6 +211,7 @@ impl Tree {
                         relative: action.is_relative,
                         invert_condition: action.invert_condition,
                         body: Body::Else,
+                        source: None,
                     });
                 },
 
@@ -214,6 +221,7 @@ impl Tree {
                     relative: action.is_relative,
                     invert_condition: action.invert_condition,
                     body: Body::BlockBegin,
+                    source: None,
                 }),
 
                 kind::END_GROUP => output.push(Action {
@@ -222,6 +230,7 @@ impl Tree {
                     relative: action.is_relative,
                     invert_condition: action.invert_condition,
                     body: Body::BlockEnd,
+                    source: None,
                 }),
 
                 kind::EXIT => {
@@ -231,6 +240,7 @@ impl Tree {
                         relative: action.is_relative,
                         invert_condition: action.invert_condition,
                         body: Body::Exit,
+                        source: None,
                     });
                 },
 
@@ -243,6 +253,7 @@ impl Tree {
                         body: Body::Repeat {
                             count: compiler.compile_expression(&action.param_strings[0].0).map_err(|e| e.message)?,
                         },
+                        source: None,
                     });
                 },
 
@@ -260,6 +271,7 @@ impl Tree {
                             body: GmlBody::Code(compiler.compile(&code).map_err(|e| e.message)?),
                             is_condition: false,
                         },
+                        source: Some(code.into()),
                     });
                 },
 
@@ -274,6 +286,7 @@ impl Tree {
                             body: GmlBody::Code(compiler.compile(&action.param_strings[0].0).map_err(|e| e.message)?),
                             is_condition: false,
                         },
+                        source: Some(action.param_strings[0].0.as_ref().into()),
                     });
                 },
 
@@ -303,21 +316,27 @@ impl Tree {
             .into_boxed_slice())
     }
 
-    pub fn new_from_code(code: Rc<[Instruction]>) -> Rc<RefCell<Self>> {
+    pub fn new_from_code(code: Rc<[Instruction]>, source: gml::String) -> Rc<RefCell<Self>> {
         let mut tree = Self(Vec::new());
-        tree.push_code(code);
+        tree.push_code(code, source);
         Rc::new(RefCell::new(tree))
     }
 
-    pub fn push_code(&mut self, code: Rc<[Instruction]>) {
+    pub fn push_code(&mut self, code: Rc<[Instruction]>, source: gml::String) {
         self.0.push(Action {
             index: self.0.len(),
             target: None,
             relative: false,
             invert_condition: false,
             body: Body::Normal { args: Box::new([]), body: GmlBody::Code(code), is_condition: false },
+            source: Some(source),
         });
     }
+
+    /// Finds an action by its original index in the list.
+    pub fn action(&self, index: usize) -> Option<&Action> {
+        self.0.iter().find(|action| action.index == index)
+    }
 }
 
 impl Game {
@@ -335,6 +354,39 @@ impl Game {
         Ok(())
     }
 
+    /// Runs the body of a normal action, either its library function or its code.
+    fn exec_action_body(
+        &mut self,
+        body: &GmlBody,
+        code_source: CodeSource,
+        context: &mut Context,
+        args: [Value; 16],
+        arg_count: usize,
+    ) -> gml::Result<Value> {
+        let address = match body {
+            GmlBody::ContextFunction(f) => f.0 as usize,
+            GmlBody::StateFunction(f) => f.0 as usize,
+            GmlBody::RoutineFunction(f) => f.0 as usize,
+            GmlBody::ValueFunction(f) => f.0 as usize,
+            GmlBody::Code(code) => {
+                context.arguments = args;
+                context.argument_count = arg_count;
+                self.execute_frame(code_source, code, context)?;
+                return Ok(context.return_value.clone())
+            },
+        };
+        self.profile_enter(profiler::Entry::Kernel(address));
+        let result = self.in_frame(code_source, context, |game, context| match body {
+            GmlBody::ContextFunction(f) => f.0(game, context, &args[..arg_count]),
+            GmlBody::StateFunction(f) => f.0(game, &args[..arg_count]),
+            GmlBody::RoutineFunction(f) => f.0(game, &args[..arg_count]),
+            GmlBody::ValueFunction(f) => f.0(&args[..arg_count]),
+            GmlBody::Code(_) => Ok(Default::default()),
+        });
+        self.profile_exit();
+        result
+    }
+
     fn skip_actions(slice: &[Action]) -> usize {
         let mut block_depth: u32 = 0;
         for (i, action) in slice.iter().enumerate() {
@@ -389,6 +441,12 @@ impl Game {
                         event_object: as_object,
                         ..Default::default()
                     };
+                    let code_source = CodeSource::Event {
+                        object: as_object,
+                        event_type,
+                        event_number,
+                        action: action.index,
+                    };
 
                     /*
                     let mut arg_values: [Value; 16] = Default::default();
@@ -410,18 +468,8 @@ impl Game {
                                 *dest = self.eval(src, &mut context)?;
                             }
 
-                            returned_value = match gml_body {
-                                GmlBody::ContextFunction(f) => f.0(self, &mut context, &arg_values[..args.len()])?,
-                                GmlBody::StateFunction(f) => f.0(self, &arg_values[..args.len()])?,
-                                GmlBody::RoutineFunction(f) => f.0(self, &arg_values[..args.len()])?,
-                                GmlBody::ValueFunction(f) => f.0(&arg_values[..args.len()])?,
-                                GmlBody::Code(code) => {
-                                    context.arguments = arg_values;
-                                    context.argument_count = args.len();
-                                    self.execute(code, &mut context)?;
-                                    context.return_value
-                                },
-                            };
+                            returned_value =
+                                self.exec_action_body(gml_body, code_source, &mut context, arg_values, args.len())?;
                         },
                         Some(i) if i < 0 => (),
                         Some(i) => {
@@ -435,18 +483,13 @@ impl Game {
                                     *dest = self.eval(src, &mut context)?;
                                 }
 
-                                returned_value = match gml_body {
-                                    GmlBody::ContextFunction(f) => f.0(self, &mut context, &arg_values[..args.len()])?,
-                                    GmlBody::StateFunction(f) => f.0(self, &arg_values[..args.len()])?,
-                                    GmlBody::RoutineFunction(f) => f.0(self, &arg_values[..args.len()])?,
-                                    GmlBody::ValueFunction(f) => f.0(&arg_values[..args.len()])?,
-                                    GmlBody::Code(code) => {
-                                        context.arguments = arg_values;
-                                        context.argument_count = args.len();
-                                        self.execute(code, &mut context)?;
-                                        context.return_value.clone()
-                                    },
-                                };
+                                returned_value = self.exec_action_body(
+                                    gml_body,
+                                    code_source,
+                                    &mut context,
+                                    arg_values,
+                                    args.len(),
+                                )?;
                             }
                         },
                     }
@@ -1,3 +1,5 @@
+pub mod truetype;
+
 use crate::{
     asset::Sprite,
     gml,
@@ -31,6 +33,21 @@ pub struct Character {
     pub atlas_ref: AtlasRef,
 }
 
+/// A character that hasn't been made into a texture yet, as the alpha channel of a white image.
+pub struct Glyph {
+    pub offset: i32,
+    pub distance: i32,
+    pub width: u32,
+    pub height: u32,
+    pub alpha: Vec<u8>,
+}
+
+impl Glyph {
+    fn rgba(&self) -> Box<[u8]> {
+        self.alpha.iter().flat_map(|&a| [0xFF, 0xFF, 0xFF, a]).collect()
+    }
+}
+
 impl Font {
     pub fn get_char(&self, index: u8) -> Option<Character> {
         if let Some(index) = index.checked_sub(self.first) { self.chars.get(index as usize).copied() } else { None }
@@ -56,16 +73,25 @@ impl Font {
             _ => default,
         }
     }
+
+    /// Frees the textures of a font that owns its graphics, as made by font_add.
+    pub fn delete_graphics(&self, renderer: &mut Renderer) {
+        if self.own_graphics {
+            for c in self.chars.iter() {
+                renderer.delete_sprite(c.atlas_ref);
+            }
+        }
+    }
 }
 
-pub fn load_default_font(atlases: &mut AtlasBuilder) -> Result<Font, String> {
+/// The characters of the built-in default font, from 0x20 to 0x7F.
+fn default_font_glyphs() -> Vec<Glyph> {
     // In GM8, the default font is Arial at size 12, but Arial is nonfree,
     // so we instead went for a free alternative called Arimo, under Apache 2.0. https://fonts.google.com/specimen/Arimo
     // arimo.dat was generated by importing Arimo into GM8 and exporting the resulting font data.
     // The `offset` field was tweaked to be closer to Arial's.
     let data = include_bytes!("../../data/arimo.dat");
-    let mut chars = Vec::with_capacity(0x60);
-    let mut tallest_char_height = 0;
+    let mut glyphs = Vec::with_capacity(0x60);
     let mut cursor = 0;
     for _ in 0..0x60 {
         let offset = data[cursor] as i8 as i32;
@@ -73,21 +99,25 @@ pub fn load_default_font(atlases: &mut AtlasBuilder) -> Result<Font, String> {
         let width = data[cursor + 2] as u32;
         let height = data[cursor + 3] as u32;
         cursor += 4;
-        if height > tallest_char_height {
-            tallest_char_height = height;
-        }
         let size = (width * height) as usize;
-        let mut char = Vec::with_capacity(size * 4);
-        for i in 0..size {
-            char.extend_from_slice(&[0xFF, 0xFF, 0xFF]);
-            char.push(data[cursor + i]);
-        }
+        glyphs.push(Glyph { offset, distance, width, height, alpha: data[cursor..cursor + size].to_vec() });
         cursor += size;
-        let atlas_ref = atlases
-            .texture(width as _, height as _, 0, 0, char.into_boxed_slice())
-            .ok_or("Couldn't pack default font")?;
-        chars.push(Character { offset, distance, atlas_ref });
     }
+    glyphs
+}
+
+pub fn load_default_font(atlases: &mut AtlasBuilder) -> Result<Font, String> {
+    let glyphs = default_font_glyphs();
+    let tallest_char_height = glyphs.iter().map(|g| g.height).max().unwrap_or(0);
+    let chars = glyphs
+        .iter()
+        .map(|glyph| {
+            let atlas_ref = atlases
+                .texture(glyph.width as _, glyph.height as _, 0, 0, glyph.rgba())
+                .ok_or("Couldn't pack default font")?;
+            Ok(Character { offset: glyph.offset, distance: glyph.distance, atlas_ref })
+        })
+        .collect::<Result<Box<_>, String>>()?;
     Ok(Font {
         name: b"default_font".as_ref().into(),
         sys_name: b"Arimo".as_ref().into(),
@@ -98,11 +128,22 @@ pub fn load_default_font(atlases: &mut AtlasBuilder) -> Result<Font, String> {
         first: 0x20,
         last: 0x7f,
         tallest_char_height,
-        chars: chars.into_boxed_slice(),
+        chars,
         own_graphics: true,
     })
 }
 
+/// Uploads each glyph as its own texture, for fonts made at runtime.
+pub fn create_chars_from_glyphs(glyphs: &[Glyph], renderer: &mut Renderer) -> Result<Box<[Character]>, String> {
+    glyphs
+        .iter()
+        .map(|glyph| {
+            let atlas_ref = renderer.upload_sprite(glyph.rgba(), glyph.width as _, glyph.height as _, 0, 0)?;
+            Ok(Character { offset: glyph.offset, distance: glyph.distance, atlas_ref })
+        })
+        .collect()
+}
+
 pub fn create_chars_from_sprite(sprite: &Sprite, prop: bool, sep: i32, renderer: &Renderer) -> Box<[Character]> {
     let mut chars = Vec::with_capacity(sprite.frames.len());
     if prop {
@@ -0,0 +1,455 @@
+use super::Glyph;
+use ab_glyph_rasterizer::{point, Point, Rasterizer};
+use image::{imageops, GrayImage};
+use std::{
+    collections::{BTreeSet, HashMap},
+    env,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use ttf_parser::{name, name_id, os2, post, Face, OutlineBuilder, Style};
+
+/// How far synthetic italics lean to the right for every pixel up from the baseline.
+const ITALIC_SLANT: f32 = 0.2;
+
+/// Families to try, in order, when the one that was asked for can't be found. They all have Arial's metrics,
+/// like GDI's usual substitute does.
+const FALLBACK_FAMILIES: [&str; 3] = ["arial", "arimo", "liberation sans"];
+
+/// How deep to look into subfolders of a font directory.
+const MAX_DEPTH: u32 = 4;
+
+/// A face in one of the font files that were found.
+struct FaceEntry {
+    path: PathBuf,
+    index: u32,
+    families: Vec<String>, // lowercase
+    bold: bool,
+    italic: bool,
+}
+
+/// Finds font files by family name, looking in a chosen directory before the system's font directories.
+/// The directories are only read the first time a font is looked for.
+#[derive(Default)]
+pub struct FontLibrary {
+    directory: Option<PathBuf>,
+    directory_only: bool,
+    faces: Option<Vec<FaceEntry>>,
+    files: HashMap<PathBuf, Arc<[u8]>>,
+    missing: BTreeSet<String>,
+}
+
+/// A font file that's been read in, along with any styles it doesn't have that need faking.
+struct LoadedFace {
+    data: Arc<[u8]>,
+    index: u32,
+    fake_bold: bool,
+    fake_italic: bool,
+}
+
+impl FontLibrary {
+    /// With `directory_only`, the system's fonts are never used, so that what gets drawn doesn't depend on
+    /// which fonts happen to be installed. That's needed for recording and replaying.
+    pub fn new(directory: Option<PathBuf>, directory_only: bool) -> Self {
+        Self { directory, directory_only, ..Default::default() }
+    }
+
+    fn system_directories() -> Vec<PathBuf> {
+        let mut dirs = Vec::new();
+        if cfg!(target_os = "windows") {
+            if let Some(windir) = env::var_os("WINDIR") {
+                dirs.push(Path::new(&windir).join("Fonts"));
+            }
+            if let Some(local) = env::var_os("LOCALAPPDATA") {
+                dirs.push(Path::new(&local).join("Microsoft").join("Windows").join("Fonts"));
+            }
+        } else {
+            if let Some(home) = env::var_os("HOME") {
+                let home = Path::new(&home);
+                dirs.extend([home.join(".fonts"), home.join(".local/share/fonts"), home.join("Library/Fonts")]);
+            }
+            dirs.extend(
+                ["/usr/local/share/fonts", "/usr/share/fonts", "/Library/Fonts", "/System/Library/Fonts"]
+                    .iter()
+                    .map(PathBuf::from),
+            );
+        }
+        dirs
+    }
+
+    /// The `fonts` folder next to the executable, where the fallback fonts get shipped. It's used even when
+    /// recording or replaying, since it comes with the emulator rather than the system.
+    fn bundled_directory() -> Option<PathBuf> {
+        Some(env::current_exe().ok()?.parent()?.join("fonts"))
+    }
+
+    fn scan(dir: &Path, depth: u32, faces: &mut Vec<FaceEntry>) {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        let mut paths = entries.filter_map(|e| e.ok().map(|e| e.path())).collect::<Vec<_>>();
+        // Keep the order the same between runs so the same file always wins
+        paths.sort();
+        for path in paths {
+            if path.is_dir() {
+                if depth < MAX_DEPTH {
+                    Self::scan(&path, depth + 1, faces);
+                }
+                continue
+            }
+            let is_font = path
+                .extension()
+                .and_then(|x| x.to_str())
+                .is_some_and(|x| ["ttf", "otf", "ttc", "otc"].iter().any(|ext| x.eq_ignore_ascii_case(ext)));
+            if is_font {
+                // A broken file just doesn't have any usable faces
+                let _ = Self::read_faces(&path, faces);
+            }
+        }
+    }
+
+    /// Finds the family and style of each face in a font file. Only the tables that say so are read, rather
+    /// than every font on the system in full.
+    fn read_faces(path: &Path, faces: &mut Vec<FaceEntry>) -> io::Result<()> {
+        let mut file = File::open(path)?;
+        let header = read_at(&mut file, 0, 12)?;
+        let offsets = if header.starts_with(b"ttcf") {
+            let count = be_u32(&header, 8).unwrap_or(0);
+            let list = read_at(&mut file, 12, count.saturating_mul(4))?;
+            list.chunks_exact(4).filter_map(|x| be_u32(x, 0)).collect()
+        } else {
+            vec![0]
+        };
+        let stem = path.file_stem().and_then(|s| s.to_str()).map(str::to_lowercase);
+        for (index, offset) in offsets.into_iter().enumerate() {
+            let directory = read_at(&mut file, offset.into(), 12)?;
+            let table_count = directory.get(4..6).map_or(0, |x| u16::from_be_bytes([x[0], x[1]]));
+            let records = read_at(&mut file, u64::from(offset) + 12, u32::from(table_count) * 16)?;
+            let mut table = |tag: &[u8]| -> io::Result<Vec<u8>> {
+                match records.chunks_exact(16).find(|record| &record[..4] == tag) {
+                    Some(record) => {
+                        let (offset, len) = (be_u32(record, 8).unwrap_or(0), be_u32(record, 12).unwrap_or(0));
+                        read_at(&mut file, offset.into(), len)
+                    },
+                    None => Ok(Vec::new()),
+                }
+            };
+            let (name_data, os2_data, post_data) = (table(b"name")?, table(b"OS/2")?, table(b"post")?);
+            let names = match name::Table::parse(&name_data) {
+                Some(table) => table.names,
+                None => continue,
+            };
+            let mut families = names
+                .into_iter()
+                .filter(|n| n.name_id == name_id::FAMILY || n.name_id == name_id::TYPOGRAPHIC_FAMILY)
+                .filter_map(|n| n.to_string())
+                .map(|n| n.to_lowercase())
+                .collect::<Vec<_>>();
+            // Something like "arial.ttf" can also be asked for by its file name
+            families.extend(stem.clone());
+            families.sort();
+            families.dedup();
+            // The same checks as ttf_parser's Face::is_bold and Face::is_italic
+            let os2 = os2::Table::parse(&os2_data);
+            let bold = os2.is_some_and(|os2| os2.is_bold());
+            let italic = os2.is_some_and(|os2| os2.style() == Style::Italic)
+                || post::Table::parse(&post_data).is_some_and(|post| post.italic_angle != 0.0);
+            faces.push(FaceEntry { path: path.to_path_buf(), index: index as u32, families, bold, italic });
+        }
+        Ok(())
+    }
+
+    /// Finds the closest match to the given family and style, preferring to fake a style over having one that
+    /// wasn't asked for.
+    fn find(&mut self, family: &str, bold: bool, italic: bool) -> Option<LoadedFace> {
+        let (directory, directory_only) = (&self.directory, self.directory_only);
+        let faces = self.faces.get_or_insert_with(|| {
+            let mut faces = Vec::new();
+            let system = if directory_only { Vec::new() } else { Self::system_directories() };
+            for dir in directory.iter().cloned().chain(system).chain(Self::bundled_directory()) {
+                Self::scan(&dir, 0, &mut faces);
+            }
+            faces
+        });
+        let family = family.trim().to_lowercase();
+        let entry = faces.iter().filter(|f| f.families.contains(&family)).min_by_key(|f| {
+            let style_cost = |has: bool, wanted: bool| match (has, wanted) {
+                (true, false) => 2,
+                (false, true) => 1,
+                _ => 0,
+            };
+            style_cost(f.bold, bold) * 3 + style_cost(f.italic, italic)
+        })?;
+        // Fonts tend to get made more than once, so the files are kept around after the first time
+        let data = match self.files.get(&entry.path) {
+            Some(data) => data.clone(),
+            None => {
+                let data = Arc::<[u8]>::from(fs::read(&entry.path).ok()?);
+                self.files.insert(entry.path.clone(), data.clone());
+                data
+            },
+        };
+        Some(LoadedFace {
+            data,
+            index: entry.index,
+            fake_bold: bold && !entry.bold,
+            fake_italic: italic && !entry.italic,
+        })
+    }
+
+    /// Renders the characters from `first` to `last` (in Windows-1252) at the given point size, the way GDI
+    /// would for GM8. If neither the family nor any of the fallbacks are installed or bundled, the built-in
+    /// default font is scaled up to size instead.
+    pub fn render(&mut self, family: &str, size: u32, bold: bool, italic: bool, first: u8, last: u8) -> Vec<Glyph> {
+        let size = size.max(1);
+        let rendered = std::iter::once(family)
+            .chain(FALLBACK_FAMILIES)
+            .filter_map(|family| self.find(family, bold, italic))
+            .find_map(|face| render_face(&face, size, first, last));
+        rendered.unwrap_or_else(|| {
+            if self.directory_only && self.missing.insert(family.to_lowercase()) {
+                eprintln!(
+                    "Warning: font \"{}\" isn't in the --font-dir directory, so the default font is being used. \
+                     System fonts aren't used when recording or replaying.",
+                    family
+                );
+            }
+            render_default(size, bold, italic, first, last)
+        })
+    }
+}
+
+/// Reads up to `len` bytes from a point in a file, or fewer if it ends first.
+fn read_at(file: &mut File, offset: u64, len: u32) -> io::Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut data = Vec::new();
+    file.by_ref().take(len.into()).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+fn be_u32(data: &[u8], at: usize) -> Option<u32> {
+    data.get(at..at + 4).map(|x| u32::from_be_bytes([x[0], x[1], x[2], x[3]]))
+}
+
+/// One piece of a glyph's outline, in font units.
+enum Segment {
+    Line(Point, Point),
+    Quad(Point, Point, Point),
+    Cubic(Point, Point, Point, Point),
+}
+
+#[derive(Default)]
+struct Outline {
+    segments: Vec<Segment>,
+    start: Point,
+    last: Point,
+}
+
+impl OutlineBuilder for Outline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.start = point(x, y);
+        self.last = self.start;
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.segments.push(Segment::Line(self.last, point(x, y)));
+        self.last = point(x, y);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.segments.push(Segment::Quad(self.last, point(x1, y1), point(x, y)));
+        self.last = point(x, y);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.segments.push(Segment::Cubic(self.last, point(x1, y1), point(x2, y2), point(x, y)));
+        self.last = point(x, y);
+    }
+
+    fn close(&mut self) {
+        if self.last != self.start {
+            self.segments.push(Segment::Line(self.last, self.start));
+        }
+        self.last = self.start;
+    }
+}
+
+/// Thickens a glyph by a pixel to the right, which is how GDI makes bold out of a regular face.
+fn embolden(glyph: &mut Glyph) {
+    let width = glyph.width as usize;
+    for row in glyph.alpha.chunks_exact_mut(width) {
+        for x in (1..width).rev() {
+            row[x] = row[x].max(row[x - 1]);
+        }
+    }
+}
+
+fn render_face(loaded: &LoadedFace, size: u32, first: u8, last: u8) -> Option<Vec<Glyph>> {
+    let face = Face::parse(&loaded.data, loaded.index).ok()?;
+    // GM8 asks for the size in points, which Windows turns into an em height in pixels at 96 DPI
+    let scale = size as f32 * 96.0 / 72.0 / f32::from(face.units_per_em());
+    let (ascender, descender) = match face.tables().os2 {
+        Some(os2) => (os2.windows_ascender(), -os2.windows_descender()),
+        None => (face.ascender(), face.descender()),
+    };
+    let ascent = (f32::from(ascender) * scale).round();
+    let height = (ascent - (f32::from(descender) * scale).round()).max(1.0) as u32;
+    let slant = if loaded.fake_italic { ITALIC_SLANT } else { 0.0 };
+    let extra_width = i32::from(loaded.fake_bold);
+
+    let glyphs = (first..=last)
+        .map(|code| {
+            let bytes = [code];
+            let (text, _) = encoding_rs::WINDOWS_1252.decode_without_bom_handling(&bytes);
+            let id = text.chars().next().and_then(|c| face.glyph_index(c));
+            let advance = id.and_then(|id| face.glyph_hor_advance(id)).map_or(0.0, |a| f32::from(a) * scale);
+            let offset = advance.round() as i32 + extra_width;
+
+            let mut outline = Outline::default();
+            let bounds = match id.and_then(|id| face.outline_glyph(id, &mut outline)) {
+                Some(bounds) => bounds,
+                None => {
+                    let alpha = vec![0; height as usize];
+                    return Glyph { offset, distance: 0, width: 1, height, alpha }
+                },
+            };
+
+            // Leaning only ever moves things right, so the left edge comes from the bottom and the right from the top
+            let left = (f32::from(bounds.x_min) + slant * f32::from(bounds.y_min)) * scale;
+            let right = (f32::from(bounds.x_max) + slant * f32::from(bounds.y_max)) * scale;
+            let distance = left.floor() as i32;
+            let width = (right.ceil() as i32 - distance + extra_width).max(1) as u32;
+            let transform = |p: Point| point((p.x + slant * p.y) * scale - distance as f32, ascent - p.y * scale);
+
+            let mut rasterizer = Rasterizer::new(width as usize, height as usize);
+            for segment in &outline.segments {
+                match *segment {
+                    Segment::Line(p0, p1) => rasterizer.draw_line(transform(p0), transform(p1)),
+                    Segment::Quad(p0, p1, p2) => rasterizer.draw_quad(transform(p0), transform(p1), transform(p2)),
+                    Segment::Cubic(p0, p1, p2, p3) => {
+                        rasterizer.draw_cubic(transform(p0), transform(p1), transform(p2), transform(p3))
+                    },
+                }
+            }
+            let mut alpha = vec![0; (width * height) as usize];
+            rasterizer.for_each_pixel_2d(|x, y, a| {
+                alpha[(y * width + x) as usize] = (a.clamp(0.0, 1.0) * 255.0).round() as u8;
+            });
+            let mut glyph = Glyph { offset, distance, width, height, alpha };
+            if loaded.fake_bold {
+                embolden(&mut glyph);
+            }
+            glyph
+        })
+        .collect();
+    Some(glyphs)
+}
+
+/// Makes the characters out of the built-in default font (which is size 12) when there's no font file to use.
+/// Anything outside of its range comes out blank.
+fn render_default(size: u32, bold: bool, italic: bool, first: u8, last: u8) -> Vec<Glyph> {
+    let scale = size as f64 / 12.0;
+    let scaled = |n: i32| (f64::from(n) * scale).round() as i32;
+    let defaults = super::default_font_glyphs();
+    let height = defaults.iter().map(|g| g.height).max().map_or(1, |h| scaled(h as i32).max(1) as u32);
+    (first..=last)
+        .map(|code| {
+            let default = match code.checked_sub(0x20).and_then(|i| defaults.get(usize::from(i))) {
+                Some(default) if default.width > 0 && default.height > 0 => default,
+                other => {
+                    let offset = other.map_or(0, |g| scaled(g.offset));
+                    return Glyph { offset, distance: 0, width: 1, height, alpha: vec![0; height as usize] }
+                },
+            };
+            let image = GrayImage::from_raw(default.width, default.height, default.alpha.clone()).unwrap();
+            let width = scaled(default.width as i32).max(1) as u32;
+            let glyph_height = scaled(default.height as i32).max(1) as u32;
+            let mut image = imageops::resize(&image, width, glyph_height, imageops::FilterType::Triangle);
+            if italic {
+                // Shift each row over by how far it is above the baseline, which is about 80% of the way down
+                let baseline = glyph_height as f32 * 0.8;
+                let lean = (ITALIC_SLANT * baseline).ceil() as u32;
+                let mut leaned = GrayImage::new(width + lean, glyph_height);
+                for (y, row) in image.rows().enumerate() {
+                    let shift = (ITALIC_SLANT * (baseline - y as f32)).round().clamp(0.0, lean as f32) as u32;
+                    for (x, pixel) in row.enumerate() {
+                        leaned.put_pixel(x as u32 + shift, y as u32, *pixel);
+                    }
+                }
+                image = leaned;
+            }
+            let mut glyph = Glyph {
+                offset: scaled(default.offset) + i32::from(bold),
+                distance: scaled(default.distance),
+                width: image.width() + u32::from(bold),
+                height: glyph_height,
+                alpha: Vec::new(),
+            };
+            let mut canvas = GrayImage::new(glyph.width, glyph.height);
+            imageops::overlay(&mut canvas, &image, 0, 0);
+            glyph.alpha = canvas.into_raw();
+            if bold {
+                embolden(&mut glyph);
+            }
+            glyph
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A face with nothing in it but a name table giving its family.
+    fn named_face(family: &str, offset: u32) -> Vec<u8> {
+        let name = family.encode_utf16().flat_map(u16::to_be_bytes).collect::<Vec<u8>>();
+        let mut table = Vec::new();
+        for x in [0u16, 1, 6 + 12, 3, 1, 0x409, name_id::FAMILY, name.len() as u16, 0] {
+            table.extend_from_slice(&x.to_be_bytes());
+        }
+        table.extend_from_slice(&name);
+        let mut face = Vec::new();
+        face.extend_from_slice(&0x00010000u32.to_be_bytes());
+        face.extend_from_slice(&[0, 1, 0, 16, 0, 0, 0, 0]);
+        face.extend_from_slice(b"name");
+        for x in [0, offset + 28, table.len() as u32] {
+            face.extend_from_slice(&x.to_be_bytes());
+        }
+        face.extend_from_slice(&table);
+        face
+    }
+
+    #[test]
+    fn finds_faces_by_name() {
+        let dir = std::env::temp_dir().join(format!("gm8emulator-fonts-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Single.ttf"), named_face("Test Sans", 0)).unwrap();
+        let second = named_face("Other Sans", 0).len() as u32;
+        let mut collection = b"ttcf\0\x01\0\0\0\0\0\x02".to_vec();
+        collection.extend_from_slice(&20u32.to_be_bytes());
+        collection.extend_from_slice(&(20 + second).to_be_bytes());
+        collection.extend(named_face("Other Sans", 20));
+        collection.extend(named_face("Third Sans", 20 + second));
+        fs::write(dir.join("pair.ttc"), collection).unwrap();
+        fs::write(dir.join("broken.ttf"), b"not a font").unwrap();
+
+        let mut library = FontLibrary::new(Some(dir.clone()), true);
+        library.find("nothing", false, false);
+        let faces = library.faces.as_ref().unwrap();
+        let found = faces.iter().map(|f| (f.families.clone(), f.index)).collect::<Vec<_>>();
+        assert_eq!(found, [
+            (vec!["single".to_string(), "test sans".to_string()], 0),
+            (vec!["other sans".to_string(), "pair".to_string()], 0),
+            (vec!["pair".to_string(), "third sans".to_string()], 1),
+        ]);
+        assert!(library.files.is_empty());
+
+        let face = library.find("Third Sans", false, true).unwrap();
+        assert_eq!((face.index, face.fake_bold, face.fake_italic), (1, false, true));
+        assert_eq!(library.files.len(), 1);
+        fs::remove_dir_all(dir).ok();
+    }
+}
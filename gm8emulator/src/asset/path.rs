@@ -31,6 +31,10 @@ impl Path {
     /// Generates a new set of control nodes for this Path and updates its start, end and length
     pub fn update(&mut self) {
         self.control_nodes.clear(); // since you can dynamically add path points...
+        // ...and remove them, in which case nothing below sets these
+        self.length = Real::from(0.0);
+        self.start = Default::default();
+        self.end = Default::default();
         if self.curve {
             if let (Some(&first_point), Some(&last_point)) = (self.points.first(), self.points.last()) {
                 if !self.closed {
@@ -178,3 +182,39 @@ impl Point {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_when_emptied() {
+        let point = |x: f64, y: f64| Point { x: x.into(), y: y.into(), speed: 100.into() };
+        for curve in [false, true] {
+            let mut path = Path {
+                name: "path".into(),
+                points: vec![point(10.0, 20.0), point(50.0, 20.0), point(50.0, 60.0)],
+                control_nodes: Vec::new(),
+                length: 0.into(),
+                curve,
+                closed: false,
+                precision: 4,
+                start: Default::default(),
+                end: Default::default(),
+            };
+            path.update();
+            assert!(path.length > 0.into());
+            assert_eq!(path.start.x, Real::from(10.0));
+
+            while !path.points.is_empty() {
+                path.points.remove(0);
+                path.update();
+            }
+            assert!(path.control_nodes.is_empty());
+            assert_eq!(path.length, Real::from(0.0));
+            assert_eq!(path.start.x, Real::from(0.0));
+            assert_eq!(path.end.y, Real::from(0.0));
+            assert_eq!(path.get_point(Real::from(0.5)).x, Real::from(0.0));
+        }
+    }
+}
@@ -12,6 +12,7 @@ pub struct Sprite {
     pub origin_x: i32,
     pub origin_y: i32,
     pub per_frame_colliders: bool,
+    pub mask: MaskSettings,
     pub bbox_left: u32,
     pub bbox_right: u32,
     pub bbox_top: u32,
@@ -33,6 +34,28 @@ pub struct BoundingBox {
     pub bottom: u32,
 }
 
+/// The collision mask settings a sprite was made with, so its mask can be made again when frames are added.
+/// Sprites that come with the game don't have theirs saved, so they count as precise with an automatic bbox.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct MaskSettings {
+    pub kind: i32,
+    pub tolerance: u8,
+    pub bbox_mode: i32,
+    pub bbox_left: i32,
+    pub bbox_top: i32,
+    pub bbox_right: i32,
+    pub bbox_bottom: i32,
+}
+
+impl MaskSettings {
+    /// Makes colliders for frames of the given size, as `make_colliders` does.
+    pub fn make_colliders(&self, frames: &[RgbaImage], sepmasks: bool, width: u32, height: u32) -> Vec<Collider> {
+        let (left, top, right, bottom) = (self.bbox_left, self.bbox_top, self.bbox_right, self.bbox_bottom);
+        let bbox = mask_bbox(self.bbox_mode, left, top, right, bottom, width, height);
+        make_colliders(frames, self.tolerance, sepmasks, bbox, self.kind)
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Collider {
     pub width: u32,
@@ -273,6 +296,67 @@ pub fn make_colliders_shaped(
         .collect()
 }
 
+/// Works out the bounding box for a collision mask's settings: mode 0 is automatic (giving `None`),
+/// 1 is the full image and 2 is the box that's given.
+pub fn mask_bbox(
+    mode: i32,
+    left: i32,
+    top: i32,
+    right: i32,
+    bottom: i32,
+    width: u32,
+    height: u32,
+) -> Option<BoundingBox> {
+    match mode {
+        0 => None, // automatic
+        1 => Some(BoundingBox {
+            // full image
+            left: 0,
+            right: width.saturating_sub(1),
+            top: 0,
+            bottom: height.saturating_sub(1),
+        }),
+        _ => Some(BoundingBox {
+            // user defined
+            left: left.max(0) as u32,
+            right: (right.max(0) as u32).min(width.saturating_sub(1)),
+            top: top.max(0) as u32,
+            bottom: (bottom.max(0) as u32).min(height.saturating_sub(1)),
+        }),
+    }
+}
+
+/// Makes colliders from a collision mask's settings. `kind` is 0 for precise, 1 for a rectangle, 2 for an ellipse
+/// and 3 for a diamond.
+pub fn make_colliders(
+    frames: &[RgbaImage],
+    tolerance: u8,
+    sepmasks: bool,
+    bbox: Option<BoundingBox>,
+    kind: i32,
+) -> Vec<Collider> {
+    let mut colliders = match kind {
+        0 => make_colliders_precise(frames, tolerance, sepmasks), // precise
+        _ => make_colliders_shaped(frames, tolerance, sepmasks, bbox, match kind {
+            1 => Some(ColliderShape::Rectangle),
+            2 => Some(ColliderShape::Ellipse),
+            3 => Some(ColliderShape::Diamond),
+            _ => None,
+        }),
+    };
+
+    // set bbox variables manually if needed (even if using precise collision)
+    if let Some(bbox) = bbox {
+        for c in &mut colliders {
+            c.bbox_left = bbox.left;
+            c.bbox_top = bbox.top;
+            c.bbox_right = bbox.right;
+            c.bbox_bottom = bbox.bottom;
+        }
+    }
+    colliders
+}
+
 // used for adding frames to sprites
 pub fn scale(input: &mut RgbaImage, width: u32, height: u32) {
     if input.dimensions() != (width, height) {
@@ -310,6 +394,14 @@ impl Sprite {
     pub fn get_atlas_ref(&self, image_index: i32) -> Option<AtlasRef> {
         Some(self.get_frame(image_index)?.atlas_ref)
     }
+
+    /// Sets the sprite's bounding box to cover all of its colliders.
+    pub fn update_bbox(&mut self) {
+        self.bbox_left = self.colliders.iter().map(|c| c.bbox_left).min().unwrap_or(0);
+        self.bbox_top = self.colliders.iter().map(|c| c.bbox_top).min().unwrap_or(0);
+        self.bbox_right = self.colliders.iter().map(|c| c.bbox_right).max().unwrap_or(0);
+        self.bbox_bottom = self.colliders.iter().map(|c| c.bbox_bottom).max().unwrap_or(0);
+    }
 }
 
 impl Collider {
@@ -327,3 +419,22 @@ impl Collider {
             && self.data.get((y as usize * self.width as usize) + x as usize).copied().unwrap_or(false)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edges(bbox: Option<BoundingBox>) -> Option<(u32, u32, u32, u32)> {
+        bbox.map(|b| (b.left, b.top, b.right, b.bottom))
+    }
+
+    #[test]
+    fn bbox_modes() {
+        assert!(mask_bbox(0, 1, 2, 3, 4, 16, 8).is_none());
+        assert_eq!(edges(mask_bbox(1, 1, 2, 3, 4, 16, 8)), Some((0, 0, 15, 7)));
+        assert_eq!(edges(mask_bbox(2, 1, 2, 3, 4, 16, 8)), Some((1, 2, 3, 4)));
+        // Anything outside the image is brought back inside it
+        assert_eq!(edges(mask_bbox(2, -5, -5, 100, 100, 16, 8)), Some((0, 0, 15, 7)));
+        assert_eq!(edges(mask_bbox(2, 0, 0, -1, -1, 16, 8)), Some((0, 0, 0, 0)));
+    }
+}
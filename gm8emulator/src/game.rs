@@ -1,20 +1,28 @@
 pub mod audio;
 pub mod background;
+pub mod dialog;
 pub mod draw;
 pub mod events;
 pub mod external;
 pub mod gm_save;
+pub mod highscore;
 pub mod includedfile;
+pub mod mci;
+pub mod message;
 pub mod model;
 pub mod movement;
 pub mod particle;
 pub mod pathfinding;
 pub mod platform;
 pub mod recording;
+pub mod registry;
 pub mod replay;
+pub mod richtext;
 pub mod savestate;
+pub mod splash;
 pub mod surface;
 pub mod transition;
+pub mod video;
 pub mod view;
 
 pub use background::Background;
@@ -26,7 +34,7 @@ use crate::{
     action::Tree,
     asset::{
         self,
-        font::{Character, Font},
+        font::{truetype::FontLibrary, Character, Font},
         path::{self, Path},
         room::{self, Room},
         sprite::{Collider, Frame, Sprite},
@@ -34,7 +42,7 @@ use crate::{
         Object, Script, Sound, Timeline,
     },
     game::gm_save::GMSave,
-    gml::{self, ds, ev, file, rand::Random, runtime::Instruction, Compiler, Context},
+    gml::{self, debugger, ds, ev, file, network, profiler, rand::Random, runtime::Instruction, Compiler, Context},
     handleman::{HandleArray, HandleList, HandleManager},
     input::{self, Input},
     instance::{DummyFieldHolder, Instance, InstanceState},
@@ -163,6 +171,8 @@ pub struct Game {
     pub ds_precision: Real,
 
     pub default_font: Font,
+    pub font_library: FontLibrary,
+    pub message_art: message::Art,
     pub draw_font_id: ID,
     pub draw_colour: Colour,
     pub draw_alpha: Real,
@@ -198,6 +208,21 @@ pub struct Game {
 
     pub error_occurred: bool,
     pub error_last: gml::String,
+    pub show_errors: bool,
+    pub log_errors: bool,
+    pub abort_on_error: bool,
+    pub dialog_hook: Option<dialog::DialogHook>,
+    pub message_style: message::Style,
+    pub registry: registry::Registry,
+    pub highscores: highscore::Highscores,
+    pub splash: splash::Settings,
+    pub game_info: splash::GameInfo,
+    pub splash_overlay: Option<splash::Overlay>,
+
+    pub call_stack: Vec<debugger::Frame>,
+    pub debugger: debugger::Debugger,
+    pub profiler: Option<profiler::Profiler>,
+    pub video_dump: Option<video::VideoDump>,
 
     pub game_id: i32,
     pub program_directory: gml::String,
@@ -207,11 +232,14 @@ pub struct Game {
     pub open_ini: Option<(ini::Ini, gml::String)>, // keep the filename for writing
     pub open_file: Option<file::TextHandle>,       // for legacy file functions from GM <= 5.1
     pub file_finder: Option<Box<dyn Iterator<Item = PathBuf>>>,
+    pub mplay: network::Multiplayer,
+    pub mci: mci::State,
     pub clock: GameClock,
     pub parameters: Vec<String>,
     pub encoding: &'static Encoding,
 
     pub esc_close_game: bool,
+    pub f1_help_menu: bool,
 
     pub play_type: PlayType,
     pub stored_events: VecDeque<replay::Event>,
@@ -334,6 +362,7 @@ impl Game {
         frame_limiter: bool,
         frame_limit_at: usize,
         play_type: PlayType,
+        emulate_dlls: bool,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         // Parse file path
         let mut file_path2 = file_path.clone();
@@ -372,6 +401,7 @@ impl Game {
             constants,
             extensions,
             fonts,
+            help_dialog,
             included_files,
             last_instance_id,
             last_tile_id,
@@ -650,7 +680,9 @@ impl Game {
 
         let default_font = asset::font::load_default_font(&mut atlases)?;
 
-        let mut externals = external::ExternalManager::new(play_type == PlayType::Record);
+        let message_art = message::load_art(&mut atlases)?;
+
+        let mut externals = external::ExternalManager::new(play_type == PlayType::Record, emulate_dlls);
 
         // Code compiling starts here. The order in which things are compiled is important for
         // keeping savestates compatible. This isn't 100% accurate right now, but it's mostly right.
@@ -808,6 +840,16 @@ impl Game {
                                     FileType::None
                                 },
                             },
+                            b".mid" | b".midi" => match audio.add_midi(data, sound_id as i32, b.volume) {
+                                Some(x) => FileType::Midi(x),
+                                None => {
+                                    println!(
+                                        "WARNING: invalid midi data in sound '{}'",
+                                        String::from_utf8_lossy(b.name.0.as_ref())
+                                    );
+                                    FileType::None
+                                },
+                            },
                             _ => FileType::None,
                         },
                         None => FileType::None,
@@ -866,6 +908,7 @@ impl Game {
                         origin_x,
                         origin_y,
                         per_frame_colliders: b.per_frame_colliders,
+                        mask: Default::default(),
                         bbox_left,
                         bbox_right,
                         bbox_top,
@@ -1286,6 +1329,8 @@ impl Game {
             mpgrids: HandleList::new(),
             ds_precision: Real::from(0.00000001),
             default_font,
+            font_library: Default::default(),
+            message_art,
             draw_font_id: -1,
             draw_colour: Colour::new(0.0, 0.0, 0.0),
             draw_alpha: Real::from(1.0),
@@ -1320,6 +1365,8 @@ impl Game {
             open_ini: None,
             open_file: None,
             file_finder: None,
+            mplay: network::Multiplayer::new(game_id as i32),
+            mci: mci::State::new(),
             clock: GameClock::SpoofedNanos(0),  // to avoid accessing the system timer for now
             frame_limiter,
             frame_limit_at,
@@ -1328,12 +1375,35 @@ impl Game {
             parameters: game_arguments,
             encoding,
             esc_close_game: settings.esc_close_game,
+            f1_help_menu: settings.f1_help_menu,
             score_capt_d: true,
             has_set_show_score: false,
             lives_capt_d: false,
             health_capt_d: false,
             error_occurred: false,
             error_last: "".to_string().into(),
+            show_errors: settings.show_error_messages,
+            log_errors: settings.log_errors,
+            abort_on_error: settings.always_abort,
+            dialog_hook: None,
+            message_style: Default::default(),
+            registry: match play_type {
+                // Recordings and replays always start from an empty registry, so that they play out the same way
+                PlayType::Normal => registry::Registry::load(file_path.with_extension("gmreg")),
+                _ => Default::default(),
+            },
+            highscores: match play_type {
+                // Same as the registry
+                PlayType::Normal => highscore::Highscores::load(file_path.with_extension("gmhs")),
+                _ => Default::default(),
+            },
+            splash: Default::default(),
+            game_info: help_dialog.into(),
+            splash_overlay: None,
+            call_stack: Vec::new(),
+            debugger: Default::default(),
+            profiler: None,
+            video_dump: None,
             audio,
             window,
             window_border,
@@ -1674,7 +1744,8 @@ impl Game {
                 // Run this instance's room creation code
                 let mut new_context = Context::with_single_instance(*handle);
                 new_context.event_object = instance.object;
-                self.execute(&instance.creation.clone()?, &mut new_context)?;
+                let code_source = debugger::CodeSource::InstanceCreation(instance.id as _);
+                self.execute_frame(code_source, &instance.creation.clone()?, &mut new_context)?;
 
                 if !self.swap_creation_events {
                     // Run create event for this instance
@@ -1696,7 +1767,7 @@ impl Game {
                 .instance_list
                 .insert_dummy(Instance::new_dummy(self.assets.objects.get_asset(0).map(|x| x.as_ref())));
             let mut new_context = Context::with_single_instance(dummy_instance);
-            self.execute(&room.creation_code?, &mut new_context)?;
+            self.execute_frame(debugger::CodeSource::RoomCreation(room_id), &room.creation_code?, &mut new_context)?;
             self.room.instance_list.remove_dummy(dummy_instance);
         }
 
@@ -1749,7 +1820,7 @@ impl Game {
                         }
                         transition(self, trans_surf_old, trans_surf_new, width as _, height as _, progress)?;
                         if self.play_type != PlayType::Record {
-                            self.renderer.present(width, height, self.scaling);
+                            self.present(width, height)?;
                             let diff = current_time.elapsed();
                             if let Some(dur) = FRAME_TIME.checked_sub(diff) {
                                 gml::datetime::sleep(dur);
@@ -1820,6 +1891,28 @@ impl Game {
             self.scene_change = Some(SceneChange::End);
             return Ok(())
         }
+        if self.f1_help_menu && self.input.keyboard_check_pressed(input::Button::F1 as u8) {
+            self.show_game_info();
+        }
+
+        // Keep any multiplayer session going, even if the game isn't calling mplay functions right now
+        self.mplay.poll();
+
+        // Write out the audio up to now if it's being dumped, before this frame starts any more of it
+        if let Err(e) = self.audio.render_wav_dump(self.clock.as_nanos()) {
+            return Err(gml::Error::Dump(format!("couldn't write the audio dump: {}", e)))
+        }
+
+        // Move any fading sounds' volumes along
+        for (sound_id, volume) in self.audio.fade_volumes(self.clock.as_nanos()) {
+            if let Some(sound) = self.assets.sounds.get_asset(sound_id) {
+                match &sound.handle {
+                    asset::sound::FileType::Wav(handle) => handle.set_volume(volume),
+                    asset::sound::FileType::Midi(handle) => handle.set_volume(volume),
+                    _ => (),
+                }
+            }
+        }
 
         // Update xprevious and yprevious for all instances
         let mut iter = self.room.instance_list.iter_by_drawing();
@@ -2011,7 +2104,7 @@ impl Game {
 
         // Tell renderer to finish the frame
         if self.auto_draw && self.scene_change.is_none() && self.play_type != PlayType::Record {
-            self.renderer.present(self.window_inner_size.0, self.window_inner_size.1, self.scaling);
+            self.present(self.window_inner_size.0, self.window_inner_size.1)?;
         }
 
         // Clear inputs for this frame
@@ -2067,9 +2160,19 @@ impl Game {
                     })
                     .collect::<Vec<_>>()
             };
+            let type_return = external.signature.type_return;
             match &external.call {
                 external::Call::Dummy(x) => Ok(x.clone()),
-                &external::Call::Emulated(func) => func.invoke(self, context, args),
+                &external::Call::Emulated(func) => {
+                    // The game only ever sees what the DLL was declared to return
+                    func.invoke(self, context, args).map(|value| match (value, type_return) {
+                        (value @ gml::Value::Real(_), dll::ValueType::Str) => value.repr().into(),
+                        (gml::Value::Str(s), dll::ValueType::Real) => {
+                            String::from_utf8_lossy(s.as_ref()).trim().parse::<f64>().unwrap_or(0.0).into()
+                        },
+                        (value, _) => value,
+                    })
+                },
                 external::Call::Native(_) => {
                     let args = convert_args();
                     Ok(self.externals.call_native(id as _, &args).into())
@@ -2103,7 +2206,7 @@ impl Game {
             Some(ExtensionFunction::Gml(gml)) => {
                 let instructions = gml.clone();
                 let mut context = Context::copy_with_args(context, args, arg_count);
-                self.execute(&instructions, &mut context)?;
+                self.execute_frame(debugger::CodeSource::Extension(id), &instructions, &mut context)?;
                 Ok(context.return_value)
             },
             None => Ok(Default::default()), // unfortunately required
@@ -2119,7 +2222,8 @@ impl Game {
                 .instance_list
                 .insert_dummy(Instance::new_dummy(self.assets.objects.get_asset(0).map(|x| x.as_ref())));
             let instructions = self.compiler.compile(&self.library_init_strings[i])?;
-            self.execute(&instructions, &mut Context::with_single_instance(dummy_instance))?;
+            let mut context = Context::with_single_instance(dummy_instance);
+            self.execute_frame(debugger::CodeSource::Library(i), &instructions, &mut context)?;
             self.room.instance_list.remove_dummy(dummy_instance);
         }
 
@@ -2206,7 +2310,7 @@ impl Game {
     }
 
     // Replays some recorded inputs to the game
-    pub fn replay(mut self, replay: Replay, output_bin: Option<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn replay(&mut self, replay: Replay, output_bin: Option<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
         let mut frame_count: usize = 0;
         self.rand.set_seed(replay.start_seed);
         self.clock = GameClock::SpoofedNanos(replay.start_time);
@@ -2262,7 +2366,7 @@ impl Game {
                 }
             } else if let Some(bin) = &output_bin {
                 let render_state = self.renderer.state();
-                match SaveState::from(&mut self, replay.clone(), render_state)
+                match SaveState::from(self, replay.clone(), render_state)
                     .save_to_file(bin, &mut savestate::Buffer::new())
                 {
                     Ok(()) => break Ok(()),
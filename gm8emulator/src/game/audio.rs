@@ -1,11 +1,19 @@
+mod dump;
+mod effect;
+mod midi;
 mod mixer;
 mod mp3;
+mod ogg;
+mod sound3d;
+mod voice;
 
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
+    io,
+    path::Path,
     sync::{
-        atomic::{AtomicU32, Ordering},
+        atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicU64, Ordering},
         Arc,
     },
 };
@@ -19,8 +27,17 @@ use udon::{
 };
 
 use self::{
+    dump::WavDump,
+    midi::{Sequencer, Song},
     mixer::{Mixer, MixerHandle},
     mp3::Mp3Player,
+    voice::Varispeed,
+};
+
+pub use self::{
+    effect::{Compressor, Echo, Effects, Equalizer, Gargle, Modulation, Reverb},
+    sound3d::Sound3D,
+    voice::{Clip, Voice},
 };
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -29,11 +46,36 @@ pub struct Mp3Handle {
     id: i32,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MidiHandle {
+    song: Arc<Song>,
+    volume: Arc<AtomicU32>,
+    id: i32,
+}
+
+/// The midi that's playing, kept so it can be picked up from the right place when a state is loaded.
+/// Its handle is looked up again from the sound then, so the song and volume stay shared with the asset.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct MidiPlayback {
+    id: i32,
+    start_time: u128,
+    looping: bool,
+}
+
+/// A sound's volume changing over time, from sound_fade.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct Fade {
+    from: f64,
+    to: f64,
+    start_time: u128,
+    duration: u128,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct WavHandle {
     player: WavPlayer,
     params: Arc<SoundParams>,
-    _use_3d: bool,
+    use_3d: bool,
     exclusive: bool,
     id: i32,
 }
@@ -41,6 +83,30 @@ pub struct WavHandle {
 #[derive(Serialize, Deserialize)]
 pub struct SoundParams {
     pub volume: AtomicU32,
+    pub pan: AtomicU32,
+    pub attenuation: AtomicU32,
+
+    // Only voices and 3D sounds follow these
+    pub pitch: AtomicU32,
+    pub paused: AtomicBool,
+    pub loops: AtomicI32,
+    pub seek: AtomicU64,
+    pub end: AtomicU64,
+}
+
+impl SoundParams {
+    fn new(volume: f32) -> Self {
+        Self {
+            volume: AtomicU32::new(volume.to_bits()),
+            pan: AtomicU32::new(0.0f32.to_bits()),
+            attenuation: AtomicU32::new(1.0f32.to_bits()),
+            pitch: AtomicU32::new(1.0f32.to_bits()),
+            paused: AtomicBool::new(false),
+            loops: AtomicI32::new(0),
+            seek: AtomicU64::new(voice::NO_SEEK),
+            end: AtomicU64::new(voice::NO_END),
+        }
+    }
 }
 
 pub struct AudioManager {
@@ -51,6 +117,12 @@ pub struct AudioManager {
     global_volume: Arc<AtomicU32>,
     end_times: HashMap<i32, Option<u128>>,
     multimedia_end: Option<(i32, Option<u128>)>,
+    midi: Option<MidiPlayback>,
+    effects: HashMap<i32, Effects>,
+    fades: HashMap<i32, Fade>,
+    sounds_3d: HashMap<i32, Sound3D>,
+    next_voice_id: i32,
+    wav_dump: Option<WavDump>,
 }
 
 impl AudioManager {
@@ -68,6 +140,24 @@ impl AudioManager {
             stream.play(mixer).unwrap();
         });
 
+        Self::with_mixer(mixer_handle, sample_rate, channel_count, global_volume, do_output)
+    }
+
+    /// An AudioManager with nothing listening to it, for tests.
+    #[cfg(test)]
+    pub fn silent() -> Self {
+        let global_volume = Arc::new(AtomicU32::from(1.0f32.to_bits()));
+        let (_, mixer_handle) = Mixer::new(WavDump::sample_rate(), WavDump::channel_count(), global_volume.clone());
+        Self::with_mixer(mixer_handle, WavDump::sample_rate(), WavDump::channel_count(), global_volume, false)
+    }
+
+    fn with_mixer(
+        mixer_handle: MixerHandle,
+        sample_rate: SampleRate,
+        channel_count: ChannelCount,
+        global_volume: Arc<AtomicU32>,
+        do_output: bool,
+    ) -> Self {
         Self {
             mixer_handle,
             mixer_channel_count: channel_count,
@@ -76,6 +166,41 @@ impl AudioManager {
             global_volume,
             end_times: HashMap::new(),
             multimedia_end: None,
+            midi: None,
+            effects: HashMap::new(),
+            fades: HashMap::new(),
+            sounds_3d: HashMap::new(),
+            next_voice_id: -1,
+            wav_dump: None,
+        }
+    }
+
+    /// Sends all the audio into a WAV file from now on, instead of the audio device.
+    /// It only gets written when `render_wav_dump` is called, so it follows the game clock.
+    pub fn start_wav_dump(&mut self, path: &Path) -> io::Result<()> {
+        let (mixer, mixer_handle) =
+            Mixer::new(WavDump::sample_rate(), WavDump::channel_count(), self.global_volume.clone());
+        self.wav_dump = Some(WavDump::new(mixer, path)?);
+        self.mixer_handle = mixer_handle;
+        self.mixer_sample_rate = WavDump::sample_rate();
+        self.mixer_channel_count = WavDump::channel_count();
+        self.do_output = true;
+        Ok(())
+    }
+
+    /// Writes the audio up to the given time to the WAV file, if there is one.
+    pub fn render_wav_dump(&mut self, now: u128) -> io::Result<()> {
+        match &mut self.wav_dump {
+            Some(dump) => dump.render(now),
+            None => Ok(()),
+        }
+    }
+
+    /// Completes the WAV file, if there is one. Nothing more gets written to it after this.
+    pub fn finish_wav_dump(&mut self) -> io::Result<()> {
+        match self.wav_dump.take() {
+            Some(dump) => dump.finish(),
+            None => Ok(()),
         }
     }
 
@@ -83,6 +208,14 @@ impl AudioManager {
         Mp3Player::new(file).map(|player| Mp3Handle { player, id: sound_id }).ok()
     }
 
+    pub fn add_midi(&mut self, file: Box<[u8]>, sound_id: i32, volume: f64) -> Option<MidiHandle> {
+        Song::new(&file).map(|song| MidiHandle {
+            song: Arc::new(song),
+            volume: Arc::new(AtomicU32::new(make_volume(volume).to_bits())),
+            id: sound_id,
+        })
+    }
+
     pub fn add_wav(
         &mut self,
         file: Box<[u8]>,
@@ -94,8 +227,8 @@ impl AudioManager {
         WavPlayer::new(file)
             .map(|player| WavHandle {
                 player,
-                params: Arc::new(SoundParams { volume: AtomicU32::new(make_volume(volume).to_bits()) }),
-                _use_3d: use_3d,
+                params: Arc::new(SoundParams::new(make_volume(volume))),
+                use_3d,
                 exclusive,
                 id: sound_id,
             })
@@ -120,6 +253,45 @@ impl AudioManager {
         }
     }
 
+    pub fn play_midi(&mut self, handle: &MidiHandle, start_time: u128) {
+        let end_time = length_to_ns(handle.song.length() as usize, midi::SAMPLE_RATE, 1) + start_time;
+        self.multimedia_end = Some((handle.id, Some(end_time)));
+        self.midi = Some(MidiPlayback { id: handle.id, start_time, looping: false });
+        self.start_midi(handle, start_time);
+    }
+
+    pub fn loop_midi(&mut self, handle: &MidiHandle, start_time: u128) {
+        self.multimedia_end = Some((handle.id, None));
+        self.midi = Some(MidiPlayback { id: handle.id, start_time, looping: true });
+        self.start_midi(handle, start_time);
+    }
+
+    /// The sound whose midi should be playing, so its handle can be passed to `resume_midi` after loading a state.
+    pub fn midi_id(&self) -> Option<i32> {
+        self.midi.map(|midi| midi.id)
+    }
+
+    /// Carries on with the midi from a loaded state, from wherever it should be up to by now.
+    pub fn resume_midi(&mut self, handle: &MidiHandle, now: u128) {
+        if self.midi_id() == Some(handle.id) {
+            self.start_midi(handle, now);
+        }
+    }
+
+    /// Sends the current midi to the mixer, starting from wherever it should be up to.
+    fn start_midi(&mut self, handle: &MidiHandle, now: u128) {
+        if let (true, Some(midi)) = (self.do_output, self.midi) {
+            let mut sequencer =
+                Sequencer::new(handle.song.clone(), handle.volume.clone(), self.global_volume.clone(), midi.looping);
+            let elapsed = now.saturating_sub(midi.start_time);
+            sequencer.seek((elapsed * u128::from(midi::SAMPLE_RATE) / 1_000_000_000) as u64);
+            let _ = self.mixer_handle.add_exclusive(
+                Rechanneler::new(Resampler::new(sequencer, self.mixer_sample_rate), self.mixer_channel_count),
+                handle.id,
+            );
+        }
+    }
+
     pub fn play_wav(&mut self, handle: &WavHandle, start_time: u128) {
         let end_time = length_to_ns(
             handle.player.length(),
@@ -141,6 +313,17 @@ impl AudioManager {
                     ),
                     handle.id,
                 );
+            } else if handle.use_3d {
+                // 3D sounds go through a varispeed so the Doppler effect can change their pitch
+                let _ = self.mixer_handle.add(
+                    Rechanneler::new(
+                        Varispeed::new(Clip::Wav(handle.player.clone()), handle.params.clone(), self.mixer_sample_rate),
+                        self.mixer_channel_count,
+                    ),
+                    handle.params.clone(),
+                    handle.id,
+                    self.effects.get(&handle.id).cloned(),
+                );
             } else {
                 let _ = self.mixer_handle.add(
                     Rechanneler::new(
@@ -149,6 +332,7 @@ impl AudioManager {
                     ),
                     handle.params.clone(),
                     handle.id,
+                    self.effects.get(&handle.id).cloned(),
                 );
             }
         }
@@ -183,6 +367,16 @@ impl AudioManager {
                     )),
                     handle.id,
                 );
+            } else if handle.use_3d {
+                let _ = self.mixer_handle.add(
+                    Cycle::new(Rechanneler::new(
+                        Varispeed::new(Clip::Wav(handle.player.clone()), handle.params.clone(), self.mixer_sample_rate),
+                        self.mixer_channel_count,
+                    )),
+                    handle.params.clone(),
+                    handle.id,
+                    self.effects.get(&handle.id).cloned(),
+                );
             } else {
                 let _ = self.mixer_handle.add(
                     Cycle::new(Rechanneler::new(
@@ -191,6 +385,7 @@ impl AudioManager {
                     )),
                     handle.params.clone(),
                     handle.id,
+                    self.effects.get(&handle.id).cloned(),
                 );
             }
         }
@@ -200,6 +395,7 @@ impl AudioManager {
         self.end_times.remove(&id);
         if self.multimedia_end.map(|(x, _)| x) == Some(id) {
             self.multimedia_end = None;
+            self.midi = None;
         }
         if self.do_output {
             let _ = self.mixer_handle.stop(id);
@@ -209,11 +405,94 @@ impl AudioManager {
     pub fn stop_all(&mut self) {
         self.end_times.clear();
         self.multimedia_end = None;
+        self.midi = None;
         if self.do_output {
             let _ = self.mixer_handle.stop_all();
         }
     }
 
+    /// Creates a voice for the given clip. It doesn't make any sound until it's passed to `play_voice`.
+    pub fn new_voice(&mut self, clip: &Clip, loops: i32, paused: bool, now: u128) -> Voice {
+        // Voices get negative IDs so they never clash with sounds
+        let id = self.next_voice_id;
+        self.next_voice_id = id.checked_sub(1).unwrap_or(-1);
+        Voice::new(id, clip, loops, paused, now)
+    }
+
+    /// Starts playing a voice from wherever it's meant to be up to.
+    pub fn play_voice(&mut self, voice: &Voice, clip: &Clip, now: u128) {
+        if self.do_output {
+            let _ = self.mixer_handle.stop(voice.id);
+            if voice.playing(now) {
+                voice.params.seek.store(voice.frame(now), Ordering::Release);
+                let _ = self.mixer_handle.add(
+                    Rechanneler::new(
+                        Varispeed::new(clip.clone(), voice.params.clone(), self.mixer_sample_rate),
+                        self.mixer_channel_count,
+                    ),
+                    voice.params.clone(),
+                    voice.id,
+                    None,
+                );
+            }
+        }
+    }
+
+    pub fn stop_voice(&mut self, voice: &Voice) {
+        if self.do_output {
+            let _ = self.mixer_handle.stop(voice.id);
+        }
+    }
+
+    /// Changes the effects on a sound, including on any of it that's already playing.
+    pub fn set_effects(&mut self, sound_id: i32, change: impl FnOnce(&mut Effects)) {
+        change(self.effects.entry(sound_id).or_default());
+        self.apply_effects(sound_id);
+    }
+
+    fn apply_effects(&mut self, sound_id: i32) {
+        if let (true, Some(effects)) = (self.do_output, self.effects.get(&sound_id)) {
+            let _ = self.mixer_handle.set_effects(sound_id, effects.clone());
+        }
+    }
+
+    /// Starts changing a sound's volume from one level to another over the given number of milliseconds.
+    pub fn fade(&mut self, sound_id: i32, from: f64, to: f64, milliseconds: f64, now: u128) {
+        let duration = (milliseconds.max(0.0) * 1_000_000.0) as u128;
+        self.fades.insert(sound_id, Fade { from, to, start_time: now, duration });
+    }
+
+    /// Stops a sound fading, for when its volume gets set directly.
+    pub fn cancel_fade(&mut self, sound_id: i32) {
+        self.fades.remove(&sound_id);
+    }
+
+    /// Works out the volume each fading sound should be at by now, and forgets the fades that are finished.
+    /// It goes by the game clock rather than the mixer, so it comes out the same every time.
+    pub fn fade_volumes(&mut self, now: u128) -> Vec<(i32, f64)> {
+        let volumes = self
+            .fades
+            .iter()
+            .map(|(&id, fade)| {
+                let elapsed = now.saturating_sub(fade.start_time);
+                let progress = if elapsed >= fade.duration { 1.0 } else { elapsed as f64 / fade.duration as f64 };
+                (id, fade.from + (fade.to - fade.from) * progress)
+            })
+            .collect();
+        self.fades.retain(|_, fade| now.saturating_sub(fade.start_time) < fade.duration);
+        volumes
+    }
+
+    /// Changes where a 3D sound is and how it's heard, including on any of it that's already playing.
+    /// Sounds that weren't loaded as 3D sounds aren't affected.
+    pub fn set_sound_3d(&mut self, handle: &WavHandle, change: impl FnOnce(&mut Sound3D)) {
+        if handle.use_3d {
+            let sound = self.sounds_3d.entry(handle.id).or_default();
+            change(sound);
+            sound.apply(&handle.params);
+        }
+    }
+
     pub fn set_global_volume(&self, vol: f64) {
         self.global_volume.store(make_volume(vol).to_bits(), Ordering::Release)
     }
@@ -238,23 +517,70 @@ impl AudioManager {
 
     pub fn state(&self) -> AudioState {
         AudioState {
-            global_volume: self.global_volume.clone(),
+            global_volume: Arc::new(AtomicU32::new(self.global_volume.load(Ordering::Acquire))),
             end_times: self.end_times.clone(),
             multimedia_end: self.multimedia_end,
+            midi: self.midi,
+            effects: self.effects.clone(),
+            fades: self.fades.clone(),
+            sounds_3d: self.sounds_3d.clone(),
+            next_voice_id: self.next_voice_id,
         }
     }
 
-    pub fn set_state(&mut self, state: AudioState) {
-        self.global_volume = state.global_volume;
+    /// Restores a saved state. Any voices that were playing are stopped, so whoever owns the saved voices
+    /// should pass them to `play_voice` again. A midi that was playing only starts again once its handle is
+    /// passed to `resume_midi`.
+    pub fn set_state(&mut self, state: AudioState, now: u128) {
+        // The mixer and any midi share this, so it has to stay the same one
+        self.global_volume.store(state.global_volume.load(Ordering::Acquire), Ordering::Release);
         self.end_times = state.end_times;
         self.multimedia_end = state.multimedia_end;
+        self.fades = state.fades;
+        self.sounds_3d = state.sounds_3d;
+        self.next_voice_id = state.next_voice_id;
+        let old_effects = std::mem::replace(&mut self.effects, state.effects);
+        for id in old_effects.keys().chain(self.effects.keys()) {
+            let effects = self.effects.get(id).cloned().unwrap_or_default();
+            if self.do_output {
+                let _ = self.mixer_handle.set_effects(*id, effects);
+            }
+        }
+        if self.do_output {
+            let _ = self.mixer_handle.stop_voices();
+            if let Some(midi) = self.midi.take() {
+                let _ = self.mixer_handle.stop(midi.id);
+            }
+        }
+        self.midi = state.midi.filter(|midi| self.mp3_playing(midi.id, now));
     }
 }
 
 impl WavHandle {
+    pub fn volume(&self) -> f64 {
+        unmake_volume(f32::from_bits(self.params.volume.load(Ordering::Acquire)))
+    }
+
     pub fn set_volume(&self, vol: f64) {
         self.params.volume.store(make_volume(vol).to_bits(), Ordering::Release);
     }
+
+    /// Sets the pan, from -1 (left) to 1 (right). 3D sounds are panned by where they are instead.
+    pub fn set_pan(&self, pan: f64) {
+        if !self.use_3d {
+            self.params.pan.store((pan.clamp(-1.0, 1.0) as f32).to_bits(), Ordering::Release);
+        }
+    }
+}
+
+impl MidiHandle {
+    pub fn volume(&self) -> f64 {
+        unmake_volume(f32::from_bits(self.volume.load(Ordering::Acquire)))
+    }
+
+    pub fn set_volume(&self, vol: f64) {
+        self.volume.store(make_volume(vol).to_bits(), Ordering::Release);
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -262,6 +588,11 @@ pub struct AudioState {
     global_volume: Arc<AtomicU32>,
     end_times: HashMap<i32, Option<u128>>,
     multimedia_end: Option<(i32, Option<u128>)>,
+    midi: Option<MidiPlayback>,
+    effects: HashMap<i32, Effects>,
+    fades: HashMap<i32, Fade>,
+    sounds_3d: HashMap<i32, Sound3D>,
+    next_voice_id: i32,
 }
 
 fn length_to_ns(sample_count: usize, sample_rate: u32, channels: u16) -> u128 {
@@ -274,3 +605,25 @@ fn length_to_ns(sample_count: usize, sample_rate: u32, channels: u16) -> u128 {
 fn make_volume(vol: f64) -> f32 {
     1000.0f64.powf(vol.clamp(0.0, 1.0) - 1.0) as f32
 }
+
+// The inverse of make_volume, for getting back the volume the game set.
+fn unmake_volume(vol: f32) -> f64 {
+    (f64::from(vol).log(1000.0) + 1.0).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_keeps_global_volume_shared() {
+        let mut audio = AudioManager::silent();
+        let shared = audio.global_volume.clone();
+        audio.set_global_volume(0.5);
+        let state = audio.state();
+        audio.set_global_volume(1.0);
+        audio.set_state(state, 0);
+        assert!(Arc::ptr_eq(&shared, &audio.global_volume));
+        assert_eq!(shared.load(Ordering::Acquire), make_volume(0.5).to_bits());
+    }
+}
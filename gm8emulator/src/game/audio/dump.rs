@@ -0,0 +1,170 @@
+use super::mixer::Mixer;
+use byteorder::{WriteBytesExt, LE};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+use udon::source::{ChannelCount, Sample, SampleRate, Source};
+
+pub const SAMPLE_RATE: u32 = 44100;
+pub const CHANNELS: u16 = 2;
+
+/// Renders a mixer into a 16-bit PCM WAV file in step with the game clock, instead of playing it through
+/// the audio device. Given the same run of frames, the file always comes out the same.
+/// The lengths in the header are only filled in by `finish`.
+pub struct WavDump {
+    mixer: Mixer,
+    file: BufWriter<File>,
+    buffer: Vec<Sample>,
+    frames_written: u64,
+    full: bool,
+    last_time: Option<u128>,
+    // The clock time and frame count that `frames_written` is worked out from
+    base_time: u128,
+    base_frame: u64,
+}
+
+impl WavDump {
+    /// The most frames that fit in a WAV file, whose lengths are 32-bit.
+    const MAX_FRAMES: u64 = (u32::MAX as u64 - 36) / (CHANNELS as u64 * 2);
+
+    /// Any jump in the clock longer than this (or backwards) is skipped over instead of being filled in.
+    const MAX_STEP: u128 = 1_000_000_000;
+
+    pub fn new(mixer: Mixer, path: &Path) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let block_align = CHANNELS * 2;
+        file.write_all(b"RIFF")?;
+        file.write_u32::<LE>(36)?;
+        file.write_all(b"WAVEfmt ")?;
+        file.write_u32::<LE>(16)?;
+        file.write_u16::<LE>(1)?; // PCM
+        file.write_u16::<LE>(CHANNELS)?;
+        file.write_u32::<LE>(SAMPLE_RATE)?;
+        file.write_u32::<LE>(SAMPLE_RATE * u32::from(block_align))?;
+        file.write_u16::<LE>(block_align)?;
+        file.write_u16::<LE>(16)?;
+        file.write_all(b"data")?;
+        file.write_u32::<LE>(0)?;
+        Ok(Self {
+            mixer,
+            file,
+            buffer: Vec::new(),
+            frames_written: 0,
+            full: false,
+            last_time: None,
+            base_time: 0,
+            base_frame: 0,
+        })
+    }
+
+    pub fn sample_rate() -> SampleRate {
+        SampleRate::new(SAMPLE_RATE).unwrap()
+    }
+
+    pub fn channel_count() -> ChannelCount {
+        ChannelCount::new(CHANNELS).unwrap()
+    }
+
+    /// Mixes everything that would have played up until the given clock time and adds it to the file.
+    pub fn render(&mut self, now: u128) -> io::Result<()> {
+        let last_time = self.last_time.replace(now);
+        if last_time.map_or(true, |last| now < last || now - last > Self::MAX_STEP) {
+            // The clock was changed rather than moved along, so start counting again from here
+            self.base_time = now;
+            self.base_frame = self.frames_written;
+            return Ok(())
+        }
+        let elapsed = now - self.base_time;
+        let target = self.base_frame + (elapsed * u128::from(SAMPLE_RATE) / 1_000_000_000) as u64;
+        if target > Self::MAX_FRAMES && !self.full {
+            self.full = true;
+            eprintln!("Warning: the audio dump has reached the 4GB limit for WAV files, so the rest won't be written");
+        }
+        let target = target.min(Self::MAX_FRAMES);
+        if target <= self.frames_written {
+            return Ok(())
+        }
+        let frames = (target - self.frames_written) as usize;
+        self.buffer.resize(frames * usize::from(CHANNELS), 0.0);
+        self.mixer.write_samples(&mut self.buffer);
+        for sample in &self.buffer {
+            self.file.write_i16::<LE>((sample.clamp(-1.0, 1.0) * 32767.0).round() as i16)?;
+        }
+        self.frames_written = target;
+        Ok(())
+    }
+
+    /// Fills in the lengths in the header and writes out anything that's still buffered.
+    pub fn finish(mut self) -> io::Result<()> {
+        // MAX_FRAMES keeps this in range
+        let data_len = (self.frames_written * u64::from(CHANNELS) * 2) as u32;
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_u32::<LE>(36 + data_len)?;
+        self.file.seek(SeekFrom::Start(40))?;
+        self.file.write_u32::<LE>(data_len)?;
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::audio::SoundParams;
+    use std::sync::{atomic::AtomicU32, Arc};
+
+    /// A sawtooth that never ends.
+    struct Saw(usize);
+
+    impl Source for Saw {
+        fn write_samples(&mut self, buffer: &mut [Sample]) -> usize {
+            for sample in buffer.iter_mut() {
+                *sample = (self.0 % 100) as f32 / 50.0 - 1.0;
+                self.0 += 1;
+            }
+            buffer.len()
+        }
+
+        fn channel_count(&self) -> ChannelCount {
+            WavDump::channel_count()
+        }
+
+        fn sample_rate(&self) -> SampleRate {
+            WavDump::sample_rate()
+        }
+
+        fn reset(&mut self) {
+            self.0 = 0;
+        }
+    }
+
+    fn dump(name: &str) -> Vec<u8> {
+        let path = std::env::temp_dir().join(format!("gm8emulator-dump-{}-{}.wav", name, std::process::id()));
+        let global_volume = Arc::new(AtomicU32::new(1.0f32.to_bits()));
+        let (mixer, handle) = Mixer::new(WavDump::sample_rate(), WavDump::channel_count(), global_volume);
+        handle.add(Saw(0), Arc::new(SoundParams::new(0.5)), 1, None).unwrap();
+        let mut dump = WavDump::new(mixer, &path).unwrap();
+        // Uneven frame times, with the clock being set back partway through
+        for now in (0..20).map(|i| 1000 + i * 33_333_333).chain((0..20).map(|i| i * 16_666_667)) {
+            dump.render(now).unwrap();
+        }
+        dump.finish().unwrap();
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(path).ok();
+        data
+    }
+
+    #[test]
+    fn same_every_time() {
+        let first = dump("first");
+        let second = dump("second");
+        assert!(first == second);
+
+        let data_len = first.len() as u32 - 44;
+        assert!(data_len > 0 && data_len % 4 == 0);
+        assert_eq!(first[4..8], (36 + data_len).to_le_bytes());
+        assert_eq!(first[40..44], data_len.to_le_bytes());
+        assert!(first[44..].iter().any(|&b| b != 0));
+    }
+}
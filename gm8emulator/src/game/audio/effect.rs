@@ -0,0 +1,585 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{LN_2, PI, TAU};
+use udon::source::Sample;
+
+// The bits in the flags given to sound_effect_set, which is also the order the effects are applied in
+pub const CHORUS: u32 = 1;
+pub const ECHO: u32 = 2;
+pub const FLANGER: u32 = 4;
+pub const GARGLE: u32 = 8;
+pub const REVERB: u32 = 16;
+pub const COMPRESSOR: u32 = 32;
+pub const EQUALIZER: u32 = 64;
+
+/// Settings for a chorus or flanger, which both mix in a copy of the sound with a wobbling delay.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Modulation {
+    pub wet_dry: f32,   // 0 to 100 percent
+    pub depth: f32,     // 0 to 100 percent of the delay
+    pub feedback: f32,  // -99 to 99 percent
+    pub frequency: f32, // 0 to 10 Hz
+    pub sine: bool,     // otherwise triangle
+    pub delay: f32,     // in milliseconds, 0 to 20 for chorus and 0 to 4 for flanger
+    pub phase: i32,     // 0 to 4, for -180 to 180 degrees between the left and right channels
+}
+
+impl Modulation {
+    pub const CHORUS: Self =
+        Self { wet_dry: 50.0, depth: 10.0, feedback: 25.0, frequency: 1.1, sine: true, delay: 16.0, phase: 3 };
+    pub const FLANGER: Self =
+        Self { wet_dry: 50.0, depth: 100.0, feedback: -50.0, frequency: 0.25, sine: true, delay: 2.0, phase: 2 };
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Echo {
+    pub wet_dry: f32,     // 0 to 100 percent
+    pub feedback: f32,    // 0 to 100 percent
+    pub left_delay: f32,  // 1 to 2000 milliseconds
+    pub right_delay: f32, // 1 to 2000 milliseconds
+    pub pan_delay: bool,  // whether the echoes swap sides each time
+}
+
+impl Default for Echo {
+    fn default() -> Self {
+        Self { wet_dry: 50.0, feedback: 50.0, left_delay: 500.0, right_delay: 500.0, pan_delay: false }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Gargle {
+    pub rate: f32,    // 1 to 1000 Hz
+    pub square: bool, // otherwise triangle
+}
+
+impl Default for Gargle {
+    fn default() -> Self {
+        Self { rate: 20.0, square: false }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Reverb {
+    pub gain: f32,       // -96 to 0 dB
+    pub mix: f32,        // -96 to 0 dB
+    pub time: f32,       // 0.001 to 3000 milliseconds
+    pub high_ratio: f32, // 0.001 to 0.999, how long high frequencies last compared to the rest
+}
+
+impl Default for Reverb {
+    fn default() -> Self {
+        Self { gain: 0.0, mix: 0.0, time: 1000.0, high_ratio: 0.001 }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Compressor {
+    pub gain: f32,      // -60 to 60 dB
+    pub attack: f32,    // 0.01 to 500 milliseconds
+    pub release: f32,   // 50 to 3000 milliseconds
+    pub threshold: f32, // -60 to 0 dB
+    pub ratio: f32,     // 1 to 100
+    pub delay: f32,     // 0 to 4 milliseconds
+}
+
+impl Default for Compressor {
+    fn default() -> Self {
+        Self { gain: 0.0, attack: 10.0, release: 200.0, threshold: -20.0, ratio: 3.0, delay: 4.0 }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Equalizer {
+    pub center: f32,    // 80 to 16000 Hz
+    pub bandwidth: f32, // 1 to 36 semitones
+    pub gain: f32,      // -15 to 15 dB
+}
+
+impl Default for Equalizer {
+    fn default() -> Self {
+        Self { center: 8000.0, bandwidth: 12.0, gain: 0.0 }
+    }
+}
+
+/// The effects set on a sound, with the same ranges as the DirectX 8 effects GM8 uses.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Effects {
+    pub enabled: u32,
+    pub chorus: Modulation,
+    pub echo: Echo,
+    pub flanger: Modulation,
+    pub gargle: Gargle,
+    pub reverb: Reverb,
+    pub compressor: Compressor,
+    pub equalizer: Equalizer,
+}
+
+impl Default for Effects {
+    fn default() -> Self {
+        Self {
+            enabled: 0,
+            chorus: Modulation::CHORUS,
+            echo: Default::default(),
+            flanger: Modulation::FLANGER,
+            gargle: Default::default(),
+            reverb: Default::default(),
+            compressor: Default::default(),
+            equalizer: Default::default(),
+        }
+    }
+}
+
+/// The effects being applied to a sound, in order.
+pub struct FilterChain {
+    rate: f32,
+    channels: usize,
+    filters: Vec<(u32, Box<dyn Filter + Send>)>,
+}
+
+impl FilterChain {
+    /// An empty chain for interleaved audio at the given rate.
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        Self { rate: sample_rate as f32, channels, filters: Vec::new() }
+    }
+
+    /// Brings the chain in line with the given effects. Effects that were already enabled keep their state,
+    /// so changing their settings doesn't cut off any echoes or reverb that are still ringing.
+    pub fn update(&mut self, effects: &Effects) {
+        self.filters.retain(|(bit, _)| effects.enabled & bit != 0);
+        for (i, bit) in (0..7).map(|i| 1 << i).filter(|bit| effects.enabled & bit != 0).enumerate() {
+            if self.filters.get(i).map(|(x, _)| *x) != Some(bit) {
+                let (rate, channels) = (self.rate, self.channels);
+                let filter: Box<dyn Filter + Send> = match bit {
+                    CHORUS | FLANGER => Box::new(ModulationFilter::new(bit == FLANGER, rate, channels)),
+                    ECHO => Box::new(EchoFilter::new(rate, channels)),
+                    GARGLE => Box::new(GargleFilter { settings: effects.gargle, rate, phase: 0.0, channels }),
+                    REVERB => Box::new(ReverbFilter::new(rate, channels)),
+                    COMPRESSOR => Box::new(CompressorFilter::new(rate, channels)),
+                    EQUALIZER => Box::new(EqualizerFilter::new(rate, channels)),
+                    _ => unreachable!(),
+                };
+                self.filters.insert(i, (bit, filter));
+            }
+            self.filters[i].1.update(effects);
+        }
+    }
+
+    pub fn process(&mut self, buffer: &mut [Sample]) {
+        for (_, filter) in self.filters.iter_mut() {
+            filter.process(buffer);
+        }
+    }
+}
+
+/// Something that processes interleaved audio in place.
+trait Filter {
+    /// Takes on new settings without losing anything it's in the middle of.
+    fn update(&mut self, effects: &Effects);
+
+    fn process(&mut self, buffer: &mut [Sample]);
+}
+
+fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// A circular buffer that can be read from any number of samples in the past.
+struct DelayLine {
+    buffer: Vec<f32>,
+    pos: usize,
+}
+
+impl DelayLine {
+    fn new(max_delay: usize) -> Self {
+        Self { buffer: vec![0.0; max_delay + 2], pos: 0 }
+    }
+
+    /// Reads what was written `delay` samples before the next write, interpolating between samples.
+    fn read(&self, delay: f32) -> f32 {
+        let len = self.buffer.len();
+        let delay = delay.clamp(1.0, (len - 1) as f32);
+        let whole = delay as usize;
+        let fraction = delay - whole as f32;
+        let a = self.buffer[(self.pos + len + 1 - whole) % len];
+        let b = self.buffer[(self.pos + len - whole) % len];
+        a + (b - a) * fraction
+    }
+
+    fn write(&mut self, sample: f32) {
+        self.pos = (self.pos + 1) % self.buffer.len();
+        self.buffer[self.pos] = sample;
+    }
+}
+
+struct ModulationFilter {
+    flanger: bool,
+    settings: Modulation,
+    lines: Vec<DelayLine>,
+    lfo_phase: f32,
+    lfo_step: f32,
+    rate: f32,
+}
+
+impl ModulationFilter {
+    /// The longest delay a chorus can have, in milliseconds. The LFO can double it.
+    const MAX_DELAY: f32 = 20.0;
+
+    fn new(flanger: bool, rate: f32, channels: usize) -> Self {
+        let max_delay = (Self::MAX_DELAY * 2.0 * rate / 1000.0).ceil() as usize;
+        Self {
+            flanger,
+            settings: if flanger { Modulation::FLANGER } else { Modulation::CHORUS },
+            lines: (0..channels).map(|_| DelayLine::new(max_delay)).collect(),
+            lfo_phase: 0.0,
+            lfo_step: 0.0,
+            rate,
+        }
+    }
+}
+
+impl Filter for ModulationFilter {
+    fn update(&mut self, effects: &Effects) {
+        self.settings = if self.flanger { effects.flanger } else { effects.chorus };
+        self.lfo_step = self.settings.frequency / self.rate;
+    }
+
+    fn process(&mut self, buffer: &mut [Sample]) {
+        let s = self.settings;
+        let (wet, feedback) = (s.wet_dry / 100.0, s.feedback / 100.0);
+        let base = s.delay * self.rate / 1000.0;
+        // The right channel's LFO is offset from the left's
+        let offset = (s.phase - 2) as f32 / 4.0;
+        for frame in buffer.chunks_exact_mut(self.lines.len()) {
+            for (c, (sample, line)) in frame.iter_mut().zip(&mut self.lines).enumerate() {
+                let phase = (self.lfo_phase + if c % 2 == 1 { offset } else { 0.0 }).rem_euclid(1.0);
+                let lfo = if s.sine { (phase * TAU).sin() } else { 1.0 - 4.0 * (phase - 0.5).abs() };
+                let delayed = line.read(base * (1.0 + lfo * s.depth / 100.0));
+                line.write(*sample + delayed * feedback);
+                *sample = *sample * (1.0 - wet) + delayed * wet;
+            }
+            self.lfo_phase = (self.lfo_phase + self.lfo_step).fract();
+        }
+    }
+}
+
+struct EchoFilter {
+    settings: Echo,
+    lines: Vec<DelayLine>,
+    delays: Vec<f32>,
+    delayed: Vec<f32>,
+    rate: f32,
+}
+
+impl EchoFilter {
+    /// The longest delay an echo can have, in milliseconds.
+    const MAX_DELAY: f32 = 2000.0;
+
+    fn new(rate: f32, channels: usize) -> Self {
+        let max_delay = (Self::MAX_DELAY * rate / 1000.0).ceil() as usize;
+        Self {
+            settings: Default::default(),
+            lines: (0..channels).map(|_| DelayLine::new(max_delay)).collect(),
+            delays: vec![0.0; channels],
+            delayed: vec![0.0; channels],
+            rate,
+        }
+    }
+}
+
+impl Filter for EchoFilter {
+    fn update(&mut self, effects: &Effects) {
+        self.settings = effects.echo;
+        for (c, delay) in self.delays.iter_mut().enumerate() {
+            let ms = if c % 2 == 1 { self.settings.right_delay } else { self.settings.left_delay };
+            *delay = ms * self.rate / 1000.0;
+        }
+    }
+
+    fn process(&mut self, buffer: &mut [Sample]) {
+        let (wet, feedback) = (self.settings.wet_dry / 100.0, self.settings.feedback / 100.0);
+        let channels = self.lines.len();
+        for frame in buffer.chunks_exact_mut(channels) {
+            for (c, line) in self.lines.iter().enumerate() {
+                self.delayed[c] = line.read(self.delays[c]);
+            }
+            for (c, sample) in frame.iter_mut().enumerate() {
+                // Panned echoes feed back into the opposite side
+                let source = if self.settings.pan_delay && channels == 2 { 1 - c } else { c };
+                self.lines[c].write(*sample + self.delayed[source] * feedback);
+                *sample = *sample * (1.0 - wet) + self.delayed[c] * wet;
+            }
+        }
+    }
+}
+
+struct GargleFilter {
+    settings: Gargle,
+    rate: f32,
+    phase: f32,
+    channels: usize,
+}
+
+impl Filter for GargleFilter {
+    fn update(&mut self, effects: &Effects) {
+        self.settings = effects.gargle;
+    }
+
+    fn process(&mut self, buffer: &mut [Sample]) {
+        let step = self.settings.rate / self.rate;
+        for frame in buffer.chunks_exact_mut(self.channels) {
+            let gain = if self.settings.square {
+                if self.phase < 0.5 { 1.0 } else { 0.0 }
+            } else {
+                1.0 - (2.0 * self.phase - 1.0).abs()
+            };
+            frame.iter_mut().for_each(|x| *x *= gain);
+            self.phase = (self.phase + step).fract();
+        }
+    }
+}
+
+/// A comb filter with a low-pass in its feedback, as used in Schroeder reverbs.
+struct Comb {
+    line: DelayLine,
+    delay: f32,
+    feedback: f32,
+    damping: f32,
+    store: f32,
+}
+
+impl Comb {
+    fn process(&mut self, input: f32) -> f32 {
+        let output = self.line.read(self.delay);
+        self.store = output * (1.0 - self.damping) + self.store * self.damping;
+        self.line.write(input + self.store * self.feedback);
+        output
+    }
+}
+
+struct Allpass {
+    line: DelayLine,
+    delay: f32,
+}
+
+impl Allpass {
+    fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.line.read(self.delay);
+        self.line.write(input + delayed * 0.5);
+        delayed - input
+    }
+}
+
+struct ReverbFilter {
+    gain: f32,
+    mix: f32,
+    rate: f32,
+    combs: Vec<Vec<Comb>>,
+    allpasses: Vec<Vec<Allpass>>,
+}
+
+impl ReverbFilter {
+    // Delay lengths in samples at 44.1kHz, from Freeverb
+    const ALLPASSES: [f32; 2] = [556.0, 441.0];
+    const COMBS: [f32; 4] = [1116.0, 1188.0, 1277.0, 1356.0];
+    const STEREO_SPREAD: f32 = 23.0;
+
+    fn new(rate: f32, channels: usize) -> Self {
+        let scale = rate / 44100.0;
+        let spread = |c: usize, length: f32| (length + if c % 2 == 1 { Self::STEREO_SPREAD } else { 0.0 }) * scale;
+        Self {
+            gain: 1.0,
+            mix: 1.0,
+            rate,
+            combs: (0..channels)
+                .map(|c| {
+                    Self::COMBS
+                        .iter()
+                        .map(|&length| {
+                            let delay = spread(c, length);
+                            let line = DelayLine::new(delay as usize + 1);
+                            Comb { line, delay, feedback: 0.0, damping: 0.0, store: 0.0 }
+                        })
+                        .collect()
+                })
+                .collect(),
+            allpasses: (0..channels)
+                .map(|c| {
+                    Self::ALLPASSES
+                        .iter()
+                        .map(|&length| {
+                            let delay = spread(c, length);
+                            Allpass { line: DelayLine::new(delay as usize + 1), delay }
+                        })
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Filter for ReverbFilter {
+    fn update(&mut self, effects: &Effects) {
+        let settings = effects.reverb;
+        let time = settings.time / 1000.0;
+        self.gain = db_to_gain(settings.gain);
+        self.mix = db_to_gain(settings.mix);
+        for comb in self.combs.iter_mut().flatten() {
+            // Each trip round the comb takes it closer to -60dB after the reverb time
+            comb.feedback = 10f32.powf(-3.0 * comb.delay / self.rate / time).min(0.98);
+            comb.damping = 0.4 * (1.0 - settings.high_ratio);
+        }
+    }
+
+    fn process(&mut self, buffer: &mut [Sample]) {
+        for frame in buffer.chunks_exact_mut(self.combs.len()) {
+            for ((sample, combs), allpasses) in frame.iter_mut().zip(&mut self.combs).zip(&mut self.allpasses) {
+                let input = *sample * self.gain;
+                let mut wet = combs.iter_mut().map(|comb| comb.process(input)).sum::<f32>() / combs.len() as f32;
+                for allpass in allpasses.iter_mut() {
+                    wet = allpass.process(wet);
+                }
+                *sample = input + wet * self.mix;
+            }
+        }
+    }
+}
+
+struct CompressorFilter {
+    settings: Compressor,
+    lines: Vec<DelayLine>,
+    delay: f32,
+    attack: f32,
+    release: f32,
+    envelope: f32,
+    rate: f32,
+}
+
+impl CompressorFilter {
+    /// The longest pre-delay, in milliseconds.
+    const MAX_DELAY: f32 = 4.0;
+
+    fn new(rate: f32, channels: usize) -> Self {
+        let max_delay = (Self::MAX_DELAY * rate / 1000.0).ceil() as usize;
+        Self {
+            settings: Default::default(),
+            lines: (0..channels).map(|_| DelayLine::new(max_delay)).collect(),
+            delay: 0.0,
+            attack: 0.0,
+            release: 0.0,
+            envelope: 0.0,
+            rate,
+        }
+    }
+}
+
+impl Filter for CompressorFilter {
+    fn update(&mut self, effects: &Effects) {
+        let rate = self.rate;
+        let coefficient = |ms: f32| (-1.0 / (ms / 1000.0 * rate)).exp();
+        self.settings = effects.compressor;
+        self.delay = self.settings.delay * rate / 1000.0;
+        self.attack = coefficient(self.settings.attack);
+        self.release = coefficient(self.settings.release);
+    }
+
+    fn process(&mut self, buffer: &mut [Sample]) {
+        let s = self.settings;
+        for frame in buffer.chunks_exact_mut(self.lines.len()) {
+            // The level's taken from the incoming sound, so the pre-delay lets it react ahead of time
+            let level = frame.iter().fold(0.0f32, |level, x| level.max(x.abs()));
+            let coefficient = if level > self.envelope { self.attack } else { self.release };
+            self.envelope = level + (self.envelope - level) * coefficient;
+            let over = 20.0 * self.envelope.max(1e-6).log10() - s.threshold;
+            let reduction = if over > 0.0 { over * (1.0 - 1.0 / s.ratio) } else { 0.0 };
+            let gain = db_to_gain(s.gain - reduction);
+            for (sample, line) in frame.iter_mut().zip(&mut self.lines) {
+                let delayed = if self.delay >= 1.0 { line.read(self.delay) } else { *sample };
+                line.write(*sample);
+                *sample = delayed * gain;
+            }
+        }
+    }
+}
+
+/// A peaking filter, using the biquad from Robert Bristow-Johnson's Audio EQ Cookbook.
+struct EqualizerFilter {
+    coefficients: [f32; 5],
+    history: Vec<[f32; 4]>,
+    rate: f32,
+}
+
+impl EqualizerFilter {
+    fn new(rate: f32, channels: usize) -> Self {
+        Self { coefficients: [1.0, 0.0, 0.0, 0.0, 0.0], history: vec![[0.0; 4]; channels], rate }
+    }
+}
+
+impl Filter for EqualizerFilter {
+    fn update(&mut self, effects: &Effects) {
+        let (settings, rate) = (effects.equalizer, self.rate);
+        let a = 10f32.powf(settings.gain / 40.0);
+        let w0 = 2.0 * PI * settings.center.min(rate * 0.45) / rate;
+        let octaves = settings.bandwidth / 12.0;
+        let alpha = w0.sin() * (LN_2 / 2.0 * octaves * w0 / w0.sin()).sinh();
+        let a0 = 1.0 + alpha / a;
+        self.coefficients = [
+            (1.0 + alpha * a) / a0,
+            -2.0 * w0.cos() / a0,
+            (1.0 - alpha * a) / a0,
+            -2.0 * w0.cos() / a0,
+            (1.0 - alpha / a) / a0,
+        ];
+    }
+
+    fn process(&mut self, buffer: &mut [Sample]) {
+        let [b0, b1, b2, a1, a2] = self.coefficients;
+        for frame in buffer.chunks_exact_mut(self.history.len()) {
+            for (sample, [x1, x2, y1, y2]) in frame.iter_mut().zip(&mut self.history) {
+                let y = b0 * *sample + b1 * *x1 + b2 * *x2 - a1 * *y1 - a2 * *y2;
+                *x2 = *x1;
+                *x1 = *sample;
+                *y2 = *y1;
+                *y1 = y;
+                *sample = y;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_keeps_echoes() {
+        let mut effects = Effects { enabled: ECHO, ..Default::default() };
+        effects.echo = Echo { wet_dry: 50.0, feedback: 0.0, left_delay: 10.0, right_delay: 10.0, pan_delay: false };
+        let mut chain = FilterChain::new(1000, 1);
+        chain.update(&effects);
+
+        let mut first = [0.0; 5];
+        first[0] = 1.0;
+        chain.process(&mut first);
+        assert_eq!(first[0], 0.5);
+
+        // The echo of the first sample should still come through after the settings change
+        effects.echo.wet_dry = 100.0;
+        chain.update(&effects);
+        let mut second = [0.0; 10];
+        chain.process(&mut second);
+        assert_eq!(second[5], 1.0);
+    }
+
+    #[test]
+    fn update_order() {
+        let mut chain = FilterChain::new(44100, 2);
+        let bits = |chain: &FilterChain| chain.filters.iter().map(|(bit, _)| *bit).collect::<Vec<_>>();
+        chain.update(&Effects { enabled: REVERB, ..Default::default() });
+        assert_eq!(bits(&chain), [REVERB]);
+        chain.update(&Effects { enabled: CHORUS | REVERB | EQUALIZER, ..Default::default() });
+        assert_eq!(bits(&chain), [CHORUS, REVERB, EQUALIZER]);
+        chain.update(&Effects { enabled: EQUALIZER, ..Default::default() });
+        assert_eq!(bits(&chain), [EQUALIZER]);
+        chain.update(&Default::default());
+        assert!(bits(&chain).is_empty());
+    }
+}
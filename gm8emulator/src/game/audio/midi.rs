@@ -0,0 +1,559 @@
+use midly::{Format, MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+use serde::{Deserialize, Serialize};
+use std::{
+    f32::consts::TAU,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+use udon::source::{ChannelCount, Sample, SampleRate, Source};
+
+/// The rate the synthesizer renders at. The mixer resamples it from there.
+pub const SAMPLE_RATE: u32 = 44100;
+
+/// How many notes can sound at once before the oldest ones get cut off.
+const MAX_NOTES: usize = 64;
+
+/// Channel 10, which General MIDI reserves for percussion.
+const DRUM_CHANNEL: u8 = 9;
+
+/// Scales the mix down so a full orchestra doesn't clip.
+const MASTER_GAIN: f32 = 0.2;
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+enum Message {
+    NoteOn { channel: u8, key: u8, velocity: u8 },
+    NoteOff { channel: u8, key: u8 },
+    ProgramChange { channel: u8, program: u8 },
+    Controller { channel: u8, controller: u8, value: u8 },
+    PitchBend { channel: u8, bend: i16 },
+}
+
+/// A MIDI file, with all its tracks merged and every event's time worked out in sample frames.
+#[derive(Serialize, Deserialize)]
+pub struct Song {
+    events: Vec<(u64, Message)>,
+    length: u64,
+}
+
+impl Song {
+    pub fn new(data: &[u8]) -> Option<Self> {
+        let smf = Smf::parse(data).ok()?;
+
+        // Absolute ticks for every event, with sequential tracks placed one after another
+        let mut timeline = Vec::new();
+        let mut track_start = 0u64;
+        for track in &smf.tracks {
+            let mut tick = track_start;
+            for event in track {
+                tick += u64::from(event.delta.as_int());
+                timeline.push((tick, event.kind));
+            }
+            if smf.header.format == Format::Sequential {
+                track_start = tick;
+            }
+        }
+        // Stable, so events on the same tick stay in track order
+        timeline.sort_by_key(|(tick, _)| *tick);
+
+        let mut events = Vec::new();
+        let (mut last_tick, mut seconds) = (0u64, 0.0f64);
+        let mut seconds_per_tick = match smf.header.timing {
+            Timing::Metrical(ppq) => 0.5 / f64::from(ppq.as_int().max(1)),
+            Timing::Timecode(fps, subframes) => 1.0 / (f64::from(fps.as_f32()) * f64::from(subframes.max(1))),
+        };
+        for (tick, kind) in timeline {
+            seconds += (tick - last_tick) as f64 * seconds_per_tick;
+            last_tick = tick;
+            let frame = (seconds * f64::from(SAMPLE_RATE)) as u64;
+            let message = match kind {
+                TrackEventKind::Midi { channel, message } => {
+                    let channel = channel.as_int();
+                    match message {
+                        MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => {
+                            Message::NoteOn { channel, key: key.as_int(), velocity: vel.as_int() }
+                        },
+                        MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                            Message::NoteOff { channel, key: key.as_int() }
+                        },
+                        MidiMessage::ProgramChange { program } => {
+                            Message::ProgramChange { channel, program: program.as_int() }
+                        },
+                        MidiMessage::Controller { controller, value } => {
+                            Message::Controller { channel, controller: controller.as_int(), value: value.as_int() }
+                        },
+                        MidiMessage::PitchBend { bend } => Message::PitchBend { channel, bend: bend.as_int() },
+                        MidiMessage::Aftertouch { .. } | MidiMessage::ChannelAftertouch { .. } => continue,
+                    }
+                },
+                TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => {
+                    if let Timing::Metrical(ppq) = smf.header.timing {
+                        seconds_per_tick = f64::from(tempo.as_int()) / 1_000_000.0 / f64::from(ppq.as_int().max(1));
+                    }
+                    continue
+                },
+                _ => continue,
+            };
+            events.push((frame, message));
+        }
+
+        // End-of-track markers can leave some silence at the end, which counts towards the length
+        let length = (seconds * f64::from(SAMPLE_RATE)) as u64;
+        Some(Self { events, length })
+    }
+
+    /// The length of the song in sample frames.
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Wave {
+    Sine,
+    Triangle,
+    Saw,
+    Square,
+    Noise,
+}
+
+/// What a melodic instrument sounds like. Times are in seconds.
+#[derive(Clone, Copy)]
+struct Instrument {
+    wave: Wave,
+    attack: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+}
+
+impl Instrument {
+    /// Picks a rough approximation of a General MIDI program based on its family.
+    fn from_program(program: u8) -> Self {
+        let (wave, attack, decay, sustain, release) = match program / 8 {
+            0 => (Wave::Triangle, 0.002, 1.2, 0.0, 0.3),  // piano
+            1 => (Wave::Sine, 0.001, 0.6, 0.0, 0.3),      // chromatic percussion
+            2 => (Wave::Square, 0.01, 0.1, 0.8, 0.05),    // organ
+            3 => (Wave::Saw, 0.002, 0.8, 0.0, 0.15),      // guitar
+            4 => (Wave::Triangle, 0.005, 0.5, 0.6, 0.1),  // bass
+            5 | 6 => (Wave::Saw, 0.08, 0.3, 0.8, 0.3),    // strings and ensembles
+            7 => (Wave::Saw, 0.03, 0.2, 0.7, 0.1),        // brass
+            8 => (Wave::Square, 0.02, 0.2, 0.7, 0.1),     // reed
+            9 => (Wave::Sine, 0.03, 0.2, 0.8, 0.15),      // pipe
+            10 => (Wave::Square, 0.005, 0.2, 0.7, 0.1),   // synth lead
+            11 => (Wave::Triangle, 0.3, 0.5, 0.7, 0.6),   // synth pad
+            12 => (Wave::Saw, 0.05, 0.5, 0.5, 0.5),       // synth effects
+            13 => (Wave::Triangle, 0.002, 0.7, 0.0, 0.2), // ethnic
+            14 => (Wave::Sine, 0.001, 0.3, 0.0, 0.1),     // percussive
+            _ => (Wave::Noise, 0.05, 0.5, 0.5, 0.3),      // sound effects
+        };
+        Self { wave, attack, decay, sustain, release }
+    }
+}
+
+/// A sound on the percussion channel.
+#[derive(Clone, Copy)]
+enum Drum {
+    Kick,
+    Snare,
+    HiHat { open: bool },
+    Tom(f32),
+    Cymbal,
+    Other,
+}
+
+impl Drum {
+    fn from_key(key: u8) -> Self {
+        match key {
+            35 | 36 => Self::Kick,
+            38..=40 => Self::Snare,
+            42 | 44 => Self::HiHat { open: false },
+            46 => Self::HiHat { open: true },
+            41 | 43 | 45 | 47 | 48 | 50 => Self::Tom(60.0 + f32::from(key - 41) * 15.0),
+            49 | 51 | 52 | 53 | 55 | 57 | 59 => Self::Cymbal,
+            _ => Self::Other,
+        }
+    }
+
+    /// How long it takes to die away, in seconds.
+    fn decay(self) -> f32 {
+        match self {
+            Self::Kick => 0.25,
+            Self::Snare => 0.15,
+            Self::HiHat { open: false } => 0.04,
+            Self::HiHat { open: true } => 0.3,
+            Self::Tom(_) => 0.3,
+            Self::Cymbal => 0.8,
+            Self::Other => 0.08,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Channel {
+    program: u8,
+    volume: f32,
+    expression: f32,
+    pan: f32,
+    sustain: bool,
+    bend: f32,
+    bend_range: f32,
+    rpn: (u8, u8),
+}
+
+impl Default for Channel {
+    fn default() -> Self {
+        Self {
+            program: 0,
+            volume: 100.0 / 127.0,
+            expression: 1.0,
+            pan: 0.0,
+            sustain: false,
+            bend: 0.0,
+            bend_range: 2.0,
+            rpn: (127, 127),
+        }
+    }
+}
+
+struct Note {
+    channel: u8,
+    key: u8,
+    gain: f32,
+    instrument: Instrument,
+    drum: Option<Drum>,
+    phase: f32,
+    level: f32,
+    time: f32,
+    held: bool,
+    released: bool,
+    noise: u32,
+    last_noise: f32,
+}
+
+impl Note {
+    fn new(channel: u8, key: u8, velocity: u8, program: u8) -> Self {
+        let velocity = f32::from(velocity) / 127.0;
+        Self {
+            channel,
+            key,
+            gain: velocity * velocity,
+            instrument: Instrument::from_program(program),
+            drum: if channel == DRUM_CHANNEL { Some(Drum::from_key(key)) } else { None },
+            phase: 0.0,
+            level: 0.0,
+            time: 0.0,
+            held: true,
+            released: false,
+            // The noise is seeded from the note so the output's always the same
+            noise: 0x9E37_79B9 ^ (u32::from(key) << 8 | u32::from(channel)),
+            last_noise: 0.0,
+        }
+    }
+
+    fn noise(&mut self) -> f32 {
+        self.noise ^= self.noise << 13;
+        self.noise ^= self.noise >> 17;
+        self.noise ^= self.noise << 5;
+        self.noise as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+
+    fn finished(&self) -> bool {
+        (self.released || self.drum.is_some() || self.instrument.sustain == 0.0)
+            && self.time > 0.01
+            && self.level < 1e-4
+    }
+
+    /// Renders one sample, moving the note on by one frame.
+    fn sample(&mut self, channel: &Channel) -> f32 {
+        const DT: f32 = 1.0 / SAMPLE_RATE as f32;
+        let out = match self.drum {
+            Some(drum) => self.drum_sample(drum),
+            None => {
+                let instrument = self.instrument;
+                if self.released {
+                    self.level *= (-DT / (instrument.release / 5.0)).exp();
+                } else if self.time < instrument.attack {
+                    self.level = self.time / instrument.attack;
+                } else {
+                    let target = instrument.sustain;
+                    self.level = target + (self.level - target) * (-DT / (instrument.decay / 5.0)).exp();
+                }
+                let key = f32::from(self.key) + channel.bend * channel.bend_range;
+                let frequency = 440.0 * ((key - 69.0) / 12.0).exp2();
+                let step = frequency * DT;
+                let out = match instrument.wave {
+                    Wave::Sine => (self.phase * TAU).sin(),
+                    Wave::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+                    Wave::Saw => 2.0 * self.phase - 1.0 - poly_blep(self.phase, step),
+                    Wave::Square => {
+                        let square = if self.phase < 0.5 { 1.0 } else { -1.0 };
+                        square + poly_blep(self.phase, step) - poly_blep((self.phase + 0.5).fract(), step)
+                    },
+                    Wave::Noise => self.noise(),
+                };
+                self.phase = (self.phase + step).fract();
+                out * self.level
+            },
+        };
+        self.time += DT;
+        out * self.gain
+    }
+
+    fn drum_sample(&mut self, drum: Drum) -> f32 {
+        const DT: f32 = 1.0 / SAMPLE_RATE as f32;
+        self.level = (-self.time / (drum.decay() / 5.0)).exp();
+        let (frequency, tone, noise) = match drum {
+            Drum::Kick => (50.0 + 100.0 * (-self.time * 30.0).exp(), 1.0, 0.0),
+            Drum::Snare => (180.0, 0.4, 0.7),
+            Drum::HiHat { .. } | Drum::Cymbal => (0.0, 0.0, 0.5),
+            Drum::Tom(frequency) => (frequency * (1.0 + 0.5 * (-self.time * 20.0).exp()), 1.0, 0.1),
+            Drum::Other => (0.0, 0.0, 0.6),
+        };
+        let mut out = 0.0;
+        if tone > 0.0 {
+            out += (self.phase * TAU).sin() * tone;
+            self.phase = (self.phase + frequency * DT).fract();
+        }
+        if noise > 0.0 {
+            let sample = self.noise();
+            // Taking the difference between samples makes cymbals brighter
+            out += match drum {
+                Drum::HiHat { .. } | Drum::Cymbal => (sample - self.last_noise) * 0.5,
+                _ => sample,
+            } * noise;
+            self.last_noise = sample;
+        }
+        out * self.level
+    }
+}
+
+/// Smooths out the jump in saw and square waves so they don't alias as badly.
+fn poly_blep(phase: f32, step: f32) -> f32 {
+    if phase < step {
+        let t = phase / step;
+        t + t - t * t - 1.0
+    } else if phase > 1.0 - step {
+        let t = (phase - 1.0) / step;
+        t * t + t + t + 1.0
+    } else {
+        0.0
+    }
+}
+
+/// Plays a song through a simple General MIDI synthesizer, in stereo at `SAMPLE_RATE`.
+pub struct Sequencer {
+    song: Arc<Song>,
+    volume: Arc<AtomicU32>,
+    global_volume: Arc<AtomicU32>,
+    looping: bool,
+    frame: u64,
+    next_event: usize,
+    channels: [Channel; 16],
+    notes: Vec<Note>,
+    ended: bool,
+}
+
+impl Sequencer {
+    pub fn new(song: Arc<Song>, volume: Arc<AtomicU32>, global_volume: Arc<AtomicU32>, looping: bool) -> Self {
+        Self {
+            song,
+            volume,
+            global_volume,
+            looping,
+            frame: 0,
+            next_event: 0,
+            channels: [Channel::default(); 16],
+            notes: Vec::with_capacity(MAX_NOTES),
+            ended: false,
+        }
+    }
+
+    /// Skips to a frame, setting up the channels as they'd be there. Notes that would already be playing aren't.
+    pub fn seek(&mut self, frame: u64) {
+        self.reset();
+        let frame = if self.looping && self.song.length > 0 { frame % self.song.length } else { frame };
+        while let Some(&(time, message)) = self.song.events.get(self.next_event) {
+            if time >= frame {
+                break
+            }
+            if !matches!(message, Message::NoteOn { .. }) {
+                self.apply(message);
+            }
+            self.next_event += 1;
+        }
+        self.frame = frame;
+    }
+
+    fn apply(&mut self, message: Message) {
+        match message {
+            Message::NoteOn { channel, key, velocity } => {
+                // Retriggering a key cuts off what it was playing
+                self.notes.retain(|n| n.channel != channel || n.key != key);
+                if self.notes.len() >= MAX_NOTES {
+                    let oldest = self.notes.iter().position(|n| n.released).unwrap_or(0);
+                    self.notes.remove(oldest);
+                }
+                self.notes.push(Note::new(channel, key, velocity, self.channels[usize::from(channel)].program));
+            },
+            Message::NoteOff { channel, key } => {
+                let sustain = self.channels[usize::from(channel)].sustain;
+                for note in self.notes.iter_mut().filter(|n| n.channel == channel && n.key == key && n.held) {
+                    note.held = false;
+                    note.released = !sustain;
+                }
+            },
+            Message::ProgramChange { channel, program } => self.channels[usize::from(channel)].program = program,
+            Message::Controller { channel, controller, value } => {
+                let state = &mut self.channels[usize::from(channel)];
+                let value_f = f32::from(value) / 127.0;
+                match controller {
+                    6 if state.rpn == (0, 0) => state.bend_range = f32::from(value),
+                    7 => state.volume = value_f,
+                    10 => state.pan = (f32::from(value) - 64.0) / 64.0,
+                    11 => state.expression = value_f,
+                    64 => {
+                        state.sustain = value >= 64;
+                        if !state.sustain {
+                            for note in self.notes.iter_mut().filter(|n| n.channel == channel && !n.held) {
+                                note.released = true;
+                            }
+                        }
+                    },
+                    100 => state.rpn.1 = value,
+                    101 => state.rpn.0 = value,
+                    120 => self.notes.retain(|n| n.channel != channel),
+                    121 => {
+                        let program = state.program;
+                        *state = Channel { program, ..Channel::default() };
+                    },
+                    123 => {
+                        for note in self.notes.iter_mut().filter(|n| n.channel == channel) {
+                            note.held = false;
+                            note.released = true;
+                        }
+                    },
+                    _ => (),
+                }
+            },
+            Message::PitchBend { channel, bend } => {
+                self.channels[usize::from(channel)].bend = f32::from(bend) / 8192.0;
+            },
+        }
+    }
+}
+
+impl Source for Sequencer {
+    fn write_samples(&mut self, buffer: &mut [Sample]) -> usize {
+        let volume = f32::from_bits(self.volume.load(Ordering::Acquire))
+            * f32::from_bits(self.global_volume.load(Ordering::Acquire))
+            * MASTER_GAIN;
+        let mut written = 0;
+        for frame in buffer.chunks_exact_mut(2) {
+            if self.ended {
+                break
+            }
+            while let Some(&(time, message)) = self.song.events.get(self.next_event) {
+                if time > self.frame {
+                    break
+                }
+                self.apply(message);
+                self.next_event += 1;
+            }
+
+            let (mut left, mut right) = (0.0, 0.0);
+            for note in &mut self.notes {
+                let channel = &self.channels[usize::from(note.channel)];
+                let sample = note.sample(channel) * channel.volume * channel.volume * channel.expression;
+                left += sample * (1.0 - channel.pan).min(1.0);
+                right += sample * (1.0 + channel.pan).min(1.0);
+            }
+            self.notes.retain(|n| !n.finished());
+            frame[0] = left * volume;
+            frame[1] = right * volume;
+            written += 2;
+
+            self.frame += 1;
+            if self.frame >= self.song.length && self.next_event >= self.song.events.len() {
+                if self.looping && self.song.length > 0 {
+                    self.seek(0);
+                } else if self.notes.is_empty() {
+                    self.ended = true;
+                }
+            }
+        }
+        buffer[written..].iter_mut().for_each(|x| *x = 0.0);
+        written
+    }
+
+    fn channel_count(&self) -> ChannelCount {
+        ChannelCount::new(2).unwrap()
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        SampleRate::new(SAMPLE_RATE).unwrap()
+    }
+
+    fn reset(&mut self) {
+        self.frame = 0;
+        self.next_event = 0;
+        self.channels = [Channel::default(); 16];
+        self.notes.clear();
+        self.ended = false;
+    }
+}
+
+/// A song that voices can play like any other clip. The voice takes care of the volume.
+#[derive(Serialize, Deserialize)]
+#[serde(from = "Arc<Song>", into = "Arc<Song>")]
+pub struct MidiClip(Sequencer);
+
+impl From<Arc<Song>> for MidiClip {
+    fn from(song: Arc<Song>) -> Self {
+        let full = || Arc::new(AtomicU32::new(1.0f32.to_bits()));
+        Self(Sequencer::new(song, full(), full(), false))
+    }
+}
+
+impl From<MidiClip> for Arc<Song> {
+    fn from(clip: MidiClip) -> Self {
+        clip.0.song
+    }
+}
+
+impl Clone for MidiClip {
+    /// Clones start from the beginning, since the synthesizer's state isn't worth copying.
+    fn clone(&self) -> Self {
+        self.0.song.clone().into()
+    }
+}
+
+impl MidiClip {
+    pub fn new(data: &[u8]) -> Option<Self> {
+        Song::new(data).map(|song| Arc::new(song).into())
+    }
+
+    /// The length of the song in sample frames.
+    pub fn length(&self) -> u64 {
+        self.0.song.length()
+    }
+}
+
+impl Source for MidiClip {
+    fn write_samples(&mut self, buffer: &mut [Sample]) -> usize {
+        self.0.write_samples(buffer)
+    }
+
+    fn channel_count(&self) -> ChannelCount {
+        self.0.channel_count()
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.0.sample_rate()
+    }
+
+    fn reset(&mut self) {
+        self.0.reset()
+    }
+}
@@ -1,4 +1,7 @@
-use super::SoundParams;
+use super::{
+    effect::{Effects, FilterChain},
+    SoundParams,
+};
 use std::sync::{
     atomic::{AtomicU32, Ordering},
     mpsc::{self, Receiver, Sender},
@@ -12,17 +15,27 @@ const INIT_CAPACITY: usize = 16;
 pub struct Mixer {
     channels: ChannelCount,
     sample_rate: SampleRate,
-    sources: Vec<(Box<dyn Source + Send + 'static>, Arc<SoundParams>, i32)>,
+    sources: Vec<Sound>,
     exclusive_source: Option<(Box<dyn Source + Send + 'static>, i32)>,
     global_volume: Arc<AtomicU32>,
     input_buffer: Vec<Sample>,
     receiver: Receiver<Command>,
 }
 
+/// A sound being mixed, other than the exclusive one.
+struct Sound {
+    source: Box<dyn Source + Send + 'static>,
+    params: Arc<SoundParams>,
+    id: i32,
+    filters: FilterChain,
+}
+
 enum Command {
-    Add { source: Box<dyn Source + Send + 'static>, params: Arc<SoundParams>, id: i32 },
+    Add { source: Box<dyn Source + Send + 'static>, params: Arc<SoundParams>, id: i32, effects: Option<Effects> },
     AddExclusive { source: Box<dyn Source + Send + 'static>, id: i32 },
+    SetEffects { id: i32, effects: Effects },
     Stop(i32),
+    StopVoices,
     StopAll,
 }
 
@@ -61,16 +74,28 @@ impl Source for Mixer {
         // Check for new incoming commands
         while let Ok(cmd) = self.receiver.try_recv() {
             match cmd {
-                Command::Add { source, params, id } => self.sources.push((source, params, id)),
+                Command::Add { source, params, id, effects } => {
+                    let mut filters = FilterChain::new(self.sample_rate.get(), usize::from(self.channels.get()));
+                    if let Some(effects) = effects {
+                        filters.update(&effects);
+                    }
+                    self.sources.push(Sound { source, params, id, filters });
+                },
                 Command::AddExclusive { source, id } => self.exclusive_source = Some((source, id)),
+                Command::SetEffects { id, effects } => {
+                    for sound in self.sources.iter_mut().filter(|sound| sound.id == id) {
+                        sound.filters.update(&effects);
+                    }
+                },
                 Command::Stop(id) => {
-                    self.sources.retain(|(_, _, x)| *x != id);
+                    self.sources.retain(|sound| sound.id != id);
                     if let Some((_, x)) = &self.exclusive_source {
                         if *x == id {
                             self.exclusive_source = None;
                         }
                     }
                 },
+                Command::StopVoices => self.sources.retain(|sound| sound.id >= 0),
                 Command::StopAll => {
                     self.sources.clear();
                     self.exclusive_source = None;
@@ -91,13 +116,21 @@ impl Source for Mixer {
         let input_buffer = &mut self.input_buffer;
         input_buffer.resize_with(buffer.len(), Default::default);
         let global_volume = f32::from_bits(self.global_volume.load(Ordering::Acquire));
+        let channels = usize::from(self.channels.get());
 
-        RetainMut::retain_mut(&mut self.sources, |(source, params, _)| {
-            let volume = f32::from_bits(params.volume.load(Ordering::Acquire));
+        RetainMut::retain_mut(&mut self.sources, |Sound { source, params, filters, .. }| {
+            let volume = f32::from_bits(params.volume.load(Ordering::Acquire))
+                * f32::from_bits(params.attenuation.load(Ordering::Acquire));
+            let pan = f32::from_bits(params.pan.load(Ordering::Acquire));
             let count = source.write_samples(input_buffer);
-
-            for (in_sample, out_sample) in input_buffer.iter().take(count).copied().zip(buffer.iter_mut()) {
-                *out_sample += in_sample * volume * global_volume;
+            filters.process(&mut input_buffer[..count]);
+
+            // Panning turns down one side of a stereo output
+            let gains = if channels == 2 { [(1.0 - pan).min(1.0), (1.0 + pan).min(1.0)] } else { [1.0, 1.0] };
+            for (i, (in_sample, out_sample)) in
+                input_buffer.iter().take(count).copied().zip(buffer.iter_mut()).enumerate()
+            {
+                *out_sample += in_sample * volume * global_volume * gains[i % 2];
             }
 
             count == input_buffer.len()
@@ -120,9 +153,15 @@ impl Source for Mixer {
 }
 
 impl MixerHandle {
-    /// Adds a sound to be mixed, along with its ID and atomic params
-    pub fn add(&self, source: impl Source + Send + 'static, params: Arc<SoundParams>, id: i32) -> Result<(), Error> {
-        let command = Command::Add { source: Box::new(source), params, id };
+    /// Adds a sound to be mixed, along with its ID, atomic params and any effects it should start with
+    pub fn add(
+        &self,
+        source: impl Source + Send + 'static,
+        params: Arc<SoundParams>,
+        id: i32,
+        effects: Option<Effects>,
+    ) -> Result<(), Error> {
+        let command = Command::Add { source: Box::new(source), params, id, effects };
         self.0.send(command).map_err(|_| Error::SendError)
     }
 
@@ -132,11 +171,21 @@ impl MixerHandle {
         self.0.send(command).map_err(|_| Error::SendError)
     }
 
+    /// Applies effects to the sounds with a certain ID that are already playing
+    pub fn set_effects(&self, id: i32, effects: Effects) -> Result<(), Error> {
+        self.0.send(Command::SetEffects { id, effects }).map_err(|_| Error::SendError)
+    }
+
     /// Stops all sounds with a certain ID
     pub fn stop(&self, id: i32) -> Result<(), Error> {
         self.0.send(Command::Stop(id)).map_err(|_| Error::SendError)
     }
 
+    /// Stops all voices, which are the sounds with negative IDs
+    pub fn stop_voices(&self) -> Result<(), Error> {
+        self.0.send(Command::StopVoices).map_err(|_| Error::SendError)
+    }
+
     /// Stops all sounds
     pub fn stop_all(&self) -> Result<(), Error> {
         self.0.send(Command::StopAll).map_err(|_| Error::SendError)
@@ -0,0 +1,109 @@
+use lewton::inside_ogg::OggStreamReader;
+use serde::{Deserialize, Serialize};
+use std::{io::Cursor, sync::Arc};
+use udon::source::{ChannelCount, Sample, SampleRate, Source};
+
+type Reader = OggStreamReader<Cursor<Arc<[u8]>>>;
+
+/// Plays an Ogg Vorbis file, decoding it a packet at a time.
+#[derive(Serialize, Deserialize)]
+pub struct OggPlayer {
+    file: Arc<[u8]>,
+    channels: ChannelCount,
+    sample_rate: SampleRate,
+    length: usize,
+    #[serde(skip)]
+    reader: Option<Reader>,
+    #[serde(skip)]
+    buffer: Vec<Sample>,
+    #[serde(skip)]
+    buffer_off: usize,
+}
+
+impl Clone for OggPlayer {
+    fn clone(&self) -> Self {
+        // The reader can't be cloned, so the clone starts from the beginning
+        Self {
+            file: self.file.clone(),
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            length: self.length,
+            reader: None,
+            buffer: Vec::new(),
+            buffer_off: 0,
+        }
+    }
+}
+
+impl OggPlayer {
+    pub fn new(file: impl Into<Arc<[u8]>>) -> Option<Self> {
+        let file = file.into();
+        let reader = OggStreamReader::new(Cursor::new(file.clone())).ok()?;
+        let channels = ChannelCount::new(reader.ident_hdr.audio_channels.into())?;
+        let sample_rate = SampleRate::new(reader.ident_hdr.audio_sample_rate)?;
+
+        // The granule position of the last page is the number of sample frames in the whole stream
+        let length = file
+            .windows(4)
+            .rposition(|w| w == b"OggS")
+            .and_then(|i| file.get(i + 6..i + 14))
+            .map(|granule| u64::from_le_bytes(granule.try_into().unwrap()))
+            .filter(|&granule| granule != u64::MAX)
+            .map_or(0, |granule| granule as usize);
+
+        Some(Self { file, channels, sample_rate, length, reader: Some(reader), buffer: Vec::new(), buffer_off: 0 })
+    }
+
+    /// The number of sample frames in the file.
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    fn refill(&mut self) -> bool {
+        if self.reader.is_none() {
+            self.reader = OggStreamReader::new(Cursor::new(self.file.clone())).ok();
+        }
+        while let Some(reader) = &mut self.reader {
+            match reader.read_dec_packet_itl() {
+                Ok(Some(samples)) if samples.is_empty() => (),
+                Ok(Some(samples)) => {
+                    self.buffer = samples.into_iter().map(|s| Sample::from(s) / 32768.0).collect();
+                    self.buffer_off = 0;
+                    return true
+                },
+                Ok(None) | Err(_) => break,
+            }
+        }
+        false
+    }
+}
+
+impl Source for OggPlayer {
+    fn channel_count(&self) -> ChannelCount {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    fn write_samples(&mut self, buffer: &mut [Sample]) -> usize {
+        let mut written = 0;
+        while written < buffer.len() {
+            if self.buffer_off >= self.buffer.len() && !self.refill() {
+                break
+            }
+            let count = (self.buffer.len() - self.buffer_off).min(buffer.len() - written);
+            buffer[written..written + count].copy_from_slice(&self.buffer[self.buffer_off..self.buffer_off + count]);
+            self.buffer_off += count;
+            written += count;
+        }
+        written
+    }
+
+    fn reset(&mut self) {
+        self.reader = None;
+        self.buffer.clear();
+        self.buffer_off = 0;
+    }
+}
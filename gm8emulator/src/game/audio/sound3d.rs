@@ -0,0 +1,97 @@
+use super::SoundParams;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
+
+/// How fast sound travels in DirectSound3D's default units (metres per second), used for the Doppler effect.
+const SPEED_OF_SOUND: f64 = 343.3;
+
+/// Where a 3D sound is and how it can be heard, going by DirectSound3D's model with the listener standing still
+/// at the origin and facing along the z axis, which is where GM8 leaves it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Sound3D {
+    pub position: [f64; 3],
+    pub velocity: [f64; 3],
+    pub min_distance: f64,
+    pub max_distance: f64,
+    pub cone_direction: [f64; 3],
+    pub cone_inside: f64,         // in degrees, 0 to 360
+    pub cone_outside: f64,        // in degrees, 0 to 360
+    pub cone_outside_volume: f64, // in hundredths of decibels, -10000 to 0
+}
+
+impl Default for Sound3D {
+    fn default() -> Self {
+        Self {
+            position: [0.0; 3],
+            velocity: [0.0; 3],
+            min_distance: 1.0,
+            max_distance: 1000000000.0,
+            cone_direction: [0.0, 0.0, 1.0],
+            cone_inside: 360.0,
+            cone_outside: 360.0,
+            cone_outside_volume: 0.0,
+        }
+    }
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn length(a: [f64; 3]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+impl Sound3D {
+    /// The volume multiplier for how far away it is. It halves every time the distance doubles,
+    /// starting from the minimum distance and stopping at the maximum distance.
+    fn distance_gain(&self) -> f64 {
+        let min = self.min_distance.max(f64::MIN_POSITIVE);
+        min / length(self.position).clamp(min, self.max_distance.max(min))
+    }
+
+    /// The volume multiplier for where the listener is compared to its cone.
+    fn cone_gain(&self) -> f64 {
+        let scale = length(self.position) * length(self.cone_direction);
+        if scale == 0.0 {
+            return 1.0
+        }
+        // The listener's at the origin, so the direction to it is the opposite of the position
+        let cos = -dot(self.position, self.cone_direction) / scale;
+        let angle = cos.clamp(-1.0, 1.0).acos().to_degrees() * 2.0;
+        let (inside, outside) = (self.cone_inside, self.cone_outside.max(self.cone_inside));
+        let outsideness = if angle <= inside {
+            0.0
+        } else if angle >= outside {
+            1.0
+        } else {
+            (angle - inside) / (outside - inside)
+        };
+        10.0f64.powf(self.cone_outside_volume * outsideness / 2000.0)
+    }
+
+    /// How far to the left or right it is, from -1 to 1.
+    fn pan(&self) -> f64 {
+        let distance = length(self.position);
+        if distance > 0.0 { self.position[0] / distance } else { 0.0 }
+    }
+
+    /// The pitch multiplier from the Doppler effect of it moving towards or away from the listener.
+    fn pitch(&self) -> f64 {
+        let distance = length(self.position);
+        if distance > 0.0 {
+            let receding = dot(self.velocity, self.position) / distance;
+            (SPEED_OF_SOUND / (SPEED_OF_SOUND + receding).max(f64::MIN_POSITIVE)).clamp(0.25, 4.0)
+        } else {
+            1.0
+        }
+    }
+
+    /// Puts the resulting volume, pan and pitch into the params the mixer reads from.
+    pub(super) fn apply(&self, params: &SoundParams) {
+        let attenuation = self.distance_gain() * self.cone_gain();
+        params.attenuation.store((attenuation as f32).to_bits(), Ordering::Release);
+        params.pan.store((self.pan() as f32).to_bits(), Ordering::Release);
+        params.pitch.store((self.pitch() as f32).to_bits(), Ordering::Release);
+    }
+}
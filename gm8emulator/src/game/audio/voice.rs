@@ -0,0 +1,369 @@
+use super::{midi::MidiClip, mp3::Mp3Player, ogg::OggPlayer, SoundParams};
+use serde::{Deserialize, Serialize};
+use std::sync::{atomic::Ordering, Arc};
+use udon::{
+    source::{ChannelCount, Sample, SampleRate, Source},
+    wav::WavPlayer,
+};
+
+/// Sent in `SoundParams::seek` when there's nowhere to seek to.
+pub const NO_SEEK: u64 = u64::MAX;
+
+/// Left in `SoundParams::end` when the voice plays to the end of its clip.
+pub const NO_END: u64 = u64::MAX;
+
+/// Some audio that voices can be played from.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Clip {
+    Wav(WavPlayer),
+    Mp3(Mp3Player),
+    // Boxed because the decoder state is much bigger than the others
+    Ogg(Box<OggPlayer>),
+    Midi(Box<MidiClip>),
+
+    /// A file in a format we can't decode, which plays as silence.
+    Silent,
+}
+
+impl Clip {
+    pub fn new(data: Box<[u8]>) -> Self {
+        if data.starts_with(b"RIFF") {
+            WavPlayer::new(data).map_or(Self::Silent, Self::Wav)
+        } else if data.starts_with(b"OggS") {
+            OggPlayer::new(data).map_or(Self::Silent, |player| Self::Ogg(Box::new(player)))
+        } else if data.starts_with(b"MThd") {
+            MidiClip::new(&data).map_or(Self::Silent, |clip| Self::Midi(Box::new(clip)))
+        } else {
+            Mp3Player::new(data).map_or(Self::Silent, Self::Mp3)
+        }
+    }
+
+    /// The number of sample frames in the clip.
+    pub fn frames(&self) -> usize {
+        match self {
+            Self::Wav(player) => player.length() / usize::from(player.channel_count().get()),
+            Self::Mp3(player) => player.length(), // mp3 length() already takes channels into account
+            Self::Ogg(player) => player.length(),
+            Self::Midi(clip) => clip.length() as usize,
+            Self::Silent => 0,
+        }
+    }
+
+    /// The length of the clip in seconds.
+    pub fn duration(&self) -> f64 {
+        self.frames() as f64 / f64::from(self.sample_rate().get())
+    }
+}
+
+impl Source for Clip {
+    fn write_samples(&mut self, buffer: &mut [Sample]) -> usize {
+        match self {
+            Self::Wav(player) => player.write_samples(buffer),
+            Self::Mp3(player) => player.write_samples(buffer),
+            Self::Ogg(player) => player.write_samples(buffer),
+            Self::Midi(clip) => clip.write_samples(buffer),
+            Self::Silent => 0,
+        }
+    }
+
+    fn channel_count(&self) -> ChannelCount {
+        match self {
+            Self::Wav(player) => player.channel_count(),
+            Self::Mp3(player) => player.channel_count(),
+            Self::Ogg(player) => player.channel_count(),
+            Self::Midi(clip) => clip.channel_count(),
+            Self::Silent => ChannelCount::new(1).unwrap(),
+        }
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        match self {
+            Self::Wav(player) => player.sample_rate(),
+            Self::Mp3(player) => player.sample_rate(),
+            Self::Ogg(player) => player.sample_rate(),
+            Self::Midi(clip) => clip.sample_rate(),
+            Self::Silent => SampleRate::new(44100).unwrap(),
+        }
+    }
+
+    fn reset(&mut self) {
+        match self {
+            Self::Wav(player) => player.reset(),
+            Self::Mp3(player) => player.reset(),
+            Self::Ogg(player) => player.reset(),
+            Self::Midi(clip) => clip.reset(),
+            Self::Silent => (),
+        }
+    }
+}
+
+/// A clip being played with its own volume, pan, pitch and position, the way the audio DLLs do it.
+///
+/// Everything the game can ask about a voice is worked out from the game clock rather than the mixer,
+/// so it's the same however the audio device behaves.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Voice {
+    pub(super) params: Arc<SoundParams>,
+    pub(super) id: i32,
+    duration: f64,
+    sample_rate: u32,
+    loops: i32,
+    pitch: f64,
+    paused: bool,
+    start_time: u128,
+    start_pos: f64,
+}
+
+impl Voice {
+    pub(super) fn new(id: i32, clip: &Clip, loops: i32, paused: bool, now: u128) -> Self {
+        Self {
+            params: Arc::new(SoundParams::new(1.0)),
+            id,
+            duration: clip.duration(),
+            sample_rate: clip.sample_rate().get(),
+            loops,
+            pitch: 1.0,
+            paused,
+            start_time: now,
+            start_pos: 0.0,
+        }
+        .with_params()
+    }
+
+    fn with_params(self) -> Self {
+        self.params.loops.store(self.loops, Ordering::Release);
+        self.params.paused.store(self.paused, Ordering::Release);
+        self
+    }
+
+    /// How far into the clip it's played, in seconds, counting every time it's looped.
+    fn elapsed(&self, now: u128) -> f64 {
+        if self.paused {
+            self.start_pos
+        } else {
+            self.start_pos + now.saturating_sub(self.start_time) as f64 / 1_000_000_000.0 * self.pitch
+        }
+    }
+
+    fn rebase(&mut self, now: u128) {
+        self.start_pos = self.elapsed(now);
+        self.start_time = now;
+    }
+
+    /// Whether it's still playing (or paused) rather than having reached the end.
+    pub fn playing(&self, now: u128) -> bool {
+        self.duration > 0.0 && (self.loops < 0 || self.elapsed(now) < self.duration * f64::from(self.loops + 1))
+    }
+
+    /// The position in the clip, in seconds.
+    pub fn position(&self, now: u128) -> f64 {
+        if self.playing(now) { self.elapsed(now) % self.duration } else { self.duration }
+    }
+
+    /// The position in the clip, in sample frames.
+    pub(super) fn frame(&self, now: u128) -> u64 {
+        (self.position(now) * f64::from(self.sample_rate)) as u64
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.duration
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn pitch(&self) -> f64 {
+        self.pitch
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn seek(&mut self, seconds: f64, now: u128) {
+        if self.duration > 0.0 {
+            let passes = (self.elapsed(now) / self.duration).floor().max(0.0);
+            let seconds = seconds.clamp(0.0, self.duration);
+            self.start_pos = passes * self.duration + seconds;
+            self.start_time = now;
+            self.params.seek.store((seconds * f64::from(self.sample_rate)) as u64, Ordering::Release);
+        }
+    }
+
+    pub fn set_pitch(&mut self, pitch: f64, now: u128) {
+        self.rebase(now);
+        self.pitch = pitch.max(0.0);
+        self.params.pitch.store((self.pitch as f32).to_bits(), Ordering::Release);
+    }
+
+    pub fn set_paused(&mut self, paused: bool, now: u128) {
+        self.rebase(now);
+        self.paused = paused;
+        self.params.paused.store(paused, Ordering::Release);
+    }
+
+    /// Sets how many more times it loops after this, or -1 for forever.
+    pub fn set_loops(&mut self, loops: i32, now: u128) {
+        if self.duration > 0.0 {
+            self.rebase(now);
+            let passes = (self.start_pos / self.duration).floor() as i32;
+            self.loops = if loops < 0 { -1 } else { passes + loops };
+            self.params.loops.store(self.loops, Ordering::Release);
+        }
+    }
+
+    /// Makes it stop, or loop back to the start, at the given point in the clip. This should be set before it plays.
+    pub fn set_end(&mut self, seconds: f64) {
+        self.duration = seconds.clamp(0.0, self.duration);
+        self.params.end.store((self.duration * f64::from(self.sample_rate)) as u64, Ordering::Release);
+    }
+
+    /// Sets the volume, as a plain multiplier.
+    pub fn set_volume(&self, volume: f64) {
+        self.params.volume.store((volume.max(0.0) as f32).to_bits(), Ordering::Release);
+    }
+
+    /// Sets the pan, from -1 (left) to 1 (right).
+    pub fn set_pan(&self, pan: f64) {
+        self.params.pan.store((pan.clamp(-1.0, 1.0) as f32).to_bits(), Ordering::Release);
+    }
+}
+
+/// Plays a clip at the mixer's sample rate, following the pitch, pausing, seeking and looping in a voice's params.
+pub struct Varispeed {
+    source: Clip,
+    params: Arc<SoundParams>,
+    channels: usize,
+    step: f64,
+    output_rate: SampleRate,
+    input: Vec<Sample>,
+    input_len: usize,
+    input_pos: usize,
+    pass_len: usize,
+    passes: i32,
+    current: Vec<Sample>,
+    next: Vec<Sample>,
+    fraction: f64,
+    ended: bool,
+}
+
+impl Varispeed {
+    const BLOCK_FRAMES: usize = 1024;
+
+    pub fn new(source: Clip, params: Arc<SoundParams>, output_rate: SampleRate) -> Self {
+        let channels = usize::from(source.channel_count().get());
+        let step = f64::from(source.sample_rate().get()) / f64::from(output_rate.get());
+        let mut varispeed = Self {
+            source,
+            params,
+            channels,
+            step,
+            output_rate,
+            input: vec![0.0; Self::BLOCK_FRAMES * channels],
+            input_len: 0,
+            input_pos: 0,
+            pass_len: 0,
+            passes: 0,
+            current: vec![0.0; channels],
+            next: vec![0.0; channels],
+            fraction: 0.0,
+            ended: false,
+        };
+        varispeed.seek(0);
+        varispeed
+    }
+
+    /// Reads the next frame of the clip into `self.next`, looping if it should. Returns false at the end.
+    fn read_frame(&mut self) -> bool {
+        let at_end = self.pass_len as u64 >= self.params.end.load(Ordering::Acquire);
+        if at_end || self.input_pos >= self.input_len {
+            self.input_len = if at_end { 0 } else { self.source.write_samples(&mut self.input) / self.channels };
+            self.input_pos = 0;
+            if self.input_len == 0 {
+                let loops = self.params.loops.load(Ordering::Acquire);
+                if self.pass_len == 0 || (loops >= 0 && self.passes >= loops) {
+                    return false
+                }
+                self.source.reset();
+                self.passes += 1;
+                self.pass_len = 0;
+                return self.read_frame()
+            }
+        }
+        let start = self.input_pos * self.channels;
+        self.next.copy_from_slice(&self.input[start..start + self.channels]);
+        self.input_pos += 1;
+        self.pass_len += 1;
+        true
+    }
+
+    fn seek(&mut self, frame: u64) {
+        self.source.reset();
+        self.input_len = 0;
+        self.input_pos = 0;
+        self.pass_len = 0;
+        self.ended = false;
+        self.fraction = 0.0;
+        for _ in 0..frame {
+            if !self.read_frame() {
+                break
+            }
+        }
+        self.ended = !self.read_frame();
+        self.current.copy_from_slice(&self.next);
+        if !self.ended && !self.read_frame() {
+            self.next.copy_from_slice(&self.current);
+        }
+    }
+}
+
+impl Source for Varispeed {
+    fn write_samples(&mut self, buffer: &mut [Sample]) -> usize {
+        let seek = self.params.seek.swap(NO_SEEK, Ordering::AcqRel);
+        if seek != NO_SEEK {
+            self.seek(seek);
+        }
+        if self.params.paused.load(Ordering::Acquire) {
+            buffer.iter_mut().for_each(|x| *x = 0.0);
+            return buffer.len()
+        }
+
+        let step = self.step * f64::from(f32::from_bits(self.params.pitch.load(Ordering::Acquire))).max(0.0);
+        let mut written = 0;
+        for frame in buffer.chunks_exact_mut(self.channels) {
+            if self.ended {
+                break
+            }
+            let fraction = self.fraction as f32;
+            for ((out, current), next) in frame.iter_mut().zip(&self.current).zip(&self.next) {
+                *out = current + (next - current) * fraction;
+            }
+            written += self.channels;
+            self.fraction += step;
+            while self.fraction >= 1.0 {
+                self.fraction -= 1.0;
+                std::mem::swap(&mut self.current, &mut self.next);
+                if !self.read_frame() {
+                    self.ended = true;
+                    break
+                }
+            }
+        }
+        buffer[written..].iter_mut().for_each(|x| *x = 0.0);
+        written
+    }
+
+    fn channel_count(&self) -> ChannelCount {
+        self.source.channel_count()
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.output_rate
+    }
+
+    fn reset(&mut self) {
+        self.passes = 0;
+        self.seek(0);
+    }
+}
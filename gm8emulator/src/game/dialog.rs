@@ -0,0 +1,390 @@
+use crate::{
+    game::{draw, replay, Game, PlayType},
+    gml, input,
+    math::Real,
+    render::{BlendType, RendererState},
+};
+use ramen::{
+    event::Event,
+    input::{Key, MouseButton},
+};
+use std::{fs::OpenOptions, io::Write, path::PathBuf, time::Duration};
+
+/// Something the game wants the user to answer.
+pub enum Dialog<'a> {
+    /// Some text and a row of buttons, answered with the index of the chosen button.
+    Message { text: &'a str, buttons: &'a [&'a str] },
+
+    /// A prompt for a line of text, answered with the text.
+    Input { prompt: &'a str, default: &'a str },
+
+    /// A colour picker, answered with the colour or -1 if cancelled.
+    Colour { default: i32 },
+
+    /// A popup menu, answered with the index of the item chosen. Items which are "-" are separators,
+    /// and an index past the end means nothing was chosen.
+    Menu { items: &'a [&'a str] },
+}
+
+pub enum Answer {
+    Button(usize),
+    Text(String),
+    Colour(i32),
+}
+
+/// Shows a dialog in place of the game's own one, for when the game window isn't the game's to draw in,
+/// such as while recording. Returns None if the game was closed instead of answering.
+pub type DialogHook = Box<dyn FnMut(&mut Game, &Dialog) -> Option<Answer>>;
+
+/// A renderer state for drawing flat 2D things over the game, based on the game's own state.
+pub fn plain_state(game_state: &RendererState) -> RendererState {
+    RendererState {
+        model_matrix: [1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0],
+        alpha_blending: true,
+        blend_mode: (BlendType::SrcAlpha, BlendType::InvSrcAlpha),
+        using_3d: false,
+        depth_test: false,
+        write_depth: false,
+        culling: false,
+        perspective: false,
+        fog: None,
+        lighting_enabled: false,
+        ..game_state.clone()
+    }
+}
+
+const DIALOG_PADDING: i32 = 12;
+const BUTTON_WIDTH: i32 = 75;
+const BUTTON_HEIGHT: i32 = 23;
+
+impl Game {
+    /// Shows a modal dialog with some text and a row of buttons, returning the index of the button chosen,
+    /// or None if the game window was closed instead. The choice isn't recorded, so callers which can run
+    /// during a replay need to store it themselves.
+    pub fn show_dialog(&mut self, text: &str, buttons: &[&str]) -> Option<usize> {
+        match self.dialog_hook(&Dialog::Message { text, buttons }) {
+            Some(answer) => match answer {
+                Some(Answer::Button(i)) => Some(i),
+                _ => None,
+            },
+            None => self.draw_message(text, buttons),
+        }
+    }
+
+    /// Asks the user for a line of text, returning None if the game window was closed instead.
+    /// Like `show_dialog`, the answer isn't recorded.
+    pub fn show_input(&mut self, prompt: &str, default: &str) -> Option<String> {
+        match self.dialog_hook(&Dialog::Input { prompt, default }) {
+            Some(answer) => match answer {
+                Some(Answer::Text(text)) => Some(text),
+                _ => None,
+            },
+            None => self.draw_input(prompt, default),
+        }
+    }
+
+    /// Passes a dialog to the hook if there is one. The outer Option is None if the engine should draw it instead.
+    pub(super) fn dialog_hook(&mut self, dialog: &Dialog) -> Option<Option<Answer>> {
+        if let Some(mut hook) = self.dialog_hook.take() {
+            let answer = hook(self, dialog);
+            if self.dialog_hook.is_none() {
+                self.dialog_hook = Some(hook);
+            }
+            Some(answer)
+        } else if self.play_type == PlayType::Normal {
+            None
+        } else {
+            Some(None)
+        }
+    }
+
+    /// Finds a font resource standing in for a system font given by name, size and style (1 for bold plus 2 for
+    /// italic), since system fonts aren't available. Failing that, it's the default font scaled to the size.
+    /// Returns the font id and the scale to draw it at.
+    pub fn system_font(&self, name: &[u8], size: i32, style: i32) -> (i32, Real) {
+        let font = self.assets.fonts.iter().position(|f| {
+            f.as_ref().is_some_and(|f| {
+                f.sys_name.as_ref().eq_ignore_ascii_case(name)
+                    && f.size as i32 == size
+                    && f.bold == (style & 1 != 0)
+                    && f.italic == (style & 2 != 0)
+            })
+        });
+        match font {
+            Some(id) => (id as i32, Real::from(1.0)),
+            None => (-1, Real::from(size.max(1) as f64 / 12.0)),
+        }
+    }
+
+    /// Handles an error which made it out of a piece of GML, the way GM8 does, according to the game's settings.
+    /// Returns the error back if the game should be aborted, or Ok if it should carry on.
+    pub fn runtime_error(&mut self, error: gml::Error) -> gml::Result<()> {
+        if error.is_fatal() {
+            return Err(error)
+        }
+        self.report_error(error, self.show_errors, self.abort_on_error)
+    }
+
+    /// Reports an error like GM8: it's stored in `error_occurred` and `error_last`, logged if the game asks for
+    /// that, and shown if `show` is set, with an Ignore button unless `always_abort` is set.
+    /// Returns the error wrapped in `Aborted` if the game should be aborted, or Ok if it should carry on.
+    pub fn report_error(&mut self, error: gml::Error, show: bool, always_abort: bool) -> gml::Result<()> {
+        let message = error.to_string();
+        self.error_occurred = true;
+        self.error_last = message.clone().into();
+        if self.log_errors {
+            let mut path = PathBuf::from(self.decode_str(self.program_directory.as_ref()).into_owned());
+            path.push("game_errors.log");
+            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+                let _ = write!(file, "{}\r\n", message.replace('\n', "\r\n"));
+            }
+        }
+
+        let abort = if !show {
+            always_abort
+        } else if always_abort {
+            if self.play_type != PlayType::Replay {
+                self.show_dialog(&message, &["Abort"]);
+            }
+            true
+        } else {
+            match self.play_type {
+                PlayType::Normal => self.show_dialog(&message, &["Ignore", "Abort"]) != Some(0),
+                PlayType::Record => {
+                    let abort = self.show_dialog(&message, &["Ignore", "Abort"]) != Some(0);
+                    self.stored_events.push_back(replay::Event::ShowError(abort));
+                    abort
+                },
+                PlayType::Replay => match self.stored_events.pop_front() {
+                    Some(replay::Event::ShowError(abort)) => abort,
+                    _ => return Err(gml::Error::ReplayError("runtime error dialog".into())),
+                },
+            }
+        };
+        if abort { Err(gml::Error::Aborted(Box::new(error))) } else { Ok(()) }
+    }
+
+    /// Takes over the game window until `update` returns something.
+    /// Each time round, `update` gets the window events since last time and the framebuffer size, and draws over
+    /// the game's last frame. It starts off with a plain 2D renderer state and the default font, left and top aligned.
+    pub fn run_modal<T>(&mut self, mut update: impl FnMut(&mut Self, &[Event], (i32, i32)) -> Option<T>) -> T {
+        let (width, height) = self.renderer.stored_size();
+        let game_renderer_state = self.renderer.state();
+        self.renderer.resize_framebuffer(width, height, true);
+        self.renderer.set_state(&plain_state(&game_renderer_state));
+        let font_state = (self.draw_font_id, self.draw_halign, self.draw_valign);
+
+        let result = loop {
+            self.window.poll_events();
+            let events = self.window.events().to_vec();
+            for event in events.iter().copied() {
+                match event {
+                    // Don't let the game think keys are still held after the dialog closes
+                    Event::KeyboardUp(key) => self.input.button_release(input::ramen2vk(key), true),
+                    Event::MouseUp(button) => self.input.mouse_release(input::ramen2mb(button), true),
+                    Event::Resize((w, h)) => self.window_inner_size = (w.into(), h.into()),
+                    Event::CloseRequest => self.close_requested = true,
+                    _ => (),
+                }
+            }
+
+            self.draw_font_id = -1;
+            self.draw_halign = draw::Halign::Left;
+            self.draw_valign = draw::Valign::Top;
+            self.renderer.set_view(0, 0, width as _, height as _, 0.0, 0, 0, width as _, height as _);
+            self.renderer.draw_stored(0, 0, width, height);
+            if let Some(result) = update(self, &events, (width as i32, height as i32)) {
+                break result
+            }
+            self.renderer.present(self.window_inner_size.0, self.window_inner_size.1, self.scaling);
+            gml::datetime::sleep(Duration::from_millis(16));
+        };
+
+        (self.draw_font_id, self.draw_halign, self.draw_valign) = font_state;
+        self.resume_drawing(width, height, &game_renderer_state);
+        result
+    }
+
+    /// Converts a mouse position in the window to the coordinates modal dialogs are drawn in, undoing the scaling.
+    pub fn modal_mouse(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        let (width, height) = self.renderer.stored_size();
+        let (width, height) = (width as i32, height as i32);
+        let window_size = (self.window_inner_size.0 as i32, self.window_inner_size.1 as i32);
+        let (view_x, view_y, view_w, view_h) = self.scaling.viewport((width, height), window_size);
+        if view_w <= 0 || view_h <= 0 {
+            return (x, y)
+        }
+        (((x - view_x) * width).div_euclid(view_w), ((y - view_y) * height).div_euclid(view_h))
+    }
+
+    /// Draws some text with the current font and alignment.
+    pub fn draw_label(&mut self, x: i32, y: i32, text: &str, max_width: Option<i32>, colour: i32) {
+        self.draw_string(
+            Real::from(x),
+            Real::from(y),
+            text.to_string().into(),
+            None,
+            max_width,
+            Real::from(1.0),
+            Real::from(1.0),
+            Real::from(0.0),
+            Some((colour, colour, colour, colour)),
+            Real::from(1.0),
+        );
+    }
+
+    /// Draws a box, such as a dialog background or a button.
+    pub fn draw_panel(&mut self, x1: i32, y1: i32, x2: i32, y2: i32, fill: i32, outline: i32) {
+        let (x1, y1, x2, y2) = (x1.into(), y1.into(), x2.into(), y2.into());
+        self.renderer.draw_rectangle(x1, y1, x2, y2, fill, 1.0);
+        self.renderer.draw_rectangle_outline(x1, y1, x2, y2, outline, 1.0);
+    }
+
+    /// Draws a message box over the game and waits for the user to pick one of its buttons.
+    fn draw_message(&mut self, text: &str, buttons: &[&str]) -> Option<usize> {
+        let mut focus = 0;
+        let mut mouse = self.modal_mouse((self.input.mouse_x(), self.input.mouse_y()));
+        self.run_modal(|game, events, (width, height)| {
+            // Lay the dialog out in the middle of the screen, with the buttons along the bottom right like Windows
+            let (text_w, text_h) =
+                game.get_string_size(text.to_string().into(), None, Some((width - DIALOG_PADDING * 4).max(64)));
+            let buttons_w = buttons.len() as i32 * (BUTTON_WIDTH + DIALOG_PADDING) - DIALOG_PADDING;
+            let box_w = text_w.max(buttons_w) + DIALOG_PADDING * 2;
+            let box_h = text_h + BUTTON_HEIGHT + DIALOG_PADDING * 3;
+            let box_x = (width - box_w) / 2;
+            let box_y = (height - box_h) / 2;
+            let button_y = box_y + box_h - DIALOG_PADDING - BUTTON_HEIGHT;
+            let button_x =
+                |i: usize| box_x + box_w - DIALOG_PADDING - buttons_w + i as i32 * (BUTTON_WIDTH + DIALOG_PADDING);
+
+            for event in events.iter().copied() {
+                match event {
+                    Event::KeyboardDown(Key::Tab) | Event::KeyboardDown(Key::RightArrow) => {
+                        focus = (focus + 1) % buttons.len()
+                    },
+                    Event::KeyboardDown(Key::LeftArrow) => focus = (focus + buttons.len() - 1) % buttons.len(),
+                    Event::KeyboardDown(Key::Return) | Event::KeyboardDown(Key::Space) => return Some(Some(focus)),
+                    Event::MouseMove((x, y)) => mouse = game.modal_mouse((x.into(), y.into())),
+                    Event::MouseDown(MouseButton::Left) => {
+                        if let Some(i) = (0..buttons.len()).find(|&i| {
+                            (button_x(i)..button_x(i) + BUTTON_WIDTH).contains(&mouse.0)
+                                && (button_y..button_y + BUTTON_HEIGHT).contains(&mouse.1)
+                        }) {
+                            return Some(Some(i))
+                        }
+                    },
+                    Event::CloseRequest => return Some(None),
+                    _ => (),
+                }
+            }
+
+            game.renderer.draw_rectangle(0.0, 0.0, width.into(), height.into(), 0, 0.25);
+            game.draw_panel(box_x, box_y, box_x + box_w, box_y + box_h, 0xf0f0f0, 0x808080);
+            game.draw_label(box_x + DIALOG_PADDING, box_y + DIALOG_PADDING, text, Some(box_w - DIALOG_PADDING * 2), 0);
+            game.draw_halign = draw::Halign::Middle;
+            game.draw_valign = draw::Valign::Middle;
+            for (i, label) in buttons.iter().enumerate() {
+                let x = button_x(i);
+                game.draw_panel(
+                    x,
+                    button_y,
+                    x + BUTTON_WIDTH,
+                    button_y + BUTTON_HEIGHT,
+                    0xe1e1e1,
+                    if i == focus { 0xd77800 } else { 0xadadad },
+                );
+                game.draw_label(x + BUTTON_WIDTH / 2, button_y + BUTTON_HEIGHT / 2, label, None, 0);
+            }
+            None
+        })
+    }
+
+    /// Draws a text prompt over the game and waits for the user to enter something.
+    fn draw_input(&mut self, prompt: &str, default: &str) -> Option<String> {
+        let mut text = default.to_string();
+        self.run_modal(|game, events, (width, height)| {
+            for event in events.iter().copied() {
+                match event {
+                    Event::Input(c) if !c.is_control() => text.push(c),
+                    Event::KeyboardDown(Key::Backspace) => {
+                        text.pop();
+                    },
+                    Event::KeyboardDown(Key::Return) => return Some(Some(text.clone())),
+                    Event::KeyboardDown(Key::Escape) => return Some(Some(default.to_string())),
+                    Event::CloseRequest => return Some(None),
+                    _ => (),
+                }
+            }
+
+            let box_w = (width - DIALOG_PADDING * 2).min(320);
+            let text_w = box_w - DIALOG_PADDING * 2;
+            let prompt_h = game.get_string_size(prompt.to_string().into(), None, Some(text_w)).1;
+            let line_h = game.default_font.tallest_char_height as i32 + 6;
+            let box_h = prompt_h + line_h + DIALOG_PADDING * 3;
+            let box_x = (width - box_w) / 2;
+            let box_y = (height - box_h) / 2;
+            let field_y = box_y + box_h - DIALOG_PADDING - line_h;
+            game.renderer.draw_rectangle(0.0, 0.0, width.into(), height.into(), 0, 0.25);
+            game.draw_panel(box_x, box_y, box_x + box_w, box_y + box_h, 0xf0f0f0, 0x808080);
+            game.draw_label(box_x + DIALOG_PADDING, box_y + DIALOG_PADDING, prompt, Some(text_w), 0);
+            game.draw_panel(
+                box_x + DIALOG_PADDING,
+                field_y,
+                box_x + box_w - DIALOG_PADDING,
+                field_y + line_h,
+                0xffffff,
+                0xd77800,
+            );
+            game.draw_label(box_x + DIALOG_PADDING + 3, field_y + 3, &format!("{}_", text), None, 0);
+            None
+        })
+    }
+
+    /// Puts the game's drawing back how it was before something else took over the framebuffer partway
+    /// through a frame. `width` and `height` are the size it had, and its contents must have been stored.
+    pub fn resume_drawing(&mut self, width: u32, height: u32, renderer_state: &RendererState) {
+        self.renderer.set_state(renderer_state);
+        self.renderer.resize_framebuffer(width, height, false);
+        self.renderer.set_view(
+            0,
+            0,
+            self.unscaled_width as _,
+            self.unscaled_height as _,
+            0.0,
+            0,
+            0,
+            self.unscaled_width as _,
+            self.unscaled_height as _,
+        );
+        self.renderer.draw_stored(0, 0, width, height);
+        if let Some(surf_id) = self.surface_target {
+            let _ = self.surface_set_target(&[surf_id.into()]);
+        } else if self.room.views_enabled {
+            if let Some(view) = self.room.views.get(self.view_current) {
+                self.renderer.set_view(
+                    view.source_x,
+                    view.source_y,
+                    view.source_w as _,
+                    view.source_h as _,
+                    view.angle.into(),
+                    view.port_x,
+                    view.port_y,
+                    view.port_w as _,
+                    view.port_h as _,
+                );
+            }
+        } else {
+            self.renderer.set_view(
+                0,
+                0,
+                self.room.width,
+                self.room.height,
+                0.0,
+                0,
+                0,
+                self.room.width,
+                self.room.height,
+            );
+        }
+    }
+}
@@ -215,6 +215,7 @@ impl Game {
             self.unscaled_width as _,
             self.unscaled_height as _,
         );
+        self.draw_splash_overlay();
 
         // Apply room caption
         let title = self.get_window_title();
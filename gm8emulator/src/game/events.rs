@@ -77,7 +77,14 @@ impl Game {
                 }
             };
 
-            self.execute_tree(event, instance, other, event_id, event_sub as _, object_id)
+            self.profile_enter(gml::profiler::Entry::Event {
+                object: object_id,
+                event_type: event_id,
+                event_number: event_sub as _,
+            });
+            let result = self.execute_tree(event, instance, other, event_id, event_sub as _, object_id);
+            self.profile_exit();
+            result
         } else {
             Ok(())
         }
@@ -127,7 +134,8 @@ impl Game {
                             context.event_type = 11; // ev_trigger
                             context.event_number = trigger_id as _;
                             context.event_object = self.room.instance_list.get(handle).object_index.get();
-                            self.execute(&trigger.condition, &mut context)?;
+                            let code_source = gml::debugger::CodeSource::Trigger(trigger_id as _);
+                            self.execute_frame(code_source, &trigger.condition, &mut context)?;
                             if context.return_value.is_truthy() {
                                 self.run_instance_event(gml::ev::TRIGGER, trigger_id, handle, handle, None)?;
                             }
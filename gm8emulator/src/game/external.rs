@@ -1,5 +1,8 @@
 pub mod dll;
+pub mod dll39;
 mod dummy;
+pub mod gmfmod;
+pub mod supersound;
 pub mod win32;
 mod wow64;
 
@@ -34,6 +37,10 @@ pub struct External {
 pub struct ExternalManager {
     externals: Vec<Option<External>>,
     dummy_audio: bool,
+    emulate: bool,
+    pub dll39: dll39::State,
+    pub gmfmod: gmfmod::State,
+    pub supersound: supersound::State,
 
     native_manager: native::NativeManager,
     ipc_manager: ipc::IpcManager,
@@ -42,27 +49,48 @@ pub struct ExternalManager {
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ExternalState {
     signatures: Vec<Option<dll::ExternalSignature>>,
+    dll39: dll39::SavedState,
+    gmfmod: gmfmod::State,
+    supersound: supersound::State,
 }
 
 impl ExternalManager {
-    pub fn new(dummy_audio: bool) -> Self {
+    /// With `emulate` set, DLLs that have a built-in version always use it. Otherwise the built-in version is
+    /// only used when the real DLL can't be loaded.
+    pub fn new(dummy_audio: bool, emulate: bool) -> Self {
         Self {
             externals: Vec::new(),
             dummy_audio,
+            emulate,
+            dll39: dll39::State::new(),
+            gmfmod: gmfmod::State::new(),
+            supersound: supersound::State::new(),
             native_manager: native::NativeManager::new(),
             ipc_manager: ipc::IpcManager::new(),
         }
     }
 
     fn make_call(&mut self, signature: &dll::ExternalSignature) -> Result<Call, String> {
+        if self.emulate {
+            if let Some(call) = self.emulated_call(signature) {
+                return Ok(call)
+            }
+        }
         if let Some(dummy) = self.should_dummy(&signature) {
             return Ok(Call::Dummy(dummy))
         }
-        if cfg!(all(target_os = "windows", target_arch = "x86")) {
-            Ok(Call::Native(self.native_manager.define(&signature)?))
+        let call = if cfg!(all(target_os = "windows", target_arch = "x86")) {
+            self.native_manager.define(&signature).map(Call::Native)
         } else {
-            Ok(Call::Ipc(self.ipc_manager.define(&signature)?))
-        }
+            self.ipc_manager.define(&signature).map(Call::Ipc)
+        };
+        call.or_else(|e| match self.emulated_call(signature) {
+            Some(call) => {
+                eprintln!("Using the built-in {} because the real one couldn't be loaded: {}", signature.dll, e);
+                Ok(call)
+            },
+            None => Err(e),
+        })
     }
 
     pub fn define(&mut self, signature: dll::ExternalSignature) -> Result<ID, String> {
@@ -107,11 +135,19 @@ impl ExternalManager {
                 }
             }
         }
+        if file_name(dll).eq_ignore_ascii_case("39dll.dll") {
+            self.dll39 = dll39::State::new();
+        }
     }
 
     pub fn save_state(&self) -> ExternalState {
         let signatures = self.externals.iter().map(|o| o.as_ref().map(|e| e.signature.clone())).collect();
-        ExternalState { signatures }
+        ExternalState {
+            signatures,
+            dll39: self.dll39.save_state(),
+            gmfmod: self.gmfmod.clone(),
+            supersound: self.supersound.clone(),
+        }
     }
 
     pub fn load_state(&mut self, mut state: ExternalState) {
@@ -120,12 +156,14 @@ impl ExternalManager {
             let external = opt.map(|s| External { call: self.make_call(&s).unwrap(), signature: s });
             self.externals.push(external);
         }
+        self.dll39.load_state(state.dll39);
+        self.gmfmod = state.gmfmod;
+        self.supersound = state.supersound;
     }
 
     fn should_dummy(&self, signature: &dll::ExternalSignature) -> Option<gml::Value> {
-        let dll = &signature.dll;
+        let dll = file_name(&signature.dll);
         let sym = &signature.symbol;
-        let dll = Path::new(dll).file_name().and_then(|oss| oss.to_str()).unwrap_or(dll);
 
         let mut dummy = None;
         if self.dummy_audio {
@@ -161,4 +199,57 @@ impl ExternalManager {
 
         dummy
     }
+
+    /// Gives the built-in version of a function from a DLL that's emulated, if there is one.
+    fn emulated_call(&self, signature: &dll::ExternalSignature) -> Option<Call> {
+        let dll = file_name(&signature.dll);
+        let function = if dll.eq_ignore_ascii_case("39dll.dll") {
+            dll39::function(&signature.symbol)
+        } else if dll.eq_ignore_ascii_case("gmfmodsimple.dll") {
+            gmfmod::function(&signature.symbol)
+        } else if dll.eq_ignore_ascii_case("ssound.dll") || dll.eq_ignore_ascii_case("supersound.dll") {
+            supersound::function(&signature.symbol)
+        } else {
+            return None
+        };
+        // Anything these DLLs have that isn't emulated does nothing
+        Some(match function {
+            Some(function) => Call::Emulated(function),
+            None => {
+                eprintln!("Warning: {} from {} isn't emulated, so it will do nothing", signature.symbol, dll);
+                Call::Dummy(gml::Value::Real(0.into()))
+            },
+        })
+    }
+}
+
+fn file_name(dll: &str) -> &str {
+    Path::new(dll).file_name().and_then(|oss| oss.to_str()).unwrap_or(dll)
+}
+
+// DLL arguments have already been checked against the signature, so these just pick them out.
+
+// Some DLLs pass their handles around as strings, so numbers are read out of those too.
+
+fn real(args: &[gml::Value], index: usize) -> f64 {
+    match args.get(index) {
+        Some(gml::Value::Str(s)) => String::from_utf8_lossy(s.as_ref()).trim().parse().unwrap_or(0.0),
+        Some(value) => value.clone().into(),
+        None => 0.0,
+    }
+}
+
+fn int(args: &[gml::Value], index: usize) -> i32 {
+    match args.get(index) {
+        Some(value @ gml::Value::Real(_)) => value.clone().into(),
+        _ => real(args, index).round() as i32,
+    }
+}
+
+fn bytes(args: &[gml::Value], index: usize) -> &[u8] {
+    args.get(index).map_or(&[][..], |v| v.into())
+}
+
+fn string(args: &[gml::Value], index: usize) -> String {
+    String::from_utf8_lossy(bytes(args, index)).into_owned()
 }
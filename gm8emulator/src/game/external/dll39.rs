@@ -0,0 +1,989 @@
+// An emulation of 39dll 2.5, the networking DLL most GM8 online games ship with.
+
+use super::{bytes, int, real, string};
+use crate::{
+    game::{Game, PlayType},
+    gml::{self, file, network, Function, Value},
+    handleman::{HandleList, HandleManager},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{self, Read, Seek, SeekFrom, Write},
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket},
+    ops::Range,
+    sync::mpsc,
+};
+
+// What winsock would have said, for socklasterror
+const WSAEWOULDBLOCK: i32 = 10035;
+const WSAEMSGSIZE: i32 = 10040;
+const WSAENOTCONN: i32 = 10057;
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct Buffer {
+    data: Vec<u8>,
+    read_pos: usize,
+    write_pos: usize,
+}
+
+impl Buffer {
+    fn set(&mut self, data: Vec<u8>) {
+        *self = Self { write_pos: data.len(), data, read_pos: 0 };
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        let end = self.write_pos + bytes.len();
+        if self.data.len() < end {
+            self.data.resize(end, 0);
+        }
+        self.data[self.write_pos..end].copy_from_slice(bytes);
+        self.write_pos = end;
+    }
+
+    fn read(&mut self, len: usize) -> &[u8] {
+        let start = self.read_pos.min(self.data.len());
+        let end = start.saturating_add(len).min(self.data.len());
+        self.read_pos = end;
+        &self.data[start..end]
+    }
+
+    /// Reads a fixed-size value, padding with zeroes past the end of the buffer.
+    fn read_array<const N: usize>(&mut self) -> [u8; N] {
+        let mut array = [0; N];
+        let bytes = self.read(N);
+        array[..bytes.len()].copy_from_slice(bytes);
+        array
+    }
+
+    fn read_string(&mut self) -> Vec<u8> {
+        let rest = self.data.get(self.read_pos..).unwrap_or_default();
+        let len = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+        let string = rest[..len].to_vec();
+        self.read_pos = (self.read_pos + len + 1).min(self.data.len());
+        string
+    }
+}
+
+enum Kind {
+    Stream(TcpStream),
+    Listener(TcpListener),
+    Datagram(UdpSocket),
+    Connecting(mpsc::Receiver<io::Result<TcpStream>>),
+}
+
+/// How TCP messages are split up, as chosen with setformat.
+enum Format {
+    /// Each message has its length in front as a u16.
+    Binary,
+    /// Each message ends with the given separator.
+    Text(Vec<u8>),
+    /// Whatever has arrived is a message.
+    Raw,
+}
+
+struct Socket {
+    kind: Kind,
+    blocking: bool,
+    format: Format,
+    received: Vec<u8>,
+    error: i32,
+}
+
+impl Socket {
+    fn new(kind: Kind, blocking: bool) -> Self {
+        let mut socket = Self { kind, blocking, format: Format::Binary, received: Vec::new(), error: 0 };
+        let _ = socket.set_blocking(blocking);
+        socket
+    }
+
+    fn set_blocking(&mut self, blocking: bool) -> io::Result<()> {
+        self.blocking = blocking;
+        match &self.kind {
+            Kind::Stream(stream) => stream.set_nonblocking(!blocking),
+            Kind::Listener(listener) => listener.set_nonblocking(!blocking),
+            Kind::Datagram(socket) => socket.set_nonblocking(!blocking),
+            Kind::Connecting(_) => Ok(()),
+        }
+    }
+
+    /// Checks whether a connection made in the background has gone through yet.
+    fn connected(&mut self) -> bool {
+        if let Kind::Connecting(receiver) = &self.kind {
+            match receiver.try_recv() {
+                Ok(Ok(stream)) => {
+                    self.kind = Kind::Stream(stream);
+                    let _ = self.set_blocking(self.blocking);
+                },
+                Ok(Err(e)) => {
+                    self.error = error_code(&e);
+                    return false
+                },
+                Err(_) => return false,
+            }
+        }
+        true
+    }
+
+    fn send(&mut self, data: &[u8], to: impl FnOnce() -> Option<SocketAddr>) -> io::Result<usize> {
+        if !self.connected() {
+            return Err(io::ErrorKind::NotConnected.into())
+        }
+        match &mut self.kind {
+            Kind::Stream(stream) => {
+                let mut message = Vec::with_capacity(data.len() + 2);
+                match &self.format {
+                    Format::Binary => {
+                        // The length has to fit in front of it
+                        let len =
+                            u16::try_from(data.len()).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+                        message.extend_from_slice(&len.to_le_bytes());
+                        message.extend_from_slice(data);
+                    },
+                    Format::Text(separator) => {
+                        message.extend_from_slice(data);
+                        message.extend_from_slice(separator);
+                    },
+                    Format::Raw => message.extend_from_slice(data),
+                }
+                // Send all of it even if the socket doesn't block, as the DLL would have queued the rest
+                stream.set_nonblocking(false)?;
+                let result = stream.write_all(&message);
+                stream.set_nonblocking(!self.blocking)?;
+                result.map(|()| message.len())
+            },
+            Kind::Datagram(socket) => match to() {
+                Some(address) => socket.send_to(data, address),
+                None => Err(io::ErrorKind::AddrNotAvailable.into()),
+            },
+            _ => Err(io::ErrorKind::NotConnected.into()),
+        }
+    }
+
+    /// Receives a message, or exactly `len` bytes if that isn't 0. An empty message means the connection was closed.
+    fn receive(&mut self, len: usize, peek: bool, last_in: &mut Option<SocketAddr>) -> io::Result<Vec<u8>> {
+        if !self.connected() {
+            return Err(io::ErrorKind::WouldBlock.into())
+        }
+        match &mut self.kind {
+            Kind::Stream(stream) => {
+                let mut chunk = [0u8; 4096];
+                loop {
+                    if let Some((range, taken)) = next_message(&self.received, &self.format, len) {
+                        let message = self.received[range].to_vec();
+                        if !peek {
+                            self.received.drain(..taken);
+                        }
+                        break Ok(message)
+                    }
+                    match stream.read(&mut chunk) {
+                        Ok(0) => break Ok(Vec::new()),
+                        Ok(n) => self.received.extend_from_slice(&chunk[..n]),
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                        Err(e) => break Err(e),
+                    }
+                }
+            },
+            Kind::Datagram(socket) => {
+                let mut datagram = vec![0u8; 65536];
+                let (size, from) =
+                    if peek { socket.peek_from(&mut datagram)? } else { socket.recv_from(&mut datagram)? };
+                datagram.truncate(size);
+                *last_in = Some(from);
+                Ok(datagram)
+            },
+            _ => Err(io::ErrorKind::NotConnected.into()),
+        }
+    }
+}
+
+/// Finds the next complete message in what's been received, returning its range and how much it takes up.
+fn next_message(received: &[u8], format: &Format, len: usize) -> Option<(Range<usize>, usize)> {
+    let available = received.len();
+    if len > 0 {
+        return (available >= len).then_some((0..len, len))
+    }
+    match format {
+        Format::Binary if available >= 2 => {
+            let size = usize::from(u16::from_le_bytes([received[0], received[1]]));
+            (available >= size + 2).then_some((2..size + 2, size + 2))
+        },
+        Format::Binary => None,
+        Format::Text(separator) if !separator.is_empty() => received
+            .windows(separator.len())
+            .position(|w| w == separator.as_slice())
+            .map(|end| (0..end, end + separator.len())),
+        Format::Text(_) | Format::Raw => (available > 0).then_some((0..available, available)),
+    }
+}
+
+fn error_code(error: &io::Error) -> i32 {
+    match error.kind() {
+        io::ErrorKind::WouldBlock => WSAEWOULDBLOCK,
+        io::ErrorKind::InvalidInput => WSAEMSGSIZE,
+        io::ErrorKind::AddrInUse => 10048,
+        io::ErrorKind::AddrNotAvailable => 10049,
+        io::ErrorKind::ConnectionAborted => 10053,
+        io::ErrorKind::ConnectionReset => 10054,
+        io::ErrorKind::NotConnected => WSAENOTCONN,
+        io::ErrorKind::TimedOut => 10060,
+        io::ErrorKind::ConnectionRefused => 10061,
+        _ => error.raw_os_error().unwrap_or(-1),
+    }
+}
+
+fn resolve(host: &str, port: i32) -> Option<SocketAddr> {
+    let port = u16::try_from(port).ok()?;
+    (host, port).to_socket_addrs().ok()?.find(SocketAddr::is_ipv4)
+}
+
+/// The state behind the emulated 39dll functions.
+pub struct State {
+    sockets: HandleList<Socket>,
+    buffers: HandleList<Buffer>,
+    files: HandleList<fs::File>,
+    last_in: Option<SocketAddr>,
+}
+
+/// The parts of the 39dll state that go in savestates. Sockets are only ever opened in normal play, and open files
+/// aren't saved, the same as the game's own.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedState {
+    buffers: HandleList<Buffer>,
+    last_in: Option<SocketAddr>,
+}
+
+impl State {
+    pub fn new() -> Self {
+        // Buffer 0 is the default buffer, which always exists
+        let mut buffers = HandleList::new();
+        buffers.put(Buffer::default());
+        Self { sockets: HandleList::new(), buffers, files: HandleList::new(), last_in: None }
+    }
+
+    pub fn save_state(&self) -> SavedState {
+        SavedState { buffers: self.buffers.clone(), last_in: self.last_in }
+    }
+
+    pub fn load_state(&mut self, state: SavedState) {
+        self.buffers = state.buffers;
+        self.last_in = state.last_in;
+    }
+
+    fn socket(&mut self, id: i32) -> Option<&mut Socket> {
+        self.sockets.get_mut(id - 1)
+    }
+
+    fn buffer(&mut self, id: i32) -> Option<&mut Buffer> {
+        self.buffers.get_mut(id)
+    }
+
+    fn file(&mut self, id: i32) -> Option<&mut fs::File> {
+        self.files.get_mut(id - 1)
+    }
+}
+
+/// Looks up the emulated version of a 39dll export.
+pub fn function(symbol: &str) -> Option<Function> {
+    let function: fn(&mut Game, &[Value]) -> gml::Result<Value> = match symbol.to_ascii_lowercase().as_str() {
+        "sockstart" => Game::dll39_sockstart,
+        "sockexit" => Game::dll39_sockexit,
+        "tcpconnect" => Game::dll39_tcpconnect,
+        "tcplisten" => Game::dll39_tcplisten,
+        "tcpaccept" => Game::dll39_tcpaccept,
+        "tcpip" => Game::dll39_tcpip,
+        "tcpconnected" => Game::dll39_tcpconnected,
+        "udpconnect" => Game::dll39_udpconnect,
+        "sendmessage" => Game::dll39_sendmessage,
+        "receivemessage" => Game::dll39_receivemessage,
+        "peekmessage" => Game::dll39_peekmessage,
+        "setformat" => Game::dll39_setformat,
+        "setsync" => Game::dll39_setsync,
+        "setnagle" => Game::dll39_setnagle,
+        "socklasterror" => Game::dll39_socklasterror,
+        "closesock" => Game::dll39_closesock,
+        "lastinip" => Game::dll39_lastinip,
+        "lastinport" => Game::dll39_lastinport,
+        "myhost" => Game::dll39_myhost,
+        "hostip" => Game::dll39_hostip,
+        "compareip" => Game::dll39_compareip,
+        "iptouint" => Game::dll39_iptouint,
+        "uinttoip" => Game::dll39_uinttoip,
+        "netconnected" => Game::dll39_netconnected,
+        "writebyte" => Game::dll39_writebyte,
+        "writeshort" => Game::dll39_writeshort,
+        "writeushort" => Game::dll39_writeushort,
+        "writeint" => Game::dll39_writeint,
+        "writeuint" => Game::dll39_writeuint,
+        "writefloat" => Game::dll39_writefloat,
+        "writedouble" => Game::dll39_writedouble,
+        "writechars" => Game::dll39_writechars,
+        "writestring" => Game::dll39_writestring,
+        "readbyte" => Game::dll39_readbyte,
+        "readshort" => Game::dll39_readshort,
+        "readushort" => Game::dll39_readushort,
+        "readint" => Game::dll39_readint,
+        "readuint" => Game::dll39_readuint,
+        "readfloat" => Game::dll39_readfloat,
+        "readdouble" => Game::dll39_readdouble,
+        "readchars" => Game::dll39_readchars,
+        "readstring" => Game::dll39_readstring,
+        "getpos" => Game::dll39_getpos,
+        "setpos" => Game::dll39_setpos,
+        "clearbuffer" => Game::dll39_clearbuffer,
+        "buffsize" => Game::dll39_buffsize,
+        "bytesleft" => Game::dll39_bytesleft,
+        "createbuffer" => Game::dll39_createbuffer,
+        "freebuffer" => Game::dll39_freebuffer,
+        "copybuffer" => Game::dll39_copybuffer,
+        "copybuffer2" => Game::dll39_copybuffer2,
+        "bufferexists" => Game::dll39_bufferexists,
+        "bufferencrypt" => Game::dll39_bufferencrypt,
+        "md5string" => Game::dll39_md5string,
+        "md5buffer" => Game::dll39_md5buffer,
+        "sha1string" => Game::dll39_sha1string,
+        "sha1buffer" => Game::dll39_sha1buffer,
+        "adler32" => Game::dll39_adler32,
+        "fileopen" => Game::dll39_fileopen,
+        "fileclose" => Game::dll39_fileclose,
+        "filewrite" => Game::dll39_filewrite,
+        "fileread" => Game::dll39_fileread,
+        "filepos" => Game::dll39_filepos,
+        "filesetpos" => Game::dll39_filesetpos,
+        "filesize" => Game::dll39_filesize,
+        _ => return None,
+    };
+    Some(Function::Engine(function))
+}
+
+impl Game {
+    fn dll39_sockstart(&mut self, _args: &[Value]) -> gml::Result<Value> {
+        Ok(1.into())
+    }
+
+    fn dll39_sockexit(&mut self, _args: &[Value]) -> gml::Result<Value> {
+        self.externals.dll39.sockets = HandleList::new();
+        Ok(1.into())
+    }
+
+    /// Sockets can only be opened in normal play, as there's no way to replay what they'd receive.
+    fn dll39_open(&mut self, open: impl FnOnce() -> Option<Socket>) -> gml::Result<Value> {
+        let socket = if self.play_type == PlayType::Normal { open() } else { None };
+        match socket {
+            Some(socket) => Ok((self.externals.dll39.sockets.put(socket) + 1).into()),
+            None => Ok((-1).into()),
+        }
+    }
+
+    fn dll39_tcpconnect(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (host, port, mode) = (string(args, 0), int(args, 1), int(args, 2));
+        self.dll39_open(|| {
+            let address = resolve(&host, port)?;
+            if mode == 2 {
+                // Connect in the background, and let tcpconnected say when it's done
+                let (sender, receiver) = mpsc::channel();
+                std::thread::spawn(move || {
+                    let _ = sender.send(TcpStream::connect(address));
+                });
+                Some(Socket::new(Kind::Connecting(receiver), false))
+            } else {
+                Some(Socket::new(Kind::Stream(TcpStream::connect(address).ok()?), mode == 0))
+            }
+        })
+    }
+
+    fn dll39_tcplisten(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (port, mode) = (int(args, 0), int(args, 2));
+        self.dll39_open(|| {
+            let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, u16::try_from(port).ok()?)).ok()?;
+            Some(Socket::new(Kind::Listener(listener), mode == 0))
+        })
+    }
+
+    fn dll39_tcpaccept(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (id, mode) = (int(args, 0), int(args, 1));
+        let accepted = match self.externals.dll39.socket(id) {
+            Some(Socket { kind: Kind::Listener(listener), error, .. }) => match listener.accept() {
+                Ok((stream, _)) => Some(Socket::new(Kind::Stream(stream), mode == 0)),
+                Err(e) => {
+                    *error = error_code(&e);
+                    None
+                },
+            },
+            _ => None,
+        };
+        match accepted {
+            Some(socket) => Ok((self.externals.dll39.sockets.put(socket) + 1).into()),
+            None => Ok((-1).into()),
+        }
+    }
+
+    fn dll39_tcpip(&mut self, args: &[Value]) -> gml::Result<Value> {
+        match self.externals.dll39.socket(int(args, 0)) {
+            Some(Socket { kind: Kind::Stream(stream), .. }) => {
+                Ok(stream.peer_addr().map_or_else(|_| "".into(), |a| a.ip().to_string().into()))
+            },
+            _ => Ok("".into()),
+        }
+    }
+
+    fn dll39_tcpconnected(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(self.externals.dll39.socket(int(args, 0)).is_some_and(Socket::connected).into())
+    }
+
+    fn dll39_udpconnect(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (port, mode) = (int(args, 0), int(args, 1));
+        self.dll39_open(|| {
+            let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, u16::try_from(port).ok()?)).ok()?;
+            let _ = socket.set_broadcast(true);
+            Some(Socket::new(Kind::Datagram(socket), mode == 0))
+        })
+    }
+
+    fn dll39_sendmessage(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (id, host, port, buffer) = (int(args, 0), string(args, 1), int(args, 2), int(args, 3));
+        let state = &mut self.externals.dll39;
+        let data = match state.buffer(buffer) {
+            Some(buffer) => buffer.data.clone(),
+            None => return Ok((-1).into()),
+        };
+        match state.socket(id) {
+            Some(socket) => match socket.send(&data, || resolve(&host, port)) {
+                Ok(sent) => Ok(sent.into()),
+                Err(e) => {
+                    socket.error = error_code(&e);
+                    Ok((-1).into())
+                },
+            },
+            None => Ok((-1).into()),
+        }
+    }
+
+    fn dll39_receive(&mut self, args: &[Value], peek: bool) -> gml::Result<Value> {
+        let (id, len, buffer) = (int(args, 0), int(args, 1), int(args, 2));
+        let state = &mut self.externals.dll39;
+        let received = match state.sockets.get_mut(id - 1) {
+            Some(socket) => match socket.receive(len.max(0) as usize, peek, &mut state.last_in) {
+                Ok(message) => message,
+                Err(e) => {
+                    socket.error = error_code(&e);
+                    return Ok((-1).into())
+                },
+            },
+            None => return Ok((-1).into()),
+        };
+        let size = received.len();
+        if let Some(buffer) = state.buffer(buffer) {
+            buffer.set(received);
+        }
+        Ok(size.into())
+    }
+
+    fn dll39_receivemessage(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_receive(args, false)
+    }
+
+    fn dll39_peekmessage(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_receive(args, true)
+    }
+
+    fn dll39_setformat(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (id, mode) = (int(args, 0), int(args, 1));
+        if let Some(socket) = self.externals.dll39.socket(id) {
+            socket.format = match mode {
+                0 => Format::Binary,
+                1 => Format::Text(bytes(args, 2).to_vec()),
+                _ => Format::Raw,
+            };
+        }
+        Ok(Default::default())
+    }
+
+    fn dll39_setsync(&mut self, args: &[Value]) -> gml::Result<Value> {
+        if let Some(socket) = self.externals.dll39.socket(int(args, 0)) {
+            let _ = socket.set_blocking(int(args, 1) == 0);
+        }
+        Ok(Default::default())
+    }
+
+    fn dll39_setnagle(&mut self, args: &[Value]) -> gml::Result<Value> {
+        if let Some(Socket { kind: Kind::Stream(stream), .. }) = self.externals.dll39.socket(int(args, 0)) {
+            let _ = stream.set_nodelay(int(args, 1) == 0);
+        }
+        Ok(Default::default())
+    }
+
+    fn dll39_socklasterror(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(self.externals.dll39.socket(int(args, 0)).map_or(WSAENOTCONN, |s| s.error).into())
+    }
+
+    fn dll39_closesock(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(self.externals.dll39.sockets.remove(int(args, 0) - 1).is_some().into())
+    }
+
+    fn dll39_lastinip(&mut self, _args: &[Value]) -> gml::Result<Value> {
+        Ok(self.externals.dll39.last_in.map_or_else(|| "".into(), |a| a.ip().to_string().into()))
+    }
+
+    fn dll39_lastinport(&mut self, _args: &[Value]) -> gml::Result<Value> {
+        Ok(self.externals.dll39.last_in.map_or(0, |a| i32::from(a.port())).into())
+    }
+
+    fn dll39_myhost(&mut self, _args: &[Value]) -> gml::Result<Value> {
+        // std can't get the host name, but this is only ever passed to hostip, which takes addresses too
+        Ok(network::get_local_ip().map_or_else(|_| "127.0.0.1".into(), |ip| ip.to_string().into()))
+    }
+
+    fn dll39_hostip(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(resolve(&string(args, 0), 0).map_or_else(|| "".into(), |a| a.ip().to_string().into()))
+    }
+
+    fn dll39_compareip(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (ip, mask) = (string(args, 0), string(args, 1));
+        let (ip, mask) = (ip.split('.').collect::<Vec<_>>(), mask.split('.').collect::<Vec<_>>());
+        Ok((ip.len() == mask.len() && ip.iter().zip(&mask).all(|(i, m)| *m == "*" || i == m)).into())
+    }
+
+    fn dll39_iptouint(&mut self, args: &[Value]) -> gml::Result<Value> {
+        // inet_addr's result, read as a little-endian number
+        Ok(string(args, 0).parse::<Ipv4Addr>().map_or(0, |ip| u32::from_le_bytes(ip.octets())).into())
+    }
+
+    fn dll39_uinttoip(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(Ipv4Addr::from((real(args, 0) as i64 as u32).to_le_bytes()).to_string().into())
+    }
+
+    fn dll39_netconnected(&mut self, _args: &[Value]) -> gml::Result<Value> {
+        Ok(network::get_local_ip().is_ok_and(|ip| !ip.is_loopback()).into())
+    }
+
+    fn dll39_write(&mut self, id: i32, bytes: &[u8]) -> gml::Result<Value> {
+        match self.externals.dll39.buffer(id) {
+            Some(buffer) => {
+                buffer.write(bytes);
+                Ok(buffer.data.len().into())
+            },
+            None => Ok(0.into()),
+        }
+    }
+
+    fn dll39_writebyte(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_write(int(args, 1), &(real(args, 0) as i64 as u8).to_le_bytes())
+    }
+
+    fn dll39_writeshort(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_write(int(args, 1), &(real(args, 0) as i64 as i16).to_le_bytes())
+    }
+
+    fn dll39_writeushort(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_write(int(args, 1), &(real(args, 0) as i64 as u16).to_le_bytes())
+    }
+
+    fn dll39_writeint(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_write(int(args, 1), &(real(args, 0) as i64 as i32).to_le_bytes())
+    }
+
+    fn dll39_writeuint(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_write(int(args, 1), &(real(args, 0) as i64 as u32).to_le_bytes())
+    }
+
+    fn dll39_writefloat(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_write(int(args, 1), &(real(args, 0) as f32).to_le_bytes())
+    }
+
+    fn dll39_writedouble(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_write(int(args, 1), &real(args, 0).to_le_bytes())
+    }
+
+    fn dll39_writechars(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_write(int(args, 1), bytes(args, 0))
+    }
+
+    fn dll39_writestring(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let mut string = bytes(args, 0).to_vec();
+        string.push(0);
+        self.dll39_write(int(args, 1), &string)
+    }
+
+    fn dll39_read(&mut self, id: i32, read: impl FnOnce(&mut Buffer) -> Value) -> gml::Result<Value> {
+        Ok(self.externals.dll39.buffer(id).map_or_else(Default::default, read))
+    }
+
+    fn dll39_readbyte(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_read(int(args, 0), |b| u8::from_le_bytes(b.read_array()).into())
+    }
+
+    fn dll39_readshort(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_read(int(args, 0), |b| i32::from(i16::from_le_bytes(b.read_array())).into())
+    }
+
+    fn dll39_readushort(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_read(int(args, 0), |b| i32::from(u16::from_le_bytes(b.read_array())).into())
+    }
+
+    fn dll39_readint(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_read(int(args, 0), |b| i32::from_le_bytes(b.read_array()).into())
+    }
+
+    fn dll39_readuint(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_read(int(args, 0), |b| u32::from_le_bytes(b.read_array()).into())
+    }
+
+    fn dll39_readfloat(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_read(int(args, 0), |b| f64::from(f32::from_le_bytes(b.read_array())).into())
+    }
+
+    fn dll39_readdouble(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_read(int(args, 0), |b| f64::from_le_bytes(b.read_array()).into())
+    }
+
+    fn dll39_readchars(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let len = int(args, 0).max(0) as usize;
+        self.dll39_read(int(args, 1), |b| b.read(len).into())
+    }
+
+    fn dll39_readstring(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_read(int(args, 0), |b| b.read_string().into())
+    }
+
+    fn dll39_getpos(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let which = int(args, 0);
+        self.dll39_read(int(args, 1), |b| if which == 0 { b.write_pos.into() } else { b.read_pos.into() })
+    }
+
+    fn dll39_setpos(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let pos = int(args, 0).max(0) as usize;
+        if let Some(buffer) = self.externals.dll39.buffer(int(args, 1)) {
+            buffer.read_pos = pos.min(buffer.data.len());
+            buffer.write_pos = pos.min(buffer.data.len());
+        }
+        Ok(Default::default())
+    }
+
+    fn dll39_clearbuffer(&mut self, args: &[Value]) -> gml::Result<Value> {
+        if let Some(buffer) = self.externals.dll39.buffer(int(args, 0)) {
+            buffer.set(Vec::new());
+        }
+        Ok(Default::default())
+    }
+
+    fn dll39_buffsize(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_read(int(args, 0), |b| b.data.len().into())
+    }
+
+    fn dll39_bytesleft(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_read(int(args, 0), |b| b.data.len().saturating_sub(b.read_pos).into())
+    }
+
+    fn dll39_createbuffer(&mut self, _args: &[Value]) -> gml::Result<Value> {
+        Ok(self.externals.dll39.buffers.put(Buffer::default()).into())
+    }
+
+    fn dll39_freebuffer(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let id = int(args, 0);
+        Ok((id != 0 && self.externals.dll39.buffers.remove(id).is_some()).into())
+    }
+
+    fn dll39_copybuffer(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let data = self.externals.dll39.buffer(int(args, 1)).map(|b| b.data.clone()).unwrap_or_default();
+        self.dll39_write(int(args, 0), &data)
+    }
+
+    fn dll39_copybuffer2(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (start, len) = (int(args, 1).max(0) as usize, int(args, 2).max(0) as usize);
+        let data = match self.externals.dll39.buffer(int(args, 3)) {
+            Some(buffer) => {
+                let start = start.min(buffer.data.len());
+                buffer.data[start..start.saturating_add(len).min(buffer.data.len())].to_vec()
+            },
+            None => Vec::new(),
+        };
+        self.dll39_write(int(args, 0), &data)
+    }
+
+    fn dll39_bufferexists(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(self.externals.dll39.buffer(int(args, 0)).is_some().into())
+    }
+
+    fn dll39_bufferencrypt(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let key = bytes(args, 0);
+        if let Some(buffer) = self.externals.dll39.buffer(int(args, 1)) {
+            rc4(key, &mut buffer.data);
+        }
+        Ok(Default::default())
+    }
+
+    fn dll39_md5string(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(hex::encode(md5(bytes(args, 0))).into())
+    }
+
+    fn dll39_md5buffer(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_read(int(args, 0), |b| hex::encode(md5(&b.data)).into())
+    }
+
+    fn dll39_sha1string(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(hex::encode(sha1(bytes(args, 0))).into())
+    }
+
+    fn dll39_sha1buffer(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_read(int(args, 0), |b| hex::encode(sha1(&b.data)).into())
+    }
+
+    fn dll39_adler32(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.dll39_read(int(args, 0), |b| adler32(&b.data).into())
+    }
+
+    fn dll39_fileopen(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (name, mode) = (string(args, 0), int(args, 1));
+        #[rustfmt::skip]
+        let (read, write, truncate) = match mode {
+            0 => (true,  false, false),
+            1 => (false, true,  true ),
+            _ => (true,  true,  false),
+        };
+        let file = fs::OpenOptions::new()
+            .read(read)
+            .write(write)
+            .create(write)
+            .truncate(truncate)
+            .open(file::to_path(&name).as_ref());
+        match file {
+            Ok(file) => Ok((self.externals.dll39.files.put(file) + 1).into()),
+            Err(_) => Ok((-1).into()),
+        }
+    }
+
+    fn dll39_fileclose(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(self.externals.dll39.files.remove(int(args, 0) - 1).is_some().into())
+    }
+
+    fn dll39_filewrite(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let state = &mut self.externals.dll39;
+        let data = state.buffer(int(args, 1)).map(|b| b.data.clone()).unwrap_or_default();
+        match state.file(int(args, 0)).map(|f| f.write_all(&data)) {
+            Some(Ok(())) => Ok(data.len().into()),
+            _ => Ok((-1).into()),
+        }
+    }
+
+    fn dll39_fileread(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (id, len, buffer) = (int(args, 0), int(args, 1).max(0) as u64, int(args, 2));
+        let state = &mut self.externals.dll39;
+        let mut data = Vec::new();
+        match state.file(id).map(|f| f.take(len).read_to_end(&mut data)) {
+            Some(Ok(size)) => {
+                if let Some(buffer) = state.buffer(buffer) {
+                    buffer.set(data);
+                }
+                Ok(size.into())
+            },
+            _ => Ok((-1).into()),
+        }
+    }
+
+    fn dll39_filepos(&mut self, args: &[Value]) -> gml::Result<Value> {
+        match self.externals.dll39.file(int(args, 0)).map(|f| f.stream_position()) {
+            Some(Ok(pos)) => Ok((pos as f64).into()),
+            _ => Ok((-1).into()),
+        }
+    }
+
+    fn dll39_filesetpos(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let pos = int(args, 1).max(0) as u64;
+        Ok(self.externals.dll39.file(int(args, 0)).is_some_and(|f| f.seek(SeekFrom::Start(pos)).is_ok()).into())
+    }
+
+    fn dll39_filesize(&mut self, args: &[Value]) -> gml::Result<Value> {
+        match self.externals.dll39.file(int(args, 0)).map(|f| f.metadata()) {
+            Some(Ok(metadata)) => Ok((metadata.len() as f64).into()),
+            _ => Ok((-1).into()),
+        }
+    }
+}
+
+fn rc4(key: &[u8], data: &mut [u8]) {
+    if key.is_empty() {
+        return
+    }
+    let mut s: [u8; 256] = std::array::from_fn(|i| i as u8);
+    let mut j = 0u8;
+    for i in 0..256 {
+        j = j.wrapping_add(s[i]).wrapping_add(key[i % key.len()]);
+        s.swap(i, usize::from(j));
+    }
+    let (mut i, mut j) = (0u8, 0u8);
+    for byte in data {
+        i = i.wrapping_add(1);
+        j = j.wrapping_add(s[usize::from(i)]);
+        s.swap(usize::from(i), usize::from(j));
+        *byte ^= s[usize::from(s[usize::from(i)].wrapping_add(s[usize::from(j)]))];
+    }
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/// Pads a message into 64-byte blocks, ending with its length in bits in the given byte order.
+fn pad_blocks(data: &[u8], length: [u8; 8]) -> Vec<u8> {
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&length);
+    message
+}
+
+fn md5(data: &[u8]) -> [u8; 16] {
+    #[rustfmt::skip]
+    const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+    let constants: [u32; 64] = std::array::from_fn(|i| (((i + 1) as f64).sin().abs() * 4294967296.0) as u32);
+    let mut hash = [0x67452301u32, 0xefcdab89, 0x98badcfe, 0x10325476];
+    for block in pad_blocks(data, (data.len() as u64 * 8).to_le_bytes()).chunks(64) {
+        let words: [u32; 16] = std::array::from_fn(|i| u32::from_le_bytes(block[i * 4..i * 4 + 4].try_into().unwrap()));
+        let [mut a, mut b, mut c, mut d] = hash;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let f = f.wrapping_add(a).wrapping_add(constants[i]).wrapping_add(words[g]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(SHIFTS[(i / 16) * 4 + i % 4]));
+        }
+        for (h, x) in hash.iter_mut().zip([a, b, c, d]) {
+            *h = h.wrapping_add(x);
+        }
+    }
+    let mut digest = [0; 16];
+    for (chunk, h) in digest.chunks_mut(4).zip(hash) {
+        chunk.copy_from_slice(&h.to_le_bytes());
+    }
+    digest
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut hash = [0x67452301u32, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    for block in pad_blocks(data, (data.len() as u64 * 8).to_be_bytes()).chunks(64) {
+        let mut words = [0u32; 80];
+        for i in 0..80 {
+            words[i] = if i < 16 {
+                u32::from_be_bytes(block[i * 4..i * 4 + 4].try_into().unwrap())
+            } else {
+                (words[i - 3] ^ words[i - 8] ^ words[i - 14] ^ words[i - 16]).rotate_left(1)
+            };
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = hash;
+        for (i, word) in words.iter().enumerate() {
+            let (f, k) = match i / 20 {
+                0 => ((b & c) | (!b & d), 0x5a827999),
+                1 => (b ^ c ^ d, 0x6ed9eba1),
+                2 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (h, x) in hash.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(x);
+        }
+    }
+    let mut digest = [0; 20];
+    for (chunk, h) in digest.chunks_mut(4).zip(hash) {
+        chunk.copy_from_slice(&h.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn md5_rfc1321() {
+        // From the test suite in RFC 1321
+        let cases: [(&[u8], &str); 7] = [
+            (b"", "d41d8cd98f00b204e9800998ecf8427e"),
+            (b"a", "0cc175b9c0f1b6a831c399e269772661"),
+            (b"abc", "900150983cd24fb0d6963f7d28e17f72"),
+            (b"message digest", "f96b697d7cb7938d525a2f31aaf161d0"),
+            (b"abcdefghijklmnopqrstuvwxyz", "c3fcd3d76192e4007dfb496cca67e13b"),
+            (b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789", "d174ab98d277d9f5a5611c2c9f419d9f"),
+            (
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+                "57edf4a22be3c955ac49da2e2107b67a",
+            ),
+        ];
+        for (input, digest) in cases {
+            assert_eq!(hex::encode(md5(input)), digest);
+        }
+    }
+
+    #[test]
+    fn sha1_rfc3174() {
+        // From the test suite in RFC 3174, plus the empty message
+        assert_eq!(hex::encode(sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(hex::encode(sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(
+            hex::encode(sha1(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1",
+        );
+        assert_eq!(hex::encode(sha1(&[b'a'; 1_000_000])), "34aa973cd4c4daa4f61eeb2bdbad27316534016f");
+        assert_eq!(
+            hex::encode(sha1(&b"0123456701234567012345670123456701234567012345670123456701234567".repeat(10))),
+            "dea356a2cddd90c7a7ecedc5ebb563934f460452",
+        );
+    }
+
+    #[test]
+    fn rc4_rfc6229() {
+        // The first bytes of the keystreams for the 40-bit and 128-bit keys in RFC 6229
+        let mut stream = [0u8; 16];
+        rc4(&[0x01, 0x02, 0x03, 0x04, 0x05], &mut stream);
+        assert_eq!(hex::encode(stream), "b2396305f03dc027ccc3524a0a1118a8");
+        let mut stream = [0u8; 16];
+        rc4(&std::array::from_fn::<u8, 16, _>(|i| i as u8 + 1), &mut stream);
+        assert_eq!(hex::encode(stream), "9ac7cc9a609d1ef7b2932899cde41b97");
+
+        // Encrypting twice gets the original back
+        let mut data = *b"Attack at dawn";
+        rc4(b"Secret", &mut data);
+        assert_eq!(hex::encode(data), "45a01f645fc35b383552544b9bf5");
+        rc4(b"Secret", &mut data);
+        assert_eq!(&data, b"Attack at dawn");
+    }
+
+    #[test]
+    fn adler32_known() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"a"), 0x00620062);
+        assert_eq!(adler32(b"abc"), 0x024d0127);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+        // Big enough for both sums to wrap around the modulus
+        assert_eq!(adler32(&[0xff; 6000]), 0xa49759ea);
+    }
+
+    #[test]
+    fn binary_messages() {
+        let message = [3, 0, b'a', b'b', b'c', 1];
+        assert_eq!(next_message(&message, &Format::Binary, 0), Some((2..5, 5)));
+        assert_eq!(next_message(&message[..4], &Format::Binary, 0), None);
+        assert_eq!(next_message(&message, &Format::Text(b"c".to_vec()), 0), Some((0..4, 5)));
+        assert_eq!(next_message(&message, &Format::Raw, 0), Some((0..6, 6)));
+        assert_eq!(next_message(&message, &Format::Binary, 3), Some((0..3, 3)));
+    }
+}
@@ -0,0 +1,391 @@
+// An emulation of GMFMODSimple, played through the game's own mixer.
+
+use super::{int, real, string};
+use crate::{
+    game::{
+        audio::{AudioManager, Clip, Voice},
+        Game,
+    },
+    gml::{self, file, Function, Value},
+    handleman::{HandleList, HandleManager},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Sound {
+    clip: Clip,
+    max_volume: f64,
+    group: i32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Instance {
+    sound: i32,
+    voice: Voice,
+    volume: f64,
+    pan: f64,
+    muted: bool,
+}
+
+/// The state behind the emulated GMFMODSimple functions, which is saved along with the game.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct State {
+    sounds: HandleList<Sound>,
+    instances: BTreeMap<i32, Instance>,
+    next_instance: i32,
+    master_volume: f64,
+    group_volumes: HashMap<i32, f64>,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self {
+            sounds: HandleList::new(),
+            instances: BTreeMap::new(),
+            next_instance: 1,
+            master_volume: 1.0,
+            group_volumes: HashMap::new(),
+        }
+    }
+
+    fn sound(&self, id: i32) -> Option<&Sound> {
+        self.sounds.get(id - 1)
+    }
+
+    /// Passes each instance's volume on to its voice, taking everything that scales it into account.
+    fn update_volumes(&self) {
+        for instance in self.instances.values() {
+            let volume = match self.sound(instance.sound) {
+                Some(sound) if !instance.muted => {
+                    instance.volume
+                        * sound.max_volume
+                        * self.group_volumes.get(&sound.group).copied().unwrap_or(1.0)
+                        * self.master_volume
+                },
+                _ => 0.0,
+            };
+            instance.voice.set_volume(volume);
+        }
+    }
+
+    /// Starts all the voices again after the state's been loaded.
+    pub fn resume(&self, audio: &mut AudioManager, now: u128) {
+        for instance in self.instances.values() {
+            if let Some(sound) = self.sound(instance.sound) {
+                audio.play_voice(&instance.voice, &sound.clip, now);
+            }
+        }
+    }
+}
+
+/// Looks up the emulated version of a GMFMODSimple export.
+pub fn function(symbol: &str) -> Option<Function> {
+    let function: fn(&mut Game, &[Value]) -> gml::Result<Value> = match symbol.to_ascii_lowercase().as_str() {
+        "fmodinit" => Game::gmfmod_init,
+        "fmodfree" => Game::gmfmod_free,
+        "fmodupdate" => Game::gmfmod_update,
+        "fmodallstop" => Game::gmfmod_all_stop,
+        "fmodmastersetvolume" => Game::gmfmod_master_set_volume,
+        "fmodmastergetvolume" => Game::gmfmod_master_get_volume,
+        "fmodgroupsetvolume" => Game::gmfmod_group_set_volume,
+        "fmodgroupgetvolume" => Game::gmfmod_group_get_volume,
+        "fmodgroupstop" => Game::gmfmod_group_stop,
+        "fmodgroupsetpaused" => Game::gmfmod_group_set_paused,
+        "fmodsoundadd" => Game::gmfmod_sound_add,
+        "fmodsoundfree" => Game::gmfmod_sound_free,
+        "fmodsoundplay" => Game::gmfmod_sound_play,
+        "fmodsoundloop" => Game::gmfmod_sound_loop,
+        "fmodsoundsetmaxvolume" => Game::gmfmod_sound_set_max_volume,
+        "fmodsoundgetmaxvolume" => Game::gmfmod_sound_get_max_volume,
+        "fmodsoundsetgroup" => Game::gmfmod_sound_set_group,
+        "fmodsoundgetlength" => Game::gmfmod_sound_get_length,
+        "fmodinstancestop" => Game::gmfmod_instance_stop,
+        "fmodinstanceisplaying" => Game::gmfmod_instance_is_playing,
+        "fmodinstancegetsound" => Game::gmfmod_instance_get_sound,
+        "fmodinstancesetvolume" => Game::gmfmod_instance_set_volume,
+        "fmodinstancegetvolume" => Game::gmfmod_instance_get_volume,
+        "fmodinstancesetpan" => Game::gmfmod_instance_set_pan,
+        "fmodinstancegetpan" => Game::gmfmod_instance_get_pan,
+        "fmodinstancesetpitch" => Game::gmfmod_instance_set_pitch,
+        "fmodinstancegetpitch" => Game::gmfmod_instance_get_pitch,
+        "fmodinstancesetfrequency" => Game::gmfmod_instance_set_frequency,
+        "fmodinstancegetfrequency" => Game::gmfmod_instance_get_frequency,
+        "fmodinstancesetpaused" => Game::gmfmod_instance_set_paused,
+        "fmodinstancegetpaused" => Game::gmfmod_instance_get_paused,
+        "fmodinstancesetmuted" => Game::gmfmod_instance_set_muted,
+        "fmodinstancegetmuted" => Game::gmfmod_instance_get_muted,
+        "fmodinstancesetposition" => Game::gmfmod_instance_set_position,
+        "fmodinstancegetposition" => Game::gmfmod_instance_get_position,
+        "fmodinstancesetloopcount" => Game::gmfmod_instance_set_loop_count,
+        _ => return None,
+    };
+    Some(Function::Engine(function))
+}
+
+impl Game {
+    fn gmfmod_init(&mut self, _args: &[Value]) -> gml::Result<Value> {
+        Ok(1.into())
+    }
+
+    fn gmfmod_free(&mut self, _args: &[Value]) -> gml::Result<Value> {
+        self.gmfmod_all_stop(&[])?;
+        self.externals.gmfmod = State::new();
+        Ok(Default::default())
+    }
+
+    /// Forgets about instances that have finished. The real DLL needs this calling every step, so it's a good time.
+    fn gmfmod_update(&mut self, _args: &[Value]) -> gml::Result<Value> {
+        let now = self.clock.as_nanos();
+        self.externals.gmfmod.instances.retain(|_, i| i.voice.playing(now));
+        Ok(Default::default())
+    }
+
+    fn gmfmod_all_stop(&mut self, _args: &[Value]) -> gml::Result<Value> {
+        for (_, instance) in std::mem::take(&mut self.externals.gmfmod.instances) {
+            self.audio.stop_voice(&instance.voice);
+        }
+        Ok(Default::default())
+    }
+
+    fn gmfmod_master_set_volume(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.externals.gmfmod.master_volume = real(args, 0).clamp(0.0, 1.0);
+        self.externals.gmfmod.update_volumes();
+        Ok(Default::default())
+    }
+
+    fn gmfmod_master_get_volume(&mut self, _args: &[Value]) -> gml::Result<Value> {
+        Ok(self.externals.gmfmod.master_volume.into())
+    }
+
+    fn gmfmod_group_set_volume(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.externals.gmfmod.group_volumes.insert(int(args, 0), real(args, 1).clamp(0.0, 1.0));
+        self.externals.gmfmod.update_volumes();
+        Ok(Default::default())
+    }
+
+    fn gmfmod_group_get_volume(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(self.externals.gmfmod.group_volumes.get(&int(args, 0)).copied().unwrap_or(1.0).into())
+    }
+
+    fn gmfmod_group_stop(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let group = int(args, 0);
+        let state = &mut self.externals.gmfmod;
+        let stopped = state
+            .instances
+            .iter()
+            .filter(|(_, i)| state.sound(i.sound).is_some_and(|s| s.group == group))
+            .map(|(&id, _)| id)
+            .collect::<Vec<_>>();
+        for id in stopped {
+            if let Some(instance) = self.externals.gmfmod.instances.remove(&id) {
+                self.audio.stop_voice(&instance.voice);
+            }
+        }
+        Ok(Default::default())
+    }
+
+    fn gmfmod_group_set_paused(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (group, paused, now) = (int(args, 0), int(args, 1) != 0, self.clock.as_nanos());
+        let State { sounds, instances, .. } = &mut self.externals.gmfmod;
+        for instance in instances.values_mut() {
+            if sounds.get(instance.sound - 1).is_some_and(|s| s.group == group) {
+                instance.voice.set_paused(paused, now);
+            }
+        }
+        Ok(Default::default())
+    }
+
+    fn gmfmod_sound_add(&mut self, args: &[Value]) -> gml::Result<Value> {
+        match std::fs::read(file::to_path(&string(args, 0)).as_ref()) {
+            Ok(data) => {
+                // Formats we can't decode, such as OGG, still load but play as silence
+                let sound = Sound { clip: Clip::new(data.into()), max_volume: 1.0, group: 0 };
+                Ok((self.externals.gmfmod.sounds.put(sound) + 1).into())
+            },
+            Err(_) => Ok(0.into()),
+        }
+    }
+
+    fn gmfmod_sound_free(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let id = int(args, 0);
+        let state = &mut self.externals.gmfmod;
+        for (_, instance) in state.instances.iter().filter(|(_, i)| i.sound == id) {
+            self.audio.stop_voice(&instance.voice);
+        }
+        state.instances.retain(|_, i| i.sound != id);
+        state.sounds.remove(id - 1);
+        Ok(Default::default())
+    }
+
+    fn gmfmod_start(&mut self, args: &[Value], loops: i32) -> gml::Result<Value> {
+        let (id, paused, now) = (int(args, 0), int(args, 1) != 0, self.clock.as_nanos());
+        let state = &mut self.externals.gmfmod;
+        match state.sounds.get(id - 1) {
+            Some(sound) => {
+                let voice = self.audio.new_voice(&sound.clip, loops, paused, now);
+                self.audio.play_voice(&voice, &sound.clip, now);
+                let instance = state.next_instance;
+                state.next_instance += 1;
+                state.instances.insert(instance, Instance { sound: id, voice, volume: 1.0, pan: 0.0, muted: false });
+                state.update_volumes();
+                Ok(instance.into())
+            },
+            None => Ok(0.into()),
+        }
+    }
+
+    fn gmfmod_sound_play(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.gmfmod_start(args, 0)
+    }
+
+    fn gmfmod_sound_loop(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.gmfmod_start(args, -1)
+    }
+
+    fn gmfmod_sound_set_max_volume(&mut self, args: &[Value]) -> gml::Result<Value> {
+        if let Some(sound) = self.externals.gmfmod.sounds.get_mut(int(args, 0) - 1) {
+            sound.max_volume = real(args, 1).clamp(0.0, 1.0);
+        }
+        self.externals.gmfmod.update_volumes();
+        Ok(Default::default())
+    }
+
+    fn gmfmod_sound_get_max_volume(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(self.externals.gmfmod.sound(int(args, 0)).map_or(0.0, |s| s.max_volume).into())
+    }
+
+    fn gmfmod_sound_set_group(&mut self, args: &[Value]) -> gml::Result<Value> {
+        if let Some(sound) = self.externals.gmfmod.sounds.get_mut(int(args, 0) - 1) {
+            sound.group = int(args, 1);
+        }
+        self.externals.gmfmod.update_volumes();
+        Ok(Default::default())
+    }
+
+    /// Gives the length in milliseconds.
+    fn gmfmod_sound_get_length(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(self.externals.gmfmod.sound(int(args, 0)).map_or(0.0, |s| (s.clip.duration() * 1000.0).floor()).into())
+    }
+
+    fn gmfmod_instance(&mut self, args: &[Value]) -> Option<&mut Instance> {
+        self.externals.gmfmod.instances.get_mut(&int(args, 0))
+    }
+
+    fn gmfmod_instance_stop(&mut self, args: &[Value]) -> gml::Result<Value> {
+        if let Some(instance) = self.externals.gmfmod.instances.remove(&int(args, 0)) {
+            self.audio.stop_voice(&instance.voice);
+        }
+        Ok(Default::default())
+    }
+
+    fn gmfmod_instance_is_playing(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let now = self.clock.as_nanos();
+        Ok(self.gmfmod_instance(args).is_some_and(|i| i.voice.playing(now)).into())
+    }
+
+    fn gmfmod_instance_get_sound(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(self.gmfmod_instance(args).map_or(0, |i| i.sound).into())
+    }
+
+    fn gmfmod_instance_set_volume(&mut self, args: &[Value]) -> gml::Result<Value> {
+        if let Some(instance) = self.gmfmod_instance(args) {
+            instance.volume = real(args, 1).clamp(0.0, 1.0);
+        }
+        self.externals.gmfmod.update_volumes();
+        Ok(Default::default())
+    }
+
+    fn gmfmod_instance_get_volume(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(self.gmfmod_instance(args).map_or(0.0, |i| i.volume).into())
+    }
+
+    fn gmfmod_instance_set_pan(&mut self, args: &[Value]) -> gml::Result<Value> {
+        if let Some(instance) = self.gmfmod_instance(args) {
+            instance.pan = real(args, 1).clamp(-1.0, 1.0);
+            instance.voice.set_pan(instance.pan);
+        }
+        Ok(Default::default())
+    }
+
+    fn gmfmod_instance_get_pan(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(self.gmfmod_instance(args).map_or(0.0, |i| i.pan).into())
+    }
+
+    fn gmfmod_instance_set_pitch(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let now = self.clock.as_nanos();
+        if let Some(instance) = self.gmfmod_instance(args) {
+            instance.voice.set_pitch(real(args, 1), now);
+        }
+        Ok(Default::default())
+    }
+
+    fn gmfmod_instance_get_pitch(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(self.gmfmod_instance(args).map_or(0.0, |i| i.voice.pitch()).into())
+    }
+
+    /// Sets the playback rate in Hz, which is another way of setting the pitch.
+    fn gmfmod_instance_set_frequency(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let now = self.clock.as_nanos();
+        if let Some(instance) = self.gmfmod_instance(args) {
+            let pitch = real(args, 1) / f64::from(instance.voice.sample_rate());
+            instance.voice.set_pitch(pitch, now);
+        }
+        Ok(Default::default())
+    }
+
+    fn gmfmod_instance_get_frequency(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(self.gmfmod_instance(args).map_or(0.0, |i| i.voice.pitch() * f64::from(i.voice.sample_rate())).into())
+    }
+
+    fn gmfmod_instance_set_paused(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let now = self.clock.as_nanos();
+        if let Some(instance) = self.gmfmod_instance(args) {
+            instance.voice.set_paused(int(args, 1) != 0, now);
+        }
+        Ok(Default::default())
+    }
+
+    fn gmfmod_instance_get_paused(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(self.gmfmod_instance(args).is_some_and(|i| i.voice.paused()).into())
+    }
+
+    fn gmfmod_instance_set_muted(&mut self, args: &[Value]) -> gml::Result<Value> {
+        if let Some(instance) = self.gmfmod_instance(args) {
+            instance.muted = int(args, 1) != 0;
+        }
+        self.externals.gmfmod.update_volumes();
+        Ok(Default::default())
+    }
+
+    fn gmfmod_instance_get_muted(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(self.gmfmod_instance(args).is_some_and(|i| i.muted).into())
+    }
+
+    /// Seeks to somewhere from 0 (the start) to 1 (the end).
+    fn gmfmod_instance_set_position(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let now = self.clock.as_nanos();
+        if let Some(instance) = self.gmfmod_instance(args) {
+            let seconds = real(args, 1).clamp(0.0, 1.0) * instance.voice.duration();
+            instance.voice.seek(seconds, now);
+        }
+        Ok(Default::default())
+    }
+
+    fn gmfmod_instance_get_position(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let now = self.clock.as_nanos();
+        Ok(self
+            .gmfmod_instance(args)
+            .filter(|i| i.voice.duration() > 0.0)
+            .map_or(0.0, |i| i.voice.position(now) / i.voice.duration())
+            .into())
+    }
+
+    fn gmfmod_instance_set_loop_count(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let now = self.clock.as_nanos();
+        if let Some(instance) = self.gmfmod_instance(args) {
+            instance.voice.set_loops(int(args, 1), now);
+        }
+        Ok(Default::default())
+    }
+}
@@ -0,0 +1,322 @@
+// An emulation of SuperSound, played through the game's own mixer.
+// SuperSound is a thin wrapper around DirectSound buffers, so that's what its units are based on.
+
+use super::{int, real, string};
+use crate::{
+    game::{
+        audio::{AudioManager, Clip, Voice},
+        Game,
+    },
+    gml::{self, file, Function, Value},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use udon::source::Source;
+
+/// DirectSound's full volume, in hundredths of a decibel above its quietest.
+const MAX_VOLUME: i32 = 10000;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Sound {
+    clip: Clip,
+    voice: Option<Voice>,
+    looping: bool,
+    position: f64,
+    volume: i32,
+    pan: i32,
+    frequency: f64,
+}
+
+impl Sound {
+    fn new(clip: Clip) -> Self {
+        Self { clip, voice: None, looping: false, position: 0.0, volume: MAX_VOLUME, pan: 0, frequency: 0.0 }
+    }
+
+    /// The rate the sound plays at, in Hz. Setting it to 0 goes back to the file's own rate.
+    fn frequency(&self) -> f64 {
+        if self.frequency > 0.0 { self.frequency } else { f64::from(self.clip.sample_rate().get()) }
+    }
+
+    /// How many bytes of 16-bit PCM a second of the sound would take, which is what positions are measured in.
+    fn bytes_per_second(&self) -> f64 {
+        f64::from(self.clip.sample_rate().get()) * f64::from(self.clip.channel_count().get()) * 2.0
+    }
+
+    fn position(&self, now: u128) -> f64 {
+        self.voice.as_ref().map_or(self.position, |v| v.position(now))
+    }
+
+    fn playing(&self, now: u128) -> bool {
+        self.voice.as_ref().is_some_and(|v| v.playing(now) && !v.paused())
+    }
+
+    /// Passes the volume, pan and frequency on to the voice, converting them from hundredths of a decibel.
+    fn update(&mut self, now: u128) {
+        let (pitch, volume, pan) = (self.frequency() / f64::from(self.clip.sample_rate().get()), self.volume, self.pan);
+        if let Some(voice) = &mut self.voice {
+            voice.set_volume(10f64.powf(f64::from(volume - MAX_VOLUME) / 2000.0));
+            voice.set_pan(f64::from(pan.signum()) * (1.0 - 10f64.powf(-f64::from(pan.abs()) / 2000.0)));
+            voice.set_pitch(pitch, now);
+        }
+    }
+}
+
+/// The state behind the emulated SuperSound functions, which is saved along with the game.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct State {
+    sounds: BTreeMap<i32, Sound>,
+    next_sound: i32,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self { sounds: BTreeMap::new(), next_sound: 1 }
+    }
+
+    /// Starts all the voices again after the state's been loaded.
+    pub fn resume(&self, audio: &mut AudioManager, now: u128) {
+        for sound in self.sounds.values() {
+            if let Some(voice) = &sound.voice {
+                audio.play_voice(voice, &sound.clip, now);
+            }
+        }
+    }
+}
+
+/// Looks up the emulated version of a SuperSound export.
+pub fn function(symbol: &str) -> Option<Function> {
+    let function: fn(&mut Game, &[Value]) -> gml::Result<Value> = match symbol.to_ascii_lowercase().as_str() {
+        "ss_init" => Game::ss_init,
+        "ss_unload" => Game::ss_unload,
+        "ss_loadsound" => Game::ss_load_sound,
+        "ss_freesound" => Game::ss_free_sound,
+        "ss_ishandlevalid" => Game::ss_is_handle_valid,
+        "ss_playsound" => Game::ss_play_sound,
+        "ss_loopsound" => Game::ss_loop_sound,
+        "ss_stopsound" => Game::ss_stop_sound,
+        "ss_pausesound" => Game::ss_pause_sound,
+        "ss_resumesound" => Game::ss_resume_sound,
+        "ss_issoundplaying" => Game::ss_is_sound_playing,
+        "ss_issoundlooping" => Game::ss_is_sound_looping,
+        "ss_issoundpaused" => Game::ss_is_sound_paused,
+        "ss_setsoundvol" => Game::ss_set_sound_vol,
+        "ss_getsoundvol" => Game::ss_get_sound_vol,
+        "ss_setsoundpan" => Game::ss_set_sound_pan,
+        "ss_getsoundpan" => Game::ss_get_sound_pan,
+        "ss_setsoundfreq" => Game::ss_set_sound_freq,
+        "ss_getsoundfreq" => Game::ss_get_sound_freq,
+        "ss_setsoundposition" => Game::ss_set_sound_position,
+        "ss_getsoundposition" => Game::ss_get_sound_position,
+        "ss_getsoundlength" => Game::ss_get_sound_length,
+        _ => return None,
+    };
+    Some(Function::Engine(function))
+}
+
+impl Game {
+    fn ss_init(&mut self, _args: &[Value]) -> gml::Result<Value> {
+        Ok("Yes".into())
+    }
+
+    fn ss_unload(&mut self, _args: &[Value]) -> gml::Result<Value> {
+        for sound in self.externals.supersound.sounds.values() {
+            if let Some(voice) = &sound.voice {
+                self.audio.stop_voice(voice);
+            }
+        }
+        self.externals.supersound = State::new();
+        Ok(1.into())
+    }
+
+    fn ss_sound(&mut self, args: &[Value]) -> Option<&mut Sound> {
+        self.externals.supersound.sounds.get_mut(&int(args, 0))
+    }
+
+    /// Loads a WAV, OGG or MP3 file. Whether it should be streamed doesn't matter here.
+    fn ss_load_sound(&mut self, args: &[Value]) -> gml::Result<Value> {
+        match std::fs::read(file::to_path(&string(args, 0)).as_ref()) {
+            Ok(data) => {
+                let state = &mut self.externals.supersound;
+                let handle = state.next_sound;
+                state.next_sound += 1;
+                state.sounds.insert(handle, Sound::new(Clip::new(data.into())));
+                Ok(handle.into())
+            },
+            Err(_) => Ok(0.into()),
+        }
+    }
+
+    fn ss_free_sound(&mut self, args: &[Value]) -> gml::Result<Value> {
+        if let Some(sound) = self.externals.supersound.sounds.remove(&int(args, 0)) {
+            if let Some(voice) = &sound.voice {
+                self.audio.stop_voice(voice);
+            }
+            Ok(1.into())
+        } else {
+            Ok(0.into())
+        }
+    }
+
+    fn ss_is_handle_valid(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(self.ss_sound(args).is_some().into())
+    }
+
+    /// Starts playing from the current position, or from the start if it's already played to the end.
+    fn ss_start(&mut self, args: &[Value], looping: bool) -> gml::Result<Value> {
+        let now = self.clock.as_nanos();
+        if let Some(sound) = self.externals.supersound.sounds.get_mut(&int(args, 0)) {
+            let position = sound.position(now);
+            if let Some(voice) = sound.voice.take() {
+                self.audio.stop_voice(&voice);
+            }
+            let mut voice = self.audio.new_voice(&sound.clip, if looping { -1 } else { 0 }, false, now);
+            voice.seek(if position < voice.duration() { position } else { 0.0 }, now);
+            sound.voice = Some(voice);
+            sound.looping = looping;
+            sound.update(now);
+            self.audio.play_voice(sound.voice.as_ref().unwrap(), &sound.clip, now);
+            Ok(1.into())
+        } else {
+            Ok(0.into())
+        }
+    }
+
+    fn ss_play_sound(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.ss_start(args, false)
+    }
+
+    fn ss_loop_sound(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.ss_start(args, true)
+    }
+
+    /// Stops the sound and rewinds it.
+    fn ss_stop_sound(&mut self, args: &[Value]) -> gml::Result<Value> {
+        if let Some(sound) = self.externals.supersound.sounds.get_mut(&int(args, 0)) {
+            if let Some(voice) = sound.voice.take() {
+                self.audio.stop_voice(&voice);
+            }
+            sound.position = 0.0;
+            Ok(1.into())
+        } else {
+            Ok(0.into())
+        }
+    }
+
+    fn ss_pause_sound(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let now = self.clock.as_nanos();
+        match self.ss_sound(args) {
+            Some(sound) => {
+                if let Some(voice) = &mut sound.voice {
+                    voice.set_paused(true, now);
+                }
+                Ok(1.into())
+            },
+            None => Ok(0.into()),
+        }
+    }
+
+    fn ss_resume_sound(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let now = self.clock.as_nanos();
+        match self.ss_sound(args) {
+            Some(sound) => {
+                if let Some(voice) = &mut sound.voice {
+                    voice.set_paused(false, now);
+                }
+                Ok(1.into())
+            },
+            None => Ok(0.into()),
+        }
+    }
+
+    fn ss_is_sound_playing(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let now = self.clock.as_nanos();
+        Ok(self.ss_sound(args).is_some_and(|s| s.playing(now)).into())
+    }
+
+    fn ss_is_sound_looping(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let now = self.clock.as_nanos();
+        Ok(self.ss_sound(args).is_some_and(|s| s.looping && s.playing(now)).into())
+    }
+
+    fn ss_is_sound_paused(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(self.ss_sound(args).and_then(|s| s.voice.as_ref()).is_some_and(|v| v.paused()).into())
+    }
+
+    /// Sets the volume from 0 (silent) to 10000 (full), in hundredths of a decibel.
+    fn ss_set_sound_vol(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let now = self.clock.as_nanos();
+        match self.ss_sound(args) {
+            Some(sound) => {
+                sound.volume = int(args, 1).clamp(0, MAX_VOLUME);
+                sound.update(now);
+                Ok(1.into())
+            },
+            None => Ok(0.into()),
+        }
+    }
+
+    fn ss_get_sound_vol(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(self.ss_sound(args).map_or(0, |s| s.volume).into())
+    }
+
+    /// Sets the pan from -10000 (left) to 10000 (right), in hundredths of a decibel taken off the other side.
+    fn ss_set_sound_pan(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let now = self.clock.as_nanos();
+        match self.ss_sound(args) {
+            Some(sound) => {
+                sound.pan = int(args, 1).clamp(-MAX_VOLUME, MAX_VOLUME);
+                sound.update(now);
+                Ok(1.into())
+            },
+            None => Ok(0.into()),
+        }
+    }
+
+    fn ss_get_sound_pan(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(self.ss_sound(args).map_or(0, |s| s.pan).into())
+    }
+
+    /// Sets the playback rate in Hz, within what DirectSound allows. 0 goes back to the file's own rate.
+    fn ss_set_sound_freq(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let now = self.clock.as_nanos();
+        match self.ss_sound(args) {
+            Some(sound) => {
+                let frequency = real(args, 1);
+                sound.frequency = if frequency > 0.0 { frequency.clamp(100.0, 200000.0) } else { 0.0 };
+                sound.update(now);
+                Ok(1.into())
+            },
+            None => Ok(0.into()),
+        }
+    }
+
+    fn ss_get_sound_freq(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(self.ss_sound(args).map_or(0.0, |s| s.frequency()).into())
+    }
+
+    /// Seeks to a position in bytes.
+    fn ss_set_sound_position(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let now = self.clock.as_nanos();
+        match self.ss_sound(args) {
+            Some(sound) => {
+                let seconds = (real(args, 1) / sound.bytes_per_second()).clamp(0.0, sound.clip.duration());
+                match &mut sound.voice {
+                    Some(voice) => voice.seek(seconds, now),
+                    None => sound.position = seconds,
+                }
+                Ok(1.into())
+            },
+            None => Ok(0.into()),
+        }
+    }
+
+    fn ss_get_sound_position(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let now = self.clock.as_nanos();
+        Ok(self.ss_sound(args).map_or(0.0, |s| (s.position(now) * s.bytes_per_second()).floor()).into())
+    }
+
+    /// Gives the length in bytes.
+    fn ss_get_sound_length(&mut self, args: &[Value]) -> gml::Result<Value> {
+        Ok(self.ss_sound(args).map_or(0.0, |s| (s.clip.duration() * s.bytes_per_second()).floor()).into())
+    }
+}
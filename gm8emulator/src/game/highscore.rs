@@ -0,0 +1,365 @@
+use crate::{
+    game::{draw, replay, Game, GetAsset, PlayType},
+    gml,
+    math::Real,
+};
+use ramen::{event::Event, input::Key};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::PathBuf,
+};
+
+/// How many places the highscore list has.
+pub const PLACES: usize = 10;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub name: gml::String,
+    pub score: i32,
+}
+
+/// How highscore_show draws the list, as set by the highscore_set_* functions.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Style {
+    pub background: i32,
+    pub border: bool,
+    pub back_colour: i32,
+    pub new_colour: i32,
+    pub other_colour: i32,
+    pub font_name: gml::String,
+    pub font_size: i32,
+    pub font_style: i32,
+    pub caption: gml::String,
+    pub nobody: gml::String,
+    pub escape: gml::String,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            background: -1,
+            border: true,
+            back_colour: 0xffffff,
+            new_colour: 0x0000ff,
+            other_colour: 0x000000,
+            font_name: "Times New Roman".into(),
+            font_size: 10,
+            font_style: 0,
+            caption: "Top Ten Players".into(),
+            nobody: "<nobody>".into(),
+            escape: "press <Escape> to close".into(),
+        }
+    }
+}
+
+/// The game's highscore list. Only the entries are saved to its file, the style is set by the game each run.
+/// Like in GM8, the list always has all its places, starting out filled with nobodies who scored 0.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Highscores {
+    entries: Vec<Entry>,
+    pub style: Style,
+
+    /// File the entries get written to whenever they change, if any
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+impl Default for Highscores {
+    fn default() -> Self {
+        Self { entries: Self::empty_entries(), style: Default::default(), path: None }
+    }
+}
+
+impl Highscores {
+    fn empty_entries() -> Vec<Entry> {
+        vec![Entry { name: Style::default().nobody, score: 0 }; PLACES]
+    }
+
+    /// Loads a highscore list from the given file, or starts a new one if it doesn't exist yet.
+    /// Either way, the list will be written back to that file.
+    pub fn load(path: PathBuf) -> Self {
+        let mut entries = match File::open(&path) {
+            Ok(file) => match bincode::deserialize_from(BufReader::new(file)) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    eprintln!("Warning: couldn't read highscore file {:?}: {}", path, e);
+                    Self::empty_entries()
+                },
+            },
+            Err(_) => Self::empty_entries(),
+        };
+        entries.resize(PLACES, Entry { name: Style::default().nobody, score: 0 });
+        Self { entries, style: Default::default(), path: Some(path) }
+    }
+
+    /// Replaces everything in this list with another one's contents, keeping the file it's saved to.
+    pub fn restore(&mut self, other: Self) {
+        let path = self.path.take();
+        *self = other;
+        self.path = path;
+        self.save();
+    }
+
+    /// Gets the entry in the given place, counting from 0.
+    pub fn get(&self, place: usize) -> Option<&Entry> {
+        self.entries.get(place)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries = Self::empty_entries();
+        self.save();
+    }
+
+    /// Adds a score to the list if it beats the lowest one, and returns the place it got.
+    pub fn add(&mut self, name: gml::String, score: i32) -> Option<usize> {
+        let place = self.entries.iter().position(|e| score > e.score)?;
+        self.entries.insert(place, Entry { name, score });
+        self.entries.truncate(PLACES);
+        self.save();
+        Some(place)
+    }
+
+    pub fn set_name(&mut self, place: usize, name: gml::String) {
+        if let Some(entry) = self.entries.get_mut(place) {
+            entry.name = name;
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        if let Some(path) = &self.path {
+            let result = File::create(path)
+                .map_err(bincode::Error::from)
+                .and_then(|file| bincode::serialize_into(BufWriter::new(file), &self.entries));
+            if let Err(e) = result {
+                eprintln!("Warning: couldn't write highscore file {:?}: {}", path, e);
+            }
+        }
+    }
+}
+
+impl Game {
+    /// Offers a place on the highscore list for the given score, asking the player for their name if it gets one,
+    /// then shows the list if `show_list` is set. The name is stored as a replay event, and nothing is shown during
+    /// replays, or while recording apart from the name prompt.
+    pub fn show_highscore(&mut self, score: i32, show_list: bool) -> gml::Result<()> {
+        let place = self.highscores.add("".into(), score);
+        match self.play_type {
+            PlayType::Normal => {
+                if place.is_some() || show_list {
+                    self.draw_highscore_dialog(place, show_list)
+                }
+            },
+            PlayType::Record => {
+                if let Some(place) = place {
+                    let prompt = format!(
+                        "{}\n\nYou got place {}! Please enter your name:",
+                        self.highscores.style.caption,
+                        place + 1
+                    );
+                    let name = self.show_input(&prompt, "").unwrap_or_default();
+                    self.highscores.set_name(place, name.as_str().into());
+                    self.stored_events.push_back(replay::Event::HighscoreName(name.into()));
+                }
+            },
+            PlayType::Replay => {
+                if let Some(place) = place {
+                    match self.stored_events.pop_front() {
+                        Some(replay::Event::HighscoreName(name)) => self.highscores.set_name(place, name.repr()),
+                        _ => return Err(gml::Error::ReplayError("highscore name entry".into())),
+                    }
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// Draws the highscore list in the given area with the current font, one row per place.
+    /// `colours` gives the colour for the highlighted place and the others, or None to use the draw colour.
+    /// The highlighted place shows `editing` in place of its name, if given.
+    pub fn draw_highscore_list(
+        &mut self,
+        (x1, y1, x2, y2): (Real, Real, Real, Real),
+        scale: Real,
+        highlight: Option<usize>,
+        colours: Option<(i32, i32)>,
+        alpha: Real,
+        editing: Option<&str>,
+    ) {
+        let (halign, valign) = (self.draw_halign, self.draw_valign);
+        self.draw_valign = draw::Valign::Top;
+        let row_height = (y2 - y1) / Real::from(PLACES as f64);
+        for place in 0..PLACES {
+            let (name, score) = match (self.highscores.get(place), editing) {
+                (Some(entry), Some(text)) if highlight == Some(place) => (format!("{}_", text).into(), entry.score),
+                (Some(entry), _) => (entry.name.clone(), entry.score),
+                (None, _) => (self.highscores.style.nobody.clone(), 0),
+            };
+            let colours = colours.map(|(new, other)| {
+                let c = if highlight == Some(place) { new } else { other };
+                (c, c, c, c)
+            });
+            let y = y1 + row_height * Real::from(place as f64);
+            for (x, text, align) in
+                [(x1, name, draw::Halign::Left), (x2, score.to_string().into(), draw::Halign::Right)]
+            {
+                self.draw_halign = align;
+                self.draw_string(x, y, text, None, None, scale, scale, 0.into(), colours, alpha);
+            }
+        }
+        self.draw_halign = halign;
+        self.draw_valign = valign;
+    }
+
+    /// Finds the font the highscore style asks for, as a font id and a scale.
+    fn highscore_font(&self) -> (i32, Real) {
+        let style = &self.highscores.style;
+        self.system_font(style.font_name.as_ref(), style.font_size, style.font_style)
+    }
+
+    /// Shows the highscore list over the game, first letting the player type their name into the given place.
+    /// Without `show_list`, it closes as soon as the name's entered.
+    fn draw_highscore_dialog(&mut self, place: Option<usize>, show_list: bool) {
+        let mut name = String::new();
+        let mut editing = place.is_some();
+        self.run_modal(|game, events, (width, height)| {
+            for event in events.iter().copied() {
+                match event {
+                    Event::Input(c) if editing && !c.is_control() => name.push(c),
+                    Event::KeyboardDown(Key::Backspace) if editing => {
+                        name.pop();
+                    },
+                    Event::KeyboardDown(Key::Return) if editing => {
+                        editing = false;
+                        if let Some(p) = place {
+                            game.highscores.set_name(p, name.as_str().into());
+                        }
+                        if !show_list {
+                            return Some(())
+                        }
+                    },
+                    Event::KeyboardDown(Key::Return) | Event::KeyboardDown(Key::Escape) if !editing => return Some(()),
+                    Event::CloseRequest => {
+                        if let (Some(p), true) = (place, editing) {
+                            game.highscores.set_name(p, name.as_str().into());
+                        }
+                        return Some(())
+                    },
+                    _ => (),
+                }
+            }
+
+            let (font, scale) = game.highscore_font();
+            game.draw_font_id = font;
+            let box_w = (width - 20).min(360);
+            let box_h = (height - 20).min(400);
+            let x1 = (width - box_w) / 2;
+            let y1 = (height - box_h) / 2;
+            let (x2, y2) = (x1 + box_w, y1 + box_h);
+            let row_height = box_h / (PLACES as i32 + 4);
+            let style = game.highscores.style.clone();
+            match game.assets.backgrounds.get_asset(style.background).and_then(|b| b.atlas_ref.map(|r| (r, b))) {
+                Some((atlas_ref, bg)) => game.renderer.draw_sprite(
+                    atlas_ref,
+                    x1.into(),
+                    y1.into(),
+                    f64::from(box_w) / f64::from(bg.width),
+                    f64::from(box_h) / f64::from(bg.height),
+                    0.0,
+                    0xffffff,
+                    1.0,
+                ),
+                None => {
+                    game.renderer.draw_rectangle(x1.into(), y1.into(), x2.into(), y2.into(), style.back_colour, 1.0)
+                },
+            }
+            if style.border {
+                game.renderer.draw_rectangle_outline(x1.into(), y1.into(), x2.into(), y2.into(), 0, 1.0);
+            }
+
+            game.draw_halign = draw::Halign::Middle;
+            for (y, text) in [(y1 + row_height / 2, style.caption), (y2 - row_height * 3 / 2, style.escape)] {
+                let c = style.other_colour;
+                game.draw_string(
+                    Real::from((x1 + x2) / 2),
+                    Real::from(y),
+                    text,
+                    None,
+                    None,
+                    scale,
+                    scale,
+                    0.into(),
+                    Some((c, c, c, c)),
+                    1.into(),
+                );
+            }
+            game.draw_halign = draw::Halign::Left;
+            game.draw_highscore_list(
+                (
+                    Real::from(x1 + row_height),
+                    Real::from(y1 + row_height * 2),
+                    Real::from(x2 - row_height),
+                    Real::from(y2 - row_height * 2),
+                ),
+                scale,
+                place,
+                Some((style.new_colour, style.other_colour)),
+                1.into(),
+                if editing { Some(name.as_str()) } else { None },
+            );
+            None
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scores(highscores: &Highscores) -> Vec<i32> {
+        (0..PLACES).map(|place| highscores.get(place).unwrap().score).collect()
+    }
+
+    #[test]
+    fn starts_with_nobodies() {
+        let highscores = Highscores::default();
+        assert_eq!(scores(&highscores), vec![0; PLACES]);
+        assert_eq!(highscores.get(0).unwrap().name.as_ref(), b"<nobody>");
+        assert!(highscores.get(PLACES).is_none());
+    }
+
+    #[test]
+    fn ordering() {
+        let mut highscores = Highscores::default();
+        assert_eq!(highscores.add("a".into(), 10), Some(0));
+        assert_eq!(highscores.add("b".into(), 30), Some(0));
+        assert_eq!(highscores.add("c".into(), 20), Some(1));
+        // Ties go below the existing score
+        assert_eq!(highscores.add("d".into(), 20), Some(2));
+        assert_eq!(&scores(&highscores)[..5], &[30, 20, 20, 10, 0]);
+        assert_eq!(highscores.get(2).unwrap().name.as_ref(), b"d");
+    }
+
+    #[test]
+    fn only_beats_lowest() {
+        let mut highscores = Highscores::default();
+        assert_eq!(highscores.add("zero".into(), 0), None);
+        assert_eq!(highscores.add("negative".into(), -5), None);
+        for i in 0..PLACES as i32 {
+            highscores.add("".into(), 100 - i);
+        }
+        assert_eq!(highscores.add("low".into(), 91), None);
+        assert_eq!(highscores.add("high".into(), 92), Some(PLACES - 1));
+        assert_eq!(scores(&highscores).last(), Some(&92));
+    }
+
+    #[test]
+    fn clear_refills() {
+        let mut highscores = Highscores::default();
+        highscores.add("a".into(), 5);
+        highscores.clear();
+        assert_eq!(scores(&highscores), vec![0; PLACES]);
+    }
+}
@@ -0,0 +1,428 @@
+use crate::{
+    game::{
+        audio::{AudioManager, Clip, Voice},
+        Game,
+    },
+    gml::file,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use udon::source::Source;
+
+/// The units positions and lengths are given in, as set by "set ... time format".
+#[derive(Clone, Copy, Serialize, Deserialize)]
+enum TimeFormat {
+    Milliseconds,
+    Samples,
+    Bytes,
+}
+
+/// A file opened with the "open" command.
+#[derive(Clone, Serialize, Deserialize)]
+struct Device {
+    kind: String,
+    clip: Clip,
+    voice: Option<Voice>,
+    position: f64,
+    volume: i32,
+    muted: bool,
+    time_format: TimeFormat,
+}
+
+impl Device {
+    fn position(&self, now: u128) -> f64 {
+        self.voice.as_ref().map_or(self.position, |v| v.position(now))
+    }
+
+    fn mode(&self, now: u128) -> &'static str {
+        match &self.voice {
+            Some(voice) if voice.playing(now) => {
+                if voice.paused() {
+                    "paused"
+                } else {
+                    "playing"
+                }
+            },
+            _ => "stopped",
+        }
+    }
+
+    /// How many of the current units there are in a second.
+    fn units_per_second(&self) -> f64 {
+        let sample_rate = f64::from(self.clip.sample_rate().get());
+        match self.time_format {
+            TimeFormat::Milliseconds => 1000.0,
+            TimeFormat::Samples => sample_rate,
+            TimeFormat::Bytes => sample_rate * f64::from(self.clip.channel_count().get()) * 2.0,
+        }
+    }
+
+    /// Reads a time in the current units as seconds.
+    fn parse_time(&self, time: &str) -> Option<f64> {
+        time.parse::<f64>().ok().map(|t| (t / self.units_per_second()).clamp(0.0, self.clip.duration()))
+    }
+
+    /// Gives a time in seconds in the current units.
+    fn format_time(&self, seconds: f64) -> String {
+        ((seconds * self.units_per_second()).round() as i64).to_string()
+    }
+
+    fn update_volume(&self) {
+        if let Some(voice) = &self.voice {
+            voice.set_volume(if self.muted { 0.0 } else { f64::from(self.volume) / 1000.0 });
+        }
+    }
+
+    /// Stops playing and remembers where it got to.
+    fn stop(&mut self, audio: &mut AudioManager, now: u128) {
+        self.position = self.position(now);
+        if let Some(voice) = self.voice.take() {
+            audio.stop_voice(&voice);
+        }
+    }
+}
+
+/// The devices opened through mci_command, which are saved along with the game.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct State {
+    devices: BTreeMap<String, Device>,
+    next_id: i32,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self { devices: BTreeMap::new(), next_id: 1 }
+    }
+
+    /// Starts all the voices again after the state's been loaded.
+    pub fn resume(&self, audio: &mut AudioManager, now: u128) {
+        for device in self.devices.values() {
+            if let Some(voice) = &device.voice {
+                audio.play_voice(voice, &device.clip, now);
+            }
+        }
+    }
+}
+
+/// Splits a command string into words, keeping anything in double quotes together.
+fn tokenize(command: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = command.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            tokens.push(chars.by_ref().take_while(|&c| c != '"').collect());
+        } else {
+            let mut token = String::new();
+            while let Some(&c) = chars.peek().filter(|c| !c.is_whitespace()) {
+                token.push(c);
+                chars.next();
+            }
+            tokens.push(token);
+        }
+    }
+    tokens
+}
+
+/// Finds the word after a keyword, if the keyword's there.
+fn after<'a>(args: &'a [String], keyword: &str) -> Option<&'a str> {
+    args.iter().position(|a| a.eq_ignore_ascii_case(keyword)).and_then(|i| args.get(i + 1)).map(String::as_str)
+}
+
+fn has(args: &[String], keyword: &str) -> bool {
+    args.iter().any(|a| a.eq_ignore_ascii_case(keyword))
+}
+
+impl State {
+    /// Runs an MCI command string and gives back what it would return. Errors give an empty string.
+    pub fn send_string(&mut self, command: &str, audio: &mut AudioManager, now: u128) -> String {
+        // Nothing takes long enough here to need waiting for or notifying about
+        let tokens = tokenize(command)
+            .into_iter()
+            .filter(|t| !t.eq_ignore_ascii_case("wait") && !t.eq_ignore_ascii_case("notify"))
+            .collect::<Vec<_>>();
+        let (verb, device) = match tokens.as_slice() {
+            [verb, device, ..] => (verb.to_ascii_lowercase(), device.to_ascii_lowercase()),
+            _ => return String::new(),
+        };
+        let args = &tokens[2..];
+
+        match (verb.as_str(), device.as_str()) {
+            ("open", _) => self.open(&tokens[1], args).unwrap_or_default(),
+            ("close", _) => {
+                let closed = if device == "all" {
+                    std::mem::take(&mut self.devices).into_values().collect()
+                } else {
+                    self.devices.remove(&device).into_iter().collect::<Vec<_>>()
+                };
+                for mut device in closed {
+                    device.stop(audio, now);
+                }
+                String::new()
+            },
+            ("stop" | "pause" | "resume", "all") => {
+                for device in self.devices.values_mut() {
+                    mci_device_command(device, &verb, args, audio, now);
+                }
+                String::new()
+            },
+            _ => match self.devices.get_mut(&device) {
+                Some(device) => mci_device_command(device, &verb, args, audio, now).unwrap_or_default(),
+                None => String::new(),
+            },
+        }
+    }
+
+    fn open(&mut self, name: &str, args: &[String]) -> Option<String> {
+        // The device type can go before the file name, like "waveaudio!music.wav"
+        let path = name.rsplit('!').next().unwrap_or(name);
+        let alias = after(args, "alias").unwrap_or(path).to_ascii_lowercase();
+        if self.devices.contains_key(&alias) {
+            return None
+        }
+        let clip = match std::fs::read(file::to_path(path).as_ref()) {
+            Ok(data) => Clip::new(data.into()),
+            Err(_) => return None,
+        };
+        let id = self.next_id;
+        self.next_id += 1;
+        let kind = match (after(args, "type"), name.split_once('!')) {
+            (Some(kind), _) | (None, Some((kind, _))) => kind.to_ascii_lowercase(),
+            (None, None) if matches!(clip, Clip::Wav(_)) => "waveaudio".into(),
+            (None, None) if matches!(clip, Clip::Midi(_)) => "sequencer".into(),
+            (None, None) => "mpegvideo".into(),
+        };
+        let device = Device {
+            kind,
+            clip,
+            voice: None,
+            position: 0.0,
+            volume: 1000,
+            muted: false,
+            time_format: TimeFormat::Milliseconds,
+        };
+        self.devices.insert(alias, device);
+        Some(id.to_string())
+    }
+}
+
+impl Game {
+    /// Runs an MCI command string on the game's devices.
+    pub fn mci_send_string(&mut self, command: &str) -> String {
+        self.mci.send_string(command, &mut self.audio, self.clock.as_nanos())
+    }
+}
+
+/// Runs a command on an open device. Anything unsupported counts as an error.
+fn mci_device_command(
+    device: &mut Device,
+    verb: &str,
+    args: &[String],
+    audio: &mut AudioManager,
+    now: u128,
+) -> Option<String> {
+    match verb {
+        "play" => {
+            let mut start = device.position(now);
+            match after(args, "from") {
+                Some(from) => start = device.parse_time(from)?,
+                // Playing again once it's got to the end starts it over
+                None if start >= device.clip.duration() => start = 0.0,
+                None => (),
+            }
+            let end = match after(args, "to") {
+                Some(to) => Some(device.parse_time(to)?),
+                None => None,
+            };
+            device.stop(audio, now);
+            let mut voice = audio.new_voice(&device.clip, if has(args, "repeat") { -1 } else { 0 }, false, now);
+            if let Some(end) = end {
+                voice.set_end(end);
+            }
+            voice.seek(start, now);
+            device.voice = Some(voice);
+            device.update_volume();
+            audio.play_voice(device.voice.as_ref().unwrap(), &device.clip, now);
+        },
+        "pause" | "resume" => {
+            if let Some(voice) = &mut device.voice {
+                voice.set_paused(verb == "pause", now);
+            }
+        },
+        "stop" => device.stop(audio, now),
+        "seek" => {
+            device.stop(audio, now);
+            device.position = match after(args, "to")?.to_ascii_lowercase().as_str() {
+                "start" => 0.0,
+                "end" => device.clip.duration(),
+                time => device.parse_time(time)?,
+            };
+        },
+        "setaudio" => {
+            if let Some(volume) = after(args, "volume") {
+                let volume = if volume.eq_ignore_ascii_case("to") { after(args, "to")? } else { volume };
+                device.volume = volume.parse::<i32>().ok()?.clamp(0, 1000);
+            } else if has(args, "off") || has(args, "on") {
+                device.muted = has(args, "off");
+            } else {
+                return None
+            }
+            device.update_volume();
+        },
+        "set" => {
+            if let Some(format) = after(args, "format") {
+                device.time_format = match format.to_ascii_lowercase().as_str() {
+                    "milliseconds" | "ms" => TimeFormat::Milliseconds,
+                    "samples" => TimeFormat::Samples,
+                    "bytes" => TimeFormat::Bytes,
+                    _ => return None,
+                };
+            } else if has(args, "audio") {
+                device.muted = has(args, "off");
+                device.update_volume();
+            } else {
+                return None
+            }
+        },
+        "status" => {
+            let item = args.iter().map(|a| a.to_ascii_lowercase()).collect::<Vec<_>>().join(" ");
+            return Some(match item.as_str() {
+                "mode" => device.mode(now).into(),
+                "position" => device.format_time(device.position(now)),
+                "length" => device.format_time(device.clip.duration()),
+                "volume" => device.volume.to_string(),
+                "ready" => "true".into(),
+                "time format" => match device.time_format {
+                    TimeFormat::Milliseconds => "milliseconds",
+                    TimeFormat::Samples => "samples",
+                    TimeFormat::Bytes => "bytes",
+                }
+                .into(),
+                "device type" => device.kind.clone(),
+                "media present" => "true".into(),
+                _ => return None,
+            })
+        },
+        _ => return None,
+    }
+    Some(String::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: u128 = 1_000_000_000;
+
+    /// Writes a second of silence as an 8kHz mono 16-bit WAV file, and gives its path.
+    fn write_wav(name: &str) -> std::path::PathBuf {
+        let samples = 8000u32;
+        let mut data = Vec::new();
+        data.extend_from_slice(b"RIFF");
+        data.extend_from_slice(&(36 + samples * 2).to_le_bytes());
+        data.extend_from_slice(b"WAVEfmt ");
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&8000u32.to_le_bytes());
+        data.extend_from_slice(&16000u32.to_le_bytes());
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&16u16.to_le_bytes());
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&(samples * 2).to_le_bytes());
+        data.resize(data.len() + samples as usize * 2, 0);
+        let path = std::env::temp_dir().join(format!("gm8emulator-mci-{}-{}.wav", name, std::process::id()));
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    /// Writes a MIDI file that holds one note for a second, and gives its path.
+    fn write_midi(name: &str) -> std::path::PathBuf {
+        // Note on, then note off 960 ticks (two beats at the default 120bpm) later, then the end of the track
+        let track = [0x00, 0x90, 60, 100, 0x87, 0x40, 0x80, 60, 0, 0x00, 0xFF, 0x2F, 0x00];
+        let mut data = Vec::new();
+        data.extend_from_slice(b"MThd");
+        data.extend_from_slice(&6u32.to_be_bytes());
+        data.extend_from_slice(&0u16.to_be_bytes());
+        data.extend_from_slice(&1u16.to_be_bytes());
+        data.extend_from_slice(&480u16.to_be_bytes());
+        data.extend_from_slice(b"MTrk");
+        data.extend_from_slice(&(track.len() as u32).to_be_bytes());
+        data.extend_from_slice(&track);
+        let path = std::env::temp_dir().join(format!("gm8emulator-mci-{}-{}.mid", name, std::process::id()));
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn tokenize_quotes() {
+        assert_eq!(tokenize("  open   \"my music.wav\" alias  bgm "), ["open", "my music.wav", "alias", "bgm"]);
+        assert_eq!(tokenize("play \"\" from 0"), ["play", "", "from", "0"]);
+        assert_eq!(tokenize("open \"unterminated name"), ["open", "unterminated name"]);
+        assert!(tokenize("   ").is_empty());
+    }
+
+    #[test]
+    fn play_and_status() {
+        let path = write_wav("play");
+        let mut audio = AudioManager::silent();
+        let mut mci = State::new();
+        let mut send = |command: &str, now: u128| mci.send_string(command, &mut audio, now);
+
+        assert_eq!(send(&format!("open \"{}\" alias Music", path.display()), 0), "1");
+        assert_eq!(send("status music length", 0), "1000");
+        assert_eq!(send("status music device type", 0), "waveaudio");
+        assert_eq!(send("status music mode", 0), "stopped");
+        assert_eq!(send("play music", 0), "");
+        assert_eq!(send("status music mode", SECOND / 2), "playing");
+        assert_eq!(send("status music position", SECOND / 4), "250");
+        assert_eq!(send("status music mode", SECOND * 2), "stopped");
+        assert_eq!(send("status music position", SECOND * 2), "1000");
+
+        // Playing again after the end goes back to the start
+        send("play music", SECOND * 2);
+        assert_eq!(send("status music position", SECOND * 5 / 2), "500");
+
+        send("set music time format samples", SECOND * 5 / 2);
+        assert_eq!(send("status music position", SECOND * 5 / 2), "4000");
+        assert_eq!(send("status music length", SECOND * 5 / 2), "8000");
+
+        send("pause all", SECOND * 5 / 2);
+        assert_eq!(send("status music mode", SECOND * 3), "paused");
+        send("resume all", SECOND * 3);
+        assert_eq!(send("status music mode", SECOND * 3), "playing");
+        send("stop all", SECOND * 3);
+        assert_eq!(send("status music mode", SECOND * 3), "stopped");
+
+        send("close all", SECOND * 3);
+        assert_eq!(send("status music mode", SECOND * 3), "");
+        assert_eq!(send("bogus", SECOND * 3), "");
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn play_midi() {
+        let path = write_midi("sequencer");
+        let mut audio = AudioManager::silent();
+        let mut mci = State::new();
+        let mut send = |command: &str, now: u128| mci.send_string(command, &mut audio, now);
+
+        assert_eq!(send(&format!("open \"{}\" type sequencer alias bgm", path.display()), 0), "1");
+        assert_eq!(send("status bgm device type", 0), "sequencer");
+        assert_eq!(send("status bgm length", 0), "1000");
+        send("play bgm", 0);
+        assert_eq!(send("status bgm mode", SECOND / 2), "playing");
+        assert_eq!(send("status bgm position", SECOND / 2), "500");
+        assert_eq!(send("status bgm mode", SECOND * 2), "stopped");
+        send("close bgm", SECOND * 2);
+
+        // Without a type, it's still recognised as MIDI
+        assert_eq!(send(&format!("open \"{}\" alias bgm", path.display()), SECOND * 2), "2");
+        assert_eq!(send("status bgm device type", SECOND * 2), "sequencer");
+
+        std::fs::remove_file(path).ok();
+    }
+}
@@ -0,0 +1,1111 @@
+use crate::{
+    game::{
+        dialog::{Answer, Dialog},
+        draw, replay, Game, GetAsset, PlayType,
+    },
+    gml::{self, file, Value},
+    math::Real,
+    render::atlas::{AtlasBuilder, AtlasRef},
+};
+use glob::{MatchOptions, Pattern};
+use ramen::{
+    event::Event,
+    input::{Key, MouseButton},
+};
+use serde::{Deserialize, Serialize};
+use std::path::{PathBuf, MAIN_SEPARATOR};
+
+/// A font given by name, as set by message_text_font and the like. Style is 1 for bold plus 2 for italic.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Font {
+    pub name: gml::String,
+    pub size: i32,
+    pub colour: i32,
+    pub style: i32,
+    pub charset: i32,
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Self { name: "Arial".into(), size: 12, colour: 0, style: 0, charset: 1 }
+    }
+}
+
+/// How message boxes and the other modal dialogs look, as set by the message_* functions.
+/// Positions and sizes of -1 mean the dialog is centred and sized to fit.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Style {
+    pub background: i32,
+    pub button: i32,
+    pub alpha: Real,
+    pub text_font: Font,
+    pub button_font: Font,
+    pub input_font: Font,
+    pub mouse_colour: i32,
+    pub input_colour: i32,
+    pub position: (i32, i32),
+    pub size: (i32, i32),
+    pub border: bool,
+    pub caption: gml::String,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            background: -1,
+            button: -1,
+            alpha: Real::from(1.0),
+            text_font: Default::default(),
+            button_font: Default::default(),
+            input_font: Default::default(),
+            mouse_colour: 0xffffff,
+            input_colour: 0xffffff,
+            position: (-1, -1),
+            size: (-1, -1),
+            border: true,
+            caption: "".into(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Picture {
+    atlas_ref: AtlasRef,
+    width: i32,
+    height: i32,
+    origin: (i32, i32),
+}
+
+/// The built-in look for dialogs, used wherever the game hasn't picked its own background or button sprite.
+pub struct Art {
+    background: Picture,
+    buttons: [Picture; 3],
+    text_edit: Picture,
+}
+
+pub fn load_art(atlases: &mut AtlasBuilder) -> Result<Art, String> {
+    let mut load = |png: &[u8]| {
+        let image = image::load_from_memory(png).map_err(|e| format!("Couldn't decode message box art: {}", e))?;
+        let image = image.into_rgba8();
+        let (width, height) = (image.width() as i32, image.height() as i32);
+        let atlas_ref = atlases
+            .texture(width, height, 0, 0, image.into_raw().into_boxed_slice())
+            .ok_or("Couldn't pack message box art")?;
+        Ok::<_, String>(Picture { atlas_ref, width, height, origin: (0, 0) })
+    };
+    Ok(Art {
+        background: load(include_bytes!("../../../assets/messagebox/message_background.png"))?,
+        buttons: [
+            load(include_bytes!("../../../assets/messagebox/grey_button.png"))?,
+            load(include_bytes!("../../../assets/messagebox/red_button.png"))?,
+            load(include_bytes!("../../../assets/messagebox/crimson_button.png"))?,
+        ],
+        text_edit: load(include_bytes!("../../../assets/messagebox/text_edit.png"))?,
+    })
+}
+
+/// Which kind of file dialog to show.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FileMode {
+    Open,
+    Save,
+    Directory,
+}
+
+const PADDING: i32 = 16;
+const BUTTON_GAP: i32 = 8;
+const CAPTION_COLOUR: i32 = 0x804000;
+const HIGHLIGHT_COLOUR: i32 = 0xd77800;
+const SWATCH_SIZE: i32 = 20;
+const SWATCH_GAP: i32 = 4;
+const SWATCH_COLUMNS: usize = 8;
+const MENU_BORDER: i32 = 3;
+const MENU_INDENT: i32 = 20;
+const MENU_ITEM_PADDING: i32 = 3;
+const MENU_SEPARATOR_HEIGHT: i32 = 8;
+
+/// The basic colours offered by the colour picker, the same as the Windows one.
+#[rustfmt::skip]
+const BASIC_COLOURS: [i32; 48] = [
+    0x8080ff, 0x80ffff, 0x80ff80, 0x80ff00, 0xffff80, 0xff8000, 0xc080ff, 0xff80ff,
+    0x0000ff, 0x00ffff, 0x00ff80, 0x40ff00, 0xffff00, 0xc08000, 0xc08080, 0xff00ff,
+    0x404080, 0x4080ff, 0x00ff00, 0x808000, 0x804000, 0xff8080, 0x400080, 0x8000ff,
+    0x000080, 0x0080ff, 0x008000, 0x408000, 0xff0000, 0xa00000, 0x800080, 0xff0080,
+    0x000040, 0x004080, 0x004000, 0x404000, 0x800000, 0x400000, 0x400040, 0x800040,
+    0x000000, 0x008080, 0x408080, 0x808080, 0x808040, 0xc0c0c0, 0x400040, 0xffffff,
+];
+
+/// Splits the Windows-style shortcut out of a button label, so "&Yes" is shown as "Yes" and picked with Y.
+/// A doubled ampersand is shown as one.
+fn shortcut(label: &[u8]) -> (gml::String, Option<char>) {
+    let mut text = Vec::with_capacity(label.len());
+    let mut key = None;
+    let mut bytes = label.iter().copied();
+    while let Some(b) = bytes.next() {
+        if b == b'&' {
+            match bytes.next() {
+                Some(b'&') => text.push(b'&'),
+                Some(c) => {
+                    key = key.or(Some(char::from(c.to_ascii_lowercase())));
+                    text.push(c);
+                },
+                None => (),
+            }
+        } else {
+            text.push(b);
+        }
+    }
+    (text.into(), key)
+}
+
+/// Mouse state for a dialog's buttons, kept between frames.
+#[derive(Default)]
+struct Buttons {
+    mouse: (i32, i32),
+    pressed: Option<usize>,
+    rects: Vec<(i32, i32, i32, i32)>,
+}
+
+impl Buttons {
+    fn contains((x, y, w, h): (i32, i32, i32, i32), (mx, my): (i32, i32)) -> bool {
+        (x..x + w).contains(&mx) && (y..y + h).contains(&my)
+    }
+
+    fn hovered(&self) -> Option<usize> {
+        self.rects.iter().position(|&rect| Self::contains(rect, self.mouse))
+    }
+
+    /// Tracks the mouse, returning the button that was clicked if any.
+    fn handle(&mut self, event: Event, game: &Game) -> Option<usize> {
+        match event {
+            Event::MouseMove((x, y)) => self.mouse = game.modal_mouse((x.into(), y.into())),
+            Event::MouseDown(MouseButton::Left) => self.pressed = self.hovered(),
+            Event::MouseUp(MouseButton::Left) => {
+                let pressed = self.pressed.take();
+                if pressed.is_some() && pressed == self.hovered() {
+                    return pressed
+                }
+            },
+            _ => (),
+        }
+        None
+    }
+}
+
+/// The state of the file browser.
+struct Browser {
+    mode: FileMode,
+    dir: PathBuf,
+    entries: Vec<(String, bool)>,
+    selected: Option<usize>,
+    scroll: usize,
+    name: String,
+    filters: Vec<(String, Vec<Pattern>)>,
+    filter: usize,
+}
+
+impl Browser {
+    /// Sets up a browser from a GM filter string, like "Text files|*.txt|All files|*.*", and the default path.
+    fn new(mode: FileMode, filter: &str, default: &str) -> Self {
+        let mut parts = filter.split('|');
+        let mut filters = Vec::new();
+        while let (Some(name), Some(patterns)) = (parts.next(), parts.next()) {
+            let patterns = patterns
+                .split(';')
+                .filter_map(|p| Pattern::new(if p.trim() == "*.*" { "*" } else { p.trim() }).ok())
+                .collect();
+            filters.push((name.to_string(), patterns));
+        }
+
+        let default = PathBuf::from(file::to_path(default).as_ref());
+        let current = std::env::current_dir().unwrap_or_default();
+        let (dir, name) = if default.is_dir() {
+            (current.join(default), String::new())
+        } else {
+            let name = default.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            match default.parent() {
+                Some(parent) if parent.is_dir() => (current.join(parent), name),
+                _ => (current, name),
+            }
+        };
+        let name = if mode == FileMode::Directory { String::new() } else { name };
+        let mut browser = Self { mode, dir, entries: Vec::new(), selected: None, scroll: 0, name, filters, filter: 0 };
+        browser.refresh();
+        browser
+    }
+
+    fn open(&mut self, dir: PathBuf) {
+        self.dir = dir;
+        self.refresh();
+    }
+
+    /// Lists the current directory, folders first, then files matching the current filter.
+    fn refresh(&mut self) {
+        self.selected = None;
+        self.scroll = 0;
+        let options = MatchOptions { case_sensitive: false, ..Default::default() };
+        let patterns = self.filters.get(self.filter).map(|(_, p)| p.as_slice()).unwrap_or(&[]);
+        let mut dirs = Vec::new();
+        let mut files = Vec::new();
+        if let Ok(entries) = std::fs::read_dir(&self.dir) {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if entry.path().is_dir() {
+                    dirs.push(name);
+                } else if self.mode != FileMode::Directory
+                    && (patterns.is_empty() || patterns.iter().any(|p| p.matches_with(&name, options)))
+                {
+                    files.push(name);
+                }
+            }
+        }
+        dirs.sort_by_key(|n| n.to_lowercase());
+        files.sort_by_key(|n| n.to_lowercase());
+        self.entries.clear();
+        if self.dir.parent().is_some() {
+            self.entries.push(("..".into(), true));
+        }
+        self.entries.extend(dirs.into_iter().map(|n| (n, true)));
+        self.entries.extend(files.into_iter().map(|n| (n, false)));
+    }
+
+    fn select(&mut self, index: usize, rows: usize) {
+        if let Some((name, is_dir)) = self.entries.get(index) {
+            self.selected = Some(index);
+            if !is_dir {
+                self.name = name.clone();
+            }
+            if index < self.scroll {
+                self.scroll = index;
+            } else if index >= self.scroll + rows {
+                self.scroll = index + 1 - rows;
+            }
+        }
+    }
+
+    /// Goes into an entry if it's a directory, or returns its path if it's a file.
+    fn activate(&mut self, index: usize) -> Option<PathBuf> {
+        let (name, is_dir) = self.entries.get(index)?.clone();
+        if is_dir {
+            let dir = if name == ".." { self.dir.parent()?.to_path_buf() } else { self.dir.join(name) };
+            self.open(dir);
+            None
+        } else {
+            Some(self.dir.join(name))
+        }
+    }
+
+    /// Works out what to return when OK is pressed, going into a directory instead if that's what was picked.
+    fn accept(&mut self) -> Option<PathBuf> {
+        match self.mode {
+            FileMode::Directory => Some(match self.selected.and_then(|i| self.entries.get(i)) {
+                Some((name, _)) if name != ".." => self.dir.join(name),
+                _ => self.dir.clone(),
+            }),
+            _ if self.name.is_empty() => self.selected.and_then(|i| self.activate(i)),
+            _ => {
+                let path = self.dir.join(&self.name);
+                if path.is_dir() {
+                    self.name.clear();
+                    self.open(path);
+                    None
+                } else {
+                    Some(path)
+                }
+            },
+        }
+    }
+}
+
+impl Game {
+    /// Shows a message with an OK button.
+    pub fn ask_message(&mut self, text: &gml::String) -> gml::Result<()> {
+        self.recorded_dialog(
+            "show_message",
+            |game| {
+                game.message_box(text, &["OK".into()]);
+            },
+            |_| replay::Event::ShowMessage,
+            |event| matches!(event, replay::Event::ShowMessage).then_some(()),
+        )
+    }
+
+    /// Asks a yes or no question.
+    pub fn ask_question(&mut self, text: &gml::String) -> gml::Result<bool> {
+        self.recorded_dialog(
+            "show_question",
+            |game| game.message_box(text, &["&Yes".into(), "&No".into()]) == Some(0),
+            |&yes| replay::Event::ShowQuestion(yes.into()),
+            |event| match event {
+                replay::Event::ShowQuestion(yes) => Some(yes.is_truthy()),
+                _ => None,
+            },
+        )
+    }
+
+    /// Shows a message with up to three buttons, leaving out any with empty labels. Returns which one was picked
+    /// counting from 1, or 0 if the message was closed with Escape.
+    pub fn ask_message_ext(&mut self, text: &gml::String, labels: &[gml::String]) -> gml::Result<i32> {
+        let shown = labels.iter().enumerate().filter(|(_, l)| !l.as_ref().is_empty()).collect::<Vec<_>>();
+        let shown_labels = shown.iter().map(|(_, l)| (*l).clone()).collect::<Vec<_>>();
+        self.recorded_dialog(
+            "show_message_ext",
+            |game| game.message_box(text, &shown_labels).and_then(|i| shown.get(i)).map_or(0, |(i, _)| *i as i32 + 1),
+            |&button| replay::Event::ShowMessageExt(button),
+            |event| match event {
+                replay::Event::ShowMessageExt(button) => Some(button),
+                _ => None,
+            },
+        )
+    }
+
+    /// Asks for a number, giving back the default if the answer isn't one.
+    pub fn ask_integer(&mut self, prompt: &gml::String, default: Value) -> gml::Result<Value> {
+        let default_text = self.decode_str(default.repr().as_ref()).into_owned();
+        self.recorded_dialog(
+            "get_integer",
+            |game| {
+                game.message_input(prompt, &default_text)
+                    .and_then(|text| text.trim().parse::<f64>().ok())
+                    .map_or(default, Value::from)
+            },
+            |value| replay::Event::GetInteger(value.clone()),
+            |event| match event {
+                replay::Event::GetInteger(value) => Some(value),
+                _ => None,
+            },
+        )
+    }
+
+    /// Asks for a line of text, giving back the default if cancelled.
+    pub fn ask_string(&mut self, prompt: &gml::String, default: gml::String) -> gml::Result<Value> {
+        let default_text = self.decode_str(default.as_ref()).into_owned();
+        self.recorded_dialog(
+            "get_string",
+            |game| match game.message_input(prompt, &default_text) {
+                Some(text) => game.encode_for_drawing(&text).into(),
+                None => default.into(),
+            },
+            |value: &Value| replay::Event::GetString(value.clone()),
+            |event| match event {
+                replay::Event::GetString(value) => Some(value),
+                _ => None,
+            },
+        )
+    }
+
+    /// Asks for a colour, returning -1 if cancelled.
+    pub fn ask_colour(&mut self, default: i32) -> gml::Result<i32> {
+        self.recorded_dialog(
+            "get_color",
+            |game| match game.dialog_hook(&Dialog::Colour { default }) {
+                Some(Some(Answer::Colour(colour))) => colour,
+                Some(_) => -1,
+                None => game.draw_colour_picker(default).unwrap_or(-1),
+            },
+            |&colour| replay::Event::GetColor(colour),
+            |event| match event {
+                replay::Event::GetColor(colour) => Some(colour),
+                _ => None,
+            },
+        )
+    }
+
+    /// Asks for a file or directory. `filter` is a GM filter string such as "Text files|*.txt", and `caption`
+    /// replaces the dialog's usual title. Returns an empty string if cancelled.
+    pub fn ask_filename(
+        &mut self,
+        mode: FileMode,
+        filter: &str,
+        default: &str,
+        caption: Option<&str>,
+    ) -> gml::Result<Value> {
+        let title = caption.unwrap_or(match mode {
+            FileMode::Open => "Open",
+            FileMode::Save => "Save As",
+            FileMode::Directory => "Select Directory",
+        });
+        self.recorded_dialog(
+            "file",
+            |game| {
+                let path = match game.dialog_hook(&Dialog::Input { prompt: &format!("{} {}", title, filter), default })
+                {
+                    Some(Some(Answer::Text(path))) => path,
+                    Some(_) => String::new(),
+                    None => {
+                        let browser = Browser::new(mode, filter, default);
+                        let path = game.draw_file_browser(browser, title);
+                        path.map(|p| p.to_string_lossy().into_owned()).unwrap_or_default()
+                    },
+                };
+                game.encode_for_drawing(&path).into()
+            },
+            |path: &Value| replay::Event::GetFilename(path.clone()),
+            |event| match event {
+                replay::Event::GetFilename(path) => Some(path),
+                _ => None,
+            },
+        )
+    }
+
+    /// Shows a popup menu from a GM menu string like "Open|Save|-|Quit", at the given place or else at the mouse.
+    /// Returns the index of the item chosen, counting separators, or the default if none was.
+    pub fn ask_menu(&mut self, menu: &gml::String, default: Value, position: Option<(i32, i32)>) -> gml::Result<Value> {
+        let items = menu.as_ref().split(|&b| b == b'|').map(gml::String::from).collect::<Vec<_>>();
+        let position = position.unwrap_or_else(|| self.modal_mouse((self.input.mouse_x(), self.input.mouse_y())));
+        self.recorded_dialog(
+            "show_menu",
+            |game| {
+                let names = items
+                    .iter()
+                    .map(|item| game.decode_str(shortcut(item.as_ref()).0.as_ref()).into_owned())
+                    .collect::<Vec<_>>();
+                let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+                let chosen = match game.dialog_hook(&Dialog::Menu { items: &names }) {
+                    Some(Some(Answer::Button(i))) => Some(i),
+                    Some(_) => None,
+                    None => game.draw_menu(&items, position),
+                };
+                chosen.filter(|&i| i < items.len()).map_or(default, Value::from)
+            },
+            |value: &Value| replay::Event::ShowMenu(value.clone()),
+            |event| match event {
+                replay::Event::ShowMenu(value) => Some(value),
+                _ => None,
+            },
+        )
+    }
+
+    /// Gets the answer to a dialog with `ask`, storing it as a replay event while recording.
+    /// During replays the dialog isn't shown, and `answer` picks the answer out of the next event instead.
+    fn recorded_dialog<T>(
+        &mut self,
+        name: &str,
+        ask: impl FnOnce(&mut Self) -> T,
+        event: impl FnOnce(&T) -> replay::Event,
+        answer: impl FnOnce(replay::Event) -> Option<T>,
+    ) -> gml::Result<T> {
+        match self.play_type {
+            PlayType::Normal => Ok(ask(self)),
+            PlayType::Record => {
+                let result = ask(self);
+                self.stored_events.push_back(event(&result));
+                Ok(result)
+            },
+            PlayType::Replay => self
+                .stored_events
+                .pop_front()
+                .and_then(answer)
+                .ok_or_else(|| gml::Error::ReplayError(format!("{} dialog", name))),
+        }
+    }
+
+    /// Shows a message box in the game's message style, or through the dialog hook if there is one.
+    /// Returns the index of the button picked, or None if it was closed instead.
+    fn message_box(&mut self, text: &gml::String, labels: &[gml::String]) -> Option<usize> {
+        let decoded = self.decode_str(text.as_ref()).into_owned();
+        let names =
+            labels.iter().map(|l| self.decode_str(shortcut(l.as_ref()).0.as_ref()).into_owned()).collect::<Vec<_>>();
+        let buttons = names.iter().map(String::as_str).collect::<Vec<_>>();
+        match self.dialog_hook(&Dialog::Message { text: &decoded, buttons: &buttons }) {
+            Some(Some(Answer::Button(i))) => Some(i),
+            Some(_) => None,
+            None => self.draw_message_box(text, labels, None),
+        }
+    }
+
+    /// Asks for a line of text in the game's message style, returning None if cancelled.
+    fn message_input(&mut self, prompt: &gml::String, default: &str) -> Option<String> {
+        let decoded = self.decode_str(prompt.as_ref()).into_owned();
+        match self.dialog_hook(&Dialog::Input { prompt: &decoded, default }) {
+            Some(Some(Answer::Text(text))) => Some(text),
+            Some(_) => None,
+            None => {
+                let mut text = default.to_string();
+                let button = self.draw_message_box(prompt, &["OK".into(), "Cancel".into()], Some(&mut text));
+                button.filter(|&i| i == 0).map(|_| text)
+            },
+        }
+    }
+
+    /// Sets up drawing with one of the dialog fonts, returning the scale to draw it at.
+    fn use_message_font(&mut self, font: &Font) -> f64 {
+        let (id, scale) = self.system_font(font.name.as_ref(), font.size, font.style);
+        self.draw_font_id = id;
+        scale.into_inner()
+    }
+
+    fn message_text_size(&mut self, text: &gml::String, font: &Font, max_width: Option<i32>) -> (i32, i32) {
+        let scale = self.use_message_font(font);
+        let (w, h) = self.get_string_size(text.clone(), None, max_width.map(|w| (f64::from(w) / scale) as i32));
+        ((f64::from(w) * scale) as i32, (f64::from(h) * scale) as i32)
+    }
+
+    fn draw_message_text(
+        &mut self,
+        (x, y): (i32, i32),
+        text: &gml::String,
+        font: &Font,
+        colour: i32,
+        max_width: Option<i32>,
+    ) {
+        let scale = self.use_message_font(font);
+        self.draw_string(
+            Real::from(x),
+            Real::from(y),
+            text.clone(),
+            None,
+            max_width.map(|w| (f64::from(w) / scale) as i32),
+            Real::from(scale),
+            Real::from(scale),
+            Real::from(0.0),
+            Some((colour, colour, colour, colour)),
+            self.message_style.alpha,
+        );
+    }
+
+    fn draw_picture(&mut self, picture: Picture, (x, y, w, h): (i32, i32, i32, i32), colour: i32) {
+        let xscale = f64::from(w) / f64::from(picture.width.max(1));
+        let yscale = f64::from(h) / f64::from(picture.height.max(1));
+        self.renderer.draw_sprite(
+            picture.atlas_ref,
+            f64::from(x) + f64::from(picture.origin.0) * xscale,
+            f64::from(y) + f64::from(picture.origin.1) * yscale,
+            xscale,
+            yscale,
+            0.0,
+            colour,
+            self.message_style.alpha.into_inner(),
+        );
+    }
+
+    fn background_picture(&self) -> Picture {
+        let background = self.assets.backgrounds.get_asset(self.message_style.background);
+        match background.and_then(|b| b.atlas_ref.map(|r| (r, b))) {
+            Some((atlas_ref, b)) => Picture { atlas_ref, width: b.width as _, height: b.height as _, origin: (0, 0) },
+            None => self.message_art.background,
+        }
+    }
+
+    /// Gets the picture for a button that's normal (0), under the mouse (1) or held down (2).
+    fn button_picture(&self, state: usize) -> Picture {
+        match self.assets.sprites.get_asset(self.message_style.button) {
+            Some(sprite) if !sprite.frames.is_empty() => {
+                let frame = &sprite.frames[state.min(sprite.frames.len() - 1)];
+                Picture {
+                    atlas_ref: frame.atlas_ref,
+                    width: frame.width as _,
+                    height: frame.height as _,
+                    origin: (sprite.origin_x, sprite.origin_y),
+                }
+            },
+            _ => self.message_art.buttons[state],
+        }
+    }
+
+    /// The size of a button big enough for any of the given labels.
+    fn message_button_size(&mut self, labels: &[gml::String]) -> (i32, i32) {
+        let picture = self.button_picture(0);
+        let font = self.message_style.button_font.clone();
+        labels.iter().fold((picture.width, picture.height), |(w, h), label| {
+            let (text_w, text_h) = self.message_text_size(label, &font, None);
+            (w.max(text_w + PADDING), h.max(text_h + 4))
+        })
+    }
+
+    fn message_caption_height(&mut self) -> i32 {
+        if self.message_style.border {
+            let font = self.message_style.text_font.clone();
+            self.message_text_size(&"Ag".into(), &font, None).1 + 6
+        } else {
+            0
+        }
+    }
+
+    /// Works out where a dialog of the given size goes on the screen, following message_position.
+    fn place_message(&self, (w, h): (i32, i32), (width, height): (i32, i32)) -> (i32, i32) {
+        let (x, y) = self.message_style.position;
+        (if x >= 0 { x } else { (width - w) / 2 }, if y >= 0 { y } else { (height - h) / 2 })
+    }
+
+    /// Draws a dialog's background, with a caption bar along the top `caption_h` pixels.
+    fn draw_message_frame(&mut self, (x, y, w, h): (i32, i32, i32, i32), caption: &gml::String, caption_h: i32) {
+        let background = self.background_picture();
+        self.draw_picture(background, (x, y + caption_h, w, h - caption_h), 0xffffff);
+        if caption_h > 0 {
+            let alpha = self.message_style.alpha.into_inner();
+            self.renderer.draw_rectangle(
+                x.into(),
+                y.into(),
+                (x + w).into(),
+                (y + caption_h).into(),
+                CAPTION_COLOUR,
+                alpha,
+            );
+            let font = self.message_style.text_font.clone();
+            self.draw_message_text((x + 6, y + 3), caption, &font, 0xffffff, None);
+        }
+    }
+
+    /// Draws a text field, with a cursor at the end of the text.
+    fn draw_message_field(&mut self, (x, y, w, h): (i32, i32, i32, i32), text: &str) {
+        let (font, colour) = (self.message_style.input_font.clone(), self.message_style.input_colour);
+        self.draw_picture(self.message_art.text_edit, (x, y, w, h), colour);
+        let text = self.encode_for_drawing(&format!("{}_", text));
+        let text_h = self.message_text_size(&text, &font, None).1;
+        self.draw_message_text((x + 4, y + (h - text_h) / 2), &text, &font, font.colour, None);
+    }
+
+    /// Draws a row of buttons in the given places, remembering them for the mouse to click.
+    fn draw_message_buttons(
+        &mut self,
+        buttons: &mut Buttons,
+        labels: &[gml::String],
+        rects: Vec<(i32, i32, i32, i32)>,
+    ) {
+        buttons.rects = rects;
+        let hovered = buttons.hovered();
+        let font = self.message_style.button_font.clone();
+        self.draw_halign = draw::Halign::Middle;
+        self.draw_valign = draw::Valign::Middle;
+        for (i, (label, &(x, y, w, h))) in labels.iter().zip(&buttons.rects).enumerate() {
+            let state = match (hovered == Some(i), buttons.pressed == Some(i)) {
+                (true, true) => 2,
+                (true, false) => 1,
+                _ => 0,
+            };
+            let picture = self.button_picture(state);
+            self.draw_picture(picture, (x, y, w, h), 0xffffff);
+            let colour = if state > 0 { self.message_style.mouse_colour } else { font.colour };
+            self.draw_message_text((x + w / 2, y + h / 2), &shortcut(label.as_ref()).0, &font, colour, None);
+        }
+        self.draw_halign = draw::Halign::Left;
+        self.draw_valign = draw::Valign::Top;
+    }
+
+    /// Draws a message box over the game and waits for one of its buttons to be picked, or for Escape.
+    /// With `input`, it has a text field for editing that.
+    fn draw_message_box(
+        &mut self,
+        text: &gml::String,
+        labels: &[gml::String],
+        mut input: Option<&mut String>,
+    ) -> Option<usize> {
+        let shortcuts = labels.iter().map(|l| shortcut(l.as_ref()).1).collect::<Vec<_>>();
+        let caption = match self.message_style.caption.as_ref() {
+            b"" => self.encode_for_drawing(&self.window_caption),
+            _ => self.message_style.caption.clone(),
+        };
+        let mut buttons = Buttons::default();
+        self.run_modal(|game, events, (width, height)| {
+            for event in events.iter().copied() {
+                if let Some(i) = buttons.handle(event, game) {
+                    return Some(Some(i))
+                }
+                match event {
+                    Event::KeyboardDown(Key::Return) => return Some(Some(0)),
+                    Event::KeyboardDown(Key::Escape) | Event::CloseRequest => return Some(None),
+                    Event::KeyboardDown(Key::Backspace) => {
+                        if let Some(text) = input.as_mut() {
+                            text.pop();
+                        }
+                    },
+                    Event::Input(c) if !c.is_control() => match input.as_mut() {
+                        Some(text) => text.push(c),
+                        None => {
+                            if let Some(i) = shortcuts.iter().position(|&k| k == Some(c.to_ascii_lowercase())) {
+                                return Some(Some(i))
+                            }
+                        },
+                    },
+                    _ => (),
+                }
+            }
+
+            let style = game.message_style.clone();
+            let (button_w, button_h) = game.message_button_size(labels);
+            let buttons_w = labels.len() as i32 * (button_w + BUTTON_GAP) - BUTTON_GAP;
+            let max_text_w =
+                if style.size.0 > 0 { style.size.0 - PADDING * 2 } else { (width - PADDING * 4).clamp(64, 480) };
+            let (text_w, text_h) = game.message_text_size(text, &style.text_font, Some(max_text_w));
+            let field_h = match input {
+                Some(_) => (game.message_text_size(&"Ag".into(), &style.input_font, None).1 + 8)
+                    .max(game.message_art.text_edit.height),
+                None => 0,
+            };
+            let box_w = match style.size.0 {
+                w if w > 0 => w,
+                _ => text_w.max(buttons_w).max(if input.is_some() { 240 } else { 160 }) + PADDING * 2,
+            };
+            let box_h = match style.size.1 {
+                h if h > 0 => h,
+                _ => text_h + button_h + PADDING * 3 + if input.is_some() { field_h + PADDING } else { 0 },
+            };
+            let caption_h = game.message_caption_height();
+            let (x, y) = game.place_message((box_w, box_h + caption_h), (width, height));
+
+            game.draw_message_frame((x, y, box_w, box_h + caption_h), &caption, caption_h);
+            let y = y + caption_h;
+            game.draw_message_text(
+                (x + PADDING, y + PADDING),
+                text,
+                &style.text_font,
+                style.text_font.colour,
+                Some(box_w - PADDING * 2),
+            );
+            if let Some(text) = input.as_deref() {
+                game.draw_message_field((x + PADDING, y + PADDING * 2 + text_h, box_w - PADDING * 2, field_h), text);
+            }
+            let button_x = x + (box_w - buttons_w) / 2;
+            let button_y = y + box_h - PADDING - button_h;
+            let rects = (0..labels.len() as i32)
+                .map(|i| (button_x + i * (button_w + BUTTON_GAP), button_y, button_w, button_h))
+                .collect();
+            game.draw_message_buttons(&mut buttons, labels, rects);
+            None
+        })
+    }
+
+    /// Shows a grid of colours to pick from, returning None if cancelled.
+    fn draw_colour_picker(&mut self, default: i32) -> Option<i32> {
+        let labels: [gml::String; 2] = ["OK".into(), "Cancel".into()];
+        let caption = "Color".into();
+        let mut colour = default;
+        let mut cursor = BASIC_COLOURS.iter().position(|&c| c == default).unwrap_or(0);
+        let mut buttons = Buttons::default();
+        self.run_modal(|game, events, (width, height)| {
+            let (button_w, button_h) = game.message_button_size(&labels);
+            let buttons_w = button_w * 2 + BUTTON_GAP;
+            let rows = (BASIC_COLOURS.len() / SWATCH_COLUMNS) as i32;
+            let grid_w = SWATCH_COLUMNS as i32 * (SWATCH_SIZE + SWATCH_GAP) - SWATCH_GAP;
+            let grid_h = rows * (SWATCH_SIZE + SWATCH_GAP) - SWATCH_GAP;
+            let box_w = grid_w.max(buttons_w) + PADDING * 2;
+            let box_h = grid_h + SWATCH_SIZE + button_h + PADDING * 4;
+            let caption_h = game.message_caption_height();
+            let (x, y) = game.place_message((box_w, box_h + caption_h), (width, height));
+            let grid_x = x + (box_w - grid_w) / 2;
+            let grid_y = y + caption_h + PADDING;
+            let swatch = |i: usize| {
+                let (column, row) = ((i % SWATCH_COLUMNS) as i32, (i / SWATCH_COLUMNS) as i32);
+                (
+                    grid_x + column * (SWATCH_SIZE + SWATCH_GAP),
+                    grid_y + row * (SWATCH_SIZE + SWATCH_GAP),
+                    SWATCH_SIZE,
+                    SWATCH_SIZE,
+                )
+            };
+
+            for event in events.iter().copied() {
+                if let Some(i) = buttons.handle(event, game) {
+                    return Some(if i == 0 { Some(colour) } else { None })
+                }
+                let moved = match event {
+                    Event::KeyboardDown(Key::LeftArrow) => cursor.checked_sub(1),
+                    Event::KeyboardDown(Key::RightArrow) => Some(cursor + 1),
+                    Event::KeyboardDown(Key::UpArrow) => cursor.checked_sub(SWATCH_COLUMNS),
+                    Event::KeyboardDown(Key::DownArrow) => Some(cursor + SWATCH_COLUMNS),
+                    Event::MouseDown(MouseButton::Left) => {
+                        (0..BASIC_COLOURS.len()).find(|&i| Buttons::contains(swatch(i), buttons.mouse))
+                    },
+                    Event::KeyboardDown(Key::Return) => return Some(Some(colour)),
+                    Event::KeyboardDown(Key::Escape) | Event::CloseRequest => return Some(None),
+                    _ => None,
+                };
+                if let Some(&c) = moved.and_then(|i| BASIC_COLOURS.get(i)) {
+                    cursor = moved.unwrap_or(cursor);
+                    colour = c;
+                }
+            }
+
+            let alpha = game.message_style.alpha.into_inner();
+            game.draw_message_frame((x, y, box_w, box_h + caption_h), &caption, caption_h);
+            for (i, &c) in BASIC_COLOURS.iter().enumerate() {
+                let (sx, sy, sw, sh) = swatch(i);
+                let (x1, y1, x2, y2) = (sx.into(), sy.into(), (sx + sw).into(), (sy + sh).into());
+                game.renderer.draw_rectangle(x1, y1, x2, y2, c, alpha);
+                let outline = if i == cursor && c == colour { HIGHLIGHT_COLOUR } else { 0x808080 };
+                game.renderer.draw_rectangle_outline(x1, y1, x2, y2, outline, alpha);
+            }
+            let preview_y = grid_y + grid_h + PADDING;
+            let (x1, x2) = (f64::from(grid_x), f64::from(grid_x + grid_w));
+            let (y1, y2) = (f64::from(preview_y), f64::from(preview_y + SWATCH_SIZE));
+            game.renderer.draw_rectangle(x1, y1, x2, y2, colour, alpha);
+            game.renderer.draw_rectangle_outline(x1, y1, x2, y2, 0x808080, alpha);
+            let button_x = x + (box_w - buttons_w) / 2;
+            let button_y = y + caption_h + box_h - PADDING - button_h;
+            let rects = vec![
+                (button_x, button_y, button_w, button_h),
+                (button_x + button_w + BUTTON_GAP, button_y, button_w, button_h),
+            ];
+            game.draw_message_buttons(&mut buttons, &labels, rects);
+            None
+        })
+    }
+
+    /// Shows a file browser, returning the path picked or None if cancelled.
+    /// The list is navigated with the arrow keys and Return or by clicking twice, Backspace goes up a directory
+    /// when the name field is empty, and Tab changes the filter.
+    fn draw_file_browser(&mut self, mut browser: Browser, title: &str) -> Option<PathBuf> {
+        let caption = self.encode_for_drawing(title);
+        let labels: [gml::String; 2] =
+            [if browser.mode == FileMode::Save { "Save" } else { "OK" }.into(), "Cancel".into()];
+        let mut buttons = Buttons::default();
+        self.run_modal(|game, events, (width, height)| {
+            let style = game.message_style.clone();
+            let (button_w, button_h) = game.message_button_size(&labels);
+            let row_h = game.message_text_size(&"Ag".into(), &style.input_font, None).1 + 2;
+            let field_h = (row_h + 6).max(game.message_art.text_edit.height);
+            let has_field = browser.mode != FileMode::Directory;
+            let has_filter = has_field && !browser.filters.is_empty();
+            let box_w = (width - PADDING * 2).min(480);
+            let caption_h = game.message_caption_height();
+            let box_h = (height - PADDING * 2).min(360) - caption_h;
+            let (x, y) = game.place_message((box_w, box_h + caption_h), (width, height));
+            let inner_y = y + caption_h;
+            let list = {
+                let top = inner_y + PADDING + row_h + 4;
+                let bottom = inner_y + box_h
+                    - PADDING * 2
+                    - button_h
+                    - if has_field { field_h + 6 } else { 0 }
+                    - if has_filter { row_h + 4 } else { 0 };
+                (x + PADDING, top, box_w - PADDING * 2, (bottom - top).max(row_h))
+            };
+            let rows = (list.3 / row_h).max(1) as usize;
+
+            for event in events.iter().copied() {
+                if let Some(i) = buttons.handle(event, game) {
+                    if i == 1 {
+                        return Some(None)
+                    } else if let Some(path) = browser.accept() {
+                        return Some(Some(path))
+                    }
+                    continue
+                }
+                match event {
+                    Event::KeyboardDown(Key::UpArrow) => {
+                        let index = browser.selected.map_or(0, |i| i.saturating_sub(1));
+                        browser.select(index, rows);
+                    },
+                    Event::KeyboardDown(Key::DownArrow) => {
+                        let index = browser.selected.map_or(0, |i| i + 1);
+                        browser.select(index, rows);
+                    },
+                    Event::ScrollUp => browser.scroll = browser.scroll.saturating_sub(1),
+                    Event::ScrollDown => {
+                        browser.scroll = (browser.scroll + 1).min(browser.entries.len().saturating_sub(rows))
+                    },
+                    Event::KeyboardDown(Key::Tab) if has_filter => {
+                        browser.filter = (browser.filter + 1) % browser.filters.len();
+                        browser.refresh();
+                    },
+                    Event::KeyboardDown(Key::Return) => {
+                        let picked = match browser.selected {
+                            Some(i) if browser.name.is_empty() || browser.entries[i].1 => browser.activate(i),
+                            _ => browser.accept(),
+                        };
+                        if picked.is_some() {
+                            return Some(picked)
+                        }
+                    },
+                    Event::KeyboardDown(Key::Backspace) => {
+                        // With nothing left to delete, go up a directory instead
+                        if let (None, Some(parent)) = (browser.name.pop(), browser.dir.parent().map(PathBuf::from)) {
+                            browser.open(parent);
+                        }
+                    },
+                    Event::Input(c) if has_field && !c.is_control() => browser.name.push(c),
+                    Event::MouseDown(MouseButton::Left) => {
+                        let (mx, my) = buttons.mouse;
+                        if Buttons::contains(list, (mx, my)) {
+                            let index = browser.scroll + ((my - list.1) / row_h) as usize;
+                            if browser.selected == Some(index) {
+                                if let Some(path) = browser.activate(index) {
+                                    return Some(Some(path))
+                                }
+                            } else {
+                                browser.select(index, rows);
+                            }
+                        }
+                    },
+                    Event::KeyboardDown(Key::Escape) | Event::CloseRequest => return Some(None),
+                    _ => (),
+                }
+            }
+
+            let alpha = style.alpha.into_inner();
+            game.draw_message_frame((x, y, box_w, box_h + caption_h), &caption, caption_h);
+            let path = game.encode_for_drawing(&browser.dir.to_string_lossy());
+            game.draw_message_text(
+                (x + PADDING, inner_y + PADDING),
+                &path,
+                &style.text_font,
+                style.text_font.colour,
+                None,
+            );
+
+            let (lx, ly, lw, lh) = list;
+            let (x1, y1, x2, y2) = (lx.into(), ly.into(), (lx + lw).into(), (ly + lh).into());
+            game.renderer.draw_rectangle(x1, y1, x2, y2, style.input_colour, alpha);
+            game.renderer.draw_rectangle_outline(x1, y1, x2, y2, 0x808080, alpha);
+            for (row, (index, (name, is_dir))) in
+                browser.entries.iter().enumerate().skip(browser.scroll).take(rows).enumerate()
+            {
+                let row_y = ly + row as i32 * row_h;
+                let colour = if browser.selected == Some(index) {
+                    let (y1, y2) = (row_y.into(), (row_y + row_h).into());
+                    game.renderer.draw_rectangle(x1 + 1.0, y1, x2 - 1.0, y2, HIGHLIGHT_COLOUR, alpha);
+                    0xffffff
+                } else {
+                    style.input_font.colour
+                };
+                let label = if *is_dir && name != ".." { format!("{}{}", name, MAIN_SEPARATOR) } else { name.clone() };
+                let label = game.encode_for_drawing(&label);
+                game.draw_message_text((lx + 4, row_y + 1), &label, &style.input_font, colour, None);
+            }
+
+            let mut bottom_y = ly + lh + 6;
+            if has_field {
+                game.draw_message_field((lx, bottom_y, lw, field_h), &browser.name);
+                bottom_y += field_h + 4;
+            }
+            if let Some((name, _)) = browser.filters.get(browser.filter).filter(|_| has_filter) {
+                let text = game.encode_for_drawing(&format!("Type: {}", name));
+                game.draw_message_text((lx, bottom_y), &text, &style.text_font, style.text_font.colour, None);
+            }
+
+            let button_x = x + box_w - PADDING - button_w * 2 - BUTTON_GAP;
+            let button_y = inner_y + box_h - PADDING - button_h;
+            let rects = vec![
+                (button_x, button_y, button_w, button_h),
+                (button_x + button_w + BUTTON_GAP, button_y, button_w, button_h),
+            ];
+            game.draw_message_buttons(&mut buttons, &labels, rects);
+            None
+        })
+    }
+
+    /// Draws a popup menu with its corner at the given place, and waits for an item to be chosen with the mouse,
+    /// the arrow keys or an item's shortcut. Returns None if it was closed by clicking elsewhere or with Escape.
+    fn draw_menu(&mut self, items: &[gml::String], (menu_x, menu_y): (i32, i32)) -> Option<usize> {
+        let labels = items.iter().map(|item| shortcut(item.as_ref())).collect::<Vec<_>>();
+        let is_separator = |i: usize| items[i].as_ref() == b"-";
+        let mut highlight = None;
+        // The cursor might not move before the click, so start from wherever it already is
+        let mut mouse = self.modal_mouse((self.input.mouse_x(), self.input.mouse_y()));
+        let mut pressed = false;
+        self.run_modal(|game, events, (width, height)| {
+            // Lay the items out, keeping the menu on the screen if it'd go off the edge
+            let mut rows = Vec::with_capacity(labels.len());
+            let (mut menu_w, mut menu_h) = (0, MENU_BORDER);
+            for (i, (label, _)) in labels.iter().enumerate() {
+                let row_h = if is_separator(i) {
+                    MENU_SEPARATOR_HEIGHT
+                } else {
+                    let (w, h) = game.get_string_size(label.clone(), None, None);
+                    menu_w = menu_w.max(w);
+                    h + MENU_ITEM_PADDING * 2
+                };
+                rows.push((menu_h, row_h));
+                menu_h += row_h;
+            }
+            let (menu_w, menu_h) = (menu_w + MENU_INDENT * 2, menu_h + MENU_BORDER);
+            let x = menu_x.min(width - menu_w).max(0);
+            let y = menu_y.min(height - menu_h).max(0);
+            let item_at = |(mx, my): (i32, i32)| {
+                rows.iter()
+                    .position(|&(row_y, row_h)| Buttons::contains((x, y + row_y, menu_w, row_h), (mx, my)))
+                    .filter(|&i| !is_separator(i))
+            };
+            let step = |from: Option<usize>, forward: bool| {
+                let count = items.len();
+                let mut i = from.unwrap_or(if forward { count - 1 } else { 0 });
+                for _ in 0..count {
+                    i = if forward { (i + 1) % count } else { (i + count - 1) % count };
+                    if !is_separator(i) {
+                        return Some(i)
+                    }
+                }
+                from
+            };
+
+            for event in events.iter().copied() {
+                match event {
+                    Event::MouseMove((mx, my)) => {
+                        mouse = game.modal_mouse((mx.into(), my.into()));
+                        if let Some(i) = item_at(mouse) {
+                            highlight = Some(i);
+                        }
+                    },
+                    Event::MouseDown(_) => {
+                        if !Buttons::contains((x, y, menu_w, menu_h), mouse) {
+                            return Some(None)
+                        }
+                        pressed = true;
+                    },
+                    // The button that opened the menu might still be held, so only a click inside it counts
+                    Event::MouseUp(MouseButton::Left) if pressed => {
+                        if let Some(i) = item_at(mouse) {
+                            return Some(Some(i))
+                        }
+                    },
+                    Event::KeyboardDown(Key::UpArrow) => highlight = step(highlight, false),
+                    Event::KeyboardDown(Key::DownArrow) => highlight = step(highlight, true),
+                    Event::KeyboardDown(Key::Return) if highlight.is_some() => return Some(highlight),
+                    Event::KeyboardDown(Key::Escape) | Event::CloseRequest => return Some(None),
+                    Event::Input(c) => {
+                        if let Some(i) = labels.iter().position(|&(_, k)| k == Some(c.to_ascii_lowercase())) {
+                            return Some(Some(i))
+                        }
+                    },
+                    _ => (),
+                }
+            }
+
+            game.draw_panel(x, y, x + menu_w, y + menu_h, 0xf0f0f0, 0x808080);
+            for (i, ((label, _), &(row_y, row_h))) in labels.iter().zip(&rows).enumerate() {
+                let (x1, x2) = (f64::from(x + 2), f64::from(x + menu_w - 2));
+                let row_y = y + row_y;
+                if is_separator(i) {
+                    let line_y = f64::from(row_y + row_h / 2);
+                    game.renderer.draw_rectangle(x1, line_y, x2, line_y, 0xa0a0a0, 1.0);
+                    continue
+                }
+                let colour = if highlight == Some(i) {
+                    let (y1, y2) = (f64::from(row_y), f64::from(row_y + row_h - 1));
+                    game.renderer.draw_rectangle(x1, y1, x2, y2, HIGHLIGHT_COLOUR, 1.0);
+                    0xffffff
+                } else {
+                    0
+                };
+                game.draw_string(
+                    Real::from(x + MENU_INDENT),
+                    Real::from(row_y + MENU_ITEM_PADDING),
+                    label.clone(),
+                    None,
+                    None,
+                    Real::from(1.0),
+                    Real::from(1.0),
+                    Real::from(0.0),
+                    Some((colour, colour, colour, colour)),
+                    Real::from(1.0),
+                );
+            }
+            None
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shortcuts() {
+        assert_eq!(shortcut(b"&Yes"), (gml::String::from("Yes"), Some('y')));
+        assert_eq!(shortcut(b"Save &As"), (gml::String::from("Save As"), Some('a')));
+        assert_eq!(shortcut(b"&&Quit"), (gml::String::from("&Quit"), None));
+        assert_eq!(shortcut(b"&One &Two"), (gml::String::from("One Two"), Some('o')));
+        assert_eq!(shortcut(b"Trailing&"), (gml::String::from("Trailing"), None));
+    }
+}
@@ -4,6 +4,7 @@ use crate::{
     math::Real,
 };
 use serde::{Deserialize, Serialize};
+use std::cell::Cell;
 
 #[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct PotentialStepSettings {
@@ -160,7 +161,8 @@ pub fn potential_step(
         if !res && settings.rotate_on_spot {
             instance.direction.set(instance.direction.get() + settings.max_rotation);
         }
-        res
+        // it was more than a step away, so it can't have got there whether it moved or not
+        false
     } else {
         let res = try_move(x, y);
         if res {
@@ -170,6 +172,16 @@ pub fn potential_step(
     }
 }
 
+/// The most steps mp_potential_path will take before giving up, which is the number it would take to go straight
+/// to the destination multiplied by the given factor.
+pub fn potential_path_max_steps(x: Real, y: Real, step_size: Real, factor: Real, instance: &Instance) -> usize {
+    if step_size <= 0.into() {
+        return 0
+    }
+    let distance = (x - instance.x.get()).into_inner().hypot((y - instance.y.get()).into_inner());
+    (Real::from(distance) / step_size * factor).round().to_i32().max(0) as usize
+}
+
 #[derive(PartialEq, Eq)]
 pub enum PathGenResult {
     Done,
@@ -194,3 +206,75 @@ pub fn make_path(inst: &Instance, path: &mut Path, func: impl Fn(&Instance) -> P
     inst.bbox_is_stale.set(true);
     result == PathGenResult::Done
 }
+
+/// Makes a path out of potential steps, which fails if it hasn't got there within the maximum number of steps.
+pub fn make_potential_path(
+    (x, y): (Real, Real),
+    step_size: Real,
+    factor: Real,
+    settings: &PotentialStepSettings,
+    inst: &Instance,
+    path: &mut Path,
+    coll: impl Fn() -> bool,
+) -> bool {
+    let max_steps = potential_path_max_steps(x, y, step_size, factor, inst);
+    let steps = Cell::new(0);
+    make_path(inst, path, |inst| {
+        steps.set(steps.get() + 1);
+        if potential_step(x, y, step_size, settings, inst, &coll) {
+            PathGenResult::Done
+        } else if steps.get() >= max_steps {
+            PathGenResult::Failed
+        } else {
+            PathGenResult::NotDone
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_path() -> Path {
+        Path {
+            name: "path".into(),
+            points: Vec::new(),
+            control_nodes: Vec::new(),
+            length: 0.into(),
+            curve: true,
+            closed: true,
+            precision: 4,
+            start: Default::default(),
+            end: Default::default(),
+        }
+    }
+
+    #[test]
+    fn potential_path() {
+        let settings = PotentialStepSettings::default();
+        let inst = Instance::new_dummy(None);
+        inst.x.set(10.into());
+        inst.y.set(20.into());
+        inst.set_direction(45.into());
+        let restored = |inst: &Instance| {
+            inst.x.get() == Real::from(10) && inst.y.get() == Real::from(20) && inst.direction.get() == Real::from(45)
+        };
+
+        // Nothing in the way, so it gets there
+        let mut path = empty_path();
+        let open = || false;
+        assert!(make_potential_path((110.into(), 20.into()), 10.into(), 4.into(), &settings, &inst, &mut path, open));
+        let end = path.points.last().unwrap();
+        assert_eq!((end.x, end.y), (Real::from(110), Real::from(20)));
+        assert!(!path.curve && !path.closed);
+        assert!(restored(&inst));
+
+        // A wall it can't get around, so it gives up after 100 / 10 * 2 steps
+        let mut path = empty_path();
+        let wall = || inst.x.get() >= Real::from(60);
+        assert!(!make_potential_path((110.into(), 20.into()), 10.into(), 2.into(), &settings, &inst, &mut path, wall));
+        assert_eq!(path.points.len(), 20);
+        assert!(path.points.iter().all(|p| p.x < Real::from(60)));
+        assert!(restored(&inst));
+    }
+}
@@ -1,10 +1,15 @@
 use crate::{
     game::{
+        dialog::{Answer, Dialog},
         replay::{self, Replay},
         savestate::{self, SaveState},
-        Game, GameClock, SceneChange,
+        Game, GameClock, GetAsset, SceneChange,
+    },
+    gml::{
+        self,
+        debugger::{BreakTarget, Breakpoint, Resume},
+        rand::Random,
     },
-    gml::rand::Random,
     imgui_utils::*,
     input,
     instance::Field,
@@ -14,7 +19,7 @@ use crate::{
 use imgui::{self, internal::RawWrapper, DrawCmd, DrawList};
 use ramen::{event::Event, input::Key};
 use serde::{Deserialize, Serialize};
-use std::{fs::File, path::PathBuf, time::Instant};
+use std::{cell::RefCell, collections::HashMap, fs::File, path::PathBuf, rc::Rc, time::Instant};
 
 const CLEAR_COLOUR: Colour = Colour::new(0.0196, 0.1059, 0.06275);
 const BTN_NEUTRAL_COL: Colour = Colour::new(0.15, 0.15, 0.21);
@@ -118,6 +123,42 @@ struct UIState<'g> {
 
     /// Cached UI text for select buttons
     select_text: Vec<String>,
+
+    /// Whether the user has asked to advance a frame, which will happen at the end of the current UI frame
+    advance_requested: bool,
+
+    /// Input state for the debugger window
+    debugger_window: DebuggerWindow,
+}
+
+/// Breakpoint editor state for the debugger window.
+#[derive(Default)]
+struct DebuggerWindow {
+    /// Name of the script or object to break in
+    target_name: String,
+
+    /// Event type and number to break in if the target is an object
+    event_type: i32,
+    event_number: i32,
+
+    /// Line to break on, or 0 to break on entry
+    line: i32,
+
+    /// Error message from the last time the user tried to add a breakpoint
+    message: Option<String>,
+}
+
+/// Everything the debugger and game dialogs need to run the UI by themselves while GML execution is paused.
+/// It's lent to them for the duration of a frame advance and handed back afterwards.
+struct PausedUi {
+    context: imgui::Context,
+    debugger_window: DebuggerWindow,
+    renderer_state: RendererState,
+
+    /// UI window size, which may be changed while paused and gets written back to the config
+    ui_width: u16,
+    ui_height: u16,
+    ui_maximised: bool,
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -527,6 +568,8 @@ impl Game {
             save_text: (0..16).map(|i| format!("Save {}", i + 1)).collect::<Vec<_>>(),
             load_text: (0..16).map(|i| format!("Load {}", i + 1)).collect::<Vec<_>>(),
             select_text: (0..16).map(|i| format!("Select###Select{}", i + 1)).collect::<Vec<_>>(),
+            advance_requested: false,
+            debugger_window: Default::default(),
         }
         .run(context)
     }
@@ -591,6 +634,15 @@ impl UIState<'_> {
                 .position([2.0, 210.0], imgui::Condition::Once)
                 .build(|| self.render_mouse_window(&frame, win_frame_height));
 
+            frame
+                .window("Debugger")
+                .resizable(true)
+                .size([300.0, 330.0], imgui::Condition::Once)
+                .position([539.0, 8.0], imgui::Condition::Once)
+                .build(|| {
+                    render_debugger_window(frame, self.game, &mut self.debugger_window, None);
+                });
+
             // Instance-watcher windows
             let previous_len = self.config.watched_ids.len();
             self.instance_images.clear();
@@ -798,59 +850,20 @@ impl UIState<'_> {
             );
 
             let draw_data = context.render();
-            for draw_list in draw_data.draw_lists() {
-                let draw_list: &DrawList = draw_list;
-                let vertex_buffer = draw_list.vtx_buffer();
-                let index_buffer = draw_list.idx_buffer();
-                for cmd in draw_list.commands() {
-                    match cmd {
-                        DrawCmd::Elements { count, cmd_params } => {
-                            // TODO: don't use the primitive builder for this, it allocates a lot and
-                            // also doesn't do instanced drawing I think?
-                            self.game.renderer.reset_primitive_2d(
-                                PrimitiveType::TriList,
-                                if cmd_params.texture_id.id() == 0 {
-                                    None
-                                } else {
-                                    Some(AtlasRef(cmd_params.texture_id.id() as i32))
-                                },
-                            );
-
-                            for i in 0..count {
-                                let vert: imgui::DrawVert = vertex_buffer
-                                    [cmd_params.vtx_offset + usize::from(index_buffer[i + cmd_params.idx_offset])];
-                                self.game.renderer.vertex_2d(
-                                    f64::from(vert.pos[0]) - 0.5,
-                                    f64::from(vert.pos[1]) - 0.5,
-                                    vert.uv[0].into(),
-                                    vert.uv[1].into(),
-                                    i32::from(vert.col[0])
-                                        | (i32::from(vert.col[1]) << 8)
-                                        | (i32::from(vert.col[2]) << 16),
-                                    f64::from(vert.col[3]) / 255.0,
-                                );
-                            }
-
-                            let clip_x = cmd_params.clip_rect[0] as i32;
-                            let clip_y = cmd_params.clip_rect[1] as i32;
-                            let clip_w = (cmd_params.clip_rect[2] - cmd_params.clip_rect[0]) as i32 + 1;
-                            let clip_h = (cmd_params.clip_rect[3] - cmd_params.clip_rect[1]) as i32 + 1;
-                            self.game
-                                .renderer
-                                .set_view(clip_x, clip_y, clip_w, clip_h, 0.0, clip_x, clip_y, clip_w, clip_h);
-                            self.game.renderer.draw_primitive_2d();
-                        },
-                        DrawCmd::RawCallback { callback, raw_cmd } => {
-                            unsafe { callback(draw_list.raw(), raw_cmd) };
-                        },
-                        DrawCmd::ResetRenderState => {},
-                    }
-                }
-            }
+            draw_imgui(&mut self.game.renderer, draw_data);
 
             self.game.renderer.finish(self.config.ui_width.into(), self.config.ui_height.into(), CLEAR_COLOUR);
 
             context.io_mut().delta_time = time_start.elapsed().as_micros() as f32 / 1000000.0;
+
+            // Frame advance happens here rather than in the control window, as it needs the imgui context
+            if self.advance_requested {
+                self.advance_requested = false;
+                context = self.advance_frame(context);
+                if self.game.close_requested {
+                    break 'gui
+                }
+            }
         }
 
         self.save_config();
@@ -1008,181 +1021,7 @@ impl UIState<'_> {
             && self.game_running
             && self.err_string.is_none()
         {
-            let (w, h) = self.game.renderer.stored_size();
-            let frame = self.replay.new_frame();
-
-            self.game.input.mouse_step();
-            for (i, state) in self.keyboard_state.iter().enumerate() {
-                let i = i as u8;
-                match state {
-                    KeyState::NeutralWillPress => {
-                        self.game.input.button_press(i, true);
-                        frame.inputs.push(replay::Input::KeyPress(i));
-                    },
-                    KeyState::NeutralWillDouble | KeyState::NeutralDoubleEveryFrame => {
-                        self.game.input.button_press(i, true);
-                        self.game.input.button_release(i, true);
-                        frame.inputs.push(replay::Input::KeyPress(i));
-                        frame.inputs.push(replay::Input::KeyRelease(i));
-                    },
-                    KeyState::NeutralWillTriple => {
-                        self.game.input.button_press(i, true);
-                        self.game.input.button_release(i, true);
-                        self.game.input.button_press(i, true);
-                        frame.inputs.push(replay::Input::KeyPress(i));
-                        frame.inputs.push(replay::Input::KeyRelease(i));
-                        frame.inputs.push(replay::Input::KeyPress(i));
-                    },
-                    KeyState::HeldWillRelease | KeyState::NeutralWillCactus => {
-                        self.game.input.button_release(i, true);
-                        frame.inputs.push(replay::Input::KeyRelease(i));
-                    },
-                    KeyState::HeldWillDouble | KeyState::HeldDoubleEveryFrame => {
-                        self.game.input.button_release(i, true);
-                        self.game.input.button_press(i, true);
-                        frame.inputs.push(replay::Input::KeyRelease(i));
-                        frame.inputs.push(replay::Input::KeyPress(i));
-                    },
-                    KeyState::HeldWillTriple => {
-                        self.game.input.button_release(i, true);
-                        self.game.input.button_press(i, true);
-                        self.game.input.button_release(i, true);
-                        frame.inputs.push(replay::Input::KeyRelease(i));
-                        frame.inputs.push(replay::Input::KeyPress(i));
-                        frame.inputs.push(replay::Input::KeyRelease(i));
-                    },
-                    KeyState::Neutral | KeyState::Held => (),
-                }
-            }
-
-            for (i, state) in self.mouse_state.iter().enumerate() {
-                let i = i as i8 + 1;
-                match state {
-                    KeyState::NeutralWillPress => {
-                        self.game.input.mouse_press(i, true);
-                        frame.inputs.push(replay::Input::MousePress(i));
-                    },
-                    KeyState::NeutralWillDouble | KeyState::NeutralDoubleEveryFrame => {
-                        self.game.input.mouse_press(i, true);
-                        self.game.input.mouse_release(i, true);
-                        frame.inputs.push(replay::Input::MousePress(i));
-                        frame.inputs.push(replay::Input::MouseRelease(i));
-                    },
-                    KeyState::NeutralWillTriple => {
-                        self.game.input.mouse_press(i, true);
-                        self.game.input.mouse_release(i, true);
-                        self.game.input.mouse_press(i, true);
-                        frame.inputs.push(replay::Input::MousePress(i));
-                        frame.inputs.push(replay::Input::MouseRelease(i));
-                        frame.inputs.push(replay::Input::MousePress(i));
-                    },
-                    KeyState::HeldWillRelease | KeyState::NeutralWillCactus => {
-                        self.game.input.mouse_release(i, true);
-                        frame.inputs.push(replay::Input::MouseRelease(i));
-                    },
-                    KeyState::HeldWillDouble | KeyState::HeldDoubleEveryFrame => {
-                        self.game.input.mouse_release(i, true);
-                        self.game.input.mouse_press(i, true);
-                        frame.inputs.push(replay::Input::MouseRelease(i));
-                        frame.inputs.push(replay::Input::MousePress(i));
-                    },
-                    KeyState::HeldWillTriple => {
-                        self.game.input.mouse_release(i, true);
-                        self.game.input.mouse_press(i, true);
-                        self.game.input.mouse_release(i, true);
-                        frame.inputs.push(replay::Input::MouseRelease(i));
-                        frame.inputs.push(replay::Input::MousePress(i));
-                        frame.inputs.push(replay::Input::MouseRelease(i));
-                    },
-                    KeyState::Neutral | KeyState::Held => (),
-                }
-            }
-
-            if let Some((x, y)) = self.new_mouse_pos {
-                frame.mouse_x = x;
-                frame.mouse_y = y;
-                self.game.input.mouse_move_to((x, y));
-            }
-
-            if let Some(rand) = self.new_rand.take() {
-                frame.new_seed = Some(rand.seed());
-                self.game.rand.set_seed(rand.seed());
-            }
-
-            self.game.renderer.set_state(&self.game_renderer_state);
-            self.game.renderer.resize_framebuffer(w, h, false);
-            self.game.renderer.set_view(
-                0,
-                0,
-                self.game.unscaled_width as _,
-                self.game.unscaled_height as _,
-                0.0,
-                0,
-                0,
-                self.game.unscaled_width as _,
-                self.game.unscaled_height as _,
-            );
-            self.game.renderer.draw_stored(0, 0, w, h);
-            if let Err(e) = match self.game.frame() {
-                Ok(()) => match self.game.scene_change {
-                    Some(SceneChange::Room(id)) => self.game.load_room(id),
-                    Some(SceneChange::Restart) => self.game.restart(),
-                    Some(SceneChange::End) => self.game.restart(),
-                    Some(SceneChange::Load(ref mut path)) => {
-                        let path = std::mem::take(path);
-                        self.game.load_gm_save(path)
-                    },
-                    None => Ok(()),
-                },
-                Err(e) => Err(e.into()),
-            } {
-                self.err_string = Some(format!("Game crashed: {}\n\nPlease load a savestate.", e));
-                self.game_running = false;
-            }
-
-            for ev in self.game.stored_events.iter() {
-                frame.events.push(ev.clone());
-            }
-            self.game.stored_events.clear();
-            for (i, state) in self.keyboard_state.iter_mut().enumerate() {
-                state.reset_to(self.game.input.keyboard_check_direct(i as u8));
-            }
-            for (i, state) in self.mouse_state.iter_mut().enumerate() {
-                state.reset_to(self.game.input.mouse_check_button(i as i8 + 1));
-            }
-
-            // Fake frame limiter stuff (don't actually frame-limit in record mode)
-            if let GameClock::SpoofedNanos(t) = &mut self.game.clock {
-                *t += 1_000_000_000 / self.game.room.speed as u128;
-            }
-            if self.game.frame_counter == self.game.room.speed {
-                self.game.fps = self.game.room.speed;
-                self.game.frame_counter = 0;
-            }
-            self.game.frame_counter += 1;
-
-            self.frame_text = format!("Frame: {}", self.replay.frame_count());
-            self.seed_text = format!("Seed: {}", self.game.rand.seed());
-
-            self.game.renderer.resize_framebuffer(self.config.ui_width.into(), self.config.ui_height.into(), true);
-            self.game.renderer.set_view(
-                0,
-                0,
-                self.config.ui_width.into(),
-                self.config.ui_height.into(),
-                0.0,
-                0,
-                0,
-                self.config.ui_width.into(),
-                self.config.ui_height.into(),
-            );
-            self.game.renderer.clear_view(CLEAR_COLOUR, 1.0);
-            self.game_renderer_state = self.game.renderer.state();
-            self.game.renderer.set_state(&self.ui_renderer_state);
-            self.context_menu = None;
-            self.new_mouse_pos = None;
-
-            self.redo_instance_reports();
+            self.advance_requested = true;
         }
 
         if (frame.button_with_size("Quick Save (Q)", [165.0, 20.0]) || frame.key_pressed(input::ramen2vk(Key::Q)))
@@ -1275,6 +1114,213 @@ impl UIState<'_> {
         }
     }
 
+    /// Advances the game by one frame using the inputs the user has set up.
+    /// Takes the imgui context so the debugger can keep drawing the UI if GML execution gets paused,
+    /// and gives it back afterwards. If the window got closed while paused, `game.close_requested` is set.
+    fn advance_frame(&mut self, context: imgui::Context) -> imgui::Context {
+        let (w, h) = self.game.renderer.stored_size();
+        let frame = self.replay.new_frame();
+
+        self.game.input.mouse_step();
+        for (i, state) in self.keyboard_state.iter().enumerate() {
+            let i = i as u8;
+            match state {
+                KeyState::NeutralWillPress => {
+                    self.game.input.button_press(i, true);
+                    frame.inputs.push(replay::Input::KeyPress(i));
+                },
+                KeyState::NeutralWillDouble | KeyState::NeutralDoubleEveryFrame => {
+                    self.game.input.button_press(i, true);
+                    self.game.input.button_release(i, true);
+                    frame.inputs.push(replay::Input::KeyPress(i));
+                    frame.inputs.push(replay::Input::KeyRelease(i));
+                },
+                KeyState::NeutralWillTriple => {
+                    self.game.input.button_press(i, true);
+                    self.game.input.button_release(i, true);
+                    self.game.input.button_press(i, true);
+                    frame.inputs.push(replay::Input::KeyPress(i));
+                    frame.inputs.push(replay::Input::KeyRelease(i));
+                    frame.inputs.push(replay::Input::KeyPress(i));
+                },
+                KeyState::HeldWillRelease | KeyState::NeutralWillCactus => {
+                    self.game.input.button_release(i, true);
+                    frame.inputs.push(replay::Input::KeyRelease(i));
+                },
+                KeyState::HeldWillDouble | KeyState::HeldDoubleEveryFrame => {
+                    self.game.input.button_release(i, true);
+                    self.game.input.button_press(i, true);
+                    frame.inputs.push(replay::Input::KeyRelease(i));
+                    frame.inputs.push(replay::Input::KeyPress(i));
+                },
+                KeyState::HeldWillTriple => {
+                    self.game.input.button_release(i, true);
+                    self.game.input.button_press(i, true);
+                    self.game.input.button_release(i, true);
+                    frame.inputs.push(replay::Input::KeyRelease(i));
+                    frame.inputs.push(replay::Input::KeyPress(i));
+                    frame.inputs.push(replay::Input::KeyRelease(i));
+                },
+                KeyState::Neutral | KeyState::Held => (),
+            }
+        }
+
+        for (i, state) in self.mouse_state.iter().enumerate() {
+            let i = i as i8 + 1;
+            match state {
+                KeyState::NeutralWillPress => {
+                    self.game.input.mouse_press(i, true);
+                    frame.inputs.push(replay::Input::MousePress(i));
+                },
+                KeyState::NeutralWillDouble | KeyState::NeutralDoubleEveryFrame => {
+                    self.game.input.mouse_press(i, true);
+                    self.game.input.mouse_release(i, true);
+                    frame.inputs.push(replay::Input::MousePress(i));
+                    frame.inputs.push(replay::Input::MouseRelease(i));
+                },
+                KeyState::NeutralWillTriple => {
+                    self.game.input.mouse_press(i, true);
+                    self.game.input.mouse_release(i, true);
+                    self.game.input.mouse_press(i, true);
+                    frame.inputs.push(replay::Input::MousePress(i));
+                    frame.inputs.push(replay::Input::MouseRelease(i));
+                    frame.inputs.push(replay::Input::MousePress(i));
+                },
+                KeyState::HeldWillRelease | KeyState::NeutralWillCactus => {
+                    self.game.input.mouse_release(i, true);
+                    frame.inputs.push(replay::Input::MouseRelease(i));
+                },
+                KeyState::HeldWillDouble | KeyState::HeldDoubleEveryFrame => {
+                    self.game.input.mouse_release(i, true);
+                    self.game.input.mouse_press(i, true);
+                    frame.inputs.push(replay::Input::MouseRelease(i));
+                    frame.inputs.push(replay::Input::MousePress(i));
+                },
+                KeyState::HeldWillTriple => {
+                    self.game.input.mouse_release(i, true);
+                    self.game.input.mouse_press(i, true);
+                    self.game.input.mouse_release(i, true);
+                    frame.inputs.push(replay::Input::MouseRelease(i));
+                    frame.inputs.push(replay::Input::MousePress(i));
+                    frame.inputs.push(replay::Input::MouseRelease(i));
+                },
+                KeyState::Neutral | KeyState::Held => (),
+            }
+        }
+
+        if let Some((x, y)) = self.new_mouse_pos {
+            frame.mouse_x = x;
+            frame.mouse_y = y;
+            self.game.input.mouse_move_to((x, y));
+        }
+
+        if let Some(rand) = self.new_rand.take() {
+            frame.new_seed = Some(rand.seed());
+            self.game.rand.set_seed(rand.seed());
+        }
+
+        self.game.renderer.set_state(&self.game_renderer_state);
+        self.game.renderer.resize_framebuffer(w, h, false);
+        self.game.renderer.set_view(
+            0,
+            0,
+            self.game.unscaled_width as _,
+            self.game.unscaled_height as _,
+            0.0,
+            0,
+            0,
+            self.game.unscaled_width as _,
+            self.game.unscaled_height as _,
+        );
+        self.game.renderer.draw_stored(0, 0, w, h);
+
+        // Let the debugger and game dialogs pause GML execution and run their own UI loop in the meantime
+        let paused_ui = Rc::new(RefCell::new(PausedUi {
+            context,
+            debugger_window: std::mem::take(&mut self.debugger_window),
+            renderer_state: self.ui_renderer_state.clone(),
+            ui_width: self.config.ui_width,
+            ui_height: self.config.ui_height,
+            ui_maximised: self.config.ui_maximised,
+        }));
+        let ui = paused_ui.clone();
+        self.game.debugger.hook =
+            Some(Box::new(move |game, gml_context| debugger_break(game, gml_context, &mut ui.borrow_mut())));
+        let ui = paused_ui.clone();
+        self.game.dialog_hook = Some(Box::new(move |game, dialog| dialog_break(game, dialog, &mut ui.borrow_mut())));
+        if let Err(e) = match self.game.frame() {
+            Ok(()) => match self.game.scene_change {
+                Some(SceneChange::Room(id)) => self.game.load_room(id),
+                Some(SceneChange::Restart) => self.game.restart(),
+                Some(SceneChange::End) => self.game.restart(),
+                Some(SceneChange::Load(ref mut path)) => {
+                    let path = std::mem::take(path);
+                    self.game.load_gm_save(path)
+                },
+                None => Ok(()),
+            },
+            Err(e) => Err(e.into()),
+        } {
+            self.err_string = Some(format!("Game crashed: {}\n\nPlease load a savestate.", e));
+            self.game_running = false;
+        }
+        self.game.debugger.hook = None;
+        self.game.dialog_hook = None;
+        let paused_ui = match Rc::try_unwrap(paused_ui) {
+            Ok(ui) => ui.into_inner(),
+            Err(_) => unreachable!("paused UI is still lent out after the hooks were removed"),
+        };
+        self.debugger_window = paused_ui.debugger_window;
+        self.config.ui_width = paused_ui.ui_width;
+        self.config.ui_height = paused_ui.ui_height;
+        self.config.ui_maximised = paused_ui.ui_maximised;
+
+        for ev in self.game.stored_events.iter() {
+            frame.events.push(ev.clone());
+        }
+        self.game.stored_events.clear();
+        for (i, state) in self.keyboard_state.iter_mut().enumerate() {
+            state.reset_to(self.game.input.keyboard_check_direct(i as u8));
+        }
+        for (i, state) in self.mouse_state.iter_mut().enumerate() {
+            state.reset_to(self.game.input.mouse_check_button(i as i8 + 1));
+        }
+
+        // Fake frame limiter stuff (don't actually frame-limit in record mode)
+        if let GameClock::SpoofedNanos(t) = &mut self.game.clock {
+            *t += 1_000_000_000 / self.game.room.speed as u128;
+        }
+        if self.game.frame_counter == self.game.room.speed {
+            self.game.fps = self.game.room.speed;
+            self.game.frame_counter = 0;
+        }
+        self.game.frame_counter += 1;
+
+        self.frame_text = format!("Frame: {}", self.replay.frame_count());
+        self.seed_text = format!("Seed: {}", self.game.rand.seed());
+
+        self.game.renderer.resize_framebuffer(self.config.ui_width.into(), self.config.ui_height.into(), true);
+        self.game.renderer.set_view(
+            0,
+            0,
+            self.config.ui_width.into(),
+            self.config.ui_height.into(),
+            0.0,
+            0,
+            0,
+            self.config.ui_width.into(),
+            self.config.ui_height.into(),
+        );
+        self.game.renderer.clear_view(CLEAR_COLOUR, 1.0);
+        self.game_renderer_state = self.game.renderer.state();
+        self.game.renderer.set_state(&self.ui_renderer_state);
+        self.context_menu = None;
+        self.new_mouse_pos = None;
+
+        self.redo_instance_reports();
+        paused_ui.context
+    }
+
     /// Renders the savestate menu into an imgui window
     fn render_savestates_window(&mut self, frame: &imgui::Ui) {
         let rect_size = Vec2(frame.window_size()[0], 24.0);
@@ -1747,6 +1793,7 @@ impl UIState<'_> {
     /// Load a state, reload cached UI stuff, and increase re-record count by 1
     fn load_state(&mut self, state: SaveState) {
         let (new_replay, new_renderer_state) = state.load_into(&mut self.game);
+        self.game.debugger.clear_step();
         self.replay = new_replay;
         self.game_renderer_state = new_renderer_state;
 
@@ -2064,6 +2111,373 @@ fn draw_keystate(frame: &imgui::Ui, state: &KeyState, position: Vec2<f32>, size:
     frame.rect_outline(position + wpos, position + size + wpos, Colour::new(0.4, 0.4, 0.65), u8::MAX);
 }
 
+/// Runs the UI while GML execution is paused by the debugger, until the user chooses how to resume.
+/// If the window gets closed, execution carries on without stopping again so that the frame can finish.
+fn debugger_break(game: &mut Game, gml_context: &mut gml::Context, ui: &mut PausedUi) -> Resume {
+    if game.close_requested {
+        return Resume::Continue
+    }
+    let PausedUi { debugger_window, .. } = ui;
+    let mut window = std::mem::take(debugger_window);
+    let resume = paused_ui_loop(game, ui, |frame, game| {
+        let mut resume = None;
+        frame
+            .window("Debugger")
+            .resizable(true)
+            .size([300.0, 330.0], imgui::Condition::Once)
+            .position([539.0, 8.0], imgui::Condition::Once)
+            .build(|| resume = render_debugger_window(frame, game, &mut window, Some(gml_context)));
+        resume
+    });
+    ui.debugger_window = window;
+    resume.unwrap_or(Resume::Continue)
+}
+
+/// Shows a dialog the game asked for, such as a runtime error or a name prompt, until the user answers it.
+fn dialog_break(game: &mut Game, dialog: &Dialog, ui: &mut PausedUi) -> Option<Answer> {
+    if game.close_requested {
+        return None
+    }
+    let mut input = match dialog {
+        Dialog::Input { default, .. } => default.to_string(),
+        _ => String::new(),
+    };
+    let mut colour = match dialog {
+        Dialog::Colour { default } => {
+            [*default & 0xff, (*default >> 8) & 0xff, (*default >> 16) & 0xff].map(|c| c as f32 / 255.0)
+        },
+        _ => [0.0; 3],
+    };
+    paused_ui_loop(game, ui, |frame, _| {
+        let mut answer = None;
+        frame
+            .window("Game Dialog")
+            .resizable(true)
+            .size([400.0, 200.0], imgui::Condition::Once)
+            .position([150.0, 150.0], imgui::Condition::Once)
+            .build(|| match dialog {
+                Dialog::Message { text, buttons } => {
+                    frame.text_wrapped(text);
+                    for (i, label) in buttons.iter().enumerate() {
+                        if i > 0 {
+                            frame.same_line();
+                        }
+                        if frame.button_with_size(label, [75.0, 20.0]) {
+                            answer = Some(Answer::Button(i));
+                        }
+                    }
+                },
+                Dialog::Input { prompt, .. } => {
+                    frame.text_wrapped(prompt);
+                    frame.input_text("##input", &mut input).build();
+                    if frame.button_with_size("OK", [75.0, 20.0]) {
+                        answer = Some(Answer::Text(input.clone()));
+                    }
+                },
+                Dialog::Colour { .. } => {
+                    frame.color_edit3("##colour", &mut colour);
+                    if frame.button_with_size("OK", [75.0, 20.0]) {
+                        let [r, g, b] = colour.map(|c| (c * 255.0).round() as i32);
+                        answer = Some(Answer::Colour(r | (g << 8) | (b << 16)));
+                    }
+                    frame.same_line();
+                    if frame.button_with_size("Cancel", [75.0, 20.0]) {
+                        answer = Some(Answer::Colour(-1));
+                    }
+                },
+                Dialog::Menu { items } => {
+                    for (i, item) in items.iter().enumerate() {
+                        if *item == "-" {
+                            frame.separator();
+                        } else if frame.selectable(format!("{}##{}", item, i)) {
+                            answer = Some(Answer::Button(i));
+                        }
+                    }
+                    frame.separator();
+                    if frame.selectable("Cancel##menu") {
+                        answer = Some(Answer::Button(items.len()));
+                    }
+                },
+            });
+        answer
+    })
+}
+
+/// Runs the UI by itself until `render` returns something, or returns None if the window gets closed, in which
+/// case `game.close_requested` is set like the main loop would. This is called from inside a frame advance,
+/// so the game's framebuffer and renderer state are put aside and restored afterwards.
+fn paused_ui_loop<T>(
+    game: &mut Game,
+    ui: &mut PausedUi,
+    mut render: impl FnMut(&imgui::Ui, &mut Game) -> Option<T>,
+) -> Option<T> {
+    let (w, h) = game.renderer.stored_size();
+    let game_renderer_state = game.renderer.state();
+    game.renderer.resize_framebuffer(ui.ui_width.into(), ui.ui_height.into(), true);
+    game.renderer.set_state(&ui.renderer_state);
+
+    let context = &mut ui.context;
+    let result = 'paused: loop {
+        let time_start = Instant::now();
+        let io = context.io_mut();
+        io.mouse_wheel = 0.0;
+
+        game.window.poll_events();
+        for event in game.window.events().iter().copied() {
+            match event {
+                ev @ Event::KeyboardDown(key) | ev @ Event::KeyboardUp(key) => {
+                    let state = matches!(ev, Event::KeyboardDown(_));
+                    io.keys_down[usize::from(input::ramen2vk(key))] = state;
+                    match key {
+                        Key::LeftShift | Key::RightShift => io.key_shift = state,
+                        Key::LeftControl | Key::RightControl => io.key_ctrl = state,
+                        Key::LeftAlt | Key::RightAlt => io.key_alt = state,
+                        _ => (),
+                    }
+                },
+                Event::MouseMove((x, y)) => io.mouse_pos = [x as f32, y as f32],
+                ev @ Event::MouseDown(btn) | ev @ Event::MouseUp(btn) => usize::try_from(input::ramen2mb(btn))
+                    .ok()
+                    .and_then(|x| x.checked_sub(1))
+                    .into_iter()
+                    .for_each(|x| io.mouse_down[x] = matches!(ev, Event::MouseDown(_))),
+                Event::ScrollUp => io.mouse_wheel = 1.0,
+                Event::ScrollDown => io.mouse_wheel = -1.0,
+                Event::Resize((width, height)) => {
+                    ui.ui_width = width;
+                    ui.ui_height = height;
+                    io.display_size = [f32::from(width), f32::from(height)];
+                    game.renderer.resize_framebuffer(u32::from(width), u32::from(height), false);
+                },
+                Event::Focus(false) => io.clear_inputs(),
+                Event::Maximise(b) => ui.ui_maximised = b,
+                Event::CloseRequest => {
+                    game.close_requested = true;
+                    break 'paused None
+                },
+                _ => (),
+            }
+        }
+
+        let frame = context.new_frame();
+        let result = render(frame, game);
+        let draw_data = context.render();
+        draw_imgui(&mut game.renderer, draw_data);
+        game.renderer.finish(ui.ui_width.into(), ui.ui_height.into(), CLEAR_COLOUR);
+        context.io_mut().delta_time = time_start.elapsed().as_micros() as f32 / 1000000.0;
+
+        if result.is_some() {
+            break result
+        }
+    };
+
+    game.resume_drawing(w, h, &game_renderer_state);
+    result
+}
+
+/// Draws the contents of the debugger window. If execution is paused, `paused` holds the innermost context,
+/// and the return value says how the user wants to resume, if they've chosen yet.
+fn render_debugger_window(
+    frame: &imgui::Ui,
+    game: &mut Game,
+    window: &mut DebuggerWindow,
+    paused: Option<&mut gml::Context>,
+) -> Option<Resume> {
+    let mut resume = None;
+    if let Some(context) = paused {
+        if let Some(top) = game.call_stack.last() {
+            frame.coloured_text(&format!("Paused in {}", top.describe(game)), BTN_CACTUS_COL);
+        }
+        if frame.button_with_size("Continue (F5)", [130.0, 20.0]) || frame.key_pressed(input::ramen2vk(Key::F5)) {
+            resume = Some(Resume::Continue);
+        }
+        frame.same_line();
+        if frame.button_with_size("Step Into (F11)", [130.0, 20.0])
+            || (frame.key_pressed(input::ramen2vk(Key::F11)) && !frame.io().key_shift)
+        {
+            resume = Some(Resume::StepInto);
+        }
+        if frame.button_with_size("Step Over (F10)", [130.0, 20.0]) || frame.key_pressed(input::ramen2vk(Key::F10)) {
+            resume = Some(Resume::StepOver);
+        }
+        frame.same_line();
+        if frame.button_with_size("Step Out (Shift+F11)", [130.0, 20.0])
+            || (frame.key_pressed(input::ramen2vk(Key::F11)) && frame.io().key_shift)
+        {
+            resume = Some(Resume::StepOut);
+        }
+
+        if let Some(node) = frame.tree_node("Call Stack") {
+            for stack_frame in game.call_stack.iter().rev() {
+                frame.text(format!("{} (self: {})", stack_frame.describe(game), stack_frame.instance));
+            }
+            node.pop();
+        }
+        if let Some(node) = frame.tree_node("Locals") {
+            for (i, arg) in context.arguments.iter().take(context.argument_count).enumerate() {
+                frame.text(format!("argument{}: {}", i, arg));
+            }
+            field_report(game, &context.locals.fields).iter().for_each(|s| frame.text(s));
+            node.pop();
+        }
+        if let Some(node) = frame.tree_node("Self") {
+            let instance = game.room.instance_list.get(context.this);
+            frame.text(format!("id: {}", instance.id.get()));
+            field_report(game, &instance.fields.borrow()).iter().for_each(|s| frame.text(s));
+            node.pop();
+        }
+        if let Some(node) = frame.tree_node("Globals") {
+            field_report(game, &game.globals.fields).iter().for_each(|s| frame.text(s));
+            node.pop();
+        }
+    } else {
+        let mut break_next = game.debugger.breaking_next();
+        if frame.checkbox("Break on next line", &mut break_next) {
+            if break_next {
+                game.debugger.break_next();
+            } else {
+                game.debugger.clear_step();
+            }
+        }
+    }
+
+    frame.separator();
+    if let Some(node) = frame.tree_node("Breakpoints") {
+        let mut remove = None;
+        for (i, breakpoint) in game.debugger.breakpoints.iter().enumerate() {
+            if frame.button(format!("X###RemoveBreakpoint{}", i)) {
+                remove = Some(*breakpoint);
+            }
+            frame.same_line();
+            let target = match breakpoint.target {
+                BreakTarget::Script(id) => match game.assets.scripts.get_asset(id as _) {
+                    Some(script) => format!("script {}", script.name),
+                    None => format!("<deleted script {}>", id),
+                },
+                BreakTarget::Event { object, event_type, event_number } => {
+                    let object_name = match game.assets.objects.get_asset(object) {
+                        Some(obj) => obj.name.to_string(),
+                        None => format!("<deleted object {}>", object),
+                    };
+                    format!("{} of {}", gml::debugger::event_name(game, event_type, event_number), object_name)
+                },
+            };
+            match breakpoint.line {
+                Some(line) => frame.text(format!("{}, line {}", target, line)),
+                None => frame.text(&target),
+            }
+        }
+        if let Some(breakpoint) = remove {
+            game.debugger.toggle_breakpoint(breakpoint);
+        }
+
+        frame.input_text("Script/object", &mut window.target_name).build();
+        frame.input_int("Event type", &mut window.event_type).build();
+        frame.input_int("Event number", &mut window.event_number).build();
+        frame.input_int("Line (0: entry)", &mut window.line).build();
+        if frame.button("Add breakpoint") {
+            let name = window.target_name.trim();
+            let target = if let Some(id) = game.compiler.get_script_id(name.as_bytes()) {
+                Some(BreakTarget::Script(id))
+            } else {
+                game.assets
+                    .objects
+                    .iter()
+                    .position(|o| o.as_ref().is_some_and(|o| o.name.as_ref() == name.as_bytes()))
+                    .map(|object| BreakTarget::Event {
+                        object: object as _,
+                        event_type: window.event_type.max(0) as _,
+                        event_number: window.event_number.max(0) as _,
+                    })
+            };
+            match target {
+                Some(target) => {
+                    let breakpoint = Breakpoint { target, line: usize::try_from(window.line).ok().filter(|&l| l > 0) };
+                    if !game.debugger.breakpoints.contains(&breakpoint) {
+                        game.debugger.breakpoints.push(breakpoint);
+                    }
+                    window.message = None;
+                },
+                None => window.message = Some(format!("No script or object called \"{}\"", name)),
+            }
+        }
+        if let Some(message) = &window.message {
+            frame.coloured_text(message, Colour::new(1.0, 0.4, 0.4));
+        }
+        node.pop();
+    }
+    resume
+}
+
+// Lists fields as "name: value", with each array element on its own line, in name order
+fn field_report(game: &Game, fields: &HashMap<usize, Field>) -> Vec<String> {
+    let mut report = fields
+        .iter()
+        .flat_map(|(id, field)| {
+            let field_name = game.compiler.get_field_name(*id).unwrap_or("<???>".into());
+            match field {
+                Field::Single(value) => vec![format!("{}: {}", field_name, value)],
+                Field::Array(map) => {
+                    let mut indices = map.keys().copied().collect::<Vec<_>>();
+                    indices.sort_unstable();
+                    indices.iter().map(|i| format!("{}[{}]: {}", field_name, i, map[i])).collect()
+                },
+            }
+        })
+        .collect::<Vec<_>>();
+    report.sort();
+    report
+}
+
+// Draws imgui's output with our renderer
+fn draw_imgui(renderer: &mut Renderer, draw_data: &imgui::DrawData) {
+    for draw_list in draw_data.draw_lists() {
+        let draw_list: &DrawList = draw_list;
+        let vertex_buffer = draw_list.vtx_buffer();
+        let index_buffer = draw_list.idx_buffer();
+        for cmd in draw_list.commands() {
+            match cmd {
+                DrawCmd::Elements { count, cmd_params } => {
+                    // TODO: don't use the primitive builder for this, it allocates a lot and
+                    // also doesn't do instanced drawing I think?
+                    renderer.reset_primitive_2d(
+                        PrimitiveType::TriList,
+                        if cmd_params.texture_id.id() == 0 {
+                            None
+                        } else {
+                            Some(AtlasRef(cmd_params.texture_id.id() as i32))
+                        },
+                    );
+
+                    for i in 0..count {
+                        let vert: imgui::DrawVert =
+                            vertex_buffer[cmd_params.vtx_offset + usize::from(index_buffer[i + cmd_params.idx_offset])];
+                        renderer.vertex_2d(
+                            f64::from(vert.pos[0]) - 0.5,
+                            f64::from(vert.pos[1]) - 0.5,
+                            vert.uv[0].into(),
+                            vert.uv[1].into(),
+                            i32::from(vert.col[0]) | (i32::from(vert.col[1]) << 8) | (i32::from(vert.col[2]) << 16),
+                            f64::from(vert.col[3]) / 255.0,
+                        );
+                    }
+
+                    let clip_x = cmd_params.clip_rect[0] as i32;
+                    let clip_y = cmd_params.clip_rect[1] as i32;
+                    let clip_w = (cmd_params.clip_rect[2] - cmd_params.clip_rect[0]) as i32 + 1;
+                    let clip_h = (cmd_params.clip_rect[3] - cmd_params.clip_rect[1]) as i32 + 1;
+                    renderer.set_view(clip_x, clip_y, clip_w, clip_h, 0.0, clip_x, clip_y, clip_w, clip_h);
+                    renderer.draw_primitive_2d();
+                },
+                DrawCmd::RawCallback { callback, raw_cmd } => {
+                    unsafe { callback(draw_list.raw(), raw_cmd) };
+                },
+                DrawCmd::ResetRenderState => {},
+            }
+        }
+    }
+}
+
 // for imgui callback
 struct GameViewData {
     renderer: *mut Renderer,
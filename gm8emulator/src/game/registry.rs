@@ -0,0 +1,136 @@
+use crate::gml::Value;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::PathBuf,
+};
+
+/// The registry roots which registry_set_root can select, in the order of their GML ids.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Root {
+    CurrentUser,
+    LocalMachine,
+    Classes,
+    Users,
+}
+
+impl Root {
+    pub fn from_id(id: i32) -> Option<Self> {
+        match id {
+            0 => Some(Self::CurrentUser),
+            1 => Some(Self::LocalMachine),
+            2 => Some(Self::Classes),
+            3 => Some(Self::Users),
+            _ => None,
+        }
+    }
+}
+
+/// A stand-in for the parts of the Windows registry that games can reach through the registry_* functions.
+/// Key paths and value names are case-insensitive, like the real thing.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Registry {
+    keys: BTreeMap<(Root, String), BTreeMap<String, Value>>,
+
+    /// Root used by the _ext functions
+    pub root: Root,
+
+    /// File the registry gets written back to by save(), if any
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self { keys: BTreeMap::new(), root: Root::CurrentUser, path: None }
+    }
+}
+
+/// Key path for a game's own registry values, relative to HKEY_CURRENT_USER.
+pub fn game_key(game_id: i32) -> String {
+    format!("Software\\Game Maker\\GM{}", game_id)
+}
+
+fn normalise_key(key: &str) -> String {
+    key.split('\\').filter(|s| !s.is_empty()).collect::<Vec<_>>().join("\\").to_lowercase()
+}
+
+impl Registry {
+    /// Loads a registry from the given file, or starts a new one if it doesn't exist yet.
+    /// Either way, the registry will be written back to that file.
+    pub fn load(path: PathBuf) -> Self {
+        let mut registry = match File::open(&path) {
+            Ok(file) => match bincode::deserialize_from(BufReader::new(file)) {
+                Ok(registry) => registry,
+                Err(e) => {
+                    eprintln!("Warning: couldn't read registry file {:?}: {}", path, e);
+                    Self::default()
+                },
+            },
+            Err(_) => Self::default(),
+        };
+        registry.path = Some(path);
+        registry
+    }
+
+    /// Replaces everything in this registry with another one's contents, keeping the file it's saved to.
+    pub fn restore(&mut self, other: Self) {
+        let path = self.path.take();
+        *self = other;
+        self.path = path;
+        self.save();
+    }
+
+    /// Writes the registry back to its file, if it has one.
+    pub fn save(&self) {
+        if let Some(path) = &self.path {
+            let result = File::create(path)
+                .map_err(bincode::Error::from)
+                .and_then(|file| bincode::serialize_into(BufWriter::new(file), self));
+            if let Err(e) = result {
+                eprintln!("Warning: couldn't write registry file {:?}: {}", path, e);
+            }
+        }
+    }
+
+    pub fn get(&self, root: Root, key: &str, name: &str) -> Option<&Value> {
+        self.keys.get(&(root, normalise_key(key))).and_then(|values| values.get(&name.to_lowercase()))
+    }
+
+    pub fn set(&mut self, root: Root, key: &str, name: &str, value: Value) {
+        self.keys.entry((root, normalise_key(key))).or_default().insert(name.to_lowercase(), value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn case_insensitive() {
+        let mut registry = Registry::default();
+        registry.set(Root::CurrentUser, "Software\\Game Maker\\GM1", "Name", Value::from(1.0));
+        assert!(registry.get(Root::CurrentUser, "software\\game maker\\gm1\\", "NAME").is_some());
+        assert!(registry.get(Root::LocalMachine, "Software\\Game Maker\\GM1", "Name").is_none());
+    }
+
+    #[test]
+    fn saved_on_restore_only() {
+        let path = std::env::temp_dir().join(format!("gm8emulator-registry-test-{}.bin", std::process::id()));
+        std::fs::remove_file(&path).ok();
+        let mut registry = Registry::load(path.clone());
+        registry.set(Root::CurrentUser, "key", "name", Value::from(2.0));
+        assert!(!path.exists());
+
+        let mut other = Registry::default();
+        other.set(Root::Users, "key", "name", Value::from(3.0));
+        registry.restore(other);
+        assert_eq!(registry.path.as_ref(), Some(&path));
+        let loaded = Registry::load(path.clone());
+        std::fs::remove_file(&path).ok();
+        assert!(loaded.get(Root::CurrentUser, "key", "name").is_none());
+        assert!(matches!(loaded.get(Root::Users, "key", "name"), Some(Value::Real(x)) if *x == 3.into()));
+    }
+}
@@ -39,12 +39,17 @@ pub struct Frame {
 // Stored events for certain things which must always happen the same way during replay
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Event {
-    GetInteger(Value),   // value returned from get_integer()
-    GetString(Value),    // value returned from get_string()
-    Randomize(i32),      // value assigned to seed by randomize()
-    ShowMenu(Value),     // value returned from show_menu()
-    ShowMessage,         // acknowledges that a show_message() does not need to be shown during replay
-    ShowQuestion(Value), // value returned from show_question()
+    GetInteger(Value),    // value returned from get_integer()
+    GetString(Value),     // value returned from get_string()
+    Randomize(i32),       // value assigned to seed by randomize()
+    ShowMenu(Value),      // value returned from show_menu()
+    ShowMessage,          // acknowledges that a show_message() does not need to be shown during replay
+    ShowQuestion(Value),  // value returned from show_question()
+    ShowError(bool),      // whether the game was aborted from a runtime error dialog
+    HighscoreName(Value), // name entered for a new highscore by highscore_show()
+    ShowMessageExt(i32),  // button chosen in show_message_ext(), or 0 if it was closed
+    GetColor(i32),        // value returned from get_color()
+    GetFilename(Value),   // value returned from get_open_filename(), get_save_filename() or get_directory()
 }
 
 // An input event which takes place during a frame
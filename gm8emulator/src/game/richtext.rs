@@ -0,0 +1,768 @@
+use crate::{
+    game::{draw, Game, GetAsset},
+    gml,
+    math::Real,
+};
+use encoding_rs::WINDOWS_1252;
+use std::collections::HashMap;
+
+/// How a run of text looks. Sizes are in points, colours are GML colours, and fonts are indices into
+/// the document's font names.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Style {
+    pub font: Option<usize>,
+    pub size: f64,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub colour: i32,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self { font: None, size: 12.0, bold: false, italic: false, underline: false, colour: 0 }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Centre,
+    Right,
+}
+
+#[derive(Clone)]
+pub struct Run {
+    pub text: String,
+    pub style: Style,
+}
+
+#[derive(Clone)]
+pub struct Paragraph {
+    pub align: Align,
+    pub runs: Vec<Run>,
+}
+
+/// Formatted text, as read from the RTF and HTML that splash screens and the game information use.
+/// Only the formatting the engine can draw is kept. Fonts are drawn with a font resource of the same name and style
+/// if the game has one, and with the default font otherwise.
+#[derive(Clone, Default)]
+pub struct Document {
+    pub paragraphs: Vec<Paragraph>,
+    pub fonts: Vec<String>,
+    pub background: Option<i32>,
+}
+
+/// Collects text into paragraphs, merging runs that share a style.
+struct Builder {
+    document: Document,
+    align: Align,
+    runs: Vec<Run>,
+}
+
+impl Builder {
+    fn new() -> Self {
+        Self { document: Default::default(), align: Align::Left, runs: Vec::new() }
+    }
+
+    fn push(&mut self, text: &str, style: Style) {
+        match self.runs.last_mut() {
+            Some(run) if run.style == style => run.text.push_str(text),
+            _ => self.runs.push(Run { text: text.into(), style }),
+        }
+    }
+
+    fn font(&mut self, name: &str) -> usize {
+        let fonts = &mut self.document.fonts;
+        fonts.iter().position(|f| f.eq_ignore_ascii_case(name)).unwrap_or_else(|| {
+            fonts.push(name.into());
+            fonts.len() - 1
+        })
+    }
+
+    fn is_line_empty(&self) -> bool {
+        self.runs.iter().all(|r| r.text.is_empty())
+    }
+
+    fn ends_with_space(&self) -> bool {
+        self.runs.last().map_or(true, |r| r.text.ends_with(' '))
+    }
+
+    fn end_paragraph(&mut self) {
+        let runs = std::mem::take(&mut self.runs);
+        self.document.paragraphs.push(Paragraph { align: self.align, runs });
+    }
+
+    fn finish(mut self) -> Document {
+        if !self.runs.is_empty() {
+            self.end_paragraph();
+        }
+        self.document
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Destination {
+    Text,
+    FontTable,
+    ColourTable,
+    Skip,
+}
+
+#[derive(Clone)]
+struct RtfState {
+    style: Style,
+    destination: Destination,
+    unicode_skip: usize,
+}
+
+/// Groups in an RTF file which don't hold any text that gets shown.
+const RTF_SKIPPED: &[&str] = &[
+    "stylesheet",
+    "info",
+    "pict",
+    "header",
+    "headerl",
+    "headerr",
+    "headerf",
+    "footer",
+    "footerl",
+    "footerr",
+    "footerf",
+    "footnote",
+    "object",
+    "fldinst",
+    "generator",
+    "listtable",
+    "listoverridetable",
+    "rsidtbl",
+    "themedata",
+    "colorschememapping",
+    "latentstyles",
+    "datastore",
+    "xmlnstbl",
+    "mmathPr",
+    "pgdsctbl",
+];
+
+impl Document {
+    /// Makes a document out of plain text, one paragraph per line.
+    pub fn from_text(text: &str, style: Style) -> Self {
+        let mut builder = Builder::new();
+        for line in text.lines() {
+            builder.push(line, style);
+            builder.end_paragraph();
+        }
+        builder.finish()
+    }
+
+    /// Reads an RTF document. Files which don't start like RTF are read as plain text instead, like GM8 does.
+    pub fn from_rtf(data: &[u8]) -> Self {
+        if !data.starts_with(b"{\\rtf") {
+            return Self::from_text(&WINDOWS_1252.decode_without_bom_handling(data).0, Style::default())
+        }
+
+        let mut builder = Builder::new();
+        let mut state = RtfState { style: Default::default(), destination: Destination::Text, unicode_skip: 1 };
+        let mut stack = Vec::new();
+        let mut colours = Vec::new();
+        let mut colour = 0;
+        let mut fonts = HashMap::new();
+        let mut font_name = String::new();
+        let mut font_number = 0;
+        let mut default_font = 0;
+        let mut skip = 0;
+        let emit = |builder: &mut Builder, state: &RtfState, skip: &mut usize, font_name: &mut String, c: char| {
+            if *skip > 0 {
+                *skip -= 1;
+            } else {
+                match state.destination {
+                    Destination::Text => {
+                        let mut buf = [0; 4];
+                        builder.push(c.encode_utf8(&mut buf), state.style);
+                    },
+                    Destination::FontTable => font_name.push(c),
+                    _ => (),
+                }
+            }
+        };
+
+        let mut i = 0;
+        while let Some(&c) = data.get(i) {
+            i += 1;
+            match c {
+                b'{' => stack.push(state.clone()),
+                b'}' => {
+                    if let Some(s) = stack.pop() {
+                        state = s;
+                    }
+                },
+                b'\r' | b'\n' => (),
+                b';' if state.destination == Destination::ColourTable => {
+                    colours.push(colour);
+                    colour = 0;
+                },
+                b';' if state.destination == Destination::FontTable => {
+                    fonts.insert(font_number, builder.font(font_name.trim()));
+                    font_name.clear();
+                },
+                b'\\' => {
+                    let c = match data.get(i) {
+                        Some(&c) => c,
+                        None => break,
+                    };
+                    if !c.is_ascii_alphabetic() {
+                        i += 1;
+                        match c {
+                            b'\'' => {
+                                let byte = data
+                                    .get(i..i + 2)
+                                    .and_then(|h| u8::from_str_radix(std::str::from_utf8(h).ok()?, 16).ok());
+                                i += 2;
+                                if let Some(byte) = byte {
+                                    emit(&mut builder, &state, &mut skip, &mut font_name, decode_byte(byte));
+                                }
+                            },
+                            b'*' => state.destination = Destination::Skip,
+                            b'~' => emit(&mut builder, &state, &mut skip, &mut font_name, ' '),
+                            b'_' => emit(&mut builder, &state, &mut skip, &mut font_name, '-'),
+                            b'\r' | b'\n' if state.destination == Destination::Text => builder.end_paragraph(),
+                            b'{' | b'}' | b'\\' => emit(&mut builder, &state, &mut skip, &mut font_name, c.into()),
+                            _ => (),
+                        }
+                        continue
+                    }
+
+                    // Control word, with an optional numeric parameter and a space to end it
+                    let start = i;
+                    while data.get(i).is_some_and(u8::is_ascii_alphabetic) {
+                        i += 1;
+                    }
+                    let word = std::str::from_utf8(&data[start..i]).unwrap_or_default();
+                    let param_start = i;
+                    if data.get(i) == Some(&b'-') {
+                        i += 1;
+                    }
+                    while data.get(i).is_some_and(u8::is_ascii_digit) {
+                        i += 1;
+                    }
+                    let param = std::str::from_utf8(&data[param_start..i]).ok().and_then(|p| p.parse::<i32>().ok());
+                    if data.get(i) == Some(&b' ') {
+                        i += 1;
+                    }
+
+                    let text = state.destination == Destination::Text;
+                    match word {
+                        "par" | "line" if text => builder.end_paragraph(),
+                        "tab" => emit(&mut builder, &state, &mut skip, &mut font_name, '\t'),
+                        "pard" => builder.align = Align::Left,
+                        "ql" | "qj" => builder.align = Align::Left,
+                        "qc" => builder.align = Align::Centre,
+                        "qr" => builder.align = Align::Right,
+                        "plain" => state.style = Style { colour: state.style.colour, ..Default::default() },
+                        "b" => state.style.bold = param != Some(0),
+                        "i" => state.style.italic = param != Some(0),
+                        "ul" => state.style.underline = param != Some(0),
+                        "ulnone" => state.style.underline = false,
+                        "fs" => state.style.size = f64::from(param.unwrap_or(24)) / 2.0,
+                        "cf" => state.style.colour = colours.get(param.unwrap_or(0) as usize).copied().unwrap_or(0),
+                        "uc" => state.unicode_skip = param.unwrap_or(1).max(0) as usize,
+                        "u" => {
+                            let code = param.unwrap_or(0) as u16;
+                            emit(
+                                &mut builder,
+                                &state,
+                                &mut skip,
+                                &mut font_name,
+                                char::from_u32(code.into()).unwrap_or('?'),
+                            );
+                            skip = state.unicode_skip;
+                        },
+                        "emdash" => emit(&mut builder, &state, &mut skip, &mut font_name, '\u{2014}'),
+                        "endash" => emit(&mut builder, &state, &mut skip, &mut font_name, '\u{2013}'),
+                        "bullet" => emit(&mut builder, &state, &mut skip, &mut font_name, '\u{2022}'),
+                        "lquote" => emit(&mut builder, &state, &mut skip, &mut font_name, '\u{2018}'),
+                        "rquote" => emit(&mut builder, &state, &mut skip, &mut font_name, '\u{2019}'),
+                        "ldblquote" => emit(&mut builder, &state, &mut skip, &mut font_name, '\u{201c}'),
+                        "rdblquote" => emit(&mut builder, &state, &mut skip, &mut font_name, '\u{201d}'),
+                        "fonttbl" => state.destination = Destination::FontTable,
+                        "colortbl" => state.destination = Destination::ColourTable,
+                        "deff" => default_font = param.unwrap_or(0),
+                        "f" if state.destination == Destination::FontTable => {
+                            font_number = param.unwrap_or(0);
+                            font_name.clear();
+                        },
+                        "f" => state.style.font = fonts.get(&param.unwrap_or(0)).copied(),
+                        "red" => colour = (colour & !0xff) | param.unwrap_or(0) & 0xff,
+                        "green" => colour = (colour & !0xff00) | (param.unwrap_or(0) & 0xff) << 8,
+                        "blue" => colour = (colour & !0xff0000) | (param.unwrap_or(0) & 0xff) << 16,
+                        w if RTF_SKIPPED.contains(&w) => state.destination = Destination::Skip,
+                        _ => (),
+                    }
+                },
+                c => emit(&mut builder, &state, &mut skip, &mut font_name, decode_byte(c)),
+            }
+        }
+
+        // RTF editors always end the document with a \par, which doesn't make another line
+        let mut document = builder.finish();
+        if let Some(&font) = fonts.get(&default_font) {
+            for run in document.paragraphs.iter_mut().flat_map(|p| p.runs.iter_mut()) {
+                run.style.font = run.style.font.or(Some(font));
+            }
+        }
+        if document.paragraphs.last().is_some_and(|p| p.runs.iter().all(|r| r.text.is_empty())) {
+            document.paragraphs.pop();
+        }
+        document
+    }
+
+    /// Reads an HTML page. Only the basics of text formatting are understood, and everything else is left out.
+    pub fn from_html(html: &str) -> Self {
+        let mut builder = Builder::new();
+        let mut styles = vec![(String::new(), Style::default())];
+        let mut centred = 0;
+        let mut skip_until: Option<&str> = None;
+        let mut preformatted = false;
+
+        let mut rest = html;
+        while !rest.is_empty() {
+            if let Some(comment) = rest.strip_prefix("<!--") {
+                rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+                continue
+            }
+            if let Some(tag) = rest.strip_prefix('<') {
+                let end = tag.find('>').unwrap_or(tag.len());
+                let (name, attributes) = parse_tag(&tag[..end]);
+                rest = tag.get(end + 1..).unwrap_or("");
+                let closing = name.starts_with('/');
+                let name = name.trim_start_matches('/');
+
+                if let Some(until) = skip_until {
+                    if closing && name == until {
+                        skip_until = None;
+                    }
+                    continue
+                }
+                let style = styles.last().map(|s| s.1).unwrap_or_default();
+                let block = matches!(
+                    name,
+                    "p" | "div"
+                        | "h1"
+                        | "h2"
+                        | "h3"
+                        | "h4"
+                        | "h5"
+                        | "h6"
+                        | "li"
+                        | "ul"
+                        | "ol"
+                        | "table"
+                        | "tr"
+                        | "blockquote"
+                        | "center"
+                        | "hr"
+                        | "pre"
+                        | "title"
+                );
+                if name == "br" || (block && !builder.is_line_empty()) {
+                    builder.end_paragraph();
+                }
+                if closing {
+                    if let Some(pos) = styles.iter().rposition(|s| s.0 == name) {
+                        styles.truncate(pos.max(1));
+                    }
+                    match name {
+                        "center" => centred -= 1,
+                        "pre" => preformatted = false,
+                        _ => (),
+                    }
+                    if block {
+                        builder.align = if centred > 0 { Align::Centre } else { Align::Left };
+                    }
+                    continue
+                }
+
+                if block {
+                    builder.align = match attribute(&attributes, "align") {
+                        Some(a) if a.eq_ignore_ascii_case("center") => Align::Centre,
+                        Some(a) if a.eq_ignore_ascii_case("right") => Align::Right,
+                        _ if centred > 0 => Align::Centre,
+                        _ => Align::Left,
+                    };
+                }
+                let new_style = match name {
+                    "script" | "style" | "title" | "head" => {
+                        skip_until = Some(match name {
+                            "script" => "script",
+                            "style" => "style",
+                            "title" => "title",
+                            _ => "head",
+                        });
+                        None
+                    },
+                    "body" => {
+                        builder.document.background = attribute(&attributes, "bgcolor").and_then(parse_colour);
+                        let colour = attribute(&attributes, "text").and_then(parse_colour).unwrap_or(style.colour);
+                        styles[0].1.colour = colour;
+                        None
+                    },
+                    "center" => {
+                        centred += 1;
+                        builder.align = Align::Centre;
+                        None
+                    },
+                    "pre" => {
+                        preformatted = true;
+                        None
+                    },
+                    "li" => {
+                        builder.push("\u{2022} ", style);
+                        None
+                    },
+                    "b" | "strong" => Some(Style { bold: true, ..style }),
+                    "i" | "em" => Some(Style { italic: true, ..style }),
+                    "u" => Some(Style { underline: true, ..style }),
+                    "a" => Some(Style { underline: true, colour: 0xff0000, ..style }),
+                    "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                        let size = [24.0, 18.0, 14.0, 12.0, 10.0, 8.0][usize::from(name.as_bytes()[1] - b'1')];
+                        Some(Style { size, bold: true, ..style })
+                    },
+                    "font" => Some(Style {
+                        font: attribute(&attributes, "face").map(|f| builder.font(f)).or(style.font),
+                        size: attribute(&attributes, "size")
+                            .and_then(|s| s.parse::<usize>().ok())
+                            .and_then(|s| [8.0, 10.0, 12.0, 14.0, 18.0, 24.0, 36.0].get(s.max(1) - 1).copied())
+                            .unwrap_or(style.size),
+                        colour: attribute(&attributes, "color").and_then(parse_colour).unwrap_or(style.colour),
+                        ..style
+                    }),
+                    _ => None,
+                };
+                if let Some(new_style) = new_style {
+                    styles.push((name.into(), new_style));
+                }
+                continue
+            }
+
+            let end = rest.find('<').unwrap_or(rest.len());
+            let (text, next) = rest.split_at(end);
+            rest = next;
+            if skip_until.is_some() {
+                continue
+            }
+            let style = styles.last().map(|s| s.1).unwrap_or_default();
+            let text = decode_entities(text);
+            if preformatted {
+                let mut lines = text.split('\n');
+                if let Some(line) = lines.next() {
+                    builder.push(line, style);
+                }
+                for line in lines {
+                    builder.end_paragraph();
+                    builder.push(line, style);
+                }
+            } else {
+                // Collapse whitespace, and don't start lines with it
+                for (i, word) in text.split_whitespace().enumerate() {
+                    if (i > 0 || text.starts_with(char::is_whitespace)) && !builder.ends_with_space() {
+                        builder.push(" ", style);
+                    }
+                    builder.push(word, style);
+                }
+                if text.ends_with(char::is_whitespace) && !builder.ends_with_space() {
+                    builder.push(" ", style);
+                }
+            }
+        }
+        builder.finish()
+    }
+
+    /// The document's text without any formatting.
+    pub fn plain_text(&self) -> String {
+        let lines: Vec<String> =
+            self.paragraphs.iter().map(|p| p.runs.iter().map(|r| r.text.as_str()).collect()).collect();
+        lines.join("\n")
+    }
+}
+
+fn decode_byte(byte: u8) -> char {
+    WINDOWS_1252.decode_without_bom_handling(&[byte]).0.chars().next().unwrap_or('?')
+}
+
+/// Splits the inside of an HTML tag into its lowercase name and its attributes.
+fn parse_tag(tag: &str) -> (String, Vec<(String, String)>) {
+    let tag = tag.trim_end_matches('/').trim();
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let mut attributes = Vec::new();
+    let mut rest = tag[name_end..].trim_start();
+    while !rest.is_empty() {
+        let key_end = rest.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(rest.len());
+        let key = rest[..key_end].to_ascii_lowercase();
+        rest = rest[key_end..].trim_start();
+        let value = if let Some(value) = rest.strip_prefix('=') {
+            let value = value.trim_start();
+            let (value, next) = match value.chars().next() {
+                Some(q @ '"') | Some(q @ '\'') => {
+                    let end = value[1..].find(q).map_or(value.len(), |e| e + 1);
+                    (&value[1..end], value.get(end + 1..).unwrap_or(""))
+                },
+                _ => value.split_at(value.find(char::is_whitespace).unwrap_or(value.len())),
+            };
+            rest = next.trim_start();
+            decode_entities(value)
+        } else {
+            String::new()
+        };
+        attributes.push((key, value));
+    }
+    (tag[..name_end].to_ascii_lowercase(), attributes)
+}
+
+fn attribute<'a>(attributes: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    attributes.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+}
+
+/// Reads an HTML colour, either #rrggbb or one of the common names, as a GML colour.
+fn parse_colour(colour: &str) -> Option<i32> {
+    let rgb = match colour.to_ascii_lowercase().as_str() {
+        "black" => 0x000000,
+        "white" => 0xffffff,
+        "red" => 0xff0000,
+        "lime" => 0x00ff00,
+        "green" => 0x008000,
+        "blue" => 0x0000ff,
+        "yellow" => 0xffff00,
+        "aqua" | "cyan" => 0x00ffff,
+        "fuchsia" | "magenta" => 0xff00ff,
+        "gray" | "grey" => 0x808080,
+        "silver" => 0xc0c0c0,
+        "maroon" => 0x800000,
+        "navy" => 0x000080,
+        "olive" => 0x808000,
+        "purple" => 0x800080,
+        "teal" => 0x008080,
+        "orange" => 0xffa500,
+        c => i32::from_str_radix(c.strip_prefix('#').unwrap_or(c), 16).ok()?,
+    };
+    Some((rgb & 0xff) << 16 | rgb & 0xff00 | (rgb >> 16) & 0xff)
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find(';') {
+            Some(end) if end <= 10 => end,
+            _ => {
+                out.push('&');
+                rest = &rest[1..];
+                continue
+            },
+        };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            "copy" => Some('\u{a9}'),
+            e => e
+                .strip_prefix('#')
+                .and_then(|n| match n.strip_prefix('x').or_else(|| n.strip_prefix('X')) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => n.parse().ok(),
+                })
+                .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            },
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+struct Piece {
+    x: i32,
+    width: i32,
+    text: gml::String,
+    style: Style,
+    font: i32,
+    scale: f64,
+}
+
+struct Line {
+    y: i32,
+    height: i32,
+    pieces: Vec<Piece>,
+}
+
+/// A document broken into lines to fit a certain width.
+pub struct Layout {
+    lines: Vec<Line>,
+    pub height: i32,
+}
+
+impl Game {
+    /// Finds the font to draw some text in, as a font id and a scale.
+    fn document_font(&self, document: &Document, style: &Style) -> (i32, f64) {
+        let name = match style.font.and_then(|f| document.fonts.get(f)) {
+            Some(name) => name.as_bytes(),
+            None => return (-1, style.size / 12.0),
+        };
+        let mut best: Option<(usize, f64)> = None;
+        for (id, font) in self.assets.fonts.iter().enumerate() {
+            if let Some(font) = font {
+                if font.sys_name.as_ref().eq_ignore_ascii_case(name)
+                    && font.bold == style.bold
+                    && font.italic == style.italic
+                    && best
+                        .map_or(true, |(_, size)| (size - style.size).abs() > (f64::from(font.size) - style.size).abs())
+                {
+                    best = Some((id, font.size.into()));
+                }
+            }
+        }
+        match best {
+            Some((id, size)) => (id as i32, style.size / size.max(1.0)),
+            None => (-1, style.size / 12.0),
+        }
+    }
+
+    /// Breaks a document into lines no wider than `width`.
+    pub fn layout_document(&mut self, document: &Document, width: i32) -> Layout {
+        let font_id = self.draw_font_id;
+        let base_height = self.default_font.tallest_char_height as f64;
+        let mut lines = Vec::new();
+        let mut y = 0;
+
+        for paragraph in &document.paragraphs {
+            let mut line = Line { y, height: 0, pieces: Vec::new() };
+            let mut x = 0;
+            let finish_line = |line: &mut Line, x: i32, y: &mut i32, lines: &mut Vec<Line>| {
+                let shift = match paragraph.align {
+                    Align::Left => 0,
+                    Align::Centre => (width - x) / 2,
+                    Align::Right => width - x,
+                };
+                for piece in &mut line.pieces {
+                    piece.x += shift.max(0);
+                }
+                if line.height == 0 {
+                    let size = paragraph.runs.first().map_or(12.0, |r| r.style.size);
+                    line.height = (base_height * size / 12.0).round() as i32;
+                }
+                *y += line.height;
+                let next = Line { y: *y, height: 0, pieces: Vec::new() };
+                lines.push(std::mem::replace(line, next));
+            };
+
+            for run in &paragraph.runs {
+                let (font, scale) = self.document_font(document, &run.style);
+                self.draw_font_id = font;
+                let fake_bold = run.style.bold && font < 0;
+                let height = match self.assets.fonts.get_asset(font) {
+                    Some(font) => (f64::from(font.tallest_char_height) * scale).round() as i32,
+                    None => (base_height * scale).round() as i32,
+                };
+                let text = run.text.replace('\t', "    ");
+                // Split into words, each with the whitespace that follows it
+                let mut words = Vec::new();
+                let mut start = 0;
+                let mut in_space = false;
+                for (i, c) in text.char_indices() {
+                    if in_space && !c.is_whitespace() {
+                        words.push(&text[start..i]);
+                        start = i;
+                    }
+                    in_space = c.is_whitespace();
+                }
+                words.push(&text[start..]);
+
+                for word in words.into_iter().filter(|w| !w.is_empty()) {
+                    let measure = |game: &Self, s: &str| {
+                        let (w, _) = game.get_string_size(game.encode_for_drawing(s), Some(0), None);
+                        (f64::from(w) * scale).round() as i32 + i32::from(fake_bold)
+                    };
+                    let full_width = measure(self, word);
+                    let trimmed = word.trim_end();
+                    let visible_width = if trimmed.len() == word.len() { full_width } else { measure(self, trimmed) };
+                    if x > 0 && x + visible_width > width {
+                        finish_line(&mut line, x, &mut y, &mut lines);
+                        x = 0;
+                    }
+                    line.height = line.height.max(height);
+                    line.pieces.push(Piece {
+                        x,
+                        width: visible_width,
+                        text: self.encode_for_drawing(word),
+                        style: run.style,
+                        font,
+                        scale,
+                    });
+                    x += full_width;
+                }
+            }
+            finish_line(&mut line, x, &mut y, &mut lines);
+        }
+
+        self.draw_font_id = font_id;
+        Layout { lines, height: y }
+    }
+
+    /// Draws a laid out document at the given position. Only lines between `top` and `bottom` get drawn,
+    /// so it can be scrolled.
+    pub fn draw_document(&mut self, layout: &Layout, x: i32, y: i32, top: i32, bottom: i32) {
+        let font_state = (self.draw_font_id, self.draw_halign, self.draw_valign);
+        self.draw_halign = draw::Halign::Left;
+        self.draw_valign = draw::Valign::Bottom;
+        for line in layout.lines.iter().filter(|l| y + l.y >= top && y + l.y + l.height <= bottom) {
+            let line_bottom = y + line.y + line.height;
+            for piece in &line.pieces {
+                let scale = Real::from(piece.scale);
+                let colour = piece.style.colour;
+                self.draw_font_id = piece.font;
+                for offset in 0..=i32::from(piece.style.bold && piece.font < 0) {
+                    self.draw_string(
+                        Real::from(x + piece.x + offset),
+                        Real::from(line_bottom),
+                        piece.text.clone(),
+                        None,
+                        None,
+                        scale,
+                        scale,
+                        Real::from(0.0),
+                        Some((colour, colour, colour, colour)),
+                        Real::from(1.0),
+                    );
+                }
+                if piece.style.underline {
+                    let (x1, x2) = (f64::from(x + piece.x), f64::from(x + piece.x + piece.width));
+                    let y = f64::from(line_bottom) - 1.0;
+                    self.renderer.draw_rectangle(x1, y, x2, y + 1.0, colour, 1.0);
+                }
+            }
+        }
+        (self.draw_font_id, self.draw_halign, self.draw_valign) = font_state;
+    }
+
+    /// Encodes text the way draw_string expects it for this game.
+    pub fn encode_for_drawing(&self, text: &str) -> gml::String {
+        match self.encode_str_maybe(text) {
+            Some(bytes) => bytes.into_owned().into(),
+            None => text.into(),
+        }
+    }
+}
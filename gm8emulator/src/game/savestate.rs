@@ -1,8 +1,9 @@
 use crate::{
+    asset,
     game::{
-        audio::AudioState, draw, external, includedfile::IncludedFile, model::Model, particle,
-        pathfinding::PotentialStepSettings, surface::Surface, transition::UserTransition, Assets,
-        Game, GameClock, Replay, RoomState, Version,
+        audio::AudioState, draw, external, highscore::Highscores, includedfile::IncludedFile, mci, message,
+        model::Model, particle, pathfinding::PotentialStepSettings, registry::Registry, splash, surface::Surface,
+        transition::UserTransition, Assets, Game, GameClock, GetAsset, Replay, RoomState, Version,
     },
     gml::{self, ds, rand::Random, Compiler},
     handleman::HandleList,
@@ -100,6 +101,12 @@ pub struct SaveState {
     pub health_capt_d: bool,
     pub error_occurred: bool,
     pub error_last: gml::String,
+    pub registry: Registry,
+    pub highscores: Highscores,
+    pub message_style: message::Style,
+    pub mci: mci::State,
+    pub splash: splash::Settings,
+    pub game_info: splash::GameInfo,
 
     pub game_id: i32,
     pub program_directory: gml::String,
@@ -188,6 +195,12 @@ impl SaveState {
             health_capt_d: game.health_capt_d.clone(),
             error_occurred: game.error_occurred,
             error_last: game.error_last.clone(),
+            registry: game.registry.clone(),
+            highscores: game.highscores.clone(),
+            message_style: game.message_style.clone(),
+            mci: game.mci.clone(),
+            splash: game.splash.clone(),
+            game_info: game.game_info.clone(),
             game_id: game.game_id.clone(),
             program_directory: game.program_directory.clone(),
             included_files: game.included_files.clone(),
@@ -277,12 +290,27 @@ impl SaveState {
         game.health_capt_d = self.health_capt_d;
         game.error_occurred = self.error_occurred;
         game.error_last = self.error_last;
+        game.registry.restore(self.registry);
+        game.highscores.restore(self.highscores);
+        game.message_style = self.message_style;
+        game.mci = self.mci;
+        game.splash = self.splash;
+        game.game_info = self.game_info;
+        game.splash_overlay = None;
         game.game_id = self.game_id;
         game.program_directory = self.program_directory;
         game.included_files = self.included_files;
         game.gm_version = self.gm_version;
         game.clock = self.clock;
-        game.audio.set_state(self.audio_state);
+        game.audio.set_state(self.audio_state, game.clock.as_nanos());
+        if let Some(asset::sound::FileType::Midi(handle)) =
+            game.audio.midi_id().and_then(|id| game.assets.sounds.get_asset(id)).map(|sound| &sound.handle)
+        {
+            game.audio.resume_midi(handle, game.clock.as_nanos());
+        }
+        game.externals.gmfmod.resume(&mut game.audio, game.clock.as_nanos());
+        game.externals.supersound.resume(&mut game.audio, game.clock.as_nanos());
+        game.mci.resume(&mut game.audio, game.clock.as_nanos());
         game.scaling = self.scaling;
         game.unscaled_width = self.unscaled_width;
         game.unscaled_height = self.unscaled_height;
@@ -0,0 +1,382 @@
+use crate::{
+    game::{
+        dialog, draw,
+        richtext::{Document, Layout},
+        Game, PlayType,
+    },
+    gml::{self, file},
+    input,
+    render::atlas::AtlasRef,
+};
+use gm8exe::settings::GameHelpDialog;
+use ramen::{
+    event::Event,
+    input::{Key, MouseButton},
+};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Options set by the splash_set_* functions. Splash screens are always drawn in the game window, so the ones for
+/// a separate window just place a box over the game instead.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Settings {
+    pub caption: gml::String,
+    pub fullscreen: bool,
+    pub border: bool,
+    pub size: (i32, i32),
+    pub position: (i32, i32),
+    pub adapt: bool,
+    pub top: bool,
+    pub colour: i32,
+    pub main: bool,
+    pub scale: i32,
+    pub cursor: bool,
+    pub interrupt: bool,
+    pub stop_key: bool,
+    pub close_button: bool,
+    pub stop_mouse: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            caption: "".into(),
+            fullscreen: false,
+            border: true,
+            size: (640, 480),
+            position: (-1, -1),
+            adapt: true,
+            top: true,
+            colour: 0,
+            main: true,
+            scale: 0,
+            cursor: true,
+            interrupt: true,
+            stop_key: true,
+            close_button: true,
+            stop_mouse: true,
+        }
+    }
+}
+
+/// The game information, as made in the game's editor or loaded with load_info.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GameInfo {
+    /// RTF text to show
+    pub text: gml::String,
+    pub caption: gml::String,
+    pub colour: i32,
+    pub new_window: bool,
+    pub position: (i32, i32),
+    pub size: (i32, i32),
+    pub border: bool,
+    pub freeze: bool,
+}
+
+impl From<GameHelpDialog> for GameInfo {
+    fn from(dialog: GameHelpDialog) -> Self {
+        Self {
+            text: dialog.info.into(),
+            caption: dialog.caption.into(),
+            colour: dialog.bg_colour.as_decimal() as i32 & 0xffffff,
+            new_window: dialog.new_window,
+            position: (dialog.left, dialog.top),
+            size: (dialog.width as i32, dialog.height as i32),
+            border: dialog.border,
+            freeze: dialog.freeze_game,
+        }
+    }
+}
+
+pub enum Content {
+    Text(Document),
+    Image { atlas_ref: AtlasRef, width: u32, height: u32 },
+}
+
+pub struct Splash {
+    content: Content,
+    settings: Settings,
+    layout: Option<(i32, Layout)>,
+    scroll: i32,
+}
+
+/// A splash screen drawn over the game while it keeps running, for when splash_set_interrupt is turned off.
+pub struct Overlay {
+    splash: Splash,
+    frames_left: Option<u32>,
+}
+
+const CLOSE_SIZE: i32 = 16;
+const CAPTION_HEIGHT: i32 = 20;
+const TEXT_PADDING: i32 = 8;
+const SCROLL_STEP: i32 = 20;
+
+impl Splash {
+    /// Where the splash screen goes in a window of the given size, as x, y, width and height.
+    fn area(&self, width: i32, height: i32) -> (i32, i32, i32, i32) {
+        if self.settings.main || self.settings.fullscreen {
+            return (0, 0, width, height)
+        }
+        let (w, h) = match self.content {
+            Content::Image { width, height, .. } if self.settings.adapt => (width as i32, height as i32),
+            _ => self.settings.size,
+        };
+        let border = if self.settings.border { CAPTION_HEIGHT } else { 0 };
+        let (w, h) = (w.min(width), (h + border).min(height));
+        let (x, y) = match self.settings.position {
+            (x, y) if x >= 0 && y >= 0 => (x.min(width - w), y.min(height - h)),
+            _ => ((width - w) / 2, (height - h) / 2),
+        };
+        (x, y, w, h)
+    }
+
+    fn close_button(&self, width: i32, height: i32) -> Option<(i32, i32)> {
+        let (x, y, w, _) = self.area(width, height);
+        if self.settings.close_button { Some((x + w - CLOSE_SIZE - 2, y + 2)) } else { None }
+    }
+
+    fn scroll_by(&mut self, amount: i32) {
+        self.scroll += amount;
+    }
+}
+
+fn contains((x, y): (i32, i32), (x1, y1, w, h): (i32, i32, i32, i32)) -> bool {
+    x >= x1 && y >= y1 && x < x1 + w && y < y1 + h
+}
+
+impl Game {
+    /// Shows a splash screen with the given settings. `delay` is how long to show it for in milliseconds,
+    /// or 0 to wait for the player.
+    ///
+    /// A splash screen never changes anything the game can see, so how it's dismissed doesn't matter to replays.
+    /// While recording, only the text of one is shown, and during replays they're skipped entirely.
+    pub fn show_splash(&mut self, content: Content, settings: Settings, delay: i32) {
+        let mut splash = Splash { content, settings, layout: None, scroll: 0 };
+        if !splash.settings.interrupt {
+            let frames_left = if delay > 0 {
+                Some(((i64::from(delay) * i64::from(self.room.speed) + 999) / 1000).max(1) as u32)
+            } else {
+                None
+            };
+            if let Some(old) = self.splash_overlay.replace(Overlay { splash, frames_left }) {
+                self.free_splash(old.splash);
+            }
+            return
+        }
+
+        match self.play_type {
+            PlayType::Normal => {
+                let start = Instant::now();
+                let mut mouse = self.modal_mouse((self.input.mouse_x(), self.input.mouse_y()));
+                self.run_modal(|game, events, (width, height)| {
+                    let area = splash.area(width, height);
+                    let close = splash.close_button(width, height);
+                    let mut done = delay > 0 && start.elapsed() >= Duration::from_millis(delay as u64);
+                    for event in events.iter().copied() {
+                        match event {
+                            Event::KeyboardDown(Key::Escape)
+                            | Event::KeyboardDown(Key::Space)
+                            | Event::KeyboardDown(Key::Return)
+                                if splash.settings.stop_key =>
+                            {
+                                done = true
+                            },
+                            Event::KeyboardDown(Key::DownArrow) | Event::ScrollDown => splash.scroll_by(SCROLL_STEP),
+                            Event::KeyboardDown(Key::UpArrow) | Event::ScrollUp => splash.scroll_by(-SCROLL_STEP),
+                            Event::MouseMove((x, y)) => mouse = game.modal_mouse((x.into(), y.into())),
+                            Event::MouseDown(MouseButton::Left)
+                                if close.is_some_and(|(x, y)| contains(mouse, (x, y, CLOSE_SIZE, CLOSE_SIZE))) =>
+                            {
+                                done = true
+                            },
+                            Event::MouseDown(MouseButton::Left) | Event::MouseDown(MouseButton::Right)
+                                if splash.settings.stop_mouse && contains(mouse, area) =>
+                            {
+                                done = true
+                            },
+                            Event::CloseRequest => done = true,
+                            _ => (),
+                        }
+                    }
+                    if done {
+                        return Some(())
+                    }
+                    game.draw_splash(&mut splash, width, height);
+                    None
+                });
+                self.free_splash(splash);
+            },
+            PlayType::Record => {
+                let text = match &splash.content {
+                    Content::Text(document) => document.plain_text(),
+                    Content::Image { .. } => "(splash screen image)".into(),
+                };
+                self.show_dialog(&text, &["Continue"]);
+                self.free_splash(splash);
+            },
+            PlayType::Replay => self.free_splash(splash),
+        }
+    }
+
+    /// Shows the game information, which is a text splash screen laid out by the game's settings.
+    pub fn show_game_info(&mut self) {
+        let info = &self.game_info;
+        let mut document = Document::from_rtf(info.text.as_ref());
+        document.background = Some(info.colour);
+        let settings = Settings {
+            caption: if info.caption.as_ref().is_empty() { "Game Information".into() } else { info.caption.clone() },
+            main: !info.new_window,
+            border: info.border,
+            size: info.size,
+            position: info.position,
+            adapt: false,
+            interrupt: !info.new_window || info.freeze,
+            stop_mouse: false,
+            close_button: info.new_window,
+            ..Default::default()
+        };
+        self.show_splash(Content::Text(document), settings, 0);
+    }
+
+    /// Loads a text splash screen from an RTF or plain text file.
+    pub fn load_splash_text(&self, path: &str) -> Option<Content> {
+        match std::fs::read(file::to_path(path).as_ref()) {
+            Ok(data) => Some(Content::Text(Document::from_rtf(&data))),
+            Err(e) => {
+                eprintln!("Warning: couldn't load splash text {}: {}", path, e);
+                None
+            },
+        }
+    }
+
+    /// Loads an image splash screen, uploading it to the renderer until the splash screen is done.
+    pub fn load_splash_image(&mut self, path: &str) -> Option<Content> {
+        let image = match file::load_image(file::to_path(path).as_ref()) {
+            Ok(image) => image,
+            Err(e) => {
+                eprintln!("Warning: couldn't load splash image {}: {}", path, e);
+                return None
+            },
+        };
+        let (width, height) = image.dimensions();
+        match self.renderer.upload_sprite(image.into_raw().into_boxed_slice(), width as _, height as _, 0, 0) {
+            Ok(atlas_ref) => Some(Content::Image { atlas_ref, width, height }),
+            Err(e) => {
+                eprintln!("Warning: couldn't upload splash image {}: {}", path, e);
+                None
+            },
+        }
+    }
+
+    /// Loads a web page splash screen. Only local files can be shown, so other URLs just show the address.
+    pub fn load_splash_web(&self, url: &str) -> Option<Content> {
+        let path = url.strip_prefix("file:///").or_else(|| url.strip_prefix("file://")).unwrap_or(url);
+        if path.contains("://") {
+            eprintln!("Warning: can't show web page {} in a splash screen, only local files are supported", url);
+            return Some(Content::Text(Document::from_text(url, Default::default())))
+        }
+        match std::fs::read(file::to_path(path).as_ref()) {
+            Ok(data) => Some(Content::Text(Document::from_html(&String::from_utf8_lossy(&data)))),
+            Err(e) => {
+                eprintln!("Warning: couldn't load splash web page {}: {}", url, e);
+                None
+            },
+        }
+    }
+
+    /// Draws and updates the splash screen left running by a non-interrupting splash screen, if there is one.
+    /// It goes away after its delay or when the player presses a key or clicks, as its settings say.
+    pub fn draw_splash_overlay(&mut self) {
+        let mut overlay = match self.splash_overlay.take() {
+            Some(overlay) => overlay,
+            None => return,
+        };
+        let settings = &overlay.splash.settings;
+        let key_stop = [input::Button::Escape, input::Button::Space, input::Button::Return]
+            .iter()
+            .any(|&b| self.input.keyboard_check_pressed(b as u8));
+        let mouse_stop = [input::MouseButton::Left, input::MouseButton::Right]
+            .iter()
+            .any(|&b| self.input.mouse_check_button_pressed(b as i8));
+        if overlay.frames_left == Some(0) || (settings.stop_key && key_stop) || (settings.stop_mouse && mouse_stop) {
+            self.free_splash(overlay.splash);
+            return
+        }
+        if let Some(frames) = overlay.frames_left.as_mut() {
+            *frames -= 1;
+        }
+
+        let game_state = self.renderer.state();
+        self.renderer.set_state(&dialog::plain_state(&game_state));
+        self.draw_splash(&mut overlay.splash, self.unscaled_width as i32, self.unscaled_height as i32);
+        self.renderer.set_state(&game_state);
+        self.splash_overlay = Some(overlay);
+    }
+
+    fn draw_splash(&mut self, splash: &mut Splash, width: i32, height: i32) {
+        let (x, mut y, w, mut h) = splash.area(width, height);
+        let windowed = !(splash.settings.main || splash.settings.fullscreen);
+        if windowed && splash.settings.border {
+            self.draw_panel(x, y, x + w, y + CAPTION_HEIGHT, 0xd1b499, 0x808080);
+            let caption = self.decode_str(splash.settings.caption.as_ref()).into_owned();
+            self.draw_label(x + 4, y + 3, &caption, None, 0);
+            y += CAPTION_HEIGHT;
+            h -= CAPTION_HEIGHT;
+        }
+
+        match &splash.content {
+            Content::Image { atlas_ref, width: image_w, height: image_h } => {
+                self.renderer.draw_rectangle(
+                    x.into(),
+                    y.into(),
+                    (x + w).into(),
+                    (y + h).into(),
+                    splash.settings.colour,
+                    1.0,
+                );
+                let scale = (f64::from(w) / f64::from(*image_w)).min(f64::from(h) / f64::from(*image_h)).min(1.0);
+                let image_x = f64::from(x) + (f64::from(w) - f64::from(*image_w) * scale) / 2.0;
+                let image_y = f64::from(y) + (f64::from(h) - f64::from(*image_h) * scale) / 2.0;
+                self.renderer.draw_sprite(*atlas_ref, image_x, image_y, scale, scale, 0.0, 0xffffff, 1.0);
+            },
+            Content::Text(document) => {
+                let background = document.background.unwrap_or(0xffffff);
+                self.renderer.draw_rectangle(x.into(), y.into(), (x + w).into(), (y + h).into(), background, 1.0);
+                let text_w = w - TEXT_PADDING * 2;
+                if splash.layout.as_ref().map_or(true, |(lw, _)| *lw != text_w) {
+                    splash.layout = Some((text_w, self.layout_document(document, text_w)));
+                }
+                if let Some((_, layout)) = &splash.layout {
+                    splash.scroll = splash.scroll.min(layout.height - h + TEXT_PADDING * 2).max(0);
+                    let top = y + TEXT_PADDING;
+                    self.draw_document(layout, x + TEXT_PADDING, top - splash.scroll, top, y + h - TEXT_PADDING);
+                }
+            },
+        }
+        if windowed && splash.settings.border {
+            self.renderer.draw_rectangle_outline(
+                x.into(),
+                (y - CAPTION_HEIGHT).into(),
+                (x + w).into(),
+                (y + h).into(),
+                0x808080,
+                1.0,
+            );
+        }
+
+        if let Some((close_x, close_y)) = splash.close_button(width, height) {
+            self.draw_panel(close_x, close_y, close_x + CLOSE_SIZE, close_y + CLOSE_SIZE, 0xe1e1e1, 0x808080);
+            let font_state = (self.draw_halign, self.draw_valign);
+            self.draw_halign = draw::Halign::Middle;
+            self.draw_valign = draw::Valign::Middle;
+            self.draw_label(close_x + CLOSE_SIZE / 2, close_y + CLOSE_SIZE / 2, "X", None, 0);
+            (self.draw_halign, self.draw_valign) = font_state;
+        }
+    }
+
+    fn free_splash(&mut self, splash: Splash) {
+        if let Content::Image { atlas_ref, .. } = splash.content {
+            self.renderer.delete_sprite(atlas_ref);
+        }
+    }
+}
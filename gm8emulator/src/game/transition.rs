@@ -1,6 +1,6 @@
 use crate::{
     game::{Game, GetAsset},
-    gml::{self, Context},
+    gml::{self, debugger::CodeSource, Context},
     instance::Instance,
     math::Real,
 };
@@ -244,17 +244,17 @@ impl Game {
         if (transition_id > 0 && transition_id < 22) || self.user_transitions.contains_key(&transition_id) {
             Some(Box::new(move |game: &mut Game, surf_old, surf_new, width, height, progress| {
                 if let Some(transition) = game.user_transitions.get(&transition_id) {
-                    if let Some(Some(script)) = game
+                    if let Some((script_id, Some(script))) = game
                         .compiler
                         .get_script_id(transition.script_name.as_ref())
-                        .and_then(|id| game.assets.scripts.get(id))
+                        .and_then(|id| game.assets.scripts.get(id).map(|s| (id, s)))
                     {
                         let instructions = script.compiled.clone();
                         let dummy_instance = game
                             .room
                             .instance_list
                             .insert_dummy(Instance::new_dummy(game.assets.objects.get_asset(0).map(|x| x.as_ref())));
-                        game.execute(&instructions, &mut Context {
+                        game.execute_frame(CodeSource::Script(script_id), &instructions, &mut Context {
                             this: dummy_instance,
                             other: dummy_instance,
                             arguments: [
@@ -0,0 +1,216 @@
+use crate::{game::Game, gml, render::Scaling};
+use image::{imageops, RgbaImage};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// What size the frames are captured at.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Size {
+    /// The window's size, scaled the same way it's shown on screen.
+    Window,
+    /// The size the game draws at, before any scaling.
+    Native,
+}
+
+enum Output {
+    /// A raw YUV4MPEG2 stream, which is opened when the first frame comes in.
+    Y4m(Option<BufWriter<File>>),
+    /// A numbered PNG file for each frame.
+    Png,
+}
+
+/// A frame waiting to be written. It's held back in case something else gets presented at the same time.
+struct Frame {
+    time: u128,
+    image: RgbaImage,
+    frame_rate: u32,
+}
+
+/// Captures every frame the game presents, along with a timestamps file in mkvmerge's v2 format (milliseconds
+/// from the first frame). Everything goes by the game clock, so replays give the same output every time.
+pub struct VideoDump {
+    path: PathBuf,
+    output: Output,
+    size: Size,
+    timestamps: BufWriter<File>,
+    dimensions: Option<(u32, u32)>,
+    start_time: Option<u128>,
+    frames_written: usize,
+    pending: Option<Frame>,
+}
+
+impl VideoDump {
+    /// Starts a dump to the given file, which should end in .y4m or .png. For PNGs, the frame number is added
+    /// to the end of the file name.
+    pub fn new(path: &Path, size: Size) -> io::Result<Self> {
+        let output = match path.extension().and_then(|x| x.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("y4m") => Output::Y4m(None),
+            Some(ext) if ext.eq_ignore_ascii_case("png") => Output::Png,
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "video dump must be a .y4m or .png file")),
+        };
+        let mut timestamps = BufWriter::new(File::create(Self::sibling(path, "_timestamps.txt"))?);
+        writeln!(timestamps, "# timestamp format v2")?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            output,
+            size,
+            timestamps,
+            dimensions: None,
+            start_time: None,
+            frames_written: 0,
+            pending: None,
+        })
+    }
+
+    /// The path of another file that goes along with the dump, with the given suffix instead of the extension.
+    pub fn sibling(path: &Path, suffix: &str) -> PathBuf {
+        let mut name = path.file_stem().unwrap_or_default().to_os_string();
+        name.push(suffix);
+        path.with_file_name(name)
+    }
+
+    /// Takes a presented frame. Frames that get presented at the same point on the clock replace each other.
+    fn capture(&mut self, time: u128, image: RgbaImage, frame_rate: u32) -> io::Result<()> {
+        if let Some(frame) = self.pending.take() {
+            if frame.time < time {
+                self.write(frame)?;
+            }
+        }
+        self.pending = Some(Frame { time, image, frame_rate });
+        Ok(())
+    }
+
+    fn write(&mut self, frame: Frame) -> io::Result<()> {
+        // The first frame decides the size of the video, and anything else is stretched to fit
+        let (width, height) = *self.dimensions.get_or_insert(frame.image.dimensions());
+        let image = if frame.image.dimensions() == (width, height) {
+            frame.image
+        } else {
+            imageops::resize(&frame.image, width, height, imageops::FilterType::Nearest)
+        };
+
+        match &mut self.output {
+            Output::Y4m(writer) => {
+                let writer = match writer {
+                    Some(writer) => writer,
+                    None => {
+                        let mut file = BufWriter::new(File::create(&self.path)?);
+                        // The frame rate's only a hint, as the timestamps have the real times
+                        writeln!(file, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444", width, height, frame.frame_rate)?;
+                        writer.insert(file)
+                    },
+                };
+                writer.write_all(b"FRAME\n")?;
+                writer.write_all(&rgba_to_yuv444(&image))?;
+            },
+            Output::Png => {
+                let path = Self::sibling(&self.path, &format!("_{:06}.png", self.frames_written));
+                image.save_with_format(path, image::ImageFormat::Png).map_err(|e| match e {
+                    image::ImageError::IoError(e) => e,
+                    e => io::Error::other(e),
+                })?;
+            },
+        }
+
+        let start_time = *self.start_time.get_or_insert(frame.time);
+        writeln!(self.timestamps, "{:.3}", frame.time.saturating_sub(start_time) as f64 / 1_000_000.0)?;
+        self.frames_written += 1;
+        Ok(())
+    }
+
+    /// Writes out the last frame and flushes everything. The last frame is lost if this isn't called.
+    pub fn finish(mut self) -> io::Result<()> {
+        if let Some(frame) = self.pending.take() {
+            self.write(frame)?;
+        }
+        if let Output::Y4m(Some(writer)) = &mut self.output {
+            writer.flush()?;
+        }
+        self.timestamps.flush()
+    }
+}
+
+/// Converts to planar 4:4:4 YUV using BT.601's limited range, which is what ffmpeg assumes for Y4M.
+fn rgba_to_yuv444(image: &RgbaImage) -> Vec<u8> {
+    let len = image.pixels().len();
+    let mut yuv = vec![0u8; len * 3];
+    let (y_plane, chroma) = yuv.split_at_mut(len);
+    let (u_plane, v_plane) = chroma.split_at_mut(len);
+    for (i, pixel) in image.pixels().enumerate() {
+        let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(f64::from);
+        y_plane[i] = (16.0 + (65.481 * r + 128.553 * g + 24.966 * b) / 255.0).round() as u8;
+        u_plane[i] = (128.0 + (-37.797 * r - 74.203 * g + 112.0 * b) / 255.0).round() as u8;
+        v_plane[i] = (128.0 + (112.0 * r - 93.786 * g - 18.214 * b) / 255.0).round() as u8;
+    }
+    yuv
+}
+
+/// Scales a frame into a window the same way the renderer does when it presents, with black around the edges.
+fn fit_to_window(
+    image: &RgbaImage,
+    window_width: u32,
+    window_height: u32,
+    scaling: Scaling,
+    smooth: bool,
+) -> RgbaImage {
+    let fb_size = (image.width() as i32, image.height() as i32);
+    let (x, y, w, h) = scaling.viewport(fb_size, (window_width as i32, window_height as i32));
+    let mut window = RgbaImage::from_pixel(window_width, window_height, image::Rgba([0, 0, 0, 255]));
+    if w > 0 && h > 0 {
+        let filter = if smooth { imageops::FilterType::Triangle } else { imageops::FilterType::Nearest };
+        let scaled = imageops::resize(image, w as u32, h as u32, filter);
+        // A fixed scale can make it bigger than the window, in which case it's cut off on every side
+        let (crop_x, crop_y) = ((-x).max(0) as u32, (-y).max(0) as u32);
+        let visible = imageops::crop_imm(&scaled, crop_x, crop_y, w as u32 - crop_x, h as u32 - crop_y);
+        imageops::overlay(&mut window, &visible, x.max(0) as u32, y.max(0) as u32);
+    }
+    window
+}
+
+impl Game {
+    /// Shows what's been drawn in the window, and captures it if a video's being dumped.
+    pub fn present(&mut self, window_width: u32, window_height: u32) -> gml::Result<()> {
+        self.renderer.present(window_width, window_height, self.scaling);
+        if let Some(dump) = &mut self.video_dump {
+            let (width, height) = (self.unscaled_width, self.unscaled_height);
+            let rgba = self.renderer.get_pixels(0, 0, width as _, height as _);
+            let mut image = RgbaImage::from_vec(width, height, rgba.into())
+                .ok_or_else(|| gml::Error::Dump("couldn't read back the frame for the video dump".into()))?;
+            image.pixels_mut().for_each(|p| p[3] = 255);
+            if dump.size == Size::Window {
+                let smooth = self.renderer.get_pixel_interpolation();
+                image = fit_to_window(&image, window_width, window_height, self.scaling, smooth);
+            }
+            if let Err(e) = dump.capture(self.clock.as_nanos(), image, self.room.speed) {
+                return Err(gml::Error::Dump(format!("couldn't write the video dump: {}", e)))
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yuv_range() {
+        let mut image = RgbaImage::from_pixel(2, 1, image::Rgba([255, 255, 255, 255]));
+        image.put_pixel(1, 0, image::Rgba([0, 0, 0, 255]));
+        assert_eq!(rgba_to_yuv444(&image), [235, 16, 128, 128, 128, 128]);
+    }
+
+    #[test]
+    fn letterboxed() {
+        let image = RgbaImage::from_pixel(2, 2, image::Rgba([255, 0, 0, 255]));
+        let window = fit_to_window(&image, 8, 4, Scaling::Aspect(1.0), false);
+        assert_eq!(window.dimensions(), (8, 4));
+        assert_eq!(window.get_pixel(1, 2), &image::Rgba([0, 0, 0, 255]));
+        assert_eq!(window.get_pixel(2, 0), &image::Rgba([255, 0, 0, 255]));
+        assert_eq!(window.get_pixel(5, 3), &image::Rgba([255, 0, 0, 255]));
+        assert_eq!(window.get_pixel(6, 1), &image::Rgba([0, 0, 0, 255]));
+    }
+}
@@ -1,14 +1,17 @@
 pub mod compiler;
 pub mod context;
+pub mod debugger;
 pub mod datetime;
 pub mod ds;
 pub mod file;
 pub mod kernel;
 pub mod mappings;
 pub mod network;
+pub mod profiler;
 pub mod rand;
 pub mod runtime;
 pub mod string;
+pub mod stub;
 pub mod value;
 
 pub use compiler::Compiler;
@@ -27,6 +27,10 @@ pub struct Compiler {
 
     /// Lookup table of unique field names
     fields: Vec<Box<[u8]>>,
+
+    /// Address range and line start offsets of the source currently being compiled
+    #[serde(skip)]
+    source_lines: (usize, usize, Vec<usize>),
 }
 
 impl Compiler {
@@ -38,6 +42,7 @@ impl Compiler {
             script_names: HashMap::new(),
             extension_fn_names: HashMap::new(),
             fields: Vec::new(),
+            source_lines: (0, 0, Vec::new()),
         }
     }
 
@@ -87,14 +92,57 @@ impl Compiler {
     pub fn compile(&mut self, source: &[u8]) -> Result<Rc<[Instruction]>, ast::Error> {
         let ast = ast::AST::new(source)?;
 
+        let line_starts = std::iter::once(0)
+            .chain(source.iter().enumerate().filter(|(_, &c)| c == b'\n').map(|(i, _)| i + 1))
+            .collect();
+        self.source_lines = (source.as_ptr() as usize, source.len(), line_starts);
+
         let mut instructions = Vec::new();
         let mut locals: Vec<&[u8]> = Vec::new();
         for node in ast.iter() {
             self.compile_ast_line(node, &mut instructions, &mut locals);
         }
+        self.source_lines.2.clear();
         Ok(instructions.into())
     }
 
+    /// Finds which line of the source being compiled the given statement starts on, if possible.
+    /// The AST doesn't store positions, but identifiers and strings are slices of the source,
+    /// so the first one found in a statement tells us where that statement is.
+    fn line_of(&self, expr: &ast::Expr) -> Option<usize> {
+        fn first_slice<'a>(expr: &ast::Expr<'a>) -> Option<&'a [u8]> {
+            match expr {
+                ast::Expr::LiteralIdentifier(s) | ast::Expr::LiteralString(s) => Some(s),
+                ast::Expr::Unary(unary) => first_slice(&unary.child),
+                ast::Expr::Binary(binary) => first_slice(&binary.left).or_else(|| first_slice(&binary.right)),
+                ast::Expr::DoUntil(dountil) => first_slice(&dountil.body).or_else(|| first_slice(&dountil.cond)),
+                ast::Expr::For(for_expr) => first_slice(&for_expr.start)
+                    .or_else(|| first_slice(&for_expr.cond))
+                    .or_else(|| first_slice(&for_expr.step))
+                    .or_else(|| first_slice(&for_expr.body)),
+                ast::Expr::Function(call) => Some(call.name),
+                ast::Expr::Group(group) => group.iter().find_map(first_slice),
+                ast::Expr::If(if_expr) => first_slice(&if_expr.cond).or_else(|| first_slice(&if_expr.body)),
+                ast::Expr::Repeat(repeat) => first_slice(&repeat.count).or_else(|| first_slice(&repeat.body)),
+                ast::Expr::Switch(switch) => first_slice(&switch.input).or_else(|| first_slice(&switch.body)),
+                ast::Expr::Var(var) => var.vars.first().copied(),
+                ast::Expr::GlobalVar(var) => var.vars.first().copied(),
+                ast::Expr::With(with) => first_slice(&with.target).or_else(|| first_slice(&with.body)),
+                ast::Expr::While(while_expr) => first_slice(&while_expr.cond).or_else(|| first_slice(&while_expr.body)),
+                ast::Expr::Case(expr) | ast::Expr::Return(expr) => first_slice(expr),
+                ast::Expr::LiteralReal(_)
+                | ast::Expr::Default
+                | ast::Expr::Continue
+                | ast::Expr::Break
+                | ast::Expr::Exit => None,
+            }
+        }
+
+        let (start, len, line_starts) = &self.source_lines;
+        let offset = (first_slice(expr)?.as_ptr() as usize).checked_sub(*start).filter(|o| o < len)?;
+        Some(line_starts.partition_point(|&s| s <= offset))
+    }
+
     /// Compile an expression into a format which can be evaluated.
     pub fn compile_expression(&mut self, source: &[u8]) -> Result<Node, ast::Error> {
         let expr = ast::AST::expression(source)?;
@@ -103,6 +151,15 @@ impl Compiler {
 
     /// Compile a single line of code from an AST expression.
     fn compile_ast_line<'a>(&mut self, line: &'a ast::Expr, output: &mut Vec<Instruction>, locals: &mut Vec<&'a [u8]>) {
+        // Mark where each statement starts, for the debugger and error messages
+        if !matches!(line, ast::Expr::Group(_) | ast::Expr::Var(_)) {
+            match (self.line_of(line), output.last()) {
+                (Some(line), Some(Instruction::Line { line: prev })) if line == *prev => (),
+                (Some(line), _) => output.push(Instruction::Line { line }),
+                (None, _) => (),
+            }
+        }
+
         match line {
             // Line of code identified by an assignment operator
             ast::Expr::Binary(binary_expr) => {
@@ -0,0 +1,502 @@
+use crate::{
+    game::{Game, GetAsset},
+    gml::{self, ev, profiler, Context},
+    types::ID,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Write};
+
+/// Called when the debugger pauses execution. Receives the innermost context and returns how to continue.
+pub type BreakHook = Box<dyn FnMut(&mut Game, &mut Context) -> Resume>;
+
+/// Where a piece of running GML came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CodeSource {
+    /// An action in an object's event. `object` is the object which owns the event, not necessarily
+    /// the object of the running instance, as events can be inherited from parents.
+    Event {
+        object: ID,
+        event_type: usize,
+        event_number: usize,
+        action: usize,
+    },
+    Script(usize),
+    Trigger(usize),
+    InstanceCreation(ID),
+    RoomCreation(ID),
+    Extension(usize),
+    Library(usize),
+    ExecuteString,
+}
+
+/// One entry in the GML call stack.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub source: CodeSource,
+
+    /// Instance ID (not handle) of the "self" instance when this frame was entered
+    pub instance: ID,
+
+    /// Source line currently being executed, starting at 1, or 0 if no line has started yet
+    pub line: usize,
+}
+
+/// A call stack frame with its names resolved, so it can be reported after the stack has unwound.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TraceFrame {
+    /// Where the code came from, worded like GM8's error messages, eg. "script scr_move"
+    pub location: String,
+
+    /// Source line being executed, starting at 1, or 0 if not known
+    pub line: usize,
+
+    /// Text of that line, if the source code is still around
+    pub code: Option<String>,
+}
+
+/// What a breakpoint is attached to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BreakTarget {
+    Script(usize),
+    Event { object: ID, event_type: usize, event_number: usize },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub target: BreakTarget,
+
+    /// Line to break on, or None to break on entry. For events, this applies to every code action in the event.
+    pub line: Option<usize>,
+}
+
+/// How to carry on after the debugger has paused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resume {
+    Continue,
+    StepInto,
+    StepOver,
+    StepOut,
+}
+
+/// Pending step request: the kind of step, plus the stack depth and line it was requested from.
+#[derive(Clone, Copy)]
+enum Step {
+    Into(usize, usize),
+    Over(usize, usize),
+    Out(usize),
+}
+
+#[derive(Default)]
+pub struct Debugger {
+    pub breakpoints: Vec<Breakpoint>,
+
+    /// Pauses execution. Breakpoints and steps do nothing unless this is set.
+    pub hook: Option<BreakHook>,
+
+    step: Option<Step>,
+}
+
+impl Debugger {
+    /// Pauses on the next line of GML that gets executed.
+    pub fn break_next(&mut self) {
+        self.step = Some(Step::Into(0, 0));
+    }
+
+    /// Whether execution will pause on the next line regardless of breakpoints.
+    pub fn breaking_next(&self) -> bool {
+        matches!(self.step, Some(Step::Into(0, 0)))
+    }
+
+    /// Cancels any ongoing step, for example when the game state gets replaced.
+    pub fn clear_step(&mut self) {
+        self.step = None;
+    }
+
+    pub fn toggle_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if let Some(i) = self.breakpoints.iter().position(|b| *b == breakpoint) {
+            self.breakpoints.remove(i);
+        } else {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    fn should_break(&self, stack: &[Frame], entered: bool) -> bool {
+        let frame = match stack.last() {
+            Some(frame) => frame,
+            None => return false,
+        };
+        let depth = stack.len();
+        let stepped = match self.step {
+            Some(Step::Into(d, l)) => depth != d || frame.line != l,
+            Some(Step::Over(d, l)) => depth < d || (depth == d && frame.line != l),
+            Some(Step::Out(d)) => depth < d,
+            None => false,
+        };
+        stepped
+            || self.breakpoints.iter().any(|bp| {
+                let hit_target = match (bp.target, frame.source) {
+                    (BreakTarget::Script(a), CodeSource::Script(b)) => a == b,
+                    (
+                        BreakTarget::Event { object, event_type, event_number },
+                        CodeSource::Event { object: o, event_type: t, event_number: n, .. },
+                    ) => object == o && event_type == t && event_number == n,
+                    _ => false,
+                };
+                hit_target
+                    && match bp.line {
+                        Some(line) => line == frame.line,
+                        None => entered,
+                    }
+            })
+    }
+}
+
+impl Frame {
+    /// Describes this frame in one line, eg. "Step Event of obj_player, action 1, line 4".
+    pub fn describe(&self, game: &Game) -> String {
+        let mut out = match self.source {
+            CodeSource::Event { object, event_type, event_number, action } => format!(
+                "{} of {}, action {}",
+                event_name(game, event_type, event_number),
+                object_name(game, object),
+                action + 1
+            ),
+            CodeSource::Script(id) => match game.assets.scripts.get_asset(id as _) {
+                Some(script) => format!("script {}", script.name),
+                None => format!("<deleted script {}>", id),
+            },
+            CodeSource::Trigger(id) => match game.assets.triggers.get_asset(id as _) {
+                Some(trigger) => format!("condition of trigger {}", trigger.name),
+                None => format!("<deleted trigger {}>", id),
+            },
+            CodeSource::InstanceCreation(id) => format!("creation code of instance {}", id),
+            CodeSource::RoomCreation(id) => match game.assets.rooms.get_asset(id) {
+                Some(room) => format!("creation code of room {}", room.name),
+                None => format!("creation code of <deleted room {}>", id),
+            },
+            CodeSource::Extension(id) => format!("extension function {}", id),
+            CodeSource::Library(id) => format!("library initialization code {}", id),
+            CodeSource::ExecuteString => "execute_string".into(),
+        };
+        if self.line > 0 {
+            let _ = write!(out, ", line {}", self.line);
+        }
+        out
+    }
+
+    /// Resolves this frame's names for an error report.
+    pub fn trace(&self, game: &Game) -> TraceFrame {
+        let location = match self.source {
+            CodeSource::Event { object, event_type, event_number, action } => format!(
+                "action number {}\nof {}\nfor object {}",
+                action + 1,
+                event_name(game, event_type, event_number),
+                object_name(game, object)
+            ),
+            _ => Frame { line: 0, ..self.clone() }.describe(game),
+        };
+        let source = match self.source {
+            _ if self.line == 0 => None,
+            CodeSource::Event { object, event_type, event_number, action } => game
+                .assets
+                .objects
+                .get_asset(object)
+                .and_then(|object| object.events.get(event_type)?.get(&(event_number as u32)).cloned())
+                .and_then(|tree| tree.borrow().action(action)?.source.clone()),
+            CodeSource::Script(id) => game.assets.scripts.get_asset(id as _).map(|script| script.source.clone()),
+            _ => None,
+        };
+        let code = source.and_then(|source| {
+            game.decode_str(source.as_ref()).lines().nth(self.line - 1).map(|line| line.trim_end().into())
+        });
+        TraceFrame { location, line: self.line, code }
+    }
+}
+
+/// Writes an error and the stack it happened in, laid out like GM8's error dialog.
+pub fn write_trace(f: &mut fmt::Formatter, stack: &[TraceFrame], error: &gml::Error) -> fmt::Result {
+    writeln!(f, "___________________________________________")?;
+    if let Some((outer, inner)) = stack.split_first() {
+        writeln!(f, "ERROR in\n{}:", outer.location)?;
+        writeln!(f)?;
+        let mut caller = outer;
+        for frame in inner {
+            if caller.line > 0 {
+                writeln!(f, "In {}, called from line {}:", frame.location, caller.line)?;
+            } else {
+                writeln!(f, "In {}:", frame.location)?;
+            }
+            caller = frame;
+        }
+        if caller.line > 0 {
+            writeln!(f, "Error in code at line {}:", caller.line)?;
+            if let Some(code) = &caller.code {
+                writeln!(f, "   {}", code.trim_start())?;
+            }
+            writeln!(f)?;
+        }
+    }
+    write!(f, "{}", error)
+}
+
+pub fn object_name(game: &Game, object: ID) -> String {
+    match game.assets.objects.get_asset(object) {
+        Some(obj) => obj.name.to_string(),
+        None => format!("<deleted object {}>", object),
+    }
+}
+
+/// Formats an event type and number the way GM8 names them, eg. "Alarm Event for alarm 0".
+pub fn event_name(game: &Game, event_type: usize, event_number: usize) -> String {
+    match event_type {
+        ev::CREATE => "Create Event".into(),
+        ev::DESTROY => "Destroy Event".into(),
+        ev::ALARMS => format!("Alarm Event for alarm {}", event_number),
+        ev::STEP => match event_number {
+            1 => "Begin Step Event".into(),
+            2 => "End Step Event".into(),
+            _ => "Step Event".into(),
+        },
+        ev::COLLISION => format!("Collision Event with object {}", object_name(game, event_number as _)),
+        ev::KEYBOARD => format!("Keyboard Event for key {}", event_number),
+        ev::MOUSE => format!("Mouse Event {}", event_number),
+        ev::OTHER => format!("Other Event {}", event_number),
+        ev::DRAW => "Draw Event".into(),
+        ev::KEYPRESS => format!("Key Press Event for key {}", event_number),
+        ev::KEYRELEASE => format!("Key Release Event for key {}", event_number),
+        ev::TRIGGER => match game.assets.triggers.get_asset(event_number as _) {
+            Some(trigger) => format!("Trigger Event {}", trigger.name),
+            None => format!("Trigger Event {}", event_number),
+        },
+        _ => format!("Event {} {}", event_type, event_number),
+    }
+}
+
+impl Game {
+    /// Pushes a frame onto the call stack, executes some code in it, then pops it again.
+    pub fn execute_frame(
+        &mut self,
+        source: CodeSource,
+        instructions: &[super::runtime::Instruction],
+        context: &mut Context,
+    ) -> gml::Result<super::runtime::ReturnType> {
+        // Event code is already being timed as part of its event
+        let profiled = !matches!(source, CodeSource::Event { .. });
+        if profiled {
+            self.profile_enter(profiler::Entry::Code(source));
+        }
+        let result = self.in_frame(source, context, |game, context| game.execute(instructions, context));
+        if profiled {
+            self.profile_exit();
+        }
+        result
+    }
+
+    /// Runs something with a frame pushed onto the call stack.
+    /// Any error it returns gets the call stack attached, unless it already has one from a deeper frame.
+    /// If this is the outermost frame, the error then goes through GM8's error handling, and if the player
+    /// chooses to ignore it, this returns a default value so that the caller carries on.
+    pub fn in_frame<T: Default>(
+        &mut self,
+        source: CodeSource,
+        context: &mut Context,
+        f: impl FnOnce(&mut Self, &mut Context) -> gml::Result<T>,
+    ) -> gml::Result<T> {
+        let instance = self.room.instance_list.get(context.this).id.get();
+        self.call_stack.push(Frame { source, instance, line: 0 });
+        let result = f(self, context).map_err(|error| match error {
+            gml::Error::Traced(..) | gml::Error::Aborted(_) => error,
+            error => {
+                gml::Error::Traced(Box::new(error), self.call_stack.iter().map(|frame| frame.trace(self)).collect())
+            },
+        });
+        self.call_stack.pop();
+        match result {
+            Err(error) if self.call_stack.is_empty() => self.runtime_error(error).map(|()| T::default()),
+            result => result,
+        }
+    }
+
+    /// Called at the start of every line while a break hook is set. Pauses if a breakpoint or step says so.
+    pub(super) fn debugger_check(&mut self, context: &mut Context, entered: bool) {
+        if !self.debugger.should_break(&self.call_stack, entered) {
+            return
+        }
+        if let Some(mut hook) = self.debugger.hook.take() {
+            let resume = hook(self, context);
+            let depth = self.call_stack.len();
+            let line = self.call_stack.last().map(|f| f.line).unwrap_or(0);
+            self.debugger.step = match resume {
+                Resume::Continue => None,
+                Resume::StepInto => Some(Step::Into(depth, line)),
+                Resume::StepOver => Some(Step::Over(depth, line)),
+                Resume::StepOut => Some(Step::Out(depth)),
+            };
+            if self.debugger.hook.is_none() {
+                self.debugger.hook = Some(hook);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(source: CodeSource, line: usize) -> Frame {
+        Frame { source, instance: 100001, line }
+    }
+
+    fn step_event(action: usize) -> CodeSource {
+        CodeSource::Event { object: 0, event_type: ev::STEP, event_number: 0, action }
+    }
+
+    fn trace_frame(location: &str, line: usize, code: Option<&str>) -> TraceFrame {
+        TraceFrame { location: location.into(), line, code: code.map(String::from) }
+    }
+
+    fn traced(stack: Vec<TraceFrame>) -> String {
+        let error = gml::Error::UninitializedVariable("speed_x".into(), 0);
+        gml::Error::Traced(Box::new(error), stack).to_string()
+    }
+
+    #[test]
+    fn trace_event_action() {
+        let stack =
+            vec![trace_frame("action number 1\nof Step Event\nfor object obj_player", 3, Some("    x += speed_x"))];
+        assert_eq!(
+            traced(stack),
+            "___________________________________________\n\
+             ERROR in\n\
+             action number 1\n\
+             of Step Event\n\
+             for object obj_player:\n\
+             \n\
+             Error in code at line 3:\n   \
+             x += speed_x\n\
+             \n\
+             uninitialized variable \"speed_x\"",
+        );
+    }
+
+    #[test]
+    fn trace_script_calls() {
+        let stack = vec![
+            trace_frame("action number 2\nof Create Event\nfor object obj_player", 1, Some("scr_outer()")),
+            trace_frame("script scr_outer", 4, Some("scr_inner();")),
+            trace_frame("script scr_inner", 2, None),
+        ];
+        assert_eq!(
+            traced(stack),
+            "___________________________________________\n\
+             ERROR in\n\
+             action number 2\n\
+             of Create Event\n\
+             for object obj_player:\n\
+             \n\
+             In script scr_outer, called from line 1:\n\
+             In script scr_inner, called from line 4:\n\
+             Error in code at line 2:\n\
+             \n\
+             uninitialized variable \"speed_x\"",
+        );
+    }
+
+    #[test]
+    fn trace_without_lines() {
+        assert_eq!(
+            traced(vec![trace_frame("execute_string", 0, None)]),
+            "___________________________________________\nERROR in\nexecute_string:\n\n\
+             uninitialized variable \"speed_x\"",
+        );
+        assert_eq!(
+            traced(Vec::new()),
+            "___________________________________________\nuninitialized variable \"speed_x\""
+        );
+    }
+
+    #[test]
+    fn no_break_without_stack() {
+        let mut debugger = Debugger::default();
+        debugger.break_next();
+        assert!(!debugger.should_break(&[], true));
+    }
+
+    #[test]
+    fn breakpoint_on_line() {
+        let mut debugger = Debugger::default();
+        debugger.toggle_breakpoint(Breakpoint { target: BreakTarget::Script(3), line: Some(2) });
+        assert!(!debugger.should_break(&[frame(CodeSource::Script(3), 1)], false));
+        assert!(debugger.should_break(&[frame(CodeSource::Script(3), 2)], false));
+        assert!(!debugger.should_break(&[frame(CodeSource::Script(4), 2)], false));
+
+        // Toggling again removes it
+        debugger.toggle_breakpoint(Breakpoint { target: BreakTarget::Script(3), line: Some(2) });
+        assert!(!debugger.should_break(&[frame(CodeSource::Script(3), 2)], false));
+    }
+
+    #[test]
+    fn breakpoint_on_entry() {
+        let mut debugger = Debugger::default();
+        debugger.toggle_breakpoint(Breakpoint { target: BreakTarget::Script(3), line: None });
+        assert!(debugger.should_break(&[frame(CodeSource::Script(3), 1)], true));
+        assert!(!debugger.should_break(&[frame(CodeSource::Script(3), 2)], false));
+    }
+
+    #[test]
+    fn breakpoint_on_event() {
+        let mut debugger = Debugger::default();
+        debugger.toggle_breakpoint(Breakpoint {
+            target: BreakTarget::Event { object: 0, event_type: ev::STEP, event_number: 0 },
+            line: Some(1),
+        });
+        // Applies to every action in the event
+        assert!(debugger.should_break(&[frame(step_event(0), 1)], false));
+        assert!(debugger.should_break(&[frame(step_event(2), 1)], false));
+        let draw = CodeSource::Event { object: 0, event_type: ev::DRAW, event_number: 0, action: 0 };
+        assert!(!debugger.should_break(&[frame(draw, 1)], false));
+        assert!(!debugger.should_break(&[frame(CodeSource::Script(0), 1)], false));
+    }
+
+    #[test]
+    fn break_next() {
+        let mut debugger = Debugger::default();
+        assert!(!debugger.breaking_next());
+        debugger.break_next();
+        assert!(debugger.breaking_next());
+        assert!(debugger.should_break(&[frame(CodeSource::Script(0), 1)], true));
+        debugger.clear_step();
+        assert!(!debugger.should_break(&[frame(CodeSource::Script(0), 1)], true));
+    }
+
+    #[test]
+    fn step_into() {
+        let mut debugger = Debugger { step: Some(Step::Into(1, 4)), ..Default::default() };
+        let outer = frame(CodeSource::Script(0), 4);
+        assert!(!debugger.should_break(std::slice::from_ref(&outer), false));
+        assert!(debugger.should_break(&[frame(CodeSource::Script(0), 5)], false));
+        assert!(debugger.should_break(&[outer.clone(), frame(CodeSource::Script(1), 0)], true));
+        debugger.step = Some(Step::Into(2, 1));
+        assert!(debugger.should_break(&[outer], false));
+    }
+
+    #[test]
+    fn step_over() {
+        let debugger = Debugger { step: Some(Step::Over(1, 4)), ..Default::default() };
+        let outer = frame(CodeSource::Script(0), 4);
+        assert!(!debugger.should_break(std::slice::from_ref(&outer), false));
+        assert!(!debugger.should_break(&[outer.clone(), frame(CodeSource::Script(1), 1)], true));
+        assert!(debugger.should_break(&[frame(CodeSource::Script(0), 5)], false));
+        assert!(debugger.should_break(&[frame(step_event(0), 1)], false));
+    }
+
+    #[test]
+    fn step_out() {
+        let debugger = Debugger { step: Some(Step::Out(2)), ..Default::default() };
+        let outer = frame(CodeSource::Script(0), 4);
+        let inner = frame(CodeSource::Script(1), 2);
+        assert!(!debugger.should_break(&[outer.clone(), inner.clone()], false));
+        assert!(!debugger.should_break(&[outer.clone(), inner, frame(CodeSource::Script(2), 1)], true));
+        assert!(debugger.should_break(&[outer], false));
+    }
+}
@@ -1,3 +1,6 @@
+use crate::util;
+use byteorder::{ReadBytesExt, LE};
+use flate2::read::ZlibDecoder;
 use image::{codecs::gif::GifDecoder, AnimationDecoder, ImageError, ImageFormat, Pixel, RgbaImage};
 use std::{
     fs::{File, OpenOptions},
@@ -34,6 +37,7 @@ pub enum Error {
     CantWrite,
     IOError(io::Error),
     ImageError(ImageError),
+    InvalidResource,
 }
 
 impl From<io::Error> for Error {
@@ -57,6 +61,7 @@ impl std::fmt::Display for Error {
             Self::CantWrite => write!(f, "file is not open for writing"),
             Self::IOError(err) => write!(f, "io error: {}", err),
             Self::ImageError(err) => write!(f, "image error: {}", err),
+            Self::InvalidResource => write!(f, "not a valid resource file"),
         }
     }
 }
@@ -445,6 +450,97 @@ pub fn load_animation(path: &str, imgnumb: usize) -> Result<Vec<RgbaImage>> {
     }
 }
 
+/// A sprite saved from GM8's sprite editor as a .gmspr file.
+pub struct SpriteFile {
+    pub origin_x: i32,
+    pub origin_y: i32,
+    pub frames: Vec<RgbaImage>,
+    pub sepmasks: bool,
+    pub mask_kind: i32,
+    pub tolerance: u8,
+    pub bbox_mode: i32,
+    pub bbox_left: i32,
+    pub bbox_right: i32,
+    pub bbox_bottom: i32,
+    pub bbox_top: i32,
+}
+
+/// The most a resource file can decompress to, which is far more than any real sprite or background needs.
+const MAX_RESOURCE_SIZE: u64 = 256 << 20;
+
+/// Reads the data out of a GM8 resource file, which is a version number followed by a zlib block laid out the same
+/// as the resource is in a .gmk, but without the name and timestamp.
+fn read_resource(path: &str) -> Result<Vec<u8>> {
+    let data = std::fs::read(path)?;
+    let mut block = data.get(4..).ok_or(Error::InvalidResource)?;
+    let len = block.read_u32::<LE>().map_err(|_| Error::InvalidResource)?;
+    if len as usize != block.len() {
+        return Err(Error::InvalidResource)
+    }
+    let mut out = Vec::new();
+    ZlibDecoder::new(block).take(MAX_RESOURCE_SIZE + 1).read_to_end(&mut out).map_err(|_| Error::InvalidResource)?;
+    if out.len() as u64 > MAX_RESOURCE_SIZE {
+        return Err(Error::InvalidResource)
+    }
+    Ok(out)
+}
+
+/// Reads an image stored in a resource, which is in BGRA. Empty images come out as `None`.
+fn read_resource_image(data: &mut &[u8]) -> Result<Option<RgbaImage>> {
+    let _version = data.read_u32::<LE>()?;
+    let width = data.read_u32::<LE>()?;
+    let height = data.read_u32::<LE>()?;
+    if width == 0 || height == 0 {
+        return Ok(None)
+    }
+    let len = data.read_u32::<LE>()? as usize;
+    if len != width as usize * height as usize * 4 || len > data.len() {
+        return Err(Error::InvalidResource)
+    }
+    let (pixels, rest) = data.split_at(len);
+    *data = rest;
+    let mut pixels = pixels.to_vec();
+    util::bgra2rgba(&mut pixels);
+    Ok(RgbaImage::from_vec(width, height, pixels))
+}
+
+pub fn load_sprite_file(path: &str) -> Result<SpriteFile> {
+    let data = read_resource(path)?;
+    let mut data = data.as_slice();
+    let _version = data.read_u32::<LE>()?;
+    let origin_x = data.read_i32::<LE>()?;
+    let origin_y = data.read_i32::<LE>()?;
+    let frame_count = data.read_u32::<LE>()?;
+    let mut frames = Vec::new();
+    for _ in 0..frame_count {
+        frames.extend(read_resource_image(&mut data)?);
+    }
+    Ok(SpriteFile {
+        origin_x,
+        origin_y,
+        frames,
+        mask_kind: data.read_i32::<LE>()?,
+        tolerance: data.read_u32::<LE>()?.min(255) as u8,
+        sepmasks: data.read_u32::<LE>()? != 0,
+        bbox_mode: data.read_i32::<LE>()?,
+        bbox_left: data.read_i32::<LE>()?,
+        bbox_right: data.read_i32::<LE>()?,
+        bbox_bottom: data.read_i32::<LE>()?,
+        bbox_top: data.read_i32::<LE>()?,
+    })
+}
+
+/// Loads the image out of a .gmbck file. The tileset settings saved with it aren't used at runtime.
+pub fn load_background_file(path: &str) -> Result<Option<RgbaImage>> {
+    let data = read_resource(path)?;
+    let mut data = data.as_slice();
+    let _version = data.read_u32::<LE>()?;
+    for _ in 0..7 {
+        data.read_u32::<LE>()?; // is tileset, tile width/height, offsets and separations
+    }
+    read_resource_image(&mut data)
+}
+
 pub fn save_image<P: AsRef<Path>>(path: P, image: RgbaImage) -> Result<()> {
     // save to png if the filename is .png otherwise bmp regardless of filename
     if path.as_ref().extension().and_then(|s| s.to_str()).map(|s| s.eq_ignore_ascii_case("png")).unwrap_or(false) {
@@ -454,3 +550,83 @@ pub fn save_image<P: AsRef<Path>>(path: P, image: RgbaImage) -> Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+    use flate2::{write::ZlibEncoder, Compression};
+
+    /// Saves data the way GM8 saves a resource file, and gives the path.
+    fn write_resource(name: &str, data: &[u8]) -> String {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        let block = encoder.finish().unwrap();
+        let mut file = Vec::new();
+        file.write_u32::<LE>(800).unwrap();
+        file.write_u32::<LE>(block.len() as u32).unwrap();
+        file.extend_from_slice(&block);
+        let path = std::env::temp_dir().join(format!("gm8emulator-{}-{}", std::process::id(), name));
+        std::fs::write(&path, file).unwrap();
+        path.to_str().unwrap().into()
+    }
+
+    /// A 2x1 image with a red and a half-transparent blue pixel, in BGRA.
+    fn write_image(data: &mut Vec<u8>) {
+        for x in [800, 2, 1, 8] {
+            data.write_u32::<LE>(x).unwrap();
+        }
+        data.extend_from_slice(&[0, 0, 255, 255, 255, 0, 0, 128]);
+    }
+
+    fn check_image(image: &RgbaImage) {
+        assert_eq!(image.dimensions(), (2, 1));
+        assert_eq!(image.as_raw(), &[255, 0, 0, 255, 0, 0, 255, 128]);
+    }
+
+    #[test]
+    fn sprite_file() {
+        let mut data = Vec::new();
+        for x in [800, 3, 4, 2] {
+            data.write_i32::<LE>(x).unwrap();
+        }
+        write_image(&mut data);
+        write_image(&mut data);
+        // Mask kind, tolerance, separate masks, bbox mode, then the bbox as left, right, bottom, top
+        for x in [1, 20, 1, 2, 0, 1, 0, 0] {
+            data.write_i32::<LE>(x).unwrap();
+        }
+        let path = write_resource("sprite.gmspr", &data);
+        let sprite = load_sprite_file(&path).unwrap();
+        std::fs::remove_file(path).ok();
+
+        assert_eq!((sprite.origin_x, sprite.origin_y), (3, 4));
+        assert_eq!(sprite.frames.len(), 2);
+        sprite.frames.iter().for_each(check_image);
+        assert_eq!((sprite.mask_kind, sprite.tolerance, sprite.sepmasks), (1, 20, true));
+        assert_eq!(sprite.bbox_mode, 2);
+        assert_eq!((sprite.bbox_left, sprite.bbox_right, sprite.bbox_bottom, sprite.bbox_top), (0, 1, 0, 0));
+    }
+
+    #[test]
+    fn background_file() {
+        let mut data = Vec::new();
+        for x in [800, 0, 16, 16, 0, 0, 0, 0] {
+            data.write_u32::<LE>(x).unwrap();
+        }
+        write_image(&mut data);
+        let path = write_resource("background.gmbck", &data);
+        let image = load_background_file(&path).unwrap().unwrap();
+        std::fs::remove_file(path).ok();
+        check_image(&image);
+    }
+
+    #[test]
+    fn not_compressed() {
+        let path = std::env::temp_dir().join(format!("gm8emulator-{}-raw.gmbck", std::process::id()));
+        std::fs::write(&path, [0x20, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]).unwrap();
+        let result = load_background_file(path.to_str().unwrap());
+        std::fs::remove_file(path).ok();
+        assert!(matches!(result, Err(Error::InvalidResource)));
+    }
+}
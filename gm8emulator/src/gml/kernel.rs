@@ -5,15 +5,17 @@
 use crate::{
     action, asset,
     game::{
-        draw, external, gm_save::GMSave, model, particle, pathfinding, platform, replay, surface::Surface,
-        transition::UserTransition, view::View, Game, GameClock, GetAsset, PlayType, SceneChange, Version,
+        audio, draw, external, gm_save::GMSave, highscore, message, model, particle, pathfinding, platform, registry,
+        replay, splash, surface::Surface, transition::UserTransition, view::View, Game, GameClock, GetAsset, PlayType,
+        SceneChange, Version,
     },
     gml::{
         self,
         datetime::{self, DateTime},
+        debugger::CodeSource,
         ds, file,
         mappings::{self, constants as gml_consts},
-        network, Context, Value,
+        network, stub, Context, Value,
     },
     handleman::HandleManager,
     input::MouseButton,
@@ -47,6 +49,9 @@ include!(concat!(env!("OUT_DIR"), "/_apply_args.macro.rs"));
 
 /// Helper macro to validate input arguments from a GML function.
 macro_rules! expect_args {
+    ($args: expr, []) => {{
+        if $args.is_empty() { Ok(()) } else { Err(gml::runtime::Error::WrongArgumentCount(0, $args.len())) }
+    }};
     ($args: expr, [$($x: ident),*]) => {{
         (|| -> gml::Result<_> {
             let argc = _count_rep!($($x)*);
@@ -141,46 +146,46 @@ impl Game {
 
     pub fn display_set_size(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function display_set_size")
+        stub::unimplemented("display_set_size")
     }
 
     pub fn display_set_colordepth(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function display_set_colordepth")
+        stub::unimplemented("display_set_colordepth")
     }
 
     pub fn display_set_frequency(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function display_set_frequency")
+        stub::unimplemented("display_set_frequency")
     }
 
     pub fn display_set_all(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function display_set_all")
+        stub::unimplemented("display_set_all")
     }
 
     pub fn display_test_all(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function display_test_all")
+        stub::unimplemented("display_test_all")
     }
 
     pub fn display_reset(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        unimplemented!("Called unimplemented kernel function display_reset")
+        stub::unimplemented("display_reset")
     }
 
     pub fn display_mouse_get_x(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function display_mouse_get_x")
+        stub::unimplemented("display_mouse_get_x")
     }
 
     pub fn display_mouse_get_y(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function display_mouse_get_y")
+        stub::unimplemented("display_mouse_get_y")
     }
 
     pub fn display_mouse_set(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function display_mouse_set")
+        stub::unimplemented("display_mouse_set")
     }
 
     pub fn window_set_visible(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -243,12 +248,12 @@ impl Game {
 
     pub fn window_set_stayontop(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function window_set_stayontop")
+        stub::unimplemented("window_set_stayontop")
     }
 
     pub fn window_get_stayontop(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function window_get_stayontop")
+        stub::unimplemented("window_get_stayontop")
     }
 
     pub fn window_set_sizeable(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -352,7 +357,7 @@ impl Game {
 
     pub fn window_set_rectangle(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function window_set_rectangle")
+        stub::unimplemented("window_set_rectangle")
     }
 
     pub fn window_center(&mut self, _args: &[Value]) -> gml::Result<Value> {
@@ -362,7 +367,7 @@ impl Game {
 
     pub fn window_default(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function window_default")
+        stub::unimplemented("window_default")
     }
 
     pub fn window_get_x(&self, args: &[Value]) -> gml::Result<Value> {
@@ -390,7 +395,7 @@ impl Game {
         // unscaled_width and unscaled_height will need to be separated into framebuffer size
         // and window region size for this to work
         // probably keep the framebuffer size on the renderer and make a getter?
-        unimplemented!("Called unimplemented kernel function window_set_region_size")
+        stub::unimplemented("window_set_region_size")
     }
 
     pub fn window_get_region_width(&self, args: &[Value]) -> gml::Result<Value> {
@@ -448,37 +453,37 @@ impl Game {
 
     pub fn window_mouse_set(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function window_mouse_set")
+        stub::unimplemented("window_mouse_set")
     }
 
     pub fn window_view_mouse_get_x(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function window_view_mouse_get_x")
+        stub::unimplemented("window_view_mouse_get_x")
     }
 
     pub fn window_view_mouse_get_y(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function window_view_mouse_get_y")
+        stub::unimplemented("window_view_mouse_get_y")
     }
 
     pub fn window_view_mouse_set(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function window_view_mouse_set")
+        stub::unimplemented("window_view_mouse_set")
     }
 
     pub fn window_views_mouse_get_x(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function window_views_mouse_get_x")
+        stub::unimplemented("window_views_mouse_get_x")
     }
 
     pub fn window_views_mouse_get_y(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function window_views_mouse_get_y")
+        stub::unimplemented("window_views_mouse_get_y")
     }
 
     pub fn window_views_mouse_set(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function window_views_mouse_set")
+        stub::unimplemented("window_views_mouse_set")
     }
 
     pub fn set_synchronization(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -498,7 +503,7 @@ impl Game {
         self.draw()?;
         let (width, height) = self.window_inner_size;
         if self.play_type != PlayType::Record {
-            self.renderer.present(width, height, self.scaling);
+            self.present(width, height)?;
         }
         Ok(Default::default())
     }
@@ -507,7 +512,7 @@ impl Game {
         expect_args!(args, [])?;
         let (width, height) = self.window_inner_size;
         if self.play_type != PlayType::Record {
-            self.renderer.present(width, height, self.scaling);
+            self.present(width, height)?;
         }
         Ok(Default::default())
     }
@@ -2083,7 +2088,7 @@ impl Game {
 
     pub fn tile_find(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function tile_find")
+        stub::unimplemented("tile_find")
     }
 
     pub fn tile_exists(&self, args: &[Value]) -> gml::Result<Value> {
@@ -2103,7 +2108,7 @@ impl Game {
 
     pub fn tile_delete_at(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function tile_delete_at")
+        stub::unimplemented("tile_delete_at")
     }
 
     pub fn tile_layer_hide(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -3091,39 +3096,46 @@ impl Game {
         }])
     }
 
-    pub fn action_splash_text(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function action_splash_text")
+    pub fn action_splash_text(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let fname = expect_args!(args, [any])?;
+        self.splash_show_text(&[fname, 0.into()])
     }
 
-    pub fn action_splash_image(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function action_splash_image")
+    pub fn action_splash_image(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let fname = expect_args!(args, [any])?;
+        self.splash_show_image(&[fname, 0.into()])
     }
 
-    pub fn action_splash_web(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function action_splash_web")
+    pub fn action_splash_web(&mut self, args: &[Value]) -> gml::Result<Value> {
+        // The second argument picks between the game window and the browser, but there's no browser to open
+        let (url, _) = expect_args!(args, [any, any])?;
+        self.splash_show_web(&[url, 0.into()])
     }
 
-    pub fn action_splash_settings(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        unimplemented!("Called unimplemented kernel function action_splash_settings")
+    pub fn action_splash_settings(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (caption, place, width, height, close_button) = expect_args!(args, [bytes, int, int, int, bool])?;
+        // Place is 0 for the game window, 1 for a normal window and 2 for fullscreen
+        self.splash.caption = caption;
+        self.splash.main = place == 0;
+        self.splash.fullscreen = place == 2;
+        self.splash.size = (width, height);
+        self.splash.close_button = close_button;
+        Ok(Default::default())
     }
 
     pub fn action_replace_sprite(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function action_replace_sprite")
+        stub::unimplemented("action_replace_sprite")
     }
 
     pub fn action_replace_sound(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function action_replace_sound")
+        stub::unimplemented("action_replace_sound")
     }
 
     pub fn action_replace_background(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function action_replace_background")
+        stub::unimplemented("action_replace_background")
     }
 
     pub fn action_if_empty(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
@@ -3229,7 +3241,7 @@ impl Game {
                 ],
                 5,
             );
-            self.execute(&instructions, &mut new_context)?;
+            self.execute_frame(CodeSource::Script(script_id as usize), &instructions, &mut new_context)?;
             Ok(new_context.return_value)
         } else {
             Err(gml::Error::NonexistentAsset(asset::Type::Script, script_id))
@@ -3305,9 +3317,21 @@ impl Game {
         self.draw_text(&[x.into(), y.into(), format!("{}{}", caption, self.score).into()])
     }
 
-    pub fn action_highscore_show(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        unimplemented!("Called unimplemented kernel function action_highscore_show")
+    pub fn action_highscore_show(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (background, border, new_colour, other_colour, font) = expect_args!(args, [int, bool, int, int, bytes])?;
+        // The font argument is stored like "Arial,12,0,0,0,0,0", but only the name and size are used
+        let mut font_args = font.as_ref().split(|&c| c == b',');
+        let style = &mut self.highscores.style;
+        style.background = background;
+        style.border = border;
+        style.new_colour = new_colour;
+        style.other_colour = other_colour;
+        style.font_name = font_args.next().unwrap_or_default().into();
+        if let Some(size) = font_args.next().and_then(|s| std::str::from_utf8(s).ok()?.trim().parse().ok()) {
+            style.font_size = size;
+        }
+        self.show_highscore(self.score, true)?;
+        Ok(Default::default())
     }
 
     pub fn action_set_life(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
@@ -3628,7 +3652,7 @@ impl Game {
 
     pub fn action_webpage(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function action_webpage")
+        stub::unimplemented("action_webpage")
     }
 
     pub fn action_draw_sprite(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
@@ -4855,51 +4879,98 @@ impl Game {
         .into())
     }
 
-    pub fn mp_linear_path(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
-        let (path_id, xg, yg, step_size, checkall) = expect_args!(args, [int, real, real, real, bool])?;
+    /// Runs make_path for the instance on the given path, returning whether the destination was reached.
+    fn make_path(
+        &mut self,
+        instance: usize,
+        path_id: i32,
+        generate: impl FnOnce(&Self, &Instance, &mut asset::Path) -> bool,
+    ) -> gml::Result<bool> {
         // we use a closure that needs a &Game for the collision calls, so we can't have a &mut Path
         // so this function needs to own the path while that closure's being used
         if let Some(mut path) =
             usize::try_from(path_id).ok().and_then(|id| self.assets.paths.get_mut(id)).and_then(Option::take)
         {
-            let inst = self.room.instance_list.get(context.this);
-            let coll = || {
-                if checkall {
-                    self.check_collision_any(context.this).is_some()
-                } else {
-                    self.check_collision_solid(context.this).is_some()
-                }
-            };
-            pathfinding::make_path(inst, &mut path, |inst| {
+            let inst = self.room.instance_list.get(instance);
+            let result = generate(self, inst, &mut path);
+            self.assets.paths[path_id as usize] = Some(path);
+            Ok(result)
+        } else {
+            Err(gml::Error::NonexistentAsset(asset::Type::Path, path_id))
+        }
+    }
+
+    /// Makes a path out of linear steps, which fails as soon as it can't move any further.
+    fn make_linear_path(
+        &mut self,
+        instance: usize,
+        path_id: i32,
+        (xg, yg): (Real, Real),
+        step_size: Real,
+        coll: impl Fn(&Self) -> bool,
+    ) -> gml::Result<bool> {
+        self.make_path(instance, path_id, |game, inst, path| {
+            pathfinding::make_path(inst, path, |inst| {
                 let (old_x, old_y) = (inst.x.get(), inst.y.get());
-                if pathfinding::linear_step(xg, yg, step_size, inst, coll) {
+                if pathfinding::linear_step(xg, yg, step_size, inst, || coll(game)) {
                     pathfinding::PathGenResult::Done
                 } else if inst.x.get() == old_x && inst.y.get() == old_y {
                     pathfinding::PathGenResult::Failed
                 } else {
                     pathfinding::PathGenResult::NotDone
                 }
-            });
-            self.assets.paths[path_id as usize] = Some(path);
-            Ok(Default::default())
-        } else {
-            Err(gml::Error::NonexistentAsset(asset::Type::Path, path_id))
-        }
+            })
+        })
     }
 
-    pub fn mp_linear_step_object(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
-        let (x, y, step_size, obj) = expect_args!(args, [real, real, real, int])?;
-        Ok(pathfinding::linear_step(x, y, step_size, self.room.instance_list.get(context.this), || match obj {
+    /// Makes a path out of potential steps, as pathfinding::make_potential_path does.
+    fn make_potential_path(
+        &mut self,
+        instance: usize,
+        path_id: i32,
+        (xg, yg): (Real, Real),
+        step_size: Real,
+        factor: Real,
+        coll: impl Fn(&Self) -> bool,
+    ) -> gml::Result<bool> {
+        self.make_path(instance, path_id, |game, inst, path| {
+            let settings = &game.potential_step_settings;
+            pathfinding::make_potential_path((xg, yg), step_size, factor, settings, inst, path, || coll(game))
+        })
+    }
+
+    /// Checks for a collision with an instance of the given object, as the _object movement functions do.
+    fn check_collision_object(&self, context: &Context, obj: i32) -> bool {
+        match obj {
             gml::SELF => false,
             gml::OTHER => self.check_collision(context.this, context.other),
             obj => self.find_instance_with(obj, |handle| self.check_collision(context.this, handle)).is_some(),
+        }
+    }
+
+    pub fn mp_linear_path(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
+        let (path_id, xg, yg, step_size, checkall) = expect_args!(args, [int, real, real, real, bool])?;
+        let this = context.this;
+        let found = self.make_linear_path(this, path_id, (xg, yg), step_size, |game| {
+            if checkall { game.check_collision_any(this).is_some() } else { game.check_collision_solid(this).is_some() }
+        })?;
+        Ok(found.into())
+    }
+
+    pub fn mp_linear_step_object(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
+        let (x, y, step_size, obj) = expect_args!(args, [real, real, real, int])?;
+        Ok(pathfinding::linear_step(x, y, step_size, self.room.instance_list.get(context.this), || {
+            self.check_collision_object(context, obj)
         })
         .into())
     }
 
-    pub fn mp_linear_path_object(&mut self, _context: &mut Context, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        unimplemented!("Called unimplemented kernel function mp_linear_path_object")
+    pub fn mp_linear_path_object(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
+        let (path_id, xg, yg, step_size, obj) = expect_args!(args, [int, real, real, real, int])?;
+        let found = self.make_linear_path(context.this, path_id, (xg, yg), step_size, |game| {
+            game.check_collision_object(context, obj)
+        })?;
+        Ok(found.into())
     }
 
     pub fn mp_potential_settings(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -4932,9 +5003,13 @@ impl Game {
         .into())
     }
 
-    pub fn mp_potential_path(&mut self, _context: &mut Context, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 6
-        unimplemented!("Called unimplemented kernel function mp_potential_path")
+    pub fn mp_potential_path(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
+        let (path_id, xg, yg, step_size, factor, checkall) = expect_args!(args, [int, real, real, real, real, bool])?;
+        let this = context.this;
+        let found = self.make_potential_path(this, path_id, (xg, yg), step_size, factor, |game| {
+            if checkall { game.check_collision_any(this).is_some() } else { game.check_collision_solid(this).is_some() }
+        })?;
+        Ok(found.into())
     }
 
     pub fn mp_potential_step_object(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
@@ -4945,18 +5020,17 @@ impl Game {
             step_size,
             &self.potential_step_settings,
             self.room.instance_list.get(context.this),
-            || match obj {
-                gml::SELF => false,
-                gml::OTHER => self.check_collision(context.this, context.other),
-                obj => self.find_instance_with(obj, |handle| self.check_collision(context.this, handle)).is_some(),
-            },
+            || self.check_collision_object(context, obj),
         )
         .into())
     }
 
-    pub fn mp_potential_path_object(&mut self, _context: &mut Context, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 6
-        unimplemented!("Called unimplemented kernel function mp_potential_path_object")
+    pub fn mp_potential_path_object(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
+        let (path_id, xg, yg, step_size, factor, obj) = expect_args!(args, [int, real, real, real, real, int])?;
+        let found = self.make_potential_path(context.this, path_id, (xg, yg), step_size, factor, |game| {
+            game.check_collision_object(context, obj)
+        })?;
+        Ok(found.into())
     }
 
     pub fn mp_grid_create(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -5091,12 +5165,12 @@ impl Game {
 
     pub fn mp_grid_add_instances(&mut self, _context: &mut Context, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function mp_grid_add_instances")
+        stub::unimplemented("mp_grid_add_instances")
     }
 
     pub fn mp_grid_path(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 7
-        unimplemented!("Called unimplemented kernel function mp_grid_path")
+        stub::unimplemented("mp_grid_path")
     }
 
     pub fn mp_grid_draw(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -5522,7 +5596,7 @@ impl Game {
 
     pub fn position_change(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function position_change")
+        stub::unimplemented("position_change")
     }
 
     pub fn instance_deactivate_all(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
@@ -6331,7 +6405,7 @@ impl Game {
 
     pub fn file_attributes(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function file_attributes")
+        stub::unimplemented("file_attributes")
     }
 
     pub fn filename_name(args: &[Value]) -> gml::Result<Value> {
@@ -6485,7 +6559,7 @@ impl Game {
 
     pub fn execute_shell(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function execute_shell")
+        stub::unimplemented("execute_shell")
     }
 
     pub fn parameter_count(&self, args: &[Value]) -> gml::Result<Value> {
@@ -6516,59 +6590,83 @@ impl Game {
         Ok(env.as_ref().into())
     }
 
-    pub fn registry_write_string(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function registry_write_string")
+    pub fn registry_write_string(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (name, value) = expect_args!(args, [string, bytes])?;
+        let key = registry::game_key(self.game_id);
+        self.registry.set(registry::Root::CurrentUser, &key, &name, value.into());
+        Ok(Default::default())
     }
 
-    pub fn registry_write_real(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function registry_write_real")
+    pub fn registry_write_real(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (name, value) = expect_args!(args, [string, real])?;
+        let key = registry::game_key(self.game_id);
+        self.registry.set(registry::Root::CurrentUser, &key, &name, value.into());
+        Ok(Default::default())
     }
 
-    pub fn registry_read_string(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function registry_read_string")
+    pub fn registry_read_string(&self, args: &[Value]) -> gml::Result<Value> {
+        let name = expect_args!(args, [string])?;
+        let key = registry::game_key(self.game_id);
+        Ok(match self.registry.get(registry::Root::CurrentUser, &key, &name) {
+            Some(value @ Value::Str(_)) => value.clone(),
+            _ => "".into(),
+        })
     }
 
-    pub fn registry_read_real(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function registry_read_real")
+    pub fn registry_read_real(&self, args: &[Value]) -> gml::Result<Value> {
+        let name = expect_args!(args, [string])?;
+        let key = registry::game_key(self.game_id);
+        Ok(match self.registry.get(registry::Root::CurrentUser, &key, &name) {
+            Some(value @ Value::Real(_)) => value.clone(),
+            _ => Default::default(),
+        })
     }
 
-    pub fn registry_exists(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function registry_exists")
+    pub fn registry_exists(&self, args: &[Value]) -> gml::Result<Value> {
+        let name = expect_args!(args, [string])?;
+        let key = registry::game_key(self.game_id);
+        Ok(self.registry.get(registry::Root::CurrentUser, &key, &name).is_some().into())
     }
 
-    pub fn registry_write_string_ext(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function registry_write_string_ext")
+    pub fn registry_write_string_ext(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (key, name, value) = expect_args!(args, [string, string, bytes])?;
+        self.registry.set(self.registry.root, &key, &name, value.into());
+        Ok(Default::default())
     }
 
-    pub fn registry_write_real_ext(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function registry_write_real_ext")
+    pub fn registry_write_real_ext(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (key, name, value) = expect_args!(args, [string, string, real])?;
+        self.registry.set(self.registry.root, &key, &name, value.into());
+        Ok(Default::default())
     }
 
-    pub fn registry_read_string_ext(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function registry_read_string_ext")
+    pub fn registry_read_string_ext(&self, args: &[Value]) -> gml::Result<Value> {
+        let (key, name) = expect_args!(args, [string, string])?;
+        Ok(match self.registry.get(self.registry.root, &key, &name) {
+            Some(value @ Value::Str(_)) => value.clone(),
+            _ => "".into(),
+        })
     }
 
-    pub fn registry_read_real_ext(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function registry_read_real_ext")
+    pub fn registry_read_real_ext(&self, args: &[Value]) -> gml::Result<Value> {
+        let (key, name) = expect_args!(args, [string, string])?;
+        Ok(match self.registry.get(self.registry.root, &key, &name) {
+            Some(value @ Value::Real(_)) => value.clone(),
+            _ => Default::default(),
+        })
     }
 
-    pub fn registry_exists_ext(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function registry_exists_ext")
+    pub fn registry_exists_ext(&self, args: &[Value]) -> gml::Result<Value> {
+        let (key, name) = expect_args!(args, [string, string])?;
+        Ok(self.registry.get(self.registry.root, &key, &name).is_some().into())
     }
 
-    pub fn registry_set_root(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function registry_set_root")
+    pub fn registry_set_root(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let root = expect_args!(args, [int])?;
+        if let Some(root) = registry::Root::from_id(root) {
+            self.registry.root = root;
+        }
+        Ok(Default::default())
     }
 
     pub fn ini_open(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -6742,338 +6840,392 @@ impl Game {
         Ok((0x1_00000_00000u64 as f64).into())
     }
 
-    pub fn splash_set_caption(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function splash_set_caption")
+    pub fn splash_set_caption(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.splash.caption = expect_args!(args, [bytes])?;
+        Ok(Default::default())
     }
 
-    pub fn splash_set_fullscreen(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function splash_set_fullscreen")
+    pub fn splash_set_fullscreen(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.splash.fullscreen = expect_args!(args, [bool])?;
+        Ok(Default::default())
     }
 
-    pub fn splash_set_border(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function splash_set_border")
+    pub fn splash_set_border(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.splash.border = expect_args!(args, [bool])?;
+        Ok(Default::default())
     }
 
-    pub fn splash_set_size(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function splash_set_size")
+    pub fn splash_set_size(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.splash.size = expect_args!(args, [int, int])?;
+        Ok(Default::default())
     }
 
-    pub fn splash_set_position(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function splash_set_position")
+    pub fn splash_set_position(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.splash.position = expect_args!(args, [int, int])?;
+        Ok(Default::default())
     }
 
-    pub fn splash_set_adapt(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function splash_set_adapt")
+    pub fn splash_set_adapt(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.splash.adapt = expect_args!(args, [bool])?;
+        Ok(Default::default())
     }
 
-    pub fn splash_set_top(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function splash_set_top")
+    pub fn splash_set_top(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.splash.top = expect_args!(args, [bool])?;
+        Ok(Default::default())
     }
 
-    pub fn splash_set_color(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function splash_set_color")
+    pub fn splash_set_color(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.splash.colour = expect_args!(args, [int])?;
+        Ok(Default::default())
     }
 
-    pub fn splash_set_main(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function splash_set_main")
+    pub fn splash_set_main(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.splash.main = expect_args!(args, [bool])?;
+        Ok(Default::default())
     }
 
-    pub fn splash_set_scale(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function splash_set_scale")
+    pub fn splash_set_scale(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.splash.scale = expect_args!(args, [int])?;
+        Ok(Default::default())
     }
 
-    pub fn splash_set_cursor(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function splash_set_cursor")
+    pub fn splash_set_cursor(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.splash.cursor = expect_args!(args, [bool])?;
+        Ok(Default::default())
     }
 
-    pub fn splash_set_interrupt(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function splash_set_interrupt")
+    pub fn splash_set_interrupt(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.splash.interrupt = expect_args!(args, [bool])?;
+        Ok(Default::default())
     }
 
-    pub fn splash_set_stop_key(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function splash_set_stop_key")
+    pub fn splash_set_stop_key(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.splash.stop_key = expect_args!(args, [bool])?;
+        Ok(Default::default())
     }
 
-    pub fn splash_set_close_button(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function splash_set_close_button")
+    pub fn splash_set_close_button(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.splash.close_button = expect_args!(args, [bool])?;
+        Ok(Default::default())
     }
 
-    pub fn splash_set_stop_mouse(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function splash_set_stop_mouse")
+    pub fn splash_set_stop_mouse(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.splash.stop_mouse = expect_args!(args, [bool])?;
+        Ok(Default::default())
     }
 
     pub fn splash_show_video(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function splash_show_video")
+        stub::unimplemented("splash_show_video")
     }
 
-    pub fn splash_show_image(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function splash_show_image")
+    pub fn splash_show_image(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (fname, delay) = expect_args!(args, [string, int])?;
+        if let Some(content) = self.load_splash_image(&fname) {
+            self.show_splash(content, self.splash.clone(), delay);
+        }
+        Ok(Default::default())
     }
 
-    pub fn splash_show_text(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function splash_show_text")
+    pub fn splash_show_text(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (fname, delay) = expect_args!(args, [string, int])?;
+        if let Some(content) = self.load_splash_text(&fname) {
+            self.show_splash(content, self.splash.clone(), delay);
+        }
+        Ok(Default::default())
     }
 
-    pub fn splash_show_web(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function splash_show_web")
+    pub fn splash_show_web(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (fname, delay) = expect_args!(args, [string, int])?;
+        if let Some(content) = self.load_splash_web(&fname) {
+            self.show_splash(content, self.splash.clone(), delay);
+        }
+        Ok(Default::default())
     }
 
-    pub fn show_image(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function show_image")
+    pub fn show_image(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (fname, full, delay) = expect_args!(args, [string, bool, int])?;
+        if let Some(content) = self.load_splash_image(&fname) {
+            let settings = splash::Settings { fullscreen: full, ..Default::default() };
+            self.show_splash(content, settings, delay);
+        }
+        Ok(Default::default())
     }
 
     pub fn show_video(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function show_video")
+        stub::unimplemented("show_video")
     }
 
-    pub fn show_text(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function show_text")
+    pub fn show_text(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (fname, full, colour, delay) = expect_args!(args, [string, bool, int, int])?;
+        if let Some(mut content) = self.load_splash_text(&fname) {
+            if let splash::Content::Text(document) = &mut content {
+                document.background = Some(colour);
+            }
+            let settings = splash::Settings { fullscreen: full, ..Default::default() };
+            self.show_splash(content, settings, delay);
+        }
+        Ok(Default::default())
     }
 
-    pub fn show_message(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function show_message")
+    pub fn show_message(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let text = expect_args!(args, [any])?;
+        self.ask_message(&text.repr())?;
+        Ok(Default::default())
     }
 
-    pub fn show_question(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function show_question")
+    pub fn show_question(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let text = expect_args!(args, [any])?;
+        Ok(self.ask_question(&text.repr())?.into())
     }
 
     pub fn show_error(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let (text, _abort) = expect_args!(args, [string, bool])?;
-        Err(gml::Error::FunctionError("show_error".into(), text.into()))
+        let (text, abort) = expect_args!(args, [string, bool])?;
+        let stack = self.call_stack.iter().map(|frame| frame.trace(self)).collect();
+        let error = gml::Error::Traced(Box::new(gml::Error::Raised(text.into())), stack);
+        self.report_error(error, true, abort)?;
+        Ok(Default::default())
     }
 
-    pub fn show_info(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function show_info")
+    pub fn show_info(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.show_game_info();
+        Ok(Default::default())
     }
 
-    pub fn load_info(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function load_info")
+    pub fn load_info(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let fname = expect_args!(args, [string])?;
+        match std::fs::read(file::to_path(&fname).as_ref()) {
+            Ok(text) => self.game_info.text = text.into(),
+            Err(e) => eprintln!("Warning: load_info on {} failed: {}", fname, e),
+        }
+        Ok(Default::default())
     }
 
-    pub fn highscore_show(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function highscore_show")
+    pub fn highscore_show(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let score = expect_args!(args, [int])?;
+        self.show_highscore(score, true)?;
+        Ok(Default::default())
     }
 
-    pub fn highscore_set_background(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function highscore_set_background")
+    pub fn highscore_set_background(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.highscores.style.background = expect_args!(args, [int])?;
+        Ok(Default::default())
     }
 
-    pub fn highscore_set_border(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function highscore_set_border")
+    pub fn highscore_set_border(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.highscores.style.border = expect_args!(args, [bool])?;
+        Ok(Default::default())
     }
 
-    pub fn highscore_set_font(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function highscore_set_font")
+    pub fn highscore_set_font(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (name, size, style) = expect_args!(args, [bytes, int, int])?;
+        self.highscores.style.font_name = name;
+        self.highscores.style.font_size = size;
+        self.highscores.style.font_style = style;
+        Ok(Default::default())
     }
 
-    pub fn highscore_set_strings(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function highscore_set_strings")
+    pub fn highscore_set_strings(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (caption, nobody, escape) = expect_args!(args, [bytes, bytes, bytes])?;
+        self.highscores.style.caption = caption;
+        self.highscores.style.nobody = nobody;
+        self.highscores.style.escape = escape;
+        Ok(Default::default())
     }
 
-    pub fn highscore_set_colors(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function highscore_set_colors")
+    pub fn highscore_set_colors(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (back, new, other) = expect_args!(args, [int, int, int])?;
+        self.highscores.style.back_colour = back;
+        self.highscores.style.new_colour = new;
+        self.highscores.style.other_colour = other;
+        Ok(Default::default())
     }
 
-    pub fn highscore_show_ext(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 7
-        unimplemented!("Called unimplemented kernel function highscore_show_ext")
+    pub fn highscore_show_ext(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (score, background, border, new_colour, other_colour, font_name, font_size) =
+            expect_args!(args, [int, int, bool, int, int, bytes, int])?;
+        let style = &mut self.highscores.style;
+        style.background = background;
+        style.border = border;
+        style.new_colour = new_colour;
+        style.other_colour = other_colour;
+        style.font_name = font_name;
+        style.font_size = font_size;
+        self.show_highscore(score, true)?;
+        Ok(Default::default())
     }
 
-    pub fn highscore_clear(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function highscore_clear")
+    pub fn highscore_clear(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.highscores.clear();
+        Ok(Default::default())
     }
 
-    pub fn highscore_add(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function highscore_add")
+    pub fn highscore_add(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (name, score) = expect_args!(args, [bytes, int])?;
+        self.highscores.add(name, score);
+        Ok(Default::default())
     }
 
-    pub fn highscore_add_current(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function highscore_add_current")
+    pub fn highscore_add_current(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.show_highscore(self.score, false)?;
+        Ok(Default::default())
     }
 
-    pub fn highscore_value(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function highscore_value")
+    pub fn highscore_value(&self, args: &[Value]) -> gml::Result<Value> {
+        let place = expect_args!(args, [int])?;
+        if (1..=highscore::PLACES as i32).contains(&place) {
+            Ok(self.highscores.get(place as usize - 1).map_or(0, |e| e.score).into())
+        } else {
+            Ok(Default::default())
+        }
     }
 
-    pub fn highscore_name(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function highscore_name")
+    pub fn highscore_name(&self, args: &[Value]) -> gml::Result<Value> {
+        let place = expect_args!(args, [int])?;
+        if (1..=highscore::PLACES as i32).contains(&place) {
+            match self.highscores.get(place as usize - 1) {
+                Some(entry) => Ok(entry.name.clone().into()),
+                None => Ok(self.highscores.style.nobody.clone().into()),
+            }
+        } else {
+            Ok("".into())
+        }
     }
 
-    pub fn draw_highscore(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function draw_highscore")
+    pub fn draw_highscore(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (x1, y1, x2, y2) = expect_args!(args, [real, real, real, real])?;
+        self.draw_highscore_list((x1, y1, x2, y2), 1.into(), None, None, self.draw_alpha, None);
+        Ok(Default::default())
     }
 
-    pub fn show_message_ext(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function show_message_ext")
+    pub fn show_message_ext(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (text, but1, but2, but3) = expect_args!(args, [any, bytes, bytes, bytes])?;
+        Ok(self.ask_message_ext(&text.repr(), &[but1, but2, but3])?.into())
     }
 
-    pub fn message_background(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function message_background")
-        // TODO
+    pub fn message_background(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.message_style.background = expect_args!(args, [int])?;
         Ok(Default::default())
     }
 
-    pub fn message_button(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function message_button")
-        // TODO
+    pub fn message_button(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.message_style.button = expect_args!(args, [int])?;
         Ok(Default::default())
     }
 
-    pub fn message_alpha(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function message_alpha")
-        // TODO
+    pub fn message_alpha(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.message_style.alpha = expect_args!(args, [real])?;
         Ok(Default::default())
     }
 
-    pub fn message_text_font(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        //unimplemented!("Called unimplemented kernel function message_text_font")
-        // TODO
+    pub fn message_text_font(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (name, size, colour, style) = expect_args!(args, [bytes, int, int, int])?;
+        let font = &mut self.message_style.text_font;
+        (font.name, font.size, font.colour, font.style) = (name, size, colour, style);
         Ok(Default::default())
     }
 
-    pub fn message_button_font(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        //unimplemented!("Called unimplemented kernel function message_button_font")
-        // TODO
+    pub fn message_button_font(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (name, size, colour, style) = expect_args!(args, [bytes, int, int, int])?;
+        let font = &mut self.message_style.button_font;
+        (font.name, font.size, font.colour, font.style) = (name, size, colour, style);
         Ok(Default::default())
     }
 
-    pub fn message_input_font(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        //unimplemented!("Called unimplemented kernel function message_input_font")
-        // TODO
+    pub fn message_input_font(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (name, size, colour, style) = expect_args!(args, [bytes, int, int, int])?;
+        let font = &mut self.message_style.input_font;
+        (font.name, font.size, font.colour, font.style) = (name, size, colour, style);
         Ok(Default::default())
     }
 
-    pub fn message_text_charset(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        //unimplemented!("Called unimplemented kernel function message_text_charset")
-        // TODO
+    pub fn message_text_charset(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (kind, charset) = expect_args!(args, [int, int])?;
+        let style = &mut self.message_style;
+        match kind {
+            0 => style.text_font.charset = charset,
+            1 => style.button_font.charset = charset,
+            2 => style.input_font.charset = charset,
+            _ => (),
+        }
         Ok(Default::default())
     }
 
-    pub fn message_mouse_color(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function message_mouse_color")
-        // TODO
+    pub fn message_mouse_color(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.message_style.mouse_colour = expect_args!(args, [int])?;
         Ok(Default::default())
     }
 
-    pub fn message_input_color(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function message_input_color")
-        // TODO
+    pub fn message_input_color(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.message_style.input_colour = expect_args!(args, [int])?;
         Ok(Default::default())
     }
 
-    pub fn message_position(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        //unimplemented!("Called unimplemented kernel function message_position")
-        // TODO
+    pub fn message_position(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.message_style.position = expect_args!(args, [int, int])?;
         Ok(Default::default())
     }
 
-    pub fn message_size(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        //unimplemented!("Called unimplemented kernel function message_size")
-        // TODO
+    pub fn message_size(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.message_style.size = expect_args!(args, [int, int])?;
         Ok(Default::default())
     }
 
-    pub fn message_caption(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        //unimplemented!("Called unimplemented kernel function message_caption")
-        // TODO
+    pub fn message_caption(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (border, caption) = expect_args!(args, [bool, bytes])?;
+        self.message_style.border = border;
+        self.message_style.caption = caption;
         Ok(Default::default())
     }
 
-    pub fn show_menu(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function show_menu")
+    pub fn show_menu(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (menu, default) = expect_args!(args, [bytes, any])?;
+        self.ask_menu(&menu, default, None)
     }
 
-    pub fn show_menu_pos(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function show_menu_pos")
+    pub fn show_menu_pos(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (x, y, menu, default) = expect_args!(args, [int, int, bytes, any])?;
+        self.ask_menu(&menu, default, Some((x, y)))
     }
 
-    pub fn get_integer(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function get_integer")
+    pub fn get_integer(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (prompt, default) = expect_args!(args, [any, any])?;
+        self.ask_integer(&prompt.repr(), default)
     }
 
-    pub fn get_string(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function get_string")
+    pub fn get_string(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (prompt, default) = expect_args!(args, [any, any])?;
+        self.ask_string(&prompt.repr(), default.repr())
     }
 
-    pub fn get_color(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function get_color")
+    pub fn get_color(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let default = expect_args!(args, [int])?;
+        Ok(self.ask_colour(default)?.into())
     }
 
-    pub fn get_open_filename(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function get_open_filename")
+    pub fn get_open_filename(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (filter, fname) = expect_args!(args, [string, string])?;
+        self.ask_filename(message::FileMode::Open, &filter, &fname, None)
     }
 
-    pub fn get_save_filename(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function get_save_filename")
+    pub fn get_save_filename(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (filter, fname) = expect_args!(args, [string, string])?;
+        self.ask_filename(message::FileMode::Save, &filter, &fname, None)
     }
 
-    pub fn get_directory(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function get_directory")
+    pub fn get_directory(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let dname = expect_args!(args, [string])?;
+        self.ask_filename(message::FileMode::Directory, "", &dname, None)
     }
 
-    pub fn get_directory_alt(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function get_directory_alt")
+    pub fn get_directory_alt(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (caption, root) = expect_args!(args, [string, string])?;
+        self.ask_filename(message::FileMode::Directory, "", &root, Some(&caption))
     }
 
     // NB: This function is constant because numlock state is tracked.
@@ -7089,21 +7241,15 @@ impl Game {
     }
 
     pub fn keyboard_key_press(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // let key = expect_args!(args, [int])?;
-        // if let Ok(vk) = u8::try_from(key) {
-        //     self.input.button_press(vk, true);
-        // }
-        // Ok(Default::default())
-        todo!() // should go on next event poll
+        // Expected arg count: 1
+        // should go on next event poll
+        stub::unimplemented("keyboard_key_press")
     }
 
     pub fn keyboard_key_release(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // let key = expect_args!(args, [int])?;
-        // if let Ok(vk) = u8::try_from(key) {
-        //     self.input.button_release(vk, true);
-        // }
-        // Ok(Default::default())
-        todo!() // should go on next event poll
+        // Expected arg count: 1
+        // should go on next event poll
+        stub::unimplemented("keyboard_key_release")
     }
 
     pub fn keyboard_set_map(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -7197,98 +7343,98 @@ impl Game {
 
     pub fn joystick_exists(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_exists")
+        //stub::unimplemented("joystick_exists")
         // TODO
         Ok(gml::FALSE.into())
     }
 
     pub fn joystick_direction(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_direction")
+        //stub::unimplemented("joystick_direction")
         // TODO
         Ok(101.into())
     }
 
     pub fn joystick_name(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_name")
+        //stub::unimplemented("joystick_name")
         // TODO
         Ok("".into())
     }
 
     pub fn joystick_axes(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_axes")
+        //stub::unimplemented("joystick_axes")
         // TODO
         Ok(0.into())
     }
 
     pub fn joystick_buttons(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_buttons")
+        //stub::unimplemented("joystick_buttons")
         // TODO
         Ok(0.into())
     }
 
     pub fn joystick_has_pov(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_has_pov")
+        //stub::unimplemented("joystick_has_pov")
         // TODO
         Ok(gml::FALSE.into())
     }
 
     pub fn joystick_check_button(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        //unimplemented!("Called unimplemented kernel function joystick_check_button")
+        //stub::unimplemented("joystick_check_button")
         // TODO
         Ok(gml::FALSE.into())
     }
 
     pub fn joystick_xpos(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_xpos")
+        //stub::unimplemented("joystick_xpos")
         // TODO
         Ok(0.into())
     }
 
     pub fn joystick_ypos(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_ypos")
+        //stub::unimplemented("joystick_ypos")
         // TODO
         Ok(0.into())
     }
 
     pub fn joystick_zpos(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_zpos")
+        //stub::unimplemented("joystick_zpos")
         // TODO
         Ok(0.into())
     }
 
     pub fn joystick_rpos(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_rpos")
+        //stub::unimplemented("joystick_rpos")
         // TODO
         Ok(0.into())
     }
 
     pub fn joystick_upos(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_upos")
+        //stub::unimplemented("joystick_upos")
         // TODO
         Ok(0.into())
     }
 
     pub fn joystick_vpos(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_vpos")
+        //stub::unimplemented("joystick_vpos")
         // TODO
         Ok(0.into())
     }
 
     pub fn joystick_pov(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_pov")
+        //stub::unimplemented("joystick_pov")
         // TODO
         Ok((-1).into())
     }
@@ -7340,147 +7486,183 @@ impl Game {
 
     pub fn mouse_wait(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mouse_wait")
+        stub::unimplemented("mouse_wait")
     }
 
-    pub fn mplay_init_ipx(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_init_ipx")
+    pub fn mplay_init_ipx(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        // Only TCP/IP is supported
+        Ok(false.into())
     }
 
-    pub fn mplay_init_tcpip(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_init_tcpip")
+    pub fn mplay_init_tcpip(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let address = expect_args!(args, [string])?;
+        // What arrives over the network can't be replayed, so there's no network play while recording or replaying
+        if self.play_type != PlayType::Normal {
+            return Ok(false.into())
+        }
+        self.mplay.init_tcpip(&address);
+        Ok(true.into())
     }
 
-    pub fn mplay_init_modem(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function mplay_init_modem")
+    pub fn mplay_init_modem(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [string, string])?;
+        // Only TCP/IP is supported
+        Ok(false.into())
     }
 
-    pub fn mplay_init_serial(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        unimplemented!("Called unimplemented kernel function mplay_init_serial")
+    pub fn mplay_init_serial(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [int, int, int, int, int])?;
+        // Only TCP/IP is supported
+        Ok(false.into())
     }
 
-    pub fn mplay_connect_status(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_connect_status")
+    pub fn mplay_connect_status(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(if self.mplay.connected() { 2 } else { 0 }.into())
     }
 
-    pub fn mplay_end(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_end")
+    pub fn mplay_end(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.mplay.end();
+        Ok(Default::default())
     }
 
-    pub fn mplay_session_mode(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_session_mode")
+    pub fn mplay_session_mode(&mut self, args: &[Value]) -> gml::Result<Value> {
+        // Sessions always end when the host leaves, see Multiplayer
+        expect_args!(args, [bool])?;
+        Ok(Default::default())
     }
 
-    pub fn mplay_session_create(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function mplay_session_create")
+    pub fn mplay_session_create(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (name, max_players, player_name) = expect_args!(args, [bytes, int, bytes])?;
+        Ok(self.mplay.create_session(name, max_players.max(0) as usize, player_name).into())
     }
 
-    pub fn mplay_session_find(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_session_find")
+    pub fn mplay_session_find(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.mplay.find_sessions().into())
     }
 
-    pub fn mplay_session_name(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_session_name")
+    pub fn mplay_session_name(&self, args: &[Value]) -> gml::Result<Value> {
+        let index = expect_args!(args, [int])?;
+        Ok(usize::try_from(index)
+            .ok()
+            .and_then(|i| self.mplay.found_session(i))
+            .map_or_else(|| "".into(), |s| s.name.clone().into()))
     }
 
-    pub fn mplay_session_join(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function mplay_session_join")
+    pub fn mplay_session_join(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (index, player_name) = expect_args!(args, [int, bytes])?;
+        match usize::try_from(index) {
+            Ok(index) => Ok(self.mplay.join_session(index, player_name).into()),
+            Err(_) => Ok(false.into()),
+        }
     }
 
-    pub fn mplay_session_status(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_session_status")
+    pub fn mplay_session_status(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.mplay.session_status().into())
     }
 
-    pub fn mplay_session_end(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_session_end")
+    pub fn mplay_session_end(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.mplay.end_session();
+        Ok(Default::default())
     }
 
-    pub fn mplay_player_find(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_player_find")
+    pub fn mplay_player_find(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.mplay.find_players().into())
     }
 
-    pub fn mplay_player_name(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_player_name")
+    pub fn mplay_player_name(&self, args: &[Value]) -> gml::Result<Value> {
+        let index = expect_args!(args, [int])?;
+        Ok(usize::try_from(index)
+            .ok()
+            .and_then(|i| self.mplay.found_player(i))
+            .map_or_else(|| "".into(), |p| p.name.clone().into()))
     }
 
-    pub fn mplay_player_id(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_player_id")
+    pub fn mplay_player_id(&self, args: &[Value]) -> gml::Result<Value> {
+        let index = expect_args!(args, [int])?;
+        Ok(usize::try_from(index).ok().and_then(|i| self.mplay.found_player(i)).map_or(0, |p| p.id).into())
     }
 
-    pub fn mplay_data_write(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function mplay_data_write")
+    pub fn mplay_data_write(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (index, value) = expect_args!(args, [int, any])?;
+        if let Ok(index) = usize::try_from(index) {
+            self.mplay.write_data(index, value);
+        }
+        Ok(Default::default())
     }
 
-    pub fn mplay_data_read(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_data_read")
+    pub fn mplay_data_read(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let index = expect_args!(args, [int])?;
+        Ok(usize::try_from(index).map_or_else(|_| Default::default(), |i| self.mplay.read_data(i)))
     }
 
-    pub fn mplay_data_mode(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_data_mode")
+    pub fn mplay_data_mode(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let guaranteed = expect_args!(args, [bool])?;
+        self.mplay.set_data_mode(guaranteed);
+        Ok(self.mplay.data_mode().into())
     }
 
-    pub fn mplay_message_send(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function mplay_message_send")
+    pub fn mplay_message_send(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (player, id, value) = expect_args!(args, [any, int, any])?;
+        match self.mplay.player_id(&player) {
+            Some(to) => Ok(self.mplay.send_message(to, id, value, false).into()),
+            None => Ok(false.into()),
+        }
     }
 
-    pub fn mplay_message_send_guaranteed(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function mplay_message_send_guaranteed")
+    pub fn mplay_message_send_guaranteed(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (player, id, value) = expect_args!(args, [any, int, any])?;
+        match self.mplay.player_id(&player) {
+            Some(to) => Ok(self.mplay.send_message(to, id, value, true).into()),
+            None => Ok(false.into()),
+        }
     }
 
-    pub fn mplay_message_receive(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_message_receive")
+    pub fn mplay_message_receive(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let player = expect_args!(args, [any])?;
+        match self.mplay.player_id(&player) {
+            Some(from) => Ok(self.mplay.receive_message(from).into()),
+            None => Ok(false.into()),
+        }
     }
 
-    pub fn mplay_message_id(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_message_id")
+    pub fn mplay_message_id(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.mplay.current.as_ref().map_or(0, |m| m.id).into())
     }
 
-    pub fn mplay_message_value(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_message_value")
+    pub fn mplay_message_value(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.mplay.current.as_ref().map_or_else(Default::default, |m| m.value.clone()))
     }
 
-    pub fn mplay_message_player(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_message_player")
+    pub fn mplay_message_player(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.mplay.current.as_ref().map_or(0, |m| m.from).into())
     }
 
-    pub fn mplay_message_name(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_message_name")
+    pub fn mplay_message_name(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.mplay.current.as_ref().map_or_else(|| "".into(), |m| m.name.clone().into()))
     }
 
-    pub fn mplay_message_count(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_message_count")
+    pub fn mplay_message_count(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let player = expect_args!(args, [any])?;
+        Ok(self.mplay.player_id(&player).map_or(0, |from| self.mplay.message_count(from)).into())
     }
 
-    pub fn mplay_message_clear(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_message_clear")
+    pub fn mplay_message_clear(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let player = expect_args!(args, [any])?;
+        if let Some(from) = self.mplay.player_id(&player) {
+            self.mplay.clear_messages(from);
+        }
+        Ok(Default::default())
     }
 
     pub fn mplay_ipaddress(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -7608,92 +7790,92 @@ impl Game {
 
     pub fn external_define0(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function external_define0")
+        stub::unimplemented("external_define0")
     }
 
     pub fn external_call0(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function external_call0")
+        stub::unimplemented("external_call0")
     }
 
     pub fn external_define1(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function external_define1")
+        stub::unimplemented("external_define1")
     }
 
     pub fn external_call1(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function external_call1")
+        stub::unimplemented("external_call1")
     }
 
     pub fn external_define2(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 5
-        unimplemented!("Called unimplemented kernel function external_define2")
+        stub::unimplemented("external_define2")
     }
 
     pub fn external_call2(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function external_call2")
+        stub::unimplemented("external_call2")
     }
 
     pub fn external_define3(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 6
-        unimplemented!("Called unimplemented kernel function external_define3")
+        stub::unimplemented("external_define3")
     }
 
     pub fn external_call3(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function external_call3")
+        stub::unimplemented("external_call3")
     }
 
     pub fn external_define4(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 7
-        unimplemented!("Called unimplemented kernel function external_define4")
+        stub::unimplemented("external_define4")
     }
 
     pub fn external_call4(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 5
-        unimplemented!("Called unimplemented kernel function external_call4")
+        stub::unimplemented("external_call4")
     }
 
     pub fn external_define5(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function external_define5")
+        stub::unimplemented("external_define5")
     }
 
     pub fn external_call5(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 6
-        unimplemented!("Called unimplemented kernel function external_call5")
+        stub::unimplemented("external_call5")
     }
 
     pub fn external_define6(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function external_define6")
+        stub::unimplemented("external_define6")
     }
 
     pub fn external_call6(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 7
-        unimplemented!("Called unimplemented kernel function external_call6")
+        stub::unimplemented("external_call6")
     }
 
     pub fn external_define7(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function external_define7")
+        stub::unimplemented("external_define7")
     }
 
     pub fn external_call7(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 8
-        unimplemented!("Called unimplemented kernel function external_call7")
+        stub::unimplemented("external_call7")
     }
 
     pub fn external_define8(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function external_define8")
+        stub::unimplemented("external_define8")
     }
 
     pub fn external_call8(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 9
-        unimplemented!("Called unimplemented kernel function external_call8")
+        stub::unimplemented("external_call8")
     }
 
     pub fn execute_string(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
@@ -7706,7 +7888,7 @@ impl Game {
                     }
                     // Note: GM8 does not update the argument_count here to (args.len() - 1) as it should
                     let mut new_context = Context::copy_with_args(context, new_args, context.argument_count);
-                    self.execute(&instrs, &mut new_context)?;
+                    self.execute_frame(CodeSource::ExecuteString, &instrs, &mut new_context)?;
                     Ok(new_context.return_value)
                 },
                 Err(e) => Err(gml::Error::FunctionError("execute_string".into(), e.message)),
@@ -7908,17 +8090,17 @@ impl Game {
 
     pub fn clipboard_has_text(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function clipboard_has_text")
+        stub::unimplemented("clipboard_has_text")
     }
 
     pub fn clipboard_set_text(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function clipboard_set_text")
+        stub::unimplemented("clipboard_set_text")
     }
 
     pub fn clipboard_get_text(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function clipboard_get_text")
+        stub::unimplemented("clipboard_get_text")
     }
 
     pub fn date_current_datetime(&self, args: &[Value]) -> gml::Result<Value> {
@@ -7970,12 +8152,12 @@ impl Game {
 
     pub fn date_inc_year(_args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_inc_year")
+        stub::unimplemented("date_inc_year")
     }
 
     pub fn date_inc_month(_args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_inc_month")
+        stub::unimplemented("date_inc_month")
     }
 
     pub fn date_inc_week(args: &[Value]) -> gml::Result<Value> {
@@ -8069,97 +8251,97 @@ impl Game {
 
     pub fn date_year_span(_args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_year_span")
+        stub::unimplemented("date_year_span")
     }
 
     pub fn date_month_span(_args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_month_span")
+        stub::unimplemented("date_month_span")
     }
 
     pub fn date_week_span(_args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_week_span")
+        stub::unimplemented("date_week_span")
     }
 
     pub fn date_day_span(_args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_day_span")
+        stub::unimplemented("date_day_span")
     }
 
     pub fn date_hour_span(_args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_hour_span")
+        stub::unimplemented("date_hour_span")
     }
 
     pub fn date_minute_span(_args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_minute_span")
+        stub::unimplemented("date_minute_span")
     }
 
     pub fn date_second_span(_args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_second_span")
+        stub::unimplemented("date_second_span")
     }
 
     pub fn date_compare_datetime(_args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_compare_datetime")
+        stub::unimplemented("date_compare_datetime")
     }
 
     pub fn date_compare_date(_args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_compare_date")
+        stub::unimplemented("date_compare_date")
     }
 
     pub fn date_compare_time(_args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_compare_time")
+        stub::unimplemented("date_compare_time")
     }
 
     pub fn date_date_of(_args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function date_date_of")
+        stub::unimplemented("date_date_of")
     }
 
     pub fn date_time_of(_args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function date_time_of")
+        stub::unimplemented("date_time_of")
     }
 
     pub fn date_datetime_string(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function date_datetime_string")
+        stub::unimplemented("date_datetime_string")
     }
 
     pub fn date_date_string(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function date_date_string")
+        stub::unimplemented("date_date_string")
     }
 
     pub fn date_time_string(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function date_time_string")
+        stub::unimplemented("date_time_string")
     }
 
     pub fn date_days_in_month(_args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function date_days_in_month")
+        stub::unimplemented("date_days_in_month")
     }
 
     pub fn date_days_in_year(_args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function date_days_in_year")
+        stub::unimplemented("date_days_in_year")
     }
 
     pub fn date_leap_year(_args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function date_leap_year")
+        stub::unimplemented("date_leap_year")
     }
 
     pub fn date_is_today(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function date_is_today")
+        stub::unimplemented("date_is_today")
     }
 
     pub fn sprite_exists(&self, args: &[Value]) -> gml::Result<Value> {
@@ -8350,6 +8532,7 @@ impl Game {
             origin_x,
             origin_y,
             per_frame_colliders: false,
+            mask: Default::default(),
         })));
         Ok(sprite_id.into())
     }
@@ -8450,6 +8633,7 @@ impl Game {
                 origin_x,
                 origin_y,
                 per_frame_colliders: false,
+                mask: Default::default(),
             })));
             Ok(sprite_id.into())
         } else {
@@ -8564,6 +8748,7 @@ impl Game {
             origin_x,
             origin_y,
             per_frame_colliders: false,
+            mask: Default::default(),
         })));
         Ok(sprite_id.into())
     }
@@ -8616,6 +8801,7 @@ impl Game {
                 origin_x,
                 origin_y,
                 per_frame_colliders: false,
+                mask: Default::default(),
             });
             Ok(Default::default())
         } else {
@@ -8623,14 +8809,102 @@ impl Game {
         }
     }
 
-    pub fn sprite_add_sprite(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function sprite_add_sprite")
+    /// Uploads the frames of a sprite loaded from a .gmspr file and makes its collision from the saved mask settings.
+    fn sprite_from_file(
+        &mut self,
+        function: &str,
+        name: gml::String,
+        mut sprite_file: file::SpriteFile,
+    ) -> gml::Result<asset::Sprite> {
+        let (width, height) = sprite_file.frames.first().map_or((0, 0), |f| f.dimensions());
+        for image in sprite_file.frames.iter_mut() {
+            asset::sprite::scale(image, width, height);
+        }
+        let mask = asset::sprite::MaskSettings {
+            kind: sprite_file.mask_kind,
+            tolerance: sprite_file.tolerance,
+            bbox_mode: sprite_file.bbox_mode,
+            bbox_left: sprite_file.bbox_left,
+            bbox_top: sprite_file.bbox_top,
+            bbox_right: sprite_file.bbox_right,
+            bbox_bottom: sprite_file.bbox_bottom,
+        };
+        let colliders = if sprite_file.frames.is_empty() {
+            Vec::new()
+        } else {
+            mask.make_colliders(&sprite_file.frames, sprite_file.sepmasks, width, height)
+        };
+        let (origin_x, origin_y) = (sprite_file.origin_x, sprite_file.origin_y);
+        let frames = sprite_file
+            .frames
+            .into_iter()
+            .map(|i| {
+                Ok(asset::sprite::Frame {
+                    width,
+                    height,
+                    atlas_ref: self
+                        .renderer
+                        .upload_sprite(i.into_raw().into_boxed_slice(), width as _, height as _, origin_x, origin_y)
+                        .map_err(|e| gml::Error::FunctionError(function.into(), e))?,
+                })
+            })
+            .collect::<gml::Result<_>>()?;
+        let mut sprite = asset::Sprite {
+            name,
+            frames,
+            colliders,
+            width,
+            height,
+            origin_x,
+            origin_y,
+            per_frame_colliders: sprite_file.sepmasks,
+            mask,
+            bbox_left: 0,
+            bbox_right: 0,
+            bbox_top: 0,
+            bbox_bottom: 0,
+        };
+        sprite.update_bbox();
+        Ok(sprite)
     }
 
-    pub fn sprite_replace_sprite(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function sprite_replace_sprite")
+    pub fn sprite_add_sprite(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let fname = expect_args!(args, [string])?;
+        let sprite_file = match file::load_sprite_file(file::to_path(&fname).as_ref()) {
+            Ok(sprite_file) => sprite_file,
+            Err(e) => {
+                eprintln!("Warning: sprite_add_sprite on {} failed: {}", fname, e);
+                return Ok((-1).into())
+            },
+        };
+        let sprite_id = self.assets.sprites.len();
+        let sprite =
+            self.sprite_from_file("sprite_add_sprite", format!("__newsprite{}", sprite_id).into(), sprite_file)?;
+        self.assets.sprites.push(Some(Box::new(sprite)));
+        Ok(sprite_id.into())
+    }
+
+    pub fn sprite_replace_sprite(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sprite_id, fname) = expect_args!(args, [int, string])?;
+        if let Some(sprite) = self.assets.sprites.get_asset(sprite_id) {
+            let name = sprite.name.clone();
+            let sprite_file = match file::load_sprite_file(file::to_path(&fname).as_ref()) {
+                Ok(sprite_file) => sprite_file,
+                Err(e) => {
+                    eprintln!("Warning: sprite_replace_sprite on {} failed: {}", fname, e);
+                    return Ok(Default::default())
+                },
+            };
+            let new_sprite = self.sprite_from_file("sprite_replace_sprite", name, sprite_file)?;
+            let sprite = self.assets.sprites.get_asset_mut(sprite_id).unwrap();
+            for frame in &sprite.frames {
+                self.renderer.delete_sprite(frame.atlas_ref);
+            }
+            **sprite = new_sprite;
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::NonexistentAsset(asset::Type::Sprite, sprite_id))
+        }
     }
 
     pub fn sprite_delete(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -8646,9 +8920,30 @@ impl Game {
         Ok(Default::default())
     }
 
-    pub fn sprite_duplicate(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function sprite_duplicate")
+    pub fn sprite_duplicate(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let src_id = expect_args!(args, [int])?;
+        if let Some(src) = self.assets.sprites.get_asset(src_id) {
+            let frames = src
+                .frames
+                .iter()
+                .map(|f| {
+                    Ok(asset::sprite::Frame {
+                        atlas_ref: self
+                            .renderer
+                            .duplicate_sprite(f.atlas_ref)
+                            .map_err(|e| gml::Error::FunctionError("sprite_duplicate".into(), e))?,
+                        width: f.width,
+                        height: f.height,
+                    })
+                })
+                .collect::<gml::Result<_>>()?;
+            let sprite_id = self.assets.sprites.len();
+            let sprite = asset::Sprite { name: format!("__newsprite{}", sprite_id).into(), frames, ..(**src).clone() };
+            self.assets.sprites.push(Some(Box::new(sprite)));
+            Ok(sprite_id.into())
+        } else {
+            Err(gml::Error::NonexistentAsset(asset::Type::Sprite, src_id))
+        }
     }
 
     pub fn sprite_assign(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -8683,6 +8978,7 @@ impl Game {
                     origin_x: src.origin_x,
                     origin_y: src.origin_y,
                     per_frame_colliders: src.per_frame_colliders,
+                    mask: src.mask,
                     bbox_left: src.bbox_left,
                     bbox_right: src.bbox_right,
                     bbox_top: src.bbox_top,
@@ -8697,9 +8993,67 @@ impl Game {
         }
     }
 
-    pub fn sprite_merge(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function sprite_merge")
+    pub fn sprite_merge(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (dst_id, src_id) = expect_args!(args, [int, int])?;
+        let src = match self.assets.sprites.get_asset(src_id) {
+            Some(src) => src,
+            None => return Err(gml::Error::NonexistentAsset(asset::Type::Sprite, src_id)),
+        };
+        let dst = match self.assets.sprites.get_asset(dst_id) {
+            Some(dst) => dst,
+            None => return Err(gml::Error::NonexistentAsset(asset::Type::Sprite, dst_id)),
+        };
+        // the new frames get stretched to the size of the sprite they're going into
+        let (width, height) = if dst.frames.is_empty() { (src.width, src.height) } else { (dst.width, dst.height) };
+        let renderer = &mut self.renderer;
+        let dump = |f: &asset::sprite::Frame| {
+            RgbaImage::from_vec(f.width, f.height, renderer.dump_sprite(f.atlas_ref).into_vec()).unwrap()
+        };
+        let mut images = dst.frames.iter().map(dump).collect::<Vec<_>>();
+        let old_count = images.len();
+        images.extend(src.frames.iter().map(dump));
+        for image in &mut images[old_count..] {
+            asset::sprite::scale(image, width, height);
+        }
+        let sprite = self.assets.sprites.get_asset_mut(dst_id).unwrap();
+        let (origin_x, origin_y) = (sprite.origin_x, sprite.origin_y);
+        let new_frames = images[old_count..]
+            .iter()
+            .map(|image| {
+                Ok(asset::sprite::Frame {
+                    width,
+                    height,
+                    atlas_ref: self
+                        .renderer
+                        .upload_sprite(image.as_raw().clone().into(), width as _, height as _, origin_x, origin_y)
+                        .map_err(|e| gml::Error::FunctionError("sprite_merge".into(), e))?,
+                })
+            })
+            .collect::<gml::Result<Vec<_>>>()?;
+        sprite.frames.extend(new_frames);
+        sprite.width = width;
+        sprite.height = height;
+        if images.len() > old_count {
+            // The old frames keep their colliders, since the mask settings they were made with aren't known.
+            // The new frames get the same kind of mask as the rest of the sprite.
+            let new_colliders =
+                sprite.mask.make_colliders(&images[old_count..], sprite.per_frame_colliders, width, height);
+            match (sprite.per_frame_colliders, sprite.colliders.first_mut()) {
+                (false, Some(collider)) => {
+                    let new = &new_colliders[0];
+                    for (old, new) in collider.data.iter_mut().zip(new.data.iter()) {
+                        *old |= new;
+                    }
+                    collider.bbox_left = collider.bbox_left.min(new.bbox_left);
+                    collider.bbox_top = collider.bbox_top.min(new.bbox_top);
+                    collider.bbox_right = collider.bbox_right.max(new.bbox_right);
+                    collider.bbox_bottom = collider.bbox_bottom.max(new.bbox_bottom);
+                },
+                _ => sprite.colliders.extend(new_colliders),
+            }
+            sprite.update_bbox();
+        }
+        Ok(Default::default())
     }
 
     pub fn sprite_save(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -8720,9 +9074,25 @@ impl Game {
         Ok(Default::default())
     }
 
-    pub fn sprite_save_strip(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function sprite_save_strip")
+    pub fn sprite_save_strip(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sprite_id, fname) = expect_args!(args, [int, string])?;
+        if let Some(sprite) = self.assets.sprites.get_asset(sprite_id) {
+            if sprite.frames.is_empty() {
+                return Ok(Default::default())
+            }
+            // frames go left to right, like the strips that sprite_add splits up
+            let mut strip = RgbaImage::new(sprite.width * sprite.frames.len() as u32, sprite.height);
+            for (i, frame) in sprite.frames.iter().enumerate() {
+                let image =
+                    RgbaImage::from_vec(frame.width, frame.height, self.renderer.dump_sprite(frame.atlas_ref).into())
+                        .unwrap();
+                image::imageops::overlay(&mut strip, &image, i as u32 * sprite.width, 0);
+            }
+            if let Err(e) = file::save_image(file::to_path(&fname).as_ref(), strip) {
+                return Err(gml::Error::FunctionError("sprite_save_strip".into(), e.to_string()))
+            }
+        }
+        Ok(Default::default())
     }
 
     pub fn sprite_collision_mask(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -8731,23 +9101,14 @@ impl Game {
         let tolerance = tolerance.clamp(0, 255) as u8;
         let sepmasks = sepmasks;
         if let Some(sprite) = self.assets.sprites.get_asset_mut(sprite_id) {
-            // formulate requested bounding box
-            let bbox = match bboxmode {
-                0 => None, // automatic
-                1 => Some(asset::sprite::BoundingBox {
-                    // full image
-                    left: 0,
-                    right: sprite.width - 1,
-                    top: 0,
-                    bottom: sprite.height - 1,
-                }),
-                _ => Some(asset::sprite::BoundingBox {
-                    // user defined
-                    left: bbleft.max(0) as u32,
-                    right: (bbright as u32).min(sprite.width),
-                    top: bbtop.max(0) as u32,
-                    bottom: (bbbottom as u32).min(sprite.height),
-                }),
+            sprite.mask = asset::sprite::MaskSettings {
+                kind,
+                tolerance,
+                bbox_mode: bboxmode,
+                bbox_left: bbleft,
+                bbox_top: bbtop,
+                bbox_right: bbright,
+                bbox_bottom: bbbottom,
             };
 
             // download frames from gpu
@@ -8758,30 +9119,8 @@ impl Game {
                 .map(|f| RgbaImage::from_vec(f.width, f.height, renderer.dump_sprite(f.atlas_ref).to_vec()).unwrap())
                 .collect::<Vec<RgbaImage>>();
 
-            // make colliders
-            sprite.colliders = match kind {
-                0 => asset::sprite::make_colliders_precise(&frames, tolerance, sepmasks), // precise
-                _ => asset::sprite::make_colliders_shaped(&frames, tolerance, sepmasks, bbox, match kind {
-                    1 => Some(asset::sprite::ColliderShape::Rectangle),
-                    2 => Some(asset::sprite::ColliderShape::Ellipse),
-                    3 => Some(asset::sprite::ColliderShape::Diamond),
-                    _ => None,
-                }),
-            };
-
-            // set bbox variables manually if needed (even if using precise collision)
-            if let Some(bbox) = bbox {
-                for c in &mut sprite.colliders {
-                    c.bbox_left = bbox.left;
-                    c.bbox_top = bbox.top;
-                    c.bbox_right = bbox.right;
-                    c.bbox_bottom = bbox.bottom;
-                }
-            }
-            sprite.bbox_left = sprite.colliders.iter().map(|c| c.bbox_left).min().unwrap();
-            sprite.bbox_top = sprite.colliders.iter().map(|c| c.bbox_top).min().unwrap();
-            sprite.bbox_right = sprite.colliders.iter().map(|c| c.bbox_right).max().unwrap();
-            sprite.bbox_bottom = sprite.colliders.iter().map(|c| c.bbox_bottom).max().unwrap();
+            sprite.colliders = sprite.mask.make_colliders(&frames, sepmasks, sprite.width, sprite.height);
+            sprite.update_bbox();
         }
         Ok(Default::default())
     }
@@ -8940,7 +9279,7 @@ impl Game {
 
     pub fn background_create_gradient(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 5
-        unimplemented!("Called unimplemented kernel function background_create_gradient")
+        stub::unimplemented("background_create_gradient")
     }
 
     pub fn background_add(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -9001,14 +9340,54 @@ impl Game {
         }
     }
 
-    pub fn background_add_background(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function background_add_background")
+    pub fn background_add_background(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let fname = expect_args!(args, [string])?;
+        let image = match file::load_background_file(file::to_path(&fname).as_ref()) {
+            Ok(image) => image,
+            Err(e) => {
+                eprintln!("Warning: background_add_background on {} failed: {}", fname, e);
+                return Ok((-1).into())
+            },
+        };
+        let (width, height) = image.as_ref().map_or((0, 0), |i| i.dimensions());
+        let atlas_ref = image
+            .map(|i| self.renderer.upload_sprite(i.into_raw().into_boxed_slice(), width as _, height as _, 0, 0))
+            .transpose()
+            .map_err(|e| gml::Error::FunctionError("background_add_background".into(), e))?;
+        let background_id = self.assets.backgrounds.len();
+        self.assets.backgrounds.push(Some(Box::new(asset::Background {
+            name: format!("__newbackground{}", background_id).into(),
+            width,
+            height,
+            atlas_ref,
+        })));
+        Ok(background_id.into())
     }
 
-    pub fn background_replace_background(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function background_replace_background")
+    pub fn background_replace_background(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (background_id, fname) = expect_args!(args, [int, string])?;
+        if let Some(background) = self.assets.backgrounds.get_asset_mut(background_id) {
+            let image = match file::load_background_file(file::to_path(&fname).as_ref()) {
+                Ok(image) => image,
+                Err(e) => {
+                    eprintln!("Warning: background_replace_background on {} failed: {}", fname, e);
+                    return Ok(Default::default())
+                },
+            };
+            if let Some(atlas_ref) = background.atlas_ref {
+                self.renderer.delete_sprite(atlas_ref);
+            }
+            let (width, height) = image.as_ref().map_or((0, 0), |i| i.dimensions());
+            background.atlas_ref = image
+                .map(|i| self.renderer.upload_sprite(i.into_raw().into_boxed_slice(), width as _, height as _, 0, 0))
+                .transpose()
+                .map_err(|e| gml::Error::FunctionError("background_replace_background".into(), e))?;
+            background.width = width;
+            background.height = height;
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::NonexistentAsset(asset::Type::Background, background_id))
+        }
     }
 
     pub fn background_delete(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -9154,6 +9533,10 @@ impl Game {
                 Some(x) => asset::sound::FileType::Wav(x),
                 None => return Ok((-1).into()),
             },
+            Some("mid") | Some("midi") => match self.audio.add_midi(data, sound_id, 1.0) {
+                Some(x) => asset::sound::FileType::Midi(x),
+                None => return Ok((-1).into()),
+            },
             _ => return Ok((-1).into()),
         };
         self.assets.sounds.push(Some(Box::new(asset::Sound {
@@ -9187,6 +9570,10 @@ impl Game {
                         Some(x) => asset::sound::FileType::Wav(x),
                         None => return Ok(0.into()),
                     },
+                    Some("mid") | Some("midi") => match self.audio.add_midi(data, sound_id, 1.0) {
+                        Some(x) => asset::sound::FileType::Midi(x),
+                        None => return Ok(0.into()),
+                    },
                     _ => return Ok(0.into()),
                 };
                 Ok(1.into())
@@ -9252,14 +9639,61 @@ impl Game {
         Ok(self.assets.fonts.get_asset(id).map(|x| x.last.into()).unwrap_or((-1).into()))
     }
 
-    pub fn font_add(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 6
-        unimplemented!("Called unimplemented kernel function font_add")
+    /// Renders a font from a font file for font_add and font_replace, giving back the new font.
+    fn create_font(
+        &mut self,
+        name: &[u8],
+        size: i32,
+        bold: bool,
+        italic: bool,
+        first: i32,
+        last: i32,
+    ) -> Result<asset::Font, String> {
+        let size = size.max(1) as u32;
+        let first = first.clamp(0, 255) as u8;
+        let last = (last.clamp(0, 255) as u8).max(first);
+        let glyphs = self.font_library.render(&self.decode_str(name), size, bold, italic, first, last);
+        let chars = asset::font::create_chars_from_glyphs(&glyphs, &mut self.renderer)?;
+        Ok(asset::Font {
+            name: "".into(),
+            sys_name: name.into(),
+            charset: 0,
+            size,
+            bold,
+            italic,
+            first,
+            last,
+            tallest_char_height: glyphs.iter().map(|g| g.height).max().unwrap_or(0),
+            chars,
+            own_graphics: true,
+        })
     }
 
-    pub fn font_replace(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 7
-        unimplemented!("Called unimplemented kernel function font_replace")
+    pub fn font_add(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (name, size, bold, italic, first, last) = expect_args!(args, [bytes, int, bool, bool, int, int])?;
+        let font_id = self.assets.fonts.len();
+        let mut font = self
+            .create_font(name.as_ref(), size, bold, italic, first, last)
+            .map_err(|e| gml::Error::FunctionError("font_add".into(), e))?;
+        font.name = format!("__newfont{}", font_id).into();
+        self.assets.fonts.push(Some(Box::new(font)));
+        Ok(font_id.into())
+    }
+
+    pub fn font_replace(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (font_id, name, size, bold, italic, first, last) =
+            expect_args!(args, [int, bytes, int, bool, bool, int, int])?;
+        if self.assets.fonts.get_asset(font_id).is_none() {
+            return Err(gml::Error::NonexistentAsset(asset::Type::Font, font_id))
+        }
+        let mut font = self
+            .create_font(name.as_ref(), size, bold, italic, first, last)
+            .map_err(|e| gml::Error::FunctionError("font_replace".into(), e))?;
+        let old_font = self.assets.fonts[font_id as usize].as_mut().unwrap();
+        old_font.delete_graphics(&mut self.renderer);
+        font.name = old_font.name.clone();
+        **old_font = font;
+        Ok(Default::default())
     }
 
     pub fn font_add_sprite(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -9292,12 +9726,7 @@ impl Game {
         let (font_id, sprite_id, first, prop, sep) = expect_args!(args, [int, int, int, bool, int])?;
         if let Some(font) = self.assets.fonts.get_asset_mut(font_id) {
             if let Some(sprite) = self.assets.sprites.get_asset(sprite_id) {
-                if font.own_graphics {
-                    // font_add isn't in yet but atm for ttfs all characters are on the same texture
-                    if let Some(c) = font.get_char(font.first) {
-                        self.renderer.delete_sprite(c.atlas_ref);
-                    }
-                }
+                font.delete_graphics(&mut self.renderer);
                 let chars = asset::font::create_chars_from_sprite(sprite, prop, sep, &self.renderer);
                 font.sys_name = "".into();
                 font.size = 12;
@@ -9319,12 +9748,7 @@ impl Game {
     pub fn font_delete(&mut self, args: &[Value]) -> gml::Result<Value> {
         let font_id = expect_args!(args, [int])?;
         if let Some(font) = self.assets.fonts.get_asset(font_id) {
-            if font.own_graphics {
-                // font_add isn't in yet but atm for ttfs all characters are on the same texture
-                if let Some(c) = font.get_char(font.first) {
-                    self.renderer.delete_sprite(c.atlas_ref);
-                }
-            }
+            font.delete_graphics(&mut self.renderer);
         } else {
             return Err(gml::Error::FunctionError("font_delete".into(), "Trying to delete non-existing font".into()))
         }
@@ -9357,7 +9781,7 @@ impl Game {
                     *dest = src.clone();
                 }
                 let mut new_context = Context::copy_with_args(context, new_args, args.len() - 1);
-                self.execute(&instructions, &mut new_context)?;
+                self.execute_frame(CodeSource::Script(script_id as usize), &instructions, &mut new_context)?;
                 Ok(new_context.return_value)
             } else {
                 Err(gml::Error::NonexistentAsset(asset::Type::Script, script_id))
@@ -9559,9 +9983,16 @@ impl Game {
         }
     }
 
-    pub fn path_append(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function path_append")
+    pub fn path_append(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (path_id, src_id) = expect_args!(args, [int, int])?;
+        if let Some(src) = self.assets.paths.get_asset(src_id) {
+            let points = src.points.clone();
+            if let Some(path) = self.assets.paths.get_asset_mut(path_id) {
+                path.points.extend(points);
+                path.update();
+            }
+        }
+        Ok(Default::default())
     }
 
     pub fn path_delete(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -9583,9 +10014,14 @@ impl Game {
         Ok(Default::default())
     }
 
-    pub fn path_insert_point(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        unimplemented!("Called unimplemented kernel function path_insert_point")
+    pub fn path_insert_point(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (path_id, n, x, y, speed) = expect_args!(args, [int, int, real, real, real])?;
+        if let Some(path) = self.assets.paths.get_asset_mut(path_id) {
+            let n = n.clamp(0, path.points.len() as i32) as usize;
+            path.points.insert(n, asset::path::Point { x, y, speed });
+            path.update();
+        }
+        Ok(Default::default())
     }
 
     pub fn path_change_point(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -9603,14 +10039,26 @@ impl Game {
         Ok(Default::default())
     }
 
-    pub fn path_delete_point(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function path_delete_point")
+    pub fn path_delete_point(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (path_id, n) = expect_args!(args, [int, int])?;
+        if n >= 0 {
+            if let Some(path) = self.assets.paths.get_asset_mut(path_id) {
+                if (n as usize) < path.points.len() {
+                    path.points.remove(n as usize);
+                    path.update();
+                }
+            }
+        }
+        Ok(Default::default())
     }
 
-    pub fn path_clear_points(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function path_clear_points")
+    pub fn path_clear_points(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let path_id = expect_args!(args, [int])?;
+        if let Some(path) = self.assets.paths.get_asset_mut(path_id) {
+            path.points.clear();
+            path.update();
+        }
+        Ok(Default::default())
     }
 
     pub fn path_reverse(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -9752,7 +10200,7 @@ impl Game {
                 .compile(code.as_ref())
                 .map_err(|e| gml::Error::FunctionError("timeline_moment_add".into(), e.message))?;
 
-            timeline.moments.borrow_mut().entry(moment).or_insert(Default::default()).borrow_mut().push_code(instrs);
+            timeline.moments.borrow_mut().entry(moment).or_insert(Default::default()).borrow_mut().push_code(instrs, code);
         }
         Ok(Default::default())
     }
@@ -9954,7 +10402,7 @@ impl Game {
 
     pub fn object_delete(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function object_delete")
+        stub::unimplemented("object_delete")
     }
 
     pub fn object_event_clear(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -9976,10 +10424,10 @@ impl Game {
             let object_event_map = &mut object.events[ev_type as usize];
             match object_event_map.get_mut(&(ev_number as u32)) {
                 Some(tree) => {
-                    tree.borrow_mut().push_code(instrs);
+                    tree.borrow_mut().push_code(instrs, code);
                 },
                 None => {
-                    object_event_map.insert(ev_number as u32, action::Tree::new_from_code(instrs));
+                    object_event_map.insert(ev_number as u32, action::Tree::new_from_code(instrs, code));
                     self.refresh_event_holders();
                 },
             }
@@ -10031,7 +10479,7 @@ impl Game {
 
     pub fn room_set_code(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function room_set_code")
+        stub::unimplemented("room_set_code")
     }
 
     pub fn room_set_background_color(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -10240,17 +10688,17 @@ impl Game {
 
     pub fn room_tile_add(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 9
-        unimplemented!("Called unimplemented kernel function room_tile_add")
+        stub::unimplemented("room_tile_add")
     }
 
     pub fn room_tile_add_ext(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 12
-        unimplemented!("Called unimplemented kernel function room_tile_add_ext")
+        stub::unimplemented("room_tile_add_ext")
     }
 
     pub fn room_tile_clear(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function room_tile_clear")
+        stub::unimplemented("room_tile_clear")
     }
 
     pub fn part_type_create(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -11334,12 +11782,12 @@ impl Game {
 
     pub fn ds_queue_write(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function ds_queue_write")
+        stub::unimplemented("ds_queue_write")
     }
 
     pub fn ds_queue_read(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function ds_queue_read")
+        stub::unimplemented("ds_queue_read")
     }
 
     pub fn ds_list_create(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -12182,12 +12630,12 @@ impl Game {
 
     pub fn ds_grid_add_region(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 6
-        unimplemented!("Called unimplemented kernel function ds_grid_add_region")
+        stub::unimplemented("ds_grid_add_region")
     }
 
     pub fn ds_grid_multiply_region(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 6
-        unimplemented!("Called unimplemented kernel function ds_grid_multiply_region")
+        stub::unimplemented("ds_grid_multiply_region")
     }
 
     pub fn ds_grid_set_disk(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -12204,27 +12652,27 @@ impl Game {
 
     pub fn ds_grid_add_disk(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 5
-        unimplemented!("Called unimplemented kernel function ds_grid_add_disk")
+        stub::unimplemented("ds_grid_add_disk")
     }
 
     pub fn ds_grid_multiply_disk(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 5
-        unimplemented!("Called unimplemented kernel function ds_grid_multiply_disk")
+        stub::unimplemented("ds_grid_multiply_disk")
     }
 
     pub fn ds_grid_set_grid_region(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 8
-        unimplemented!("Called unimplemented kernel function ds_grid_set_grid_region")
+        stub::unimplemented("ds_grid_set_grid_region")
     }
 
     pub fn ds_grid_add_grid_region(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 8
-        unimplemented!("Called unimplemented kernel function ds_grid_add_grid_region")
+        stub::unimplemented("ds_grid_add_grid_region")
     }
 
     pub fn ds_grid_multiply_grid_region(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 8
-        unimplemented!("Called unimplemented kernel function ds_grid_multiply_grid_region")
+        stub::unimplemented("ds_grid_multiply_grid_region")
     }
 
     pub fn ds_grid_get(&self, args: &[Value]) -> gml::Result<Value> {
@@ -12294,22 +12742,22 @@ impl Game {
 
     pub fn ds_grid_get_disk_sum(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function ds_grid_get_disk_sum")
+        stub::unimplemented("ds_grid_get_disk_sum")
     }
 
     pub fn ds_grid_get_disk_max(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function ds_grid_get_disk_max")
+        stub::unimplemented("ds_grid_get_disk_max")
     }
 
     pub fn ds_grid_get_disk_min(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function ds_grid_get_disk_min")
+        stub::unimplemented("ds_grid_get_disk_min")
     }
 
     pub fn ds_grid_get_disk_mean(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function ds_grid_get_disk_mean")
+        stub::unimplemented("ds_grid_get_disk_mean")
     }
 
     pub fn ds_grid_value_exists(&self, args: &[Value]) -> gml::Result<Value> {
@@ -12391,7 +12839,7 @@ impl Game {
 
     pub fn ds_grid_shuffle(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function ds_grid_shuffle")
+        stub::unimplemented("ds_grid_shuffle")
     }
 
     pub fn ds_grid_write(&self, args: &[Value]) -> gml::Result<Value> {
@@ -12451,6 +12899,7 @@ impl Game {
             match &sound.handle {
                 FileType::Mp3(handle) => self.audio.play_mp3(handle, nanos),
                 FileType::Wav(handle) => self.audio.play_wav(handle, nanos),
+                FileType::Midi(handle) => self.audio.play_midi(handle, nanos),
                 FileType::None => (),
             }
             Ok(Default::default())
@@ -12463,9 +12912,11 @@ impl Game {
         let sound_id = expect_args!(args, [int])?;
         if let Some(sound) = self.assets.sounds.get_asset(sound_id) {
             use asset::sound::FileType;
+            let nanos = self.clock.as_nanos();
             match &sound.handle {
                 FileType::Mp3(handle) => self.audio.loop_mp3(handle),
                 FileType::Wav(handle) => self.audio.loop_wav(handle),
+                FileType::Midi(handle) => self.audio.loop_midi(handle, nanos),
                 FileType::None => (),
             }
             Ok(Default::default())
@@ -12494,10 +12945,12 @@ impl Game {
     pub fn sound_volume(&mut self, args: &[Value]) -> gml::Result<Value> {
         let (sound_id, volume) = expect_args!(args, [int, real])?;
         if let Some(sound) = self.assets.sounds.get_asset(sound_id) {
-            // Deliberately written in a way that will produce an error when Kind::Midi is added
+            // Deliberately written in a way that will produce an error when a new kind is added
             use asset::sound::FileType;
+            self.audio.cancel_fade(sound_id);
             match &sound.handle {
                 FileType::Wav(handle) => handle.set_volume(volume.into()),
+                FileType::Midi(handle) => handle.set_volume(volume.into()),
                 FileType::Mp3(_) => (),
                 FileType::None => (),
             }
@@ -12507,19 +12960,38 @@ impl Game {
         }
     }
 
-    pub fn sound_fade(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function sound_fade")
+    pub fn sound_fade(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, volume, time) = expect_args!(args, [int, real, real])?;
+        if let Some(sound) = self.assets.sounds.get_asset(sound_id) {
+            use asset::sound::FileType;
+            let current = match &sound.handle {
+                FileType::Wav(handle) => handle.volume(),
+                FileType::Midi(handle) => handle.volume(),
+                FileType::Mp3(_) | FileType::None => return Ok(Default::default()),
+            };
+            // The volume gets moved along at the start of each frame
+            self.audio.fade(sound_id, current, volume.into(), time.into(), self.clock.as_nanos());
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::NonexistentAsset(asset::Type::Sound, sound_id))
+        }
     }
 
-    pub fn sound_pan(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function sound_pan")
+    pub fn sound_pan(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, pan) = expect_args!(args, [int, real])?;
+        if let Some(sound) = self.assets.sounds.get_asset(sound_id) {
+            if let asset::sound::FileType::Wav(handle) = &sound.handle {
+                handle.set_pan(pan.into());
+            }
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::NonexistentAsset(asset::Type::Sound, sound_id))
+        }
     }
 
     pub fn sound_background_tempo(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        // Does nothing unless the sound is a midi, which we don't support yet
+        // Does nothing unless the sound is a midi, and changing the tempo of those isn't supported yet
         Ok(Default::default())
     }
 
@@ -12531,162 +13003,249 @@ impl Game {
 
     pub fn sound_set_search_directory(&mut self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function sound_set_search_directory")
+        stub::unimplemented("sound_set_search_directory")
     }
 
-    pub fn sound_effect_set(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function sound_effect_set")
+    /// Changes the effects on a sound, with the parameters clamped to DirectX 8's ranges.
+    fn change_sound_effects(&mut self, sound_id: i32, change: impl FnOnce(&mut audio::Effects)) -> gml::Result<Value> {
+        if self.assets.sounds.get_asset(sound_id).is_some() {
+            self.audio.set_effects(sound_id, change);
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::NonexistentAsset(asset::Type::Sound, sound_id))
+        }
     }
 
-    pub fn sound_effect_chorus(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 8
-        unimplemented!("Called unimplemented kernel function sound_effect_chorus")
+    pub fn sound_effect_set(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, effects) = expect_args!(args, [int, int])?;
+        self.change_sound_effects(sound_id, |e| e.enabled = (effects & 127) as u32)
     }
 
-    pub fn sound_effect_compressor(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 7
-        unimplemented!("Called unimplemented kernel function sound_effect_compressor")
+    pub fn sound_effect_chorus(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, wet_dry, depth, feedback, frequency, wave, delay, phase) =
+            expect_args!(args, [int, real, real, real, real, int, real, int])?;
+        self.change_sound_effects(sound_id, |e| {
+            e.chorus = audio::Modulation {
+                wet_dry: (wet_dry.into_inner() as f32).clamp(0.0, 100.0),
+                depth: (depth.into_inner() as f32).clamp(0.0, 100.0),
+                feedback: (feedback.into_inner() as f32).clamp(-99.0, 99.0),
+                frequency: (frequency.into_inner() as f32).clamp(0.0, 10.0),
+                sine: wave != 0,
+                delay: (delay.into_inner() as f32).clamp(0.0, 20.0),
+                phase: phase.clamp(0, 4),
+            }
+        })
     }
 
-    pub fn sound_effect_echo(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 6
-        unimplemented!("Called unimplemented kernel function sound_effect_echo")
+    pub fn sound_effect_compressor(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, gain, attack, release, threshold, ratio, delay) =
+            expect_args!(args, [int, real, real, real, real, real, real])?;
+        self.change_sound_effects(sound_id, |e| {
+            e.compressor = audio::Compressor {
+                gain: (gain.into_inner() as f32).clamp(-60.0, 60.0),
+                attack: (attack.into_inner() as f32).clamp(0.01, 500.0),
+                release: (release.into_inner() as f32).clamp(50.0, 3000.0),
+                threshold: (threshold.into_inner() as f32).clamp(-60.0, 0.0),
+                ratio: (ratio.into_inner() as f32).clamp(1.0, 100.0),
+                delay: (delay.into_inner() as f32).clamp(0.0, 4.0),
+            }
+        })
     }
 
-    pub fn sound_effect_flanger(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 8
-        unimplemented!("Called unimplemented kernel function sound_effect_flanger")
+    pub fn sound_effect_echo(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, wet_dry, feedback, left_delay, right_delay, pan_delay) =
+            expect_args!(args, [int, real, real, real, real, bool])?;
+        self.change_sound_effects(sound_id, |e| {
+            e.echo = audio::Echo {
+                wet_dry: (wet_dry.into_inner() as f32).clamp(0.0, 100.0),
+                feedback: (feedback.into_inner() as f32).clamp(0.0, 100.0),
+                left_delay: (left_delay.into_inner() as f32).clamp(1.0, 2000.0),
+                right_delay: (right_delay.into_inner() as f32).clamp(1.0, 2000.0),
+                pan_delay,
+            }
+        })
     }
 
-    pub fn sound_effect_gargle(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function sound_effect_gargle")
+    pub fn sound_effect_flanger(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, wet_dry, depth, feedback, frequency, wave, delay, phase) =
+            expect_args!(args, [int, real, real, real, real, int, real, int])?;
+        self.change_sound_effects(sound_id, |e| {
+            e.flanger = audio::Modulation {
+                wet_dry: (wet_dry.into_inner() as f32).clamp(0.0, 100.0),
+                depth: (depth.into_inner() as f32).clamp(0.0, 100.0),
+                feedback: (feedback.into_inner() as f32).clamp(-99.0, 99.0),
+                frequency: (frequency.into_inner() as f32).clamp(0.0, 10.0),
+                sine: wave != 0,
+                delay: (delay.into_inner() as f32).clamp(0.0, 4.0),
+                phase: phase.clamp(0, 4),
+            }
+        })
     }
 
-    pub fn sound_effect_equalizer(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function sound_effect_equalizer")
+    pub fn sound_effect_gargle(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, rate, wave) = expect_args!(args, [int, real, int])?;
+        self.change_sound_effects(sound_id, |e| {
+            e.gargle = audio::Gargle { rate: (rate.into_inner() as f32).clamp(1.0, 1000.0), square: wave != 0 }
+        })
     }
 
-    pub fn sound_effect_reverb(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        unimplemented!("Called unimplemented kernel function sound_effect_reverb")
+    pub fn sound_effect_equalizer(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, center, bandwidth, gain) = expect_args!(args, [int, real, real, real])?;
+        self.change_sound_effects(sound_id, |e| {
+            e.equalizer = audio::Equalizer {
+                center: (center.into_inner() as f32).clamp(80.0, 16000.0),
+                bandwidth: (bandwidth.into_inner() as f32).clamp(1.0, 36.0),
+                gain: (gain.into_inner() as f32).clamp(-15.0, 15.0),
+            }
+        })
     }
 
-    pub fn sound_3d_set_sound_position(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function sound_3d_set_sound_position")
+    pub fn sound_effect_reverb(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, gain, mix, time, ratio) = expect_args!(args, [int, real, real, real, real])?;
+        self.change_sound_effects(sound_id, |e| {
+            e.reverb = audio::Reverb {
+                gain: (gain.into_inner() as f32).clamp(-96.0, 0.0),
+                mix: (mix.into_inner() as f32).clamp(-96.0, 0.0),
+                time: (time.into_inner() as f32).clamp(0.001, 3000.0),
+                high_ratio: (ratio.into_inner() as f32).clamp(0.001, 0.999),
+            }
+        })
     }
 
-    pub fn sound_3d_set_sound_velocity(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function sound_3d_set_sound_velocity")
+    /// Changes how a 3D sound is heard. This does nothing to sounds that weren't loaded as 3D sounds.
+    fn change_sound_3d(&mut self, sound_id: i32, change: impl FnOnce(&mut audio::Sound3D)) -> gml::Result<Value> {
+        if let Some(sound) = self.assets.sounds.get_asset(sound_id) {
+            if let asset::sound::FileType::Wav(handle) = &sound.handle {
+                self.audio.set_sound_3d(handle, change);
+            }
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::NonexistentAsset(asset::Type::Sound, sound_id))
+        }
     }
 
-    pub fn sound_3d_set_sound_distance(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function sound_3d_set_sound_distance")
+    pub fn sound_3d_set_sound_position(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, x, y, z) = expect_args!(args, [int, real, real, real])?;
+        self.change_sound_3d(sound_id, |s| s.position = [x.into(), y.into(), z.into()])
     }
 
-    pub fn sound_3d_set_sound_cone(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 7
-        unimplemented!("Called unimplemented kernel function sound_3d_set_sound_cone")
+    pub fn sound_3d_set_sound_velocity(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, x, y, z) = expect_args!(args, [int, real, real, real])?;
+        self.change_sound_3d(sound_id, |s| s.velocity = [x.into(), y.into(), z.into()])
+    }
+
+    pub fn sound_3d_set_sound_distance(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, min_distance, max_distance) = expect_args!(args, [int, real, real])?;
+        self.change_sound_3d(sound_id, |s| {
+            s.min_distance = min_distance.into();
+            s.max_distance = max_distance.into();
+        })
+    }
+
+    pub fn sound_3d_set_sound_cone(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, x, y, z, angle_in, angle_out, volume_out) =
+            expect_args!(args, [int, real, real, real, real, real, real])?;
+        self.change_sound_3d(sound_id, |s| {
+            s.cone_direction = [x.into(), y.into(), z.into()];
+            s.cone_inside = f64::from(angle_in).clamp(0.0, 360.0);
+            s.cone_outside = f64::from(angle_out).clamp(0.0, 360.0);
+            s.cone_outside_volume = f64::from(volume_out).clamp(-10000.0, 0.0);
+        })
     }
 
     pub fn cd_init(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_init")
+        stub::unimplemented("cd_init")
     }
 
     pub fn cd_present(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_present")
+        stub::unimplemented("cd_present")
     }
 
     pub fn cd_number(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_number")
+        stub::unimplemented("cd_number")
     }
 
     pub fn cd_playing(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_playing")
+        stub::unimplemented("cd_playing")
     }
 
     pub fn cd_paused(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_paused")
+        stub::unimplemented("cd_paused")
     }
 
     pub fn cd_track(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_track")
+        stub::unimplemented("cd_track")
     }
 
     pub fn cd_length(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_length")
+        stub::unimplemented("cd_length")
     }
 
     pub fn cd_track_length(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function cd_track_length")
+        stub::unimplemented("cd_track_length")
     }
 
     pub fn cd_position(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_position")
+        stub::unimplemented("cd_position")
     }
 
     pub fn cd_track_position(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_track_position")
+        stub::unimplemented("cd_track_position")
     }
 
     pub fn cd_play(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function cd_play")
+        stub::unimplemented("cd_play")
     }
 
     pub fn cd_stop(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_stop")
+        stub::unimplemented("cd_stop")
     }
 
     pub fn cd_pause(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_pause")
+        stub::unimplemented("cd_pause")
     }
 
     pub fn cd_resume(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_resume")
+        stub::unimplemented("cd_resume")
     }
 
     pub fn cd_set_position(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function cd_set_position")
+        stub::unimplemented("cd_set_position")
     }
 
     pub fn cd_set_track_position(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function cd_set_track_position")
+        stub::unimplemented("cd_set_track_position")
     }
 
     pub fn cd_open_door(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_open_door")
+        stub::unimplemented("cd_open_door")
     }
 
     pub fn cd_close_door(&self, _args: &[Value]) -> gml::Result<Value> {
         // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_close_door")
+        stub::unimplemented("cd_close_door")
     }
 
-    pub fn mci_command(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function MCI_command")
+    pub fn mci_command(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let command = expect_args!(args, [string])?;
+        Ok(self.mci_send_string(command.as_ref()).into())
     }
 
     pub fn d3d_start(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -1419,13 +1419,13 @@ pub const FUNCTIONS: phf::OrderedMap<&'static str, Function> = phf_ordered_map!
     "parameter_count" => Function::Constant(Game::parameter_count),
     "parameter_string" => Function::Constant(Game::parameter_string),
     "environment_get_variable" => Function::Volatile(Game::environment_get_variable),
-    "registry_write_string" => Function::Volatile(Game::registry_write_string),
-    "registry_write_real" => Function::Volatile(Game::registry_write_real),
+    "registry_write_string" => Function::Engine(Game::registry_write_string),
+    "registry_write_real" => Function::Engine(Game::registry_write_real),
     "registry_read_string" => Function::Volatile(Game::registry_read_string),
     "registry_read_real" => Function::Volatile(Game::registry_read_real),
     "registry_exists" => Function::Volatile(Game::registry_exists),
-    "registry_write_string_ext" => Function::Volatile(Game::registry_write_string_ext),
-    "registry_write_real_ext" => Function::Volatile(Game::registry_write_real_ext),
+    "registry_write_string_ext" => Function::Engine(Game::registry_write_string_ext),
+    "registry_write_real_ext" => Function::Engine(Game::registry_write_real_ext),
     "registry_read_string_ext" => Function::Volatile(Game::registry_read_string_ext),
     "registry_read_real_ext" => Function::Volatile(Game::registry_read_real_ext),
     "registry_exists_ext" => Function::Volatile(Game::registry_exists_ext),
@@ -1559,14 +1559,14 @@ pub const FUNCTIONS: phf::OrderedMap<&'static str, Function> = phf_ordered_map!
     "mplay_data_write" => Function::Engine(Game::mplay_data_write),
     "mplay_data_read" => Function::Engine(Game::mplay_data_read),
     "mplay_data_mode" => Function::Engine(Game::mplay_data_mode),
-    "mplay_message_send" => Function::Volatile(Game::mplay_message_send),
-    "mplay_message_send_guaranteed" => Function::Volatile(Game::mplay_message_send_guaranteed),
+    "mplay_message_send" => Function::Engine(Game::mplay_message_send),
+    "mplay_message_send_guaranteed" => Function::Engine(Game::mplay_message_send_guaranteed),
     "mplay_message_receive" => Function::Engine(Game::mplay_message_receive),
     "mplay_message_id" => Function::Constant(Game::mplay_message_id),
     "mplay_message_value" => Function::Constant(Game::mplay_message_value),
     "mplay_message_player" => Function::Constant(Game::mplay_message_player),
     "mplay_message_name" => Function::Constant(Game::mplay_message_name),
-    "mplay_message_count" => Function::Constant(Game::mplay_message_count),
+    "mplay_message_count" => Function::Engine(Game::mplay_message_count),
     "mplay_message_clear" => Function::Engine(Game::mplay_message_clear),
     "mplay_ipaddress" => Function::Engine(Game::mplay_ipaddress),
     "event_inherited" => Function::Runtime(Game::event_inherited),
@@ -2059,7 +2059,7 @@ pub const FUNCTIONS: phf::OrderedMap<&'static str, Function> = phf_ordered_map!
     "cd_set_track_position" => Function::Volatile(Game::cd_set_track_position),
     "cd_open_door" => Function::Volatile(Game::cd_open_door),
     "cd_close_door" => Function::Volatile(Game::cd_close_door),
-    "MCI_command" => Function::Volatile(Game::mci_command),
+    "MCI_command" => Function::Engine(Game::mci_command),
     "d3d_start" => Function::Engine(Game::d3d_start),
     "d3d_end" => Function::Engine(Game::d3d_end),
     "d3d_set_perspective" => Function::Engine(Game::d3d_set_perspective),
@@ -1,4 +1,12 @@
-use std::{io, net};
+use crate::gml::{self, Value};
+use bincode::Options;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    net::{self, Ipv4Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant},
+};
 
 pub fn get_local_ip() -> io::Result<net::IpAddr> {
     // For the meaning of 0.0.0.0, see 'INADDR_ANY'. Port 0 states that we don't expect any
@@ -42,3 +50,753 @@ pub fn get_local_ip() -> io::Result<net::IpAddr> {
     socket.connect(&broadcast[..])?;
     Ok(socket.local_addr()?.ip())
 }
+
+/// The port sessions are hosted and looked for on, which is the one DirectPlay uses.
+pub const PORT: u16 = 47624;
+
+/// How many slots the shared data array has.
+pub const DATA_SIZE: usize = 10000;
+
+/// How long mplay_session_find waits for hosts to answer, and mplay_session_join for the host to let it in.
+const FIND_TIME: Duration = Duration::from_millis(500);
+const JOIN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The biggest packet anyone's allowed to send, which leaves room for a welcome with every data slot filled.
+const MAX_PACKET: usize = 16 << 20;
+
+/// How much can be waiting to be sent to a peer before it's given up on for not reading any of it.
+const MAX_BACKLOG: usize = 4 * MAX_PACKET;
+
+/// The biggest packet that's sent over UDP. Anything bigger goes over TCP, guaranteed or not, since
+/// datagrams that get split up along the way are much more likely to be lost.
+const MAX_DATAGRAM: usize = 1400;
+
+fn encode(packet: &Packet) -> io::Result<Vec<u8>> {
+    bincode::options()
+        .with_limit(MAX_PACKET as u64)
+        .serialize(packet)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Encodes a packet to go over UDP, unless it has to be guaranteed or it's too big for that.
+fn datagram(packet: &Packet, guaranteed: bool) -> Option<Vec<u8>> {
+    if guaranteed { None } else { encode(packet).ok().filter(|data| data.len() <= MAX_DATAGRAM) }
+}
+
+fn decode<T: DeserializeOwned>(data: &[u8]) -> io::Result<T> {
+    bincode::options()
+        .with_limit(MAX_PACKET as u64)
+        .deserialize(data)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Player {
+    pub id: i32,
+    pub name: gml::String,
+}
+
+/// A message from another player, as returned by mplay_message_receive.
+#[derive(Clone)]
+pub struct Message {
+    pub id: i32,
+    pub value: Value,
+    pub from: i32,
+    pub name: gml::String,
+}
+
+/// A session found by mplay_session_find.
+pub struct FoundSession {
+    pub name: gml::String,
+    address: SocketAddr,
+}
+
+/// Everything sent between players. Sessions are found over UDP, and everything else goes to and from the host
+/// over TCP, apart from data and messages that don't need to be guaranteed, which go over UDP too. The host
+/// passes on anything meant for other players.
+#[derive(Serialize, Deserialize)]
+enum Packet {
+    Find { game_id: i32 },
+    Session { game_id: i32, name: gml::String, port: u16 },
+    Join { game_id: i32, name: gml::String, port: u16 },
+    Welcome { id: i32, players: Vec<Player>, data: Vec<(usize, Value)> },
+    Refused,
+    PlayerJoined(Player),
+    PlayerLeft(i32),
+    Data { index: usize, value: Value },
+    Message { from: i32, to: i32, id: i32, value: Value },
+}
+
+/// A TCP connection sending and receiving length-prefixed packets, without ever blocking.
+struct Peer {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    outgoing: Vec<u8>,
+    pending: Vec<Packet>,
+    player: Option<i32>,
+    closed: bool,
+    datagram_address: Option<SocketAddr>,
+}
+
+impl Peer {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+        Ok(Self {
+            stream,
+            buffer: Vec::new(),
+            outgoing: Vec::new(),
+            pending: Vec::new(),
+            player: None,
+            closed: false,
+            datagram_address: None,
+        })
+    }
+
+    /// Sends a packet over UDP if it's been encoded as a datagram and the peer takes them,
+    /// or over the connection otherwise.
+    fn send_datagram(&mut self, socket: &UdpSocket, packet: &Packet, datagram: Option<&[u8]>) -> io::Result<()> {
+        match (datagram, self.datagram_address) {
+            (Some(datagram), Some(address)) => socket.send_to(datagram, address).map(drop),
+            _ => self.send(packet),
+        }
+    }
+
+    /// Queues a packet and sends as much as the connection will take right now.
+    fn send(&mut self, packet: &Packet) -> io::Result<()> {
+        let data = encode(packet)?;
+        if self.outgoing.len() + data.len() > MAX_BACKLOG {
+            self.closed = true;
+            return Err(io::Error::new(io::ErrorKind::WouldBlock, "peer isn't reading what's sent to it"))
+        }
+        self.outgoing.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        self.outgoing.extend_from_slice(&data);
+        self.flush()
+    }
+
+    /// Sends whatever's still queued, as far as possible without blocking.
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => drop(self.outgoing.drain(..n)),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends what's queued and reads whatever packets have arrived so far.
+    /// Marks the peer as closed if the connection was closed.
+    fn receive(&mut self) -> io::Result<Vec<Packet>> {
+        self.flush()?;
+        let mut chunk = [0u8; 4096];
+        while !self.closed {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => self.closed = true,
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+        let mut packets = std::mem::take(&mut self.pending);
+        while self.buffer.len() >= 4 {
+            let len = u32::from_le_bytes([self.buffer[0], self.buffer[1], self.buffer[2], self.buffer[3]]) as usize;
+            if len > MAX_PACKET {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "packet too big"))
+            }
+            if self.buffer.len() < len + 4 {
+                break
+            }
+            packets.push(decode(&self.buffer[4..len + 4])?);
+            self.buffer.drain(..len + 4);
+        }
+        Ok(packets)
+    }
+
+    /// Waits until a packet arrives or the time runs out.
+    fn wait(&mut self, timeout: Duration) -> io::Result<Option<Packet>> {
+        let start = Instant::now();
+        while start.elapsed() < timeout && !self.closed {
+            let mut packets = self.receive()?;
+            if !packets.is_empty() {
+                let packet = packets.remove(0);
+                self.pending = packets;
+                return Ok(Some(packet))
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        Ok(None)
+    }
+}
+
+/// The host's UDP socket is the one sessions are found on, and the client's only talks to the host.
+enum Session {
+    Host { listener: TcpListener, discovery: UdpSocket, clients: Vec<Peer>, name: gml::String, max_players: usize },
+    Client { host: Peer, socket: UdpSocket },
+}
+
+/// The state behind the mplay_* functions, which play over TCP/IP only.
+///
+/// Sessions end when the host leaves, so mplay_session_mode does nothing.
+pub struct Multiplayer {
+    game_id: i32,
+    port: u16,
+    address: Option<String>,
+    found_sessions: Vec<FoundSession>,
+    session: Option<Session>,
+    local_id: i32,
+    players: Vec<Player>,
+    found_players: Vec<Player>,
+    data: Vec<Value>,
+    guaranteed_data: bool,
+    messages: VecDeque<Message>,
+    pub current: Option<Message>,
+}
+
+impl Multiplayer {
+    pub fn new(game_id: i32) -> Self {
+        Self {
+            game_id,
+            port: PORT,
+            address: None,
+            found_sessions: Vec::new(),
+            session: None,
+            local_id: 0,
+            players: Vec::new(),
+            found_players: Vec::new(),
+            data: vec![Value::from(0.0); DATA_SIZE],
+            guaranteed_data: true,
+            messages: VecDeque::new(),
+            current: None,
+        }
+    }
+
+    /// Starts using TCP/IP, looking for sessions at the given address, or on the local network if it's empty.
+    pub fn init_tcpip(&mut self, address: &str) {
+        self.end();
+        self.address = Some(address.trim().to_string());
+    }
+
+    pub fn connected(&self) -> bool {
+        self.address.is_some()
+    }
+
+    pub fn end(&mut self) {
+        self.end_session();
+        self.address = None;
+        self.found_sessions.clear();
+    }
+
+    /// 0 for no session, 1 if this player created it and 2 if they joined it.
+    pub fn session_status(&self) -> i32 {
+        match self.session {
+            None => 0,
+            Some(Session::Host { .. }) => 1,
+            Some(Session::Client { .. }) => 2,
+        }
+    }
+
+    pub fn create_session(&mut self, name: gml::String, max_players: usize, player_name: gml::String) -> bool {
+        if !self.connected() {
+            return false
+        }
+        self.end_session();
+        let listener = match TcpListener::bind((Ipv4Addr::UNSPECIFIED, self.port)) {
+            Ok(listener) => listener,
+            Err(e) => {
+                eprintln!("Warning: couldn't host a multiplayer session on port {}: {}", self.port, e);
+                return false
+            },
+        };
+        let discovery = match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, self.port)) {
+            Ok(socket) => socket,
+            Err(e) => {
+                eprintln!("Warning: couldn't listen for multiplayer players on port {}: {}", self.port, e);
+                return false
+            },
+        };
+        if listener.set_nonblocking(true).and(discovery.set_nonblocking(true)).is_err() {
+            return false
+        }
+        self.local_id = 1;
+        self.players = vec![Player { id: self.local_id, name: player_name }];
+        self.data.fill(Value::from(0.0));
+        self.session = Some(Session::Host { listener, discovery, clients: Vec::new(), name, max_players });
+        true
+    }
+
+    /// Looks for sessions of this game, returning how many were found.
+    pub fn find_sessions(&mut self) -> usize {
+        self.found_sessions.clear();
+        let address = match &self.address {
+            Some(address) => address.clone(),
+            None => return 0,
+        };
+        let socket = match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)) {
+            Ok(socket) => socket,
+            Err(_) => return 0,
+        };
+        let _ = socket.set_broadcast(true);
+        let _ = socket.set_read_timeout(Some(Duration::from_millis(50)));
+        let targets: Vec<SocketAddr> = if address.is_empty() {
+            vec![(Ipv4Addr::BROADCAST, self.port).into(), (Ipv4Addr::LOCALHOST, self.port).into()]
+        } else {
+            (address.as_str(), self.port).to_socket_addrs().map(|a| a.collect()).unwrap_or_default()
+        };
+        if let Ok(find) = encode(&Packet::Find { game_id: self.game_id }) {
+            for target in targets {
+                let _ = socket.send_to(&find, target);
+            }
+        }
+
+        let start = Instant::now();
+        let mut buf = [0u8; 1024];
+        while start.elapsed() < FIND_TIME {
+            if let Ok((len, from)) = socket.recv_from(&mut buf) {
+                if let Ok(Packet::Session { game_id, name, port }) = decode(&buf[..len]) {
+                    let address = SocketAddr::new(from.ip(), port);
+                    if game_id == self.game_id && !self.found_sessions.iter().any(|s| s.address == address) {
+                        self.found_sessions.push(FoundSession { name, address });
+                    }
+                }
+            }
+        }
+        self.found_sessions.len()
+    }
+
+    pub fn found_session(&self, index: usize) -> Option<&FoundSession> {
+        self.found_sessions.get(index)
+    }
+
+    pub fn join_session(&mut self, index: usize, player_name: gml::String) -> bool {
+        let address = match self.found_sessions.get(index) {
+            Some(session) => session.address,
+            None => return false,
+        };
+        self.end_session();
+        let join = || -> io::Result<(Peer, UdpSocket, Option<Packet>)> {
+            let mut host = Peer::new(TcpStream::connect_timeout(&address, JOIN_TIMEOUT)?)?;
+            let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+            socket.set_nonblocking(true)?;
+            host.datagram_address = Some(address);
+            host.send(&Packet::Join { game_id: self.game_id, name: player_name, port: socket.local_addr()?.port() })?;
+            let reply = host.wait(JOIN_TIMEOUT)?;
+            Ok((host, socket, reply))
+        };
+        match join() {
+            Ok((host, socket, Some(Packet::Welcome { id, players, data }))) => {
+                self.local_id = id;
+                self.players = players;
+                self.data.fill(Value::from(0.0));
+                for (index, value) in data.into_iter().filter(|(i, _)| *i < DATA_SIZE) {
+                    self.data[index] = value;
+                }
+                self.session = Some(Session::Client { host, socket });
+                true
+            },
+            Ok(_) => false,
+            Err(e) => {
+                eprintln!("Warning: couldn't join multiplayer session at {}: {}", address, e);
+                false
+            },
+        }
+    }
+
+    pub fn end_session(&mut self) {
+        self.session = None;
+        self.local_id = 0;
+        self.players.clear();
+        self.found_players.clear();
+        self.messages.clear();
+        self.current = None;
+    }
+
+    /// Takes a snapshot of the players in the session for mplay_player_name and mplay_player_id,
+    /// returning how many there are.
+    pub fn find_players(&mut self) -> usize {
+        self.poll();
+        self.found_players = self.players.clone();
+        self.found_players.len()
+    }
+
+    pub fn found_player(&self, index: usize) -> Option<&Player> {
+        self.found_players.get(index)
+    }
+
+    /// Finds a player by their id or name, where 0 means everyone.
+    pub fn player_id(&self, player: &Value) -> Option<i32> {
+        match player {
+            Value::Real(id) => Some(id.round().to_i32()),
+            Value::Str(name) => self.players.iter().find(|p| p.name.as_ref() == name.as_ref()).map(|p| p.id),
+        }
+    }
+
+    pub fn read_data(&mut self, index: usize) -> Value {
+        self.poll();
+        self.data.get(index).cloned().unwrap_or_default()
+    }
+
+    pub fn write_data(&mut self, index: usize, value: Value) {
+        if let Some(slot) = self.data.get_mut(index) {
+            *slot = value.clone();
+            self.broadcast(&Packet::Data { index, value }, None, self.guaranteed_data);
+        }
+    }
+
+    /// Sets whether changes to the shared data are guaranteed to arrive, which they are to begin with.
+    pub fn set_data_mode(&mut self, guaranteed: bool) {
+        self.guaranteed_data = guaranteed;
+    }
+
+    pub fn data_mode(&self) -> bool {
+        self.guaranteed_data
+    }
+
+    /// Sends a message to a player, or everyone else if `to` is 0.
+    pub fn send_message(&mut self, to: i32, id: i32, value: Value, guaranteed: bool) -> bool {
+        if self.session.is_none() || (to != 0 && !self.players.iter().any(|p| p.id == to)) {
+            return false
+        }
+        let packet = Packet::Message { from: self.local_id, to, id, value };
+        match &mut self.session {
+            Some(Session::Client { host, socket }) => {
+                host.send_datagram(socket, &packet, datagram(&packet, guaranteed).as_deref()).is_ok()
+            },
+            _ => {
+                self.route(packet, None, guaranteed);
+                true
+            },
+        }
+    }
+
+    /// Takes the next message from the given player, or anyone if `from` is 0, and makes it the current one.
+    pub fn receive_message(&mut self, from: i32) -> bool {
+        self.poll();
+        match self.messages.iter().position(|m| from == 0 || m.from == from) {
+            Some(index) => {
+                self.current = self.messages.remove(index);
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn message_count(&mut self, from: i32) -> usize {
+        self.poll();
+        self.messages.iter().filter(|m| from == 0 || m.from == from).count()
+    }
+
+    pub fn clear_messages(&mut self, from: i32) {
+        self.poll();
+        self.messages.retain(|m| from != 0 && m.from != from);
+    }
+
+    /// Sends a packet to the other players: from a client that's just the host, and from the host it's every
+    /// client apart from `except`.
+    fn broadcast(&mut self, packet: &Packet, except: Option<i32>, guaranteed: bool) {
+        let datagram = datagram(packet, guaranteed);
+        match &mut self.session {
+            Some(Session::Host { clients, discovery, .. }) => {
+                for client in clients.iter_mut().filter(|c| c.player.is_some() && c.player != except) {
+                    let _ = client.send_datagram(discovery, packet, datagram.as_deref());
+                }
+            },
+            Some(Session::Client { host, socket }) => {
+                let _ = host.send_datagram(socket, packet, datagram.as_deref());
+            },
+            None => (),
+        }
+    }
+
+    /// Delivers a message on the host, passing it on to whoever it's for.
+    fn route(&mut self, packet: Packet, sender: Option<i32>, guaranteed: bool) {
+        if let Packet::Message { from, to, id, value } = &packet {
+            if (*to == 0 && *from != self.local_id) || *to == self.local_id {
+                self.deliver(*from, *id, value.clone());
+            }
+            if *to == 0 {
+                self.broadcast(&packet, sender, guaranteed);
+            } else if let Some(Session::Host { clients, discovery, .. }) = &mut self.session {
+                if let Some(client) = clients.iter_mut().find(|c| c.player == Some(*to)) {
+                    let _ = client.send_datagram(discovery, &packet, datagram(&packet, guaranteed).as_deref());
+                }
+            }
+        }
+    }
+
+    fn deliver(&mut self, from: i32, id: i32, value: Value) {
+        let name = self.players.iter().find(|p| p.id == from).map_or_else(|| "".into(), |p| p.name.clone());
+        self.messages.push_back(Message { id, value, from, name });
+    }
+
+    /// Handles whatever has arrived from the other players since last time.
+    pub fn poll(&mut self) {
+        match self.session.take() {
+            Some(Session::Host { listener, discovery, mut clients, name, max_players }) => {
+                // Answer anyone looking for sessions, and pick out what players sent without guarantees
+                let mut received = Vec::new();
+                let mut buf = [0u8; MAX_DATAGRAM];
+                while let Ok((len, from)) = discovery.recv_from(&mut buf) {
+                    match decode(&buf[..len]) {
+                        Ok(Packet::Find { game_id }) if game_id == self.game_id => {
+                            let reply = Packet::Session { game_id, name: name.clone(), port: self.port };
+                            if let Ok(reply) = encode(&reply) {
+                                let _ = discovery.send_to(&reply, from);
+                            }
+                        },
+                        Ok(packet) => {
+                            let sender = clients.iter().position(|c| c.datagram_address == Some(from));
+                            received.extend(sender.map(|index| (index, packet, false)));
+                        },
+                        Err(_) => (),
+                    }
+                }
+                while let Ok((stream, _)) = listener.accept() {
+                    if let Ok(peer) = Peer::new(stream) {
+                        clients.push(peer);
+                    }
+                }
+
+                for (index, client) in clients.iter_mut().enumerate() {
+                    match client.receive() {
+                        Ok(packets) => received.extend(packets.into_iter().map(|p| (index, p, true))),
+                        Err(_) => client.closed = true,
+                    }
+                }
+                self.session = Some(Session::Host { listener, discovery, clients, name, max_players });
+
+                for (index, packet, guaranteed) in received {
+                    match (self.client(index).and_then(|c| c.player), packet) {
+                        (None, Packet::Join { game_id, name, port }) if guaranteed => {
+                            self.admit(index, game_id, name, port)
+                        },
+                        (Some(sender), Packet::Data { index, value }) if index < DATA_SIZE => {
+                            self.data[index] = value.clone();
+                            self.broadcast(&Packet::Data { index, value }, Some(sender), guaranteed);
+                        },
+                        (Some(sender), Packet::Message { to, id, value, .. }) => {
+                            self.route(Packet::Message { from: sender, to, id, value }, Some(sender), guaranteed)
+                        },
+                        _ => (),
+                    }
+                }
+
+                // Drop anyone who left or was turned away
+                let left = match &mut self.session {
+                    Some(Session::Host { clients, .. }) => {
+                        let left = clients.iter().filter(|c| c.closed).filter_map(|c| c.player).collect::<Vec<_>>();
+                        clients.retain(|c| !c.closed);
+                        left
+                    },
+                    _ => Vec::new(),
+                };
+                for id in left {
+                    self.players.retain(|p| p.id != id);
+                    self.broadcast(&Packet::PlayerLeft(id), None, true);
+                }
+            },
+            Some(Session::Client { mut host, socket }) => {
+                let packets = host.receive();
+                let closed = host.closed || packets.is_err();
+                let mut datagrams = Vec::new();
+                let mut buf = [0u8; MAX_DATAGRAM];
+                while let Ok((len, from)) = socket.recv_from(&mut buf) {
+                    if Some(from) == host.datagram_address {
+                        datagrams.extend(decode::<Packet>(&buf[..len]));
+                    }
+                }
+                self.session = Some(Session::Client { host, socket });
+                for packet in packets.unwrap_or_default().into_iter().chain(datagrams) {
+                    match packet {
+                        Packet::PlayerJoined(player) => self.players.push(player),
+                        Packet::PlayerLeft(id) => self.players.retain(|p| p.id != id),
+                        Packet::Data { index, value } if index < DATA_SIZE => self.data[index] = value,
+                        Packet::Message { from, id, value, .. } => self.deliver(from, id, value),
+                        _ => (),
+                    }
+                }
+                if closed {
+                    self.end_session();
+                }
+            },
+            None => (),
+        }
+    }
+
+    fn client(&mut self, index: usize) -> Option<&mut Peer> {
+        match &mut self.session {
+            Some(Session::Host { clients, .. }) => clients.get_mut(index),
+            _ => None,
+        }
+    }
+
+    /// Lets a new connection into the session if there's room, or turns it away.
+    /// `port` is the one the player takes datagrams on, or 0 if they don't.
+    fn admit(&mut self, index: usize, game_id: i32, name: gml::String, port: u16) {
+        let refuse = match &self.session {
+            Some(Session::Host { max_players, .. }) => {
+                game_id != self.game_id || (*max_players > 0 && self.players.len() >= *max_players)
+            },
+            _ => return,
+        };
+        let id = self.players.iter().map(|p| p.id).max().unwrap_or(0) + 1;
+        let data = self
+            .data
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| !matches!(v, Value::Real(r) if r.into_inner() == 0.0))
+            .map(|(i, v)| (i, v.clone()))
+            .collect();
+        let player = Player { id, name };
+        let mut players = self.players.clone();
+        players.push(player.clone());
+        let welcome = Packet::Welcome { id, players, data };
+        let client = match self.client(index) {
+            Some(client) => client,
+            None => return,
+        };
+        if refuse {
+            let _ = client.send(&Packet::Refused);
+            client.closed = true;
+        } else if client.send(&welcome).is_err() {
+            client.closed = true;
+        } else {
+            client.player = Some(id);
+            if port != 0 {
+                client.datagram_address = client.stream.peer_addr().ok().map(|a| SocketAddr::new(a.ip(), port));
+            }
+            self.broadcast(&Packet::PlayerJoined(player.clone()), Some(id), true);
+            self.players.push(player);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::mpsc, thread};
+
+    const GAME_ID: i32 = 1234;
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Polls until the condition holds, failing the test if it takes too long.
+    fn poll_until(mplay: &mut Multiplayer, mut condition: impl FnMut(&mut Multiplayer) -> bool) {
+        let start = Instant::now();
+        while !condition(mplay) {
+            assert!(start.elapsed() < TIMEOUT, "timed out");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn loopback_session() {
+        let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap().local_addr().unwrap().port();
+        let (ready_send, ready) = mpsc::channel();
+
+        // The host answers the client's message, then waits for them to leave
+        let host = thread::spawn(move || {
+            let mut mplay = Multiplayer::new(GAME_ID);
+            mplay.port = port;
+            mplay.init_tcpip("");
+            assert!(mplay.create_session("test".into(), 2, "host".into()));
+            ready_send.send(()).unwrap();
+            poll_until(&mut mplay, |m| m.receive_message(0));
+            let message = mplay.current.clone().unwrap();
+            assert_eq!(message.from, 2);
+            assert_eq!(message.name.as_ref(), b"client");
+            mplay.write_data(4, 9.0.into());
+            assert!(mplay.send_message(message.from, message.id + 1, message.value, true));
+            poll_until(&mut mplay, |m| m.find_players() == 1);
+            f64::from(mplay.read_data(3))
+        });
+
+        ready.recv().unwrap();
+        let mut mplay = Multiplayer::new(GAME_ID);
+        mplay.port = port;
+        mplay.init_tcpip("127.0.0.1");
+        mplay.found_sessions.push(FoundSession { name: "test".into(), address: (Ipv4Addr::LOCALHOST, port).into() });
+        assert!(mplay.join_session(0, "client".into()));
+        assert_eq!(mplay.session_status(), 2);
+        assert_eq!(mplay.find_players(), 2);
+        assert_eq!(mplay.found_player(0).unwrap().name.as_ref(), b"host");
+
+        mplay.write_data(3, 7.0.into());
+        assert!(mplay.send_message(1, 10, "hello".into(), true));
+        poll_until(&mut mplay, |m| m.receive_message(1));
+        let message = mplay.current.clone().unwrap();
+        assert_eq!(message.id, 11);
+        assert_eq!(message.name.as_ref(), b"host");
+        assert!(matches!(message.value, Value::Str(s) if s.as_ref() == b"hello"));
+        // Sent before the reply, so it's already here
+        assert_eq!(f64::from(mplay.read_data(4)), 9.0);
+
+        mplay.end_session();
+        assert_eq!(host.join().unwrap(), 7.0);
+    }
+
+    #[test]
+    fn unguaranteed_session() {
+        let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap().local_addr().unwrap().port();
+        let (ready_send, ready) = mpsc::channel();
+
+        // The host echoes the client's message without guarantees, then says when the client's data arrives
+        let host = thread::spawn(move || {
+            let mut mplay = Multiplayer::new(GAME_ID);
+            mplay.port = port;
+            mplay.init_tcpip("");
+            assert!(mplay.create_session("test".into(), 2, "host".into()));
+            ready_send.send(()).unwrap();
+            poll_until(&mut mplay, |m| m.receive_message(0));
+            let message = mplay.current.clone().unwrap();
+            assert_eq!(message.from, 2);
+            assert!(mplay.send_message(message.from, message.id + 1, message.value, false));
+            poll_until(&mut mplay, |m| f64::from(m.read_data(5)) == 3.0);
+            assert!(mplay.send_message(2, 30, 0.0.into(), true));
+            poll_until(&mut mplay, |m| m.find_players() == 1);
+        });
+
+        ready.recv().unwrap();
+        let mut mplay = Multiplayer::new(GAME_ID);
+        mplay.port = port;
+        mplay.init_tcpip("127.0.0.1");
+        mplay.found_sessions.push(FoundSession { name: "test".into(), address: (Ipv4Addr::LOCALHOST, port).into() });
+        assert!(mplay.join_session(0, "client".into()));
+        assert!(mplay.data_mode());
+        mplay.set_data_mode(false);
+
+        assert!(mplay.send_message(1, 20, "ping".into(), false));
+        poll_until(&mut mplay, |m| m.receive_message(1));
+        assert_eq!(mplay.current.as_ref().unwrap().id, 21);
+        mplay.write_data(5, 3.0.into());
+        poll_until(&mut mplay, |m| m.receive_message(1));
+        assert_eq!(mplay.current.as_ref().unwrap().id, 30);
+
+        // Anything too big for a datagram goes over TCP
+        let big = Packet::Message { from: 2, to: 1, id: 0, value: "x".repeat(MAX_DATAGRAM).into() };
+        assert!(datagram(&big, false).is_none());
+
+        mplay.end_session();
+        host.join().unwrap();
+    }
+
+    #[test]
+    fn oversized_frame() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let mut sender = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let mut peer = Peer::new(listener.accept().unwrap().0).unwrap();
+        sender.write_all(&(MAX_PACKET as u32 + 1).to_le_bytes()).unwrap();
+        let start = Instant::now();
+        loop {
+            match peer.receive() {
+                Err(e) => break assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+                Ok(packets) => assert!(packets.is_empty()),
+            }
+            assert!(start.elapsed() < TIMEOUT, "timed out");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use crate::{
+    game::Game,
+    gml::{
+        debugger::{self, CodeSource, Frame},
+        mappings,
+    },
+    types::ID,
+};
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// Something that can show up in a profile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Entry {
+    Event {
+        object: ID,
+        event_type: usize,
+        event_number: usize,
+    },
+    Code(CodeSource),
+    /// A kernel function, identified by its address
+    Kernel(usize),
+}
+
+#[derive(Clone, Copy, Default)]
+struct Stats {
+    calls: u64,
+    inclusive: Duration,
+    exclusive: Duration,
+}
+
+/// A node in the call tree. Each distinct path of entries from the root gets one of these.
+struct Node {
+    entry: Option<Entry>,
+    children: HashMap<Entry, usize>,
+    exclusive: Duration,
+}
+
+/// A call which has been entered and not yet exited.
+struct OpenCall {
+    node: usize,
+    start: Instant,
+    children: Duration,
+}
+
+/// Records how often and for how long scripts, events and kernel functions run.
+/// `finish` writes a text report to the given path and a collapsed-stack file next to it.
+pub struct Profiler {
+    output: PathBuf,
+    kernel_names: HashMap<usize, &'static str>,
+    names: HashMap<Entry, String>,
+    stats: HashMap<Entry, Stats>,
+    nodes: Vec<Node>,
+    stack: Vec<OpenCall>,
+}
+
+impl Profiler {
+    pub fn new(output: PathBuf) -> Self {
+        Self {
+            output,
+            kernel_names: mappings::FUNCTIONS.entries().map(|(name, f)| (f.addr() as usize, *name)).collect(),
+            names: HashMap::new(),
+            stats: HashMap::new(),
+            nodes: vec![Node { entry: None, children: HashMap::new(), exclusive: Duration::default() }],
+            stack: Vec::new(),
+        }
+    }
+
+    fn enter(&mut self, entry: Entry) {
+        let parent = self.stack.last().map(|call| call.node).unwrap_or(0);
+        let node = match self.nodes[parent].children.get(&entry) {
+            Some(&node) => node,
+            None => {
+                let node = self.nodes.len();
+                self.nodes.push(Node { entry: Some(entry), children: HashMap::new(), exclusive: Duration::default() });
+                self.nodes[parent].children.insert(entry, node);
+                node
+            },
+        };
+        self.stack.push(OpenCall { node, start: Instant::now(), children: Duration::default() });
+    }
+
+    fn exit(&mut self) {
+        if let Some(call) = self.stack.pop() {
+            let elapsed = call.start.elapsed();
+            let exclusive = elapsed.saturating_sub(call.children);
+            let node = &mut self.nodes[call.node];
+            node.exclusive += exclusive;
+            if let Some(entry) = node.entry {
+                // Recursive calls are already counted by the outermost one
+                let recursive = self.stack.iter().any(|c| self.nodes[c.node].entry == Some(entry));
+                let stats = self.stats.entry(entry).or_default();
+                stats.calls += 1;
+                stats.exclusive += exclusive;
+                if !recursive {
+                    stats.inclusive += elapsed;
+                }
+            }
+            if let Some(parent) = self.stack.last_mut() {
+                parent.children += elapsed;
+            }
+        }
+    }
+
+    fn name(&self, entry: &Entry) -> String {
+        self.names.get(entry).cloned().unwrap_or_else(|| format!("{:?}", entry))
+    }
+
+    /// Writes a report listing every entry, sorted by exclusive time.
+    pub fn write_report(&self, out: &mut impl Write) -> io::Result<()> {
+        let mut entries = self.stats.iter().collect::<Vec<_>>();
+        entries.sort_by_key(|(_, s)| Reverse(s.exclusive));
+        let total = entries.iter().map(|(_, s)| s.exclusive).sum::<Duration>();
+        writeln!(out, "Total profiled time: {:.3} ms", total.as_secs_f64() * 1000.0)?;
+        writeln!(out)?;
+        writeln!(out, "{:>10} {:>14} {:>14} {:>8}  name", "calls", "incl. (ms)", "excl. (ms)", "excl. %")?;
+        for (entry, stats) in entries {
+            let percent =
+                if total.is_zero() { 0.0 } else { stats.exclusive.as_secs_f64() / total.as_secs_f64() * 100.0 };
+            writeln!(
+                out,
+                "{:>10} {:>14.3} {:>14.3} {:>8.2}  {}",
+                stats.calls,
+                stats.inclusive.as_secs_f64() * 1000.0,
+                stats.exclusive.as_secs_f64() * 1000.0,
+                percent,
+                self.name(entry),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes one line per distinct call stack in the "collapsed" format used by flamegraph tools,
+    /// eg. "Step Event of obj_player;script scr_move;place_free 1234", with exclusive time in microseconds.
+    pub fn write_collapsed(&self, out: &mut impl Write) -> io::Result<()> {
+        let mut path = Vec::new();
+        let mut todo = vec![(0usize, 0usize)];
+        while let Some((node, depth)) = todo.pop() {
+            path.truncate(depth);
+            if let Some(entry) = &self.nodes[node].entry {
+                path.push(self.name(entry).replace(';', ","));
+                let micros = self.nodes[node].exclusive.as_micros();
+                if micros > 0 {
+                    writeln!(out, "{} {}", path.join(";"), micros)?;
+                }
+            }
+            let depth = path.len();
+            todo.extend(self.nodes[node].children.values().map(|&child| (child, depth)));
+        }
+        Ok(())
+    }
+
+    /// Where the report is going to be written.
+    pub fn output(&self) -> &Path {
+        &self.output
+    }
+
+    /// Stops timing anything still running and writes the report and collapsed stacks.
+    pub fn finish(mut self) -> io::Result<()> {
+        while !self.stack.is_empty() {
+            self.exit();
+        }
+        let mut report = BufWriter::new(File::create(&self.output)?);
+        self.write_report(&mut report)?;
+        report.flush()?;
+
+        let mut collapsed_path = self.output.clone().into_os_string();
+        collapsed_path.push(".folded");
+        let mut collapsed = BufWriter::new(File::create(collapsed_path)?);
+        self.write_collapsed(&mut collapsed)?;
+        collapsed.flush()
+    }
+}
+
+impl Game {
+    /// Starts timing something, if profiling is enabled. Must be paired with `profile_exit`.
+    #[inline(always)]
+    pub fn profile_enter(&mut self, entry: Entry) {
+        if self.profiler.is_some() {
+            self.profile_enter_slow(entry);
+        }
+    }
+
+    /// Stops timing whatever was last passed to `profile_enter`.
+    #[inline(always)]
+    pub fn profile_exit(&mut self) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.exit();
+        }
+    }
+
+    fn profile_enter_slow(&mut self, entry: Entry) {
+        if let Some(profiler) = self.profiler.as_ref().filter(|p| !p.names.contains_key(&entry)) {
+            let name = match entry {
+                Entry::Event { object, event_type, event_number } => format!(
+                    "{} of {}",
+                    debugger::event_name(self, event_type, event_number),
+                    debugger::object_name(self, object)
+                ),
+                Entry::Code(source) => Frame { source, instance: 0, line: 0 }.describe(self),
+                Entry::Kernel(addr) => match profiler.kernel_names.get(&addr) {
+                    Some(name) => name.to_string(),
+                    None => format!("<kernel function {:#x}>", addr),
+                },
+            };
+            if let Some(profiler) = &mut self.profiler {
+                profiler.names.insert(entry, name);
+            }
+        }
+        if let Some(profiler) = &mut self.profiler {
+            profiler.enter(entry);
+        }
+    }
+}
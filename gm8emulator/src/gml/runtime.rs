@@ -4,7 +4,9 @@ use crate::{
     gml::{
         self,
         datetime::DateTime,
+        debugger::{self, CodeSource},
         mappings::{self, constants as gml_constants},
+        profiler, stub,
         Context, InstanceVariable, Value,
     },
     instance::Field,
@@ -36,6 +38,7 @@ pub enum Instruction {
     With { target: Node, body: Box<[Instruction]> },
     GlobalVar { fields: Vec<usize> },
     RuntimeError { error: Error },
+    Line { line: usize },
 }
 
 /// Node representing one value in an expression.
@@ -105,8 +108,9 @@ pub enum UnaryOperator {
 }
 
 /// The reason for stopping execution of the current function.
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum ReturnType {
+    #[default]
     Normal,
     Continue,
     Break,
@@ -181,6 +185,30 @@ pub enum Error {
     BadDirectoryError(String),
     ExternalFunction(String, String),
     InvalidExternal(i32),
+    UnimplementedFunction(String),
+
+    /// An error message raised by the game itself through show_error
+    Raised(String),
+
+    /// Another error, along with the GML call stack at the point where it happened
+    Traced(Box<Error>, Vec<debugger::TraceFrame>),
+
+    /// An error which the player has already seen and chose to abort the game over
+    Aborted(Box<Error>),
+
+    /// Writing out an audio or video dump failed
+    Dump(String),
+}
+
+impl Error {
+    /// Whether this error must end the game, rather than being shown to the player, who could ignore it.
+    pub fn is_fatal(&self) -> bool {
+        match self {
+            Self::ReplayError(_) | Self::Aborted(_) | Self::Dump(_) => true,
+            Self::Traced(error, _) => error.is_fatal(),
+            _ => false,
+        }
+    }
 }
 
 impl std::error::Error for Error {}
@@ -228,6 +256,11 @@ impl Display for Error {
             Self::BadDirectoryError(s) => write!(f, "cannot encode working directory {} with current encoding", s),
             Self::ExternalFunction(s, e) => write!(f, "failed to call external function \"{}\": {}", s, e),
             Self::InvalidExternal(i) => write!(f, "tried to call nonexistent external function with id {}", i),
+            Self::UnimplementedFunction(fname) => write!(f, "function {} is not implemented yet", fname),
+            Self::Raised(s) => write!(f, "{}", s),
+            Self::Traced(error, stack) => debugger::write_trace(f, stack, error),
+            Self::Aborted(error) => write!(f, "{}", error),
+            Self::Dump(s) => write!(f, "{}", s),
         }
     }
 }
@@ -262,6 +295,7 @@ impl fmt::Debug for Instruction {
             Instruction::With { target, body } => write!(f, "With({:?}, {:?})", target, body),
             Instruction::GlobalVar { fields } => write!(f, "GlobalVar({:?})", fields),
             Instruction::RuntimeError { error } => write!(f, "RuntimeError({:?})", error),
+            Instruction::Line { line } => write!(f, "Line({})", line),
         }
     }
 }
@@ -598,6 +632,15 @@ impl Game {
                 }
             },
             Instruction::RuntimeError { error } => return Err(error.clone()),
+            Instruction::Line { line } => {
+                let entered = match self.call_stack.last_mut() {
+                    Some(frame) => std::mem::replace(&mut frame.line, *line) == 0,
+                    None => false,
+                };
+                if self.debugger.hook.is_some() {
+                    self.debugger_check(context, entered);
+                }
+            },
         }
 
         Ok(ReturnType::Normal)
@@ -618,28 +661,40 @@ impl Game {
                 for (src, dest) in args.iter().zip(arg_values.iter_mut()) {
                     *dest = self.eval(src, context)?;
                 }
-                function.0(self, context, &arg_values[..args.len()])
+                self.profile_enter(profiler::Entry::Kernel(function.0 as usize));
+                let result = function.0(self, context, &arg_values[..args.len()]);
+                self.profile_exit();
+                result
             },
             Node::StateFunction { args, function } => {
                 let mut arg_values: [Value; 16] = Default::default();
                 for (src, dest) in args.iter().zip(arg_values.iter_mut()) {
                     *dest = self.eval(src, context)?;
                 }
-                function.0(self, &arg_values[..args.len()])
+                self.profile_enter(profiler::Entry::Kernel(function.0 as usize));
+                let result = function.0(self, &arg_values[..args.len()]);
+                self.profile_exit();
+                result
             },
             Node::RoutineFunction { args, function } => {
                 let mut arg_values: [Value; 16] = Default::default();
                 for (src, dest) in args.iter().zip(arg_values.iter_mut()) {
                     *dest = self.eval(src, context)?;
                 }
-                function.0(self, &arg_values[..args.len()])
+                self.profile_enter(profiler::Entry::Kernel(function.0 as usize));
+                let result = function.0(self, &arg_values[..args.len()]);
+                self.profile_exit();
+                result
             },
             Node::ValueFunction { args, function } => {
                 let mut arg_values: [Value; 16] = Default::default();
                 for (src, dest) in args.iter().zip(arg_values.iter_mut()) {
                     *dest = self.eval(src, context)?;
                 }
-                function.0(&arg_values[..args.len()])
+                self.profile_enter(profiler::Entry::Kernel(function.0 as usize));
+                let result = function.0(&arg_values[..args.len()]);
+                self.profile_exit();
+                result
             },
             Node::Script { args, script_id } => {
                 if let Some(Some(script)) = self.assets.scripts.get(*script_id) {
@@ -651,7 +706,7 @@ impl Game {
                     }
 
                     let mut new_context = Context::copy_with_args(context, arg_values, args.len());
-                    self.execute(&instructions, &mut new_context)?;
+                    self.execute_frame(CodeSource::Script(*script_id), &instructions, &mut new_context)?;
                     Ok(new_context.return_value)
                 } else {
                     Err(Error::NonexistentAsset(asset::Type::Script, *script_id as i32))
@@ -1191,8 +1246,8 @@ impl Game {
             InstanceVariable::MouseLastbutton => Ok(f64::from(self.input.mouse_lastbutton()).into()),
             InstanceVariable::KeyboardKey => Ok(f64::from(self.input.keyboard_key()).into()),
             InstanceVariable::KeyboardLastkey => Ok(f64::from(self.input.keyboard_lastkey()).into()),
-            InstanceVariable::KeyboardLastchar => todo!("keyboard_lastchar getter"),
-            InstanceVariable::KeyboardString => todo!("keyboard_string getter"),
+            InstanceVariable::KeyboardLastchar => stub::unimplemented("keyboard_lastchar"),
+            InstanceVariable::KeyboardString => stub::unimplemented("keyboard_string"),
             InstanceVariable::CursorSprite => Ok(self.cursor_sprite.into()),
             InstanceVariable::ShowScore => Ok(self.score_capt_d.into()),
             InstanceVariable::ShowLives => Ok(self.lives_capt_d.into()),
@@ -1538,8 +1593,12 @@ impl Game {
                     self.input.set_keyboard_lastkey(vk);
                 }
             },
-            InstanceVariable::KeyboardLastchar => todo!("keyboard_lastchar setter"),
-            InstanceVariable::KeyboardString => todo!("keyboard_string setter"),
+            InstanceVariable::KeyboardLastchar => {
+                stub::unimplemented("keyboard_lastchar")?;
+            },
+            InstanceVariable::KeyboardString => {
+                stub::unimplemented("keyboard_string")?;
+            },
             InstanceVariable::CursorSprite => self.cursor_sprite = value.round(),
             InstanceVariable::ShowScore => {
                 self.has_set_show_score = true;
@@ -0,0 +1,63 @@
+use crate::gml::{self, Value};
+use std::{
+    collections::BTreeSet,
+    str::FromStr,
+    sync::{Mutex, OnceLock},
+};
+
+/// What to do when GML calls a kernel function which hasn't been implemented yet.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Policy {
+    /// Raise a runtime error, which goes through the game's usual error handling
+    #[default]
+    Error,
+    /// Return 0 and print a warning every time
+    Warn,
+    /// Return 0 and print a warning the first time each function is called
+    WarnOnce,
+}
+
+// Set once from the command line before the game starts, so that pure kernel functions can see it too
+static POLICY: OnceLock<Policy> = OnceLock::new();
+
+// Functions which have already been warned about
+static WARNED: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+impl FromStr for Policy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Self::Error),
+            "warn" => Ok(Self::Warn),
+            "once" => Ok(Self::WarnOnce),
+            _ => Err(format!("unknown policy \"{}\" (expected error, warn or once)", s)),
+        }
+    }
+}
+
+/// Sets the policy for the rest of this run. Only the first call has any effect.
+pub fn set_policy(policy: Policy) {
+    let _ = POLICY.set(policy);
+}
+
+pub fn policy() -> Policy {
+    POLICY.get().copied().unwrap_or_default()
+}
+
+/// Called by kernel functions which don't have an implementation yet.
+pub fn unimplemented(function: &'static str) -> gml::Result<Value> {
+    match policy() {
+        Policy::Error => return Err(gml::Error::UnimplementedFunction(function.into())),
+        Policy::Warn => eprintln!("Warning: called unimplemented kernel function {}", function),
+        Policy::WarnOnce => {
+            if WARNED.lock().unwrap_or_else(|e| e.into_inner()).insert(function) {
+                eprintln!(
+                    "Warning: called unimplemented kernel function {} (further calls won't be reported)",
+                    function
+                );
+            }
+        },
+    }
+    Ok(Default::default())
+}
@@ -15,7 +15,7 @@ mod util;
 
 use game::{
     savestate::{self, SaveState},
-    Game, GameClock, PlayType, Replay,
+    video, Game, GameClock, PlayType, Replay,
 };
 use std::{
     env, fs,
@@ -54,6 +54,32 @@ fn xmain() -> i32 {
     opts.optopt("n", "project-name", "name of TAS project to create or load", "NAME");
     opts.optopt("f", "replay-file", "path to savestate file to replay", "FILE");
     opts.optopt("o", "output-file", "output savestate name in replay mode", "FILE.bin");
+    opts.optopt("p", "profile", "write a GML profile to FILE and collapsed stacks to FILE.folded", "FILE");
+    opts.optopt("w", "wav-dump", "write the game's audio to a 44.1kHz WAV file, following the game clock", "FILE.wav");
+    opts.optopt(
+        "d",
+        "video-dump",
+        "write every frame to a Y4M stream or numbered PNGs, along with FILE.wav and FILE_timestamps.txt",
+        "FILE.y4m|FILE.png",
+    );
+    opts.optflag("", "video-native", "dump video at the game's own resolution instead of the window's");
+    opts.optopt(
+        "",
+        "font-dir",
+        "look for fonts in DIR before the system's font directories, or only in DIR when recording or replaying",
+        "DIR",
+    );
+    opts.optflag(
+        "",
+        "emulate-dlls",
+        "use the built-in 39dll, GMFMODSimple and SuperSound even when the real DLLs could be loaded",
+    );
+    opts.optopt(
+        "u",
+        "unimplemented",
+        "what to do when the game calls an unimplemented function: error (default), warn, or once",
+        "POLICY",
+    );
     opts.optmulti("a", "game-arg", "argument to pass to the game", "ARG");
 
     let matches = match opts.parse(&args[1..]) {
@@ -91,6 +117,15 @@ fn xmain() -> i32 {
     let frame_limiter = !matches.opt_present("l");
     let verbose = matches.opt_present("v");
     let output_bin = matches.opt_str("o").map(PathBuf::from);
+    if let Some(policy) = matches.opt_str("u") {
+        match policy.parse() {
+            Ok(policy) => gml::stub::set_policy(policy),
+            Err(e) => {
+                eprintln!("{}", e);
+                return EXIT_FAILURE
+            },
+        }
+    }
     let project_path = matches.opt_str("n").map(|name| {
         let mut p = env::current_dir().expect("std::env::current_dir() failed");
         p.push("projects");
@@ -235,6 +270,7 @@ fn xmain() -> i32 {
         frame_limiter,
         frame_limit_at,
         play_type,
+        matches.opt_present("emulate-dlls"),
     ) {
         Ok(g) => g,
         Err(e) => {
@@ -243,9 +279,38 @@ fn xmain() -> i32 {
         },
     };
 
+    components.profiler = matches.opt_str("p").map(|path| gml::profiler::Profiler::new(path.into()));
+    components.font_library = asset::font::truetype::FontLibrary::new(
+        matches.opt_str("font-dir").map(PathBuf::from),
+        play_type != PlayType::Normal,
+    );
+
+    if let Some(path) = matches.opt_str("d") {
+        let size = if matches.opt_present("video-native") { video::Size::Native } else { video::Size::Window };
+        match video::VideoDump::new(Path::new(&path), size) {
+            Ok(dump) => components.video_dump = Some(dump),
+            Err(e) => {
+                eprintln!("couldn't create video dump {}: {}", path, e);
+                return EXIT_FAILURE
+            },
+        }
+    }
+
+    // A video dump comes with its audio, unless it's been asked for somewhere else
+    let wav_path = matches
+        .opt_str("w")
+        .map(PathBuf::from)
+        .or_else(|| matches.opt_str("d").map(|path| video::VideoDump::sibling(Path::new(&path), ".wav")));
+    if let Some(path) = wav_path {
+        if let Err(e) = components.audio.start_wav_dump(&path) {
+            eprintln!("couldn't create audio dump {:?}: {}", path, e);
+            return EXIT_FAILURE
+        }
+    }
+
     let time_now = GameClock::SpoofedNanos(gml::datetime::now_as_nanos());
 
-    if let Err(err) = if let Some(path) = project_path {
+    let result = if let Some(path) = project_path {
         components.clock = time_now;
         components.record(path);
         Ok(())
@@ -268,6 +333,8 @@ fn xmain() -> i32 {
             components.clock = if spoof_time { time_now } else { GameClock::StartupEpoch(std::time::Instant::now()) };
             components.run()
         };
+        // Only written once the game is over rather than on every registry_write_*
+        components.registry.save();
         for file in files_to_delete.into_iter() {
             std::fs::remove_file(file).ok();
         }
@@ -275,10 +342,36 @@ fn xmain() -> i32 {
             std::fs::remove_dir_all(temp_dir).ok();
         }
         result
-    } {
-        println!("Runtime error: {}", err);
-        EXIT_FAILURE
-    } else {
-        EXIT_SUCCESS
+    };
+    let mut exit_code = match result {
+        Ok(()) => EXIT_SUCCESS,
+        Err(err) => {
+            println!("Runtime error: {}", err);
+            EXIT_FAILURE
+        },
+    };
+
+    if let Some(dump) = components.video_dump.take() {
+        if let Err(e) = dump.finish() {
+            eprintln!("Failed to finish video dump: {}", e);
+            exit_code = EXIT_FAILURE;
+        }
+    }
+    if let Err(e) = components.audio.finish_wav_dump() {
+        eprintln!("Failed to finish audio dump: {}", e);
+        exit_code = EXIT_FAILURE;
+    }
+
+    if let Some(profiler) = components.profiler.take() {
+        let output = profiler.output().to_path_buf();
+        match profiler.finish() {
+            Ok(()) => println!("Profile written to {:?}", output),
+            Err(e) => {
+                eprintln!("Failed to write profile to {:?}: {}", output, e);
+                exit_code = EXIT_FAILURE;
+            },
+        }
     }
+
+    exit_code
 }
@@ -20,6 +20,33 @@ pub enum Scaling {
     Full,
 }
 
+impl Scaling {
+    /// Gets where a framebuffer of the given size is drawn in a window of the given size, as (x, y, w, h).
+    pub fn viewport(self, fb_size: (i32, i32), window_size: (i32, i32)) -> (i32, i32, i32, i32) {
+        let ((fb_width, fb_height), (window_width, window_height)) = (fb_size, window_size);
+        match self {
+            Scaling::Fixed(scale) => {
+                let w = (f64::from(fb_width) * scale) as i32;
+                let h = (f64::from(fb_height) * scale) as i32;
+                ((window_width - w) / 2, (window_height - h) / 2, w, h)
+            },
+            Scaling::Aspect(_) if fb_width > 0 && fb_height > 0 => {
+                let fixed_width = window_height * fb_width / fb_height;
+                if fixed_width < window_width {
+                    // window is too wide
+                    ((window_width - fixed_width) / 2, 0, fixed_width, window_height)
+                } else {
+                    // window is too tall
+                    let fixed_height = window_width * fb_height / fb_width;
+                    (0, (window_height - fixed_height) / 2, window_width, fixed_height)
+                }
+            },
+            Scaling::Aspect(_) => (0, 0, fb_width, fb_height),
+            Scaling::Full => (0, 0, window_width, window_height),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SavedTexture {
     width: i32,
@@ -1249,3 +1276,17 @@ fn mat4mult(m1: [f32; 16], m2: [f32; 16]) -> [f32; 16] {
         (m1[12] * m2[3]) + (m1[13] * m2[7]) + (m1[14] * m2[11]) + (m1[15] * m2[15]),
     ]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn viewport() {
+        assert_eq!(Scaling::Full.viewport((320, 240), (800, 600)), (0, 0, 800, 600));
+        assert_eq!(Scaling::Fixed(2.0).viewport((320, 240), (800, 600)), (80, 60, 640, 480));
+        assert_eq!(Scaling::Fixed(2.0).viewport((320, 240), (320, 240)), (-160, -120, 640, 480));
+        assert_eq!(Scaling::Aspect(-1.0).viewport((320, 240), (1000, 600)), (100, 0, 800, 600));
+        assert_eq!(Scaling::Aspect(-1.0).viewport((320, 240), (800, 1000)), (0, 200, 800, 600));
+    }
+}